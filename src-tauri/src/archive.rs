@@ -0,0 +1,180 @@
+//! Directories shared as a single tar archive
+//!
+//! Sharing a directory with tens of thousands of tiny files stores and fetches
+//! one blob per file, and the per-blob overhead dominates the transfer. In
+//! archive mode the files are streamed into one tar archive instead, which is
+//! shared as a single blob and unpacked again by the receiver. Entries use the
+//! same `/`-separated relative paths as regular shares, and are placed with
+//! the same rules (see [`crate::paths`]) when they are unpacked.
+
+use anyhow::Result;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tar::{Archive, Builder, EntryType, Header};
+
+/// Extension of the archive file listed in a share
+pub const ARCHIVE_EXTENSION: &str = "tar";
+
+/// Permissions of files unpacked from an archive
+const FILE_MODE: u32 = 0o644;
+
+/// A tar archive being built in memory
+pub struct ArchiveBuilder {
+    builder: Builder<Vec<u8>>,
+    files: usize,
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveBuilder {
+    /// Creates an empty archive
+    pub fn new() -> Self {
+        Self {
+            builder: Builder::new(Vec::new()),
+            files: 0,
+        }
+    }
+
+    /// Adds a file to the archive
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - Path of the file within the shared directory
+    /// * `content` - The content of the file
+    /// * `modified` - When the file was last modified, if known
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path cannot be stored in a tar header.
+    pub fn append(
+        &mut self,
+        relative_path: &str,
+        content: &[u8],
+        modified: Option<SystemTime>,
+    ) -> Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(content.len() as u64);
+        header.set_mode(FILE_MODE);
+        header.set_mtime(
+            modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+        );
+
+        self.builder
+            .append_data(&mut header, relative_path, content)
+            .map_err(|error| anyhow::anyhow!("Failed to archive '{}': {}", relative_path, error))?;
+        self.files += 1;
+        Ok(())
+    }
+
+    /// Returns the number of files in the archive
+    pub fn len(&self) -> usize {
+        self.files
+    }
+
+    /// Checks whether no files were added
+    pub fn is_empty(&self) -> bool {
+        self.files == 0
+    }
+
+    /// Finishes the archive and returns its bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the end of the archive cannot be written.
+    pub fn finish(self) -> Result<Vec<u8>> {
+        self.builder
+            .into_inner()
+            .map_err(|error| anyhow::anyhow!("Failed to finish archive: {}", error))
+    }
+}
+
+/// A file stored in an archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveFile {
+    /// Path of the file within the shared directory, as stored in the archive
+    pub relative_path: String,
+    /// Where the content of the file is within the archive
+    pub content: Range<usize>,
+}
+
+/// Lists the files in an archive.
+///
+/// Only regular files are listed; directories, links and other entries are
+/// skipped, as archive shares never contain them.
+///
+/// # Arguments
+///
+/// * `archive` - The bytes of a tar archive
+///
+/// # Errors
+///
+/// Returns an error if the archive is malformed.
+pub fn list_files(archive: &[u8]) -> Result<Vec<ArchiveFile>> {
+    let invalid = |error: std::io::Error| anyhow::anyhow!("Invalid archive: {}", error);
+
+    let mut files = Vec::new();
+    for entry in Archive::new(archive).entries().map_err(invalid)? {
+        let entry = entry.map_err(invalid)?;
+        if entry.header().entry_type() != EntryType::Regular {
+            continue;
+        }
+
+        let start = entry.raw_file_position() as usize;
+        let end = start + entry.size() as usize;
+        if end > archive.len() {
+            anyhow::bail!("Invalid archive: entry extends past the end");
+        }
+        let relative_path = entry.path().map_err(invalid)?;
+        files.push(ArchiveFile {
+            relative_path: relative_path.to_string_lossy().to_string(),
+            content: start..end,
+        });
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let long_path = format!("{}/file.txt", "nested".repeat(30));
+        let mut builder = ArchiveBuilder::new();
+        assert!(builder.is_empty());
+        builder.append("a.txt", b"first", None).unwrap();
+        builder
+            .append(&long_path, b"second", Some(SystemTime::now()))
+            .unwrap();
+        builder.append("empty", b"", None).unwrap();
+        assert_eq!(builder.len(), 3);
+
+        let archive = builder.finish().unwrap();
+        let files = list_files(&archive).unwrap();
+
+        let contents: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|file| (file.relative_path.as_str(), &archive[file.content.clone()]))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                ("a.txt", b"first".as_slice()),
+                (long_path.as_str(), b"second".as_slice()),
+                ("empty", b"".as_slice()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_files_rejects_garbage() {
+        assert!(list_files(&[1u8; 1024]).is_err());
+    }
+}
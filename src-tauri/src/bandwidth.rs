@@ -0,0 +1,404 @@
+//! Bandwidth limits for uploads and downloads
+//!
+//! Each direction has a token bucket shared by every transfer in that
+//! direction, so the combined rate of all transfers stays under the cap. A
+//! transfer reports the bytes it moves and waits until the bucket has earned
+//! them back. After an idle period, up to one second worth of traffic may pass
+//! at once.
+//!
+//! The limits can change with the time of day, e.g. full speed at night and
+//! capped during work hours. A schedule lists windows of the day with their
+//! own limits; outside of every window the configured limits apply. The
+//! limiters are switched over as a window starts or ends, so running
+//! transfers slow down or speed up along with new ones.
+
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the schedule is checked for a window starting or ending
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upload and download caps in bytes per second
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthLimits {
+    /// Maximum rate files are sent to peers at, unlimited if None
+    #[serde(default)]
+    pub upload: Option<u64>,
+    /// Maximum rate files are received from peers at, unlimited if None
+    #[serde(default)]
+    pub download: Option<u64>,
+}
+
+impl BandwidthLimits {
+    /// Returns the limits with zero rates treated as unlimited
+    fn normalized(self) -> Self {
+        Self {
+            upload: self.upload.filter(|rate| *rate > 0),
+            download: self.download.filter(|rate| *rate > 0),
+        }
+    }
+}
+
+/// A time of day to the minute, written as `HH:MM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    /// Minutes since midnight
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    /// Creates a time of day, or None if the hour or minute is out of range
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        (hour < 24 && minute < 60).then(|| Self {
+            minutes: (hour * 60 + minute) as u16,
+        })
+    }
+
+    /// Returns the current local time of day
+    pub fn now() -> Self {
+        let now = chrono::Local::now();
+        Self {
+            minutes: (now.hour() * 60 + now.minute()) as u16,
+        }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .split_once(':')
+            .filter(|(hour, minute)| hour.len() <= 2 && minute.len() == 2)
+            .and_then(|(hour, minute)| Self::new(hour.parse().ok()?, minute.parse().ok()?))
+            .ok_or_else(|| format!("Invalid time of day '{}', expected HH:MM", value))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+/// Limits applied during the same part of every day
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthWindow {
+    /// When the window starts, inclusive
+    pub start: TimeOfDay,
+    /// When the window ends, exclusive; before `start` if the window spans
+    /// midnight, equal to it if the window lasts all day
+    pub end: TimeOfDay,
+    /// Caps in effect during the window, unlimited where None
+    pub limits: BandwidthLimits,
+}
+
+impl BandwidthWindow {
+    /// Checks whether the window covers a time of day
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Less => self.start <= time && time < self.end,
+            std::cmp::Ordering::Greater => self.start <= time || time < self.end,
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+}
+
+/// Token bucket limiting the rate of one direction
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthLimiter {
+    state: Arc<Mutex<BucketState>>,
+}
+
+/// Bucket state guarded by the limiter's lock
+#[derive(Debug, Default)]
+struct BucketState {
+    /// Bytes per second, unlimited if None
+    rate: Option<u64>,
+    /// Bytes that may pass right now; negative while transfers are in debt
+    available: f64,
+    /// When `available` was last brought up to date
+    updated: Option<Instant>,
+}
+
+impl BandwidthLimiter {
+    /// Creates a limiter with the given rate in bytes per second
+    pub fn new(rate: Option<u64>) -> Self {
+        let limiter = Self::default();
+        limiter.set_rate(rate);
+        limiter
+    }
+
+    /// Returns the rate in bytes per second, or None if unlimited
+    pub fn rate(&self) -> Option<u64> {
+        self.state.lock().ok().and_then(|state| state.rate)
+    }
+
+    /// Changes the rate, taking effect for the next bytes reported
+    ///
+    /// A rate of zero is treated as unlimited.
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let rate = rate.filter(|rate| *rate > 0);
+        *state = BucketState {
+            rate,
+            available: rate.unwrap_or(0) as f64,
+            updated: None,
+        };
+    }
+
+    /// Takes bytes from the bucket, waiting until the rate allows them
+    pub async fn acquire(&self, bytes: u64) {
+        let delay = self.reserve(bytes, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Takes bytes from the bucket and returns how long to wait for them
+    fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+        let Ok(mut state) = self.state.lock() else {
+            return Duration::ZERO;
+        };
+        let Some(rate) = state.rate else {
+            return Duration::ZERO;
+        };
+
+        let rate = rate as f64;
+        let elapsed = state.updated.map_or(Duration::ZERO, |updated| {
+            now.saturating_duration_since(updated)
+        });
+        state.available = (state.available + elapsed.as_secs_f64() * rate).min(rate);
+        state.updated = Some(now);
+        state.available -= bytes as f64;
+
+        if state.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.available / rate)
+        }
+    }
+}
+
+/// Upload and download limiters of a node
+#[derive(Debug, Clone, Default)]
+pub struct Bandwidth {
+    /// Limits files sent to peers
+    pub upload: BandwidthLimiter,
+    /// Limits files received from peers
+    pub download: BandwidthLimiter,
+    config: Arc<Mutex<BandwidthConfig>>,
+}
+
+/// Configured limits, guarded by the lock of a [`Bandwidth`]
+#[derive(Debug, Default)]
+struct BandwidthConfig {
+    /// Limits applied outside of every scheduled window
+    limits: BandwidthLimits,
+    /// Windows with their own limits; the first one covering a time wins
+    schedule: Vec<BandwidthWindow>,
+}
+
+impl Bandwidth {
+    /// Returns the limits applied outside of scheduled windows
+    pub fn limits(&self) -> BandwidthLimits {
+        self.config
+            .lock()
+            .map(|config| config.limits)
+            .unwrap_or_default()
+    }
+
+    /// Returns the limits the limiters are enforcing right now
+    pub fn active_limits(&self) -> BandwidthLimits {
+        BandwidthLimits {
+            upload: self.upload.rate(),
+            download: self.download.rate(),
+        }
+    }
+
+    /// Changes the limits applied outside of scheduled windows
+    pub fn set_limits(&self, limits: BandwidthLimits) {
+        if let Ok(mut config) = self.config.lock() {
+            config.limits = limits.normalized();
+        }
+        self.apply(TimeOfDay::now());
+    }
+
+    /// Returns the scheduled windows
+    pub fn schedule(&self) -> Vec<BandwidthWindow> {
+        self.config
+            .lock()
+            .map(|config| config.schedule.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the scheduled windows, taking effect right away
+    pub fn set_schedule(&self, schedule: Vec<BandwidthWindow>) {
+        if let Ok(mut config) = self.config.lock() {
+            config.schedule = schedule;
+        }
+        self.apply(TimeOfDay::now());
+    }
+
+    /// Returns the limits in effect at a time of day
+    pub fn limits_at(&self, time: TimeOfDay) -> BandwidthLimits {
+        let Ok(config) = self.config.lock() else {
+            return BandwidthLimits::default();
+        };
+        config
+            .schedule
+            .iter()
+            .find(|window| window.contains(time))
+            .map_or(config.limits, |window| window.limits.normalized())
+    }
+
+    /// Switches the limiters to the limits in effect at a time of day
+    ///
+    /// A limiter whose rate stays the same is left alone, so its transfers
+    /// keep their place in the bucket.
+    fn apply(&self, time: TimeOfDay) {
+        let limits = self.limits_at(time);
+        if self.upload.rate() != limits.upload {
+            self.upload.set_rate(limits.upload);
+        }
+        if self.download.rate() != limits.download {
+            self.download.set_rate(limits.download);
+        }
+    }
+
+    /// Spawns a task switching the limiters as scheduled windows start and
+    /// end, until the bandwidth is dropped.
+    pub fn spawn_schedule(&self) {
+        let config = Arc::downgrade(&self.config);
+        let upload = self.upload.clone();
+        let download = self.download.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+                let Some(config) = config.upgrade() else {
+                    return;
+                };
+                Bandwidth {
+                    upload: upload.clone(),
+                    download: download.clone(),
+                    config,
+                }
+                .apply(TimeOfDay::now());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_allows_a_burst_then_paces() {
+        let limiter = BandwidthLimiter::new(Some(1000));
+        let start = Instant::now();
+
+        // One second of traffic passes at once
+        assert_eq!(limiter.reserve(1000, start), Duration::ZERO);
+        // Then bytes have to be earned back at the rate
+        assert_eq!(limiter.reserve(500, start), Duration::from_millis(500));
+        // Waiting out the debt and a bit more earns new bytes
+        let later = start + Duration::from_millis(750);
+        assert_eq!(limiter.reserve(250, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_unlimited_and_zero_rates_never_wait() {
+        let bandwidth = Bandwidth::default();
+        assert_eq!(
+            bandwidth.download.reserve(u64::MAX, Instant::now()),
+            Duration::ZERO
+        );
+
+        bandwidth.set_limits(BandwidthLimits {
+            upload: Some(0),
+            download: Some(2048),
+        });
+        assert_eq!(
+            bandwidth.limits(),
+            BandwidthLimits {
+                upload: None,
+                download: Some(2048),
+            }
+        );
+        assert_eq!(
+            bandwidth.upload.reserve(u64::MAX, Instant::now()),
+            Duration::ZERO
+        );
+    }
+
+    fn time(value: &str) -> TimeOfDay {
+        TimeOfDay::try_from(value.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(time("7:05"), TimeOfDay::new(7, 5).unwrap());
+        assert_eq!(time("23:59").to_string(), "23:59");
+        for invalid in ["24:00", "12:60", "12", "12:5", "ab:cd", ""] {
+            assert!(TimeOfDay::try_from(invalid.to_string()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_schedule_picks_limits_by_time_of_day() {
+        let bandwidth = Bandwidth::default();
+        bandwidth.set_limits(BandwidthLimits {
+            upload: Some(1000),
+            download: Some(1000),
+        });
+        let capped = BandwidthLimits {
+            upload: Some(100),
+            download: Some(200),
+        };
+        bandwidth.set_schedule(vec![
+            BandwidthWindow {
+                start: time("09:00"),
+                end: time("17:00"),
+                limits: capped,
+            },
+            BandwidthWindow {
+                start: time("22:00"),
+                end: time("06:00"),
+                limits: BandwidthLimits::default(),
+            },
+        ]);
+
+        assert_eq!(bandwidth.limits_at(time("09:00")), capped);
+        assert_eq!(bandwidth.limits_at(time("16:59")), capped);
+        assert_eq!(bandwidth.limits_at(time("17:00")), bandwidth.limits());
+        assert_eq!(
+            bandwidth.limits_at(time("23:30")),
+            BandwidthLimits::default()
+        );
+        assert_eq!(
+            bandwidth.limits_at(time("05:59")),
+            BandwidthLimits::default()
+        );
+        assert_eq!(bandwidth.limits_at(time("06:00")), bandwidth.limits());
+
+        bandwidth.apply(time("10:00"));
+        assert_eq!(bandwidth.active_limits(), capped);
+        bandwidth.apply(time("23:00"));
+        assert_eq!(bandwidth.active_limits(), BandwidthLimits::default());
+    }
+}
@@ -0,0 +1,210 @@
+//! Access to user-selected files under the macOS sandbox
+//!
+//! A sandboxed app may only read the files and folders the user picked in a
+//! dialog, and only under the URL the dialog returned. Access to a path that
+//! was canonicalized, read by a background task or restored after a restart
+//! is not guaranteed. For each shared path a security-scoped bookmark is
+//! created while access is granted; resolving it later starts access again,
+//! which is kept up for as long as the share is served. Bookmarks of saved
+//! shares are stored with them, see [`crate::saved_shares`].
+//!
+//! On other platforms no bookmarks are created and access needs no scope.
+
+use crate::shares::ShareId;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Security-scoped bookmarks of shared paths, by path
+pub type Bookmarks = BTreeMap<PathBuf, Vec<u8>>;
+
+/// Access to a bookmarked path, held until dropped
+pub struct ScopedAccess {
+    #[cfg(target_os = "macos")]
+    url: objc2::rc::Retained<objc2_foundation::NSURL>,
+}
+
+// NSURL is immutable, and Apple documents it as safe to use from any thread
+#[cfg(target_os = "macos")]
+unsafe impl Send for ScopedAccess {}
+
+#[cfg(target_os = "macos")]
+impl Drop for ScopedAccess {
+    // Whether these calls are marked unsafe differs between binding versions
+    #[allow(unused_unsafe)]
+    fn drop(&mut self) {
+        unsafe {
+            self.url.stopAccessingSecurityScopedResource();
+        }
+    }
+}
+
+/// Creates a security-scoped bookmark for a path the app has access to.
+///
+/// # Returns
+///
+/// The bookmark, or None on platforms without sandbox scopes
+///
+/// # Errors
+///
+/// Returns an error if the system refuses to create the bookmark.
+#[cfg(target_os = "macos")]
+#[allow(unused_unsafe)]
+pub fn create_bookmark(path: &Path) -> Result<Option<Vec<u8>>> {
+    use objc2_foundation::{NSURLBookmarkCreationOptions, NSURL};
+
+    let url = NSURL::from_file_path(path)
+        .ok_or_else(|| anyhow::anyhow!("Invalid path '{}'", path.display()))?;
+    let data = unsafe {
+        url.bookmarkDataWithOptions_includingResourceValuesForKeys_relativeToURL_error(
+            NSURLBookmarkCreationOptions::WithSecurityScope,
+            None,
+            None,
+        )
+    }
+    .map_err(|error| {
+        anyhow::anyhow!(
+            "Failed to bookmark '{}': {}",
+            path.display(),
+            error.localizedDescription()
+        )
+    })?;
+    Ok(Some(data.to_vec()))
+}
+
+/// Creates a security-scoped bookmark for a path the app has access to.
+///
+/// # Returns
+///
+/// The bookmark, or None on platforms without sandbox scopes
+///
+/// # Errors
+///
+/// Returns an error if the system refuses to create the bookmark.
+#[cfg(not(target_os = "macos"))]
+pub fn create_bookmark(_path: &Path) -> Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+/// Resolves a bookmark and starts accessing the path it points to.
+///
+/// # Returns
+///
+/// The current path of the bookmarked file or folder, which differs from the
+/// original one if it was moved, and the access to it
+///
+/// # Errors
+///
+/// Returns an error if the bookmark cannot be resolved, e.g. because the file
+/// was deleted, or access is refused.
+#[cfg(target_os = "macos")]
+#[allow(unused_unsafe)]
+pub fn resolve_bookmark(bookmark: &[u8]) -> Result<(PathBuf, ScopedAccess)> {
+    use objc2::runtime::Bool;
+    use objc2_foundation::{NSData, NSURLBookmarkResolutionOptions, NSURL};
+
+    let data = NSData::with_bytes(bookmark);
+    let mut stale = Bool::NO;
+    let url = unsafe {
+        NSURL::URLByResolvingBookmarkData_options_relativeToURL_bookmarkDataIsStale_error(
+            &data,
+            NSURLBookmarkResolutionOptions::WithSecurityScope,
+            None,
+            &mut stale,
+        )
+    }
+    .map_err(|error| {
+        anyhow::anyhow!(
+            "Failed to resolve bookmark: {}",
+            error.localizedDescription()
+        )
+    })?;
+
+    if !unsafe { url.startAccessingSecurityScopedResource() } {
+        anyhow::bail!("Access to the bookmarked path was refused");
+    }
+    let access = ScopedAccess { url };
+    let path = access
+        .url
+        .to_file_path()
+        .ok_or_else(|| anyhow::anyhow!("Bookmark does not point to a file"))?;
+    Ok((path, access))
+}
+
+/// Resolves a bookmark and starts accessing the path it points to.
+///
+/// # Returns
+///
+/// The current path of the bookmarked file or folder, which differs from the
+/// original one if it was moved, and the access to it
+///
+/// # Errors
+///
+/// Returns an error if the bookmark cannot be resolved, e.g. because the file
+/// was deleted, or access is refused.
+#[cfg(not(target_os = "macos"))]
+pub fn resolve_bookmark(_bookmark: &[u8]) -> Result<(PathBuf, ScopedAccess)> {
+    anyhow::bail!("Bookmarks are only supported on macOS")
+}
+
+/// Creates bookmarks for the paths of a share.
+///
+/// Paths that cannot be bookmarked are left out; they can still be read as
+/// long as the app keeps the access the dialog granted.
+pub fn create_bookmarks(paths: &[PathBuf]) -> Bookmarks {
+    paths
+        .iter()
+        .filter_map(|path| match create_bookmark(path) {
+            Ok(bookmark) => bookmark.map(|bookmark| (path.clone(), bookmark)),
+            Err(error) => {
+                tracing::warn!(path = %path.display(), %error, "path not bookmarked");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Access to the bookmarked paths of the shares being served
+#[derive(Clone, Default)]
+pub struct AccessScopes {
+    inner: Arc<Mutex<HashMap<ShareId, Vec<ScopedAccess>>>>,
+}
+
+impl AccessScopes {
+    /// Creates an empty set of scopes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts accessing the bookmarked paths of a share until it is removed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a bookmark cannot be resolved; access to the
+    /// paths resolved before it is kept.
+    pub fn insert(&self, share_id: ShareId, bookmarks: &Bookmarks) -> Result<()> {
+        let mut scopes = Vec::with_capacity(bookmarks.len());
+        let mut result = Ok(());
+        for (path, bookmark) in bookmarks {
+            match resolve_bookmark(bookmark) {
+                Ok((_, access)) => scopes.push(access),
+                Err(error) => {
+                    result = Err(error.context(format!("No access to '{}'", path.display())));
+                    break;
+                }
+            }
+        }
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.insert(share_id, scopes);
+        }
+        result
+    }
+
+    /// Stops accessing the paths of a share that is no longer served
+    pub fn remove(&self, share_id: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.remove(share_id);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Capabilities exchanged between peers
+//!
+//! Before downloading a share, the receiver opens a short connection to the
+//! sender on [`CAPABILITIES_ALPN`] and both sides send each other the
+//! protocol version and optional features they support. The features both
+//! sides support are the ones used for the transfer, so a feature added later
+//! is only used once the other side has it too.
+//!
+//! Peers running a Ginseng that predates the exchange refuse the ALPN. They
+//! are treated as [`Capabilities::legacy`]: the features every such version
+//! already had, and nothing added since. A feature missing from a peer's
+//! message counts as unsupported, so newer fields degrade the same way.
+
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::{Endpoint, EndpointAddr};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// ALPN of the capability exchange
+pub const CAPABILITIES_ALPN: &[u8] = b"ginseng/capabilities/0";
+
+/// Version of the Ginseng protocol spoken by this build
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest capabilities message accepted from a peer
+const MAX_MESSAGE_SIZE: usize = 4 * 1024;
+
+/// How long the exchange may take before the peer is treated as legacy
+const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Protocol version and optional features of a peer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Version of the Ginseng protocol, 0 for peers without the exchange
+    pub version: u32,
+    /// ALPN of the blobs protocol files are transferred over
+    #[serde(default)]
+    pub blobs_alpn: String,
+    /// Whether zstd compressed files can be served and unpacked
+    #[serde(default)]
+    pub compression: bool,
+    /// Whether payloads can be encrypted end to end on top of the connection
+    #[serde(default)]
+    pub encryption: bool,
+    /// Whether interrupted downloads continue from the data already received
+    #[serde(default)]
+    pub resume: bool,
+}
+
+impl Capabilities {
+    /// Returns the capabilities of this build
+    pub fn local() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            blobs_alpn: blobs_alpn(),
+            compression: true,
+            encryption: false,
+            resume: true,
+        }
+    }
+
+    /// Returns the capabilities assumed for a peer without the exchange
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            blobs_alpn: blobs_alpn(),
+            compression: true,
+            encryption: false,
+            resume: true,
+        }
+    }
+
+    /// Returns the capabilities both sides support
+    ///
+    /// The lower of the two protocol versions is used. The blobs ALPN is kept
+    /// only if both sides name the same one.
+    pub fn negotiate(&self, peer: &Capabilities) -> Capabilities {
+        Capabilities {
+            version: self.version.min(peer.version),
+            blobs_alpn: if self.blobs_alpn == peer.blobs_alpn {
+                self.blobs_alpn.clone()
+            } else {
+                String::new()
+            },
+            compression: self.compression && peer.compression,
+            encryption: self.encryption && peer.encryption,
+            resume: self.resume && peer.resume,
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features: Vec<&str> = [
+            ("compression", self.compression),
+            ("encryption", self.encryption),
+            ("resume", self.resume),
+        ]
+        .into_iter()
+        .filter_map(|(name, supported)| supported.then_some(name))
+        .collect();
+        write!(
+            f,
+            "v{} over {} ({})",
+            self.version,
+            self.blobs_alpn,
+            features.join(", ")
+        )
+    }
+}
+
+/// Returns the ALPN of the blobs protocol as text
+fn blobs_alpn() -> String {
+    String::from_utf8_lossy(iroh_blobs::protocol::ALPN).into_owned()
+}
+
+/// Exchanges capabilities with a peer and negotiates the ones to use.
+///
+/// A peer that cannot be reached on [`CAPABILITIES_ALPN`] in time, or that
+/// answers with an invalid message, is treated as [`Capabilities::legacy`];
+/// whether the peer is online at all shows once the transfer starts.
+///
+/// # Returns
+///
+/// The capabilities both sides support
+pub async fn exchange_capabilities(endpoint: &Endpoint, peer: EndpointAddr) -> Capabilities {
+    let local = Capabilities::local();
+    let id = peer.id;
+    let remote = tokio::time::timeout(EXCHANGE_TIMEOUT, request_capabilities(endpoint, peer))
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out"))
+        .and_then(|result| result);
+    let remote = match remote {
+        Ok(remote) => remote,
+        Err(error) => {
+            tracing::debug!(peer = %id, %error, "no capabilities exchanged, assuming legacy peer");
+            Capabilities::legacy()
+        }
+    };
+
+    let negotiated = local.negotiate(&remote);
+    tracing::debug!(peer = %id, version = negotiated.version, "capabilities negotiated");
+    negotiated
+}
+
+/// Sends this build's capabilities to a peer and reads the peer's.
+async fn request_capabilities(
+    endpoint: &Endpoint,
+    peer: EndpointAddr,
+) -> anyhow::Result<Capabilities> {
+    let connection = endpoint.connect(peer, CAPABILITIES_ALPN).await?;
+
+    let (mut send, mut recv) = connection.open_bi().await?;
+    send.write_all(&serde_json::to_vec(&Capabilities::local())?)
+        .await?;
+    send.finish()?;
+
+    let response_bytes = recv.read_to_end(MAX_MESSAGE_SIZE).await?;
+    connection.close(0u32.into(), b"done");
+
+    Ok(serde_json::from_slice(&response_bytes)?)
+}
+
+/// Protocol handler answering capability exchanges
+#[derive(Debug, Clone, Default)]
+pub struct CapabilityExchange;
+
+impl CapabilityExchange {
+    /// Reads a peer's capabilities and answers with this build's.
+    async fn handle_connection(&self, connection: Connection) -> anyhow::Result<()> {
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let request_bytes = recv.read_to_end(MAX_MESSAGE_SIZE).await?;
+        if let Ok(peer) = serde_json::from_slice::<Capabilities>(&request_bytes) {
+            tracing::debug!(version = peer.version, "peer sent its capabilities");
+        }
+
+        send.write_all(&serde_json::to_vec(&Capabilities::local())?)
+            .await?;
+        send.finish()?;
+        connection.closed().await;
+
+        Ok(())
+    }
+}
+
+impl ProtocolHandler for CapabilityExchange {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        self.handle_connection(connection)
+            .await
+            .map_err(|error| AcceptError::from_err(std::io::Error::other(error.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_keeps_features_both_sides_support() {
+        let peer = Capabilities {
+            version: 3,
+            blobs_alpn: blobs_alpn(),
+            compression: false,
+            encryption: true,
+            resume: true,
+        };
+        assert_eq!(
+            Capabilities::local().negotiate(&peer),
+            Capabilities {
+                version: PROTOCOL_VERSION,
+                blobs_alpn: blobs_alpn(),
+                compression: false,
+                encryption: false,
+                resume: true,
+            }
+        );
+
+        let other_blobs = Capabilities {
+            blobs_alpn: "/iroh-bytes/1".to_string(),
+            ..Capabilities::local()
+        };
+        assert!(Capabilities::local()
+            .negotiate(&other_blobs)
+            .blobs_alpn
+            .is_empty());
+    }
+
+    #[test]
+    fn test_missing_features_count_as_unsupported() {
+        let peer: Capabilities = serde_json::from_str(r#"{"version":1}"#).unwrap();
+        let negotiated = Capabilities::local().negotiate(&peer);
+        assert_eq!(negotiated.version, 1);
+        assert!(!negotiated.compression && !negotiated.encryption && !negotiated.resume);
+
+        let legacy = Capabilities::local().negotiate(&Capabilities::legacy());
+        assert_eq!(legacy.version, 0);
+        assert!(legacy.compression && legacy.resume);
+    }
+}
@@ -0,0 +1,266 @@
+//! Cleanup of partial state left behind by failed or cancelled transfers
+//!
+//! A [`TransferCleanup`] records everything a transfer creates: blobs ingested
+//! into the store, files exported to disk, and directories created for them.
+//! If the transfer finishes it is disarmed and nothing happens. If the transfer
+//! fails, running it removes the partial state and reports how much space was
+//! reclaimed. If the transfer future is dropped mid-way (e.g., cancelled), the
+//! cleanup runs in the background from `Drop`.
+
+use anyhow::Result;
+use futures::StreamExt;
+use iroh_blobs::store::mem::MemStore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+/// Summary of the partial state removed after a transfer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupReport {
+    /// Number of partially exported files removed from disk
+    pub removed_files: u64,
+    /// Number of blob tags dropped from the store
+    pub dropped_tags: u64,
+    /// Bytes reclaimed from disk and the blob store
+    pub reclaimed_bytes: u64,
+}
+
+/// Callback receiving the report once cleanup has finished
+type ReportCallback = Box<dyn FnOnce(CleanupReport) + Send>;
+
+/// Partial state recorded for a transfer
+struct PartialState {
+    store: MemStore,
+    files: Vec<(PathBuf, u64)>,
+    directories: Vec<PathBuf>,
+    blobs: HashMap<String, u64>,
+}
+
+/// Guard that removes a transfer's partial state unless disarmed
+pub struct TransferCleanup {
+    state: Option<PartialState>,
+    on_report: Option<ReportCallback>,
+}
+
+impl TransferCleanup {
+    /// Creates a cleanup guard for a transfer using the given store
+    pub fn new(store: MemStore) -> Self {
+        Self {
+            state: Some(PartialState {
+                store,
+                files: Vec::new(),
+                directories: Vec::new(),
+                blobs: HashMap::new(),
+            }),
+            on_report: None,
+        }
+    }
+
+    /// Sets a callback invoked with the report whenever cleanup runs
+    ///
+    /// This includes cleanup triggered by dropping the guard, so cancelled
+    /// transfers can still report reclaimed space.
+    pub fn on_report<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(CleanupReport) + Send + 'static,
+    {
+        self.on_report = Some(Box::new(callback));
+        self
+    }
+
+    /// Records a directory created by the transfer
+    ///
+    /// Empty directories below and including it are removed during cleanup.
+    pub fn track_directory(&mut self, directory: PathBuf) {
+        if let Some(state) = self.state.as_mut() {
+            state.directories.push(directory);
+        }
+    }
+
+    /// Records a file about to be exported to disk by the transfer
+    ///
+    /// Files that already exist are not tracked, so cleanup never removes
+    /// data the transfer did not create.
+    pub fn track_file(&mut self, path: PathBuf, size: u64) {
+        if path.exists() {
+            return;
+        }
+        if let Some(state) = self.state.as_mut() {
+            state.files.push((path, size));
+        }
+    }
+
+    /// Records a blob ingested into the store by the transfer
+    pub fn track_blob(&mut self, hash: String, size: u64) {
+        if let Some(state) = self.state.as_mut() {
+            state.blobs.insert(hash, size);
+        }
+    }
+
+    /// Marks the transfer as finished so its state is kept
+    pub fn disarm(mut self) {
+        self.state = None;
+    }
+
+    /// Removes the partial state now and returns what was reclaimed
+    pub async fn run(mut self) -> CleanupReport {
+        let Some(state) = self.state.take() else {
+            return CleanupReport::default();
+        };
+
+        let report = remove_partial_state(state).await;
+        if let Some(callback) = self.on_report.take() {
+            callback(report.clone());
+        }
+        report
+    }
+}
+
+impl Drop for TransferCleanup {
+    fn drop(&mut self) {
+        let Some(state) = self.state.take() else {
+            return;
+        };
+        let on_report = self.on_report.take();
+
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let report = remove_partial_state(state).await;
+                if let Some(callback) = on_report {
+                    callback(report);
+                }
+            });
+        }
+    }
+}
+
+/// Removes exported files, empty directories, and blob tags of a transfer.
+///
+/// Cleanup is best effort: anything that cannot be removed is left in place
+/// and not counted in the report.
+async fn remove_partial_state(state: PartialState) -> CleanupReport {
+    let mut report = CleanupReport::default();
+
+    for (path, size) in &state.files {
+        if fs::remove_file(path).await.is_ok() {
+            report.removed_files += 1;
+            report.reclaimed_bytes += size;
+        }
+    }
+
+    for directory in &state.directories {
+        remove_empty_directories(directory).await;
+    }
+
+    if let Ok((dropped_tags, reclaimed_bytes)) = drop_blob_tags(&state.store, &state.blobs).await {
+        report.dropped_tags = dropped_tags;
+        report.reclaimed_bytes += reclaimed_bytes;
+    }
+
+    report
+}
+
+/// Removes a directory and all empty directories below it, deepest first.
+async fn remove_empty_directories(directory: &Path) {
+    let directories: Vec<PathBuf> = WalkDir::new(directory)
+        .contents_first(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_dir())
+        .map(|entry| entry.into_path())
+        .collect();
+
+    for directory in directories {
+        let _ = fs::remove_dir(directory).await;
+    }
+}
+
+/// Deletes the store tags protecting the given blobs so they can be collected.
+///
+/// Returns the number of tags dropped and the size of the blobs they protected.
+pub(crate) async fn drop_blob_tags(
+    store: &MemStore,
+    blobs: &HashMap<String, u64>,
+) -> Result<(u64, u64)> {
+    if blobs.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let mut tags = store
+        .tags()
+        .list()
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to list tags: {}", error))?;
+
+    let mut dropped_tags = 0;
+    let mut reclaimed_bytes = 0;
+    while let Some(tag) = tags.next().await {
+        let Ok(tag) = tag else {
+            continue;
+        };
+        let Some(size) = blobs.get(&tag.hash.to_string()) else {
+            continue;
+        };
+
+        if store.tags().delete(tag.name).await.is_ok() {
+            dropped_tags += 1;
+            reclaimed_bytes += size;
+        }
+    }
+
+    Ok((dropped_tags, reclaimed_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_run_removes_partial_exports() {
+        let temp_dir = tempdir().unwrap();
+        let target = temp_dir.path().join("share");
+        let nested = target.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let mut cleanup = TransferCleanup::new(MemStore::new());
+        cleanup.track_directory(target.clone());
+        cleanup.track_file(nested.join("partial.bin"), 5);
+        std::fs::write(nested.join("partial.bin"), b"12345").unwrap();
+
+        let report = cleanup.run().await;
+        assert_eq!(report.removed_files, 1);
+        assert_eq!(report.reclaimed_bytes, 5);
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn test_disarm_keeps_files() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("done.bin");
+
+        let mut cleanup = TransferCleanup::new(MemStore::new());
+        cleanup.track_file(path.clone(), 4);
+        std::fs::write(&path, b"data").unwrap();
+        cleanup.disarm();
+
+        assert!(path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_existing_files_are_not_removed() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("existing.bin");
+        std::fs::write(&path, b"data").unwrap();
+
+        let mut cleanup = TransferCleanup::new(MemStore::new());
+        cleanup.track_file(path.clone(), 4);
+
+        let report = cleanup.run().await;
+        assert_eq!(report.removed_files, 0);
+        assert!(path.exists());
+    }
+}
@@ -1,10 +1,33 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use ginseng_lib::{
-    core::{FileInfo, ShareMetadata, ShareType},
+    bandwidth::BandwidthLimits,
+    compression::Compression,
+    core::{
+        content_root, split_pasted_tickets, DownloadError, FileInfo, NodeInfo, NotAGinsengShare,
+        ShareMetadata, ShareOptions, ShareType, SkippedFile,
+    },
+    disk::validate_download_directory,
+    dns::{format_txt_record, looks_like_domain},
+    dropbox::{AcceptRules, DropEvent, DropResponse},
+    exclude::ExcludeRules,
+    history::{Outcome as HistoryOutcome, TransferHistory},
+    logging,
+    progress::{FileProgress, FileStatus, ProgressEvent, TransferProgress, TransferType},
+    relays::{RelayPreference, RelayRegion},
+    running::{RunningShare, RunningShares},
+    tickets::{resolve_ticket_input, save_ticket_file, TicketFile},
+    watcher::{watch_ticket_folder, WatchEvent},
     GinsengCore,
 };
+use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::ipc::{Channel, InvokeResponseBody};
 
 #[derive(Parser)]
 #[command(name = "ginseng-cli")]
@@ -14,8 +37,35 @@ struct Args {
     #[command(subcommand)]
     command: Commands,
 
+    /// Log debug messages, including each blob requested from shares
     #[arg(short, long)]
     verbose: bool,
+
+    /// Maximum upload rate in bytes per second, e.g. 500K or 2M
+    #[arg(long, global = true, value_name = "RATE", value_parser = parse_rate)]
+    upload_limit: Option<u64>,
+
+    /// Maximum download rate in bytes per second, e.g. 500K or 2M
+    #[arg(long, global = true, value_name = "RATE", value_parser = parse_rate)]
+    download_limit: Option<u64>,
+
+    /// Only connect through the public relay of this region: na, eu or ap (repeatable)
+    #[arg(long = "relay-region", global = true, value_name = "REGION")]
+    relay_regions: Vec<RelayRegion>,
+
+    /// Also connect through this relay server, e.g. https://relay.example.com;
+    /// the fastest relay is used (repeatable)
+    #[arg(long = "relay", global = true, value_name = "URL")]
+    relays: Vec<String>,
+
+    /// Directory received shares are saved to (defaults to the Downloads folder)
+    #[arg(long, global = true, value_name = "DIR")]
+    download_dir: Option<PathBuf>,
+
+    /// Ask the router to forward this node's port over UPnP, NAT-PMP or PCP,
+    /// so peers can connect directly
+    #[arg(long, global = true)]
+    port_mapping: bool,
 }
 
 #[derive(Subcommand)]
@@ -26,71 +76,1251 @@ enum Commands {
 
         #[arg(long)]
         files_only: bool,
+
+        /// Also save the ticket to a file that can be handed to the recipient
+        #[arg(long, value_name = "FILE")]
+        ticket_file: Option<PathBuf>,
+
+        /// Note stored in the ticket file
+        #[arg(long, requires = "ticket_file")]
+        note: Option<String>,
+
+        /// Expire the share after this long, e.g. 90s, 30m or 2h; receivers are then
+        /// refused and the ticket file expires too
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Custom field attached to the share as KEY=VALUE (repeatable)
+        #[arg(long = "field", value_name = "KEY=VALUE", value_parser = parse_custom_field)]
+        custom_fields: Vec<(String, String)>,
+
+        /// Stop sharing after this many completed downloads
+        #[arg(long, value_name = "DOWNLOADS", value_parser = clap::value_parser!(u64).range(1..))]
+        expire: Option<u64>,
+
+        /// Make the ticket one-time-use: refuse everyone after the first complete download
+        #[arg(long)]
+        once: bool,
+
+        /// Compress file data with zstd, which speeds up text-heavy shares on slow links
+        #[arg(long)]
+        compress: bool,
+
+        /// Send a single directory as one tar archive, much faster for many small files
+        #[arg(long, conflicts_with = "files_only")]
+        archive: bool,
+
+        /// Leave out files and folders of shared directories matching this
+        /// .gitignore-style pattern, e.g. node_modules or '*.log' (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+
+        /// Also follow .gitignore and .ginsengignore files in shared directories
+        #[arg(long)]
+        ignore_files: bool,
+
+        /// Only share the file list until you press Enter to unlock the files,
+        /// e.g. once the recipient has confirmed
+        #[arg(long)]
+        preview: bool,
+
+        /// Print the ticket as JSON on stdout instead of text; messages for
+        /// people go to stderr
+        #[arg(long)]
+        json: bool,
     },
+    #[command(
+        after_help = "Exit codes: 0 success, 1 error, 2 some files failed, 3 invalid ticket, \
+                      4 peer offline, 5 share expired, 130 cancelled. With --json, stdout only \
+                      carries a JSON summary of the outcome."
+    )]
     Receive {
+        /// A ticket string (or several pasted together), the path to a ticket file,
+        /// or a domain publishing a ticket
         #[arg(value_name = "TICKET")]
         ticket: String,
+
+        /// Receive a plain iroh-blobs ticket as a single file instead of a Ginseng share
+        #[arg(long)]
+        raw: bool,
+
+        /// Directory to save the share in, laid out as usual (defaults to --download-dir
+        /// or the Downloads folder); with --raw, the file or directory to save the blob
+        /// to (defaults to the current directory)
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Keep running and download again whenever the domain or ticket file publishes a new ticket
+        #[arg(long, conflicts_with = "raw")]
+        watch: bool,
+
+        /// How often to check for a new ticket in watch mode, e.g. 30s or 5m
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "30s",
+            value_parser = parse_duration,
+            requires = "watch"
+        )]
+        interval: Duration,
+
+        /// Print the outcome as JSON on stdout, with the metadata of every
+        /// received share; messages for people go to stderr
+        #[arg(long)]
+        json: bool,
+    },
+    Info {
+        /// Print the node information as JSON on stdout
+        #[arg(long)]
+        json: bool,
+    },
+    /// Open a drop box and receive files pushed by other peers until stopped
+    DropBox {
+        /// Name shown to senders
+        #[arg(long, default_value = "Ginseng drop box")]
+        name: String,
+
+        /// Endpoint ID allowed to push files (repeatable; anyone if omitted)
+        #[arg(long = "allow", value_name = "ENDPOINT_ID")]
+        allowed_peers: Vec<String>,
+
+        /// Maximum total size of a single pushed share in bytes
+        #[arg(long, value_name = "BYTES")]
+        max_size: Option<u64>,
+
+        /// Directory pushed shares are saved to (defaults to --download-dir or the Downloads folder)
+        #[arg(long, value_name = "DIR")]
+        output: Option<PathBuf>,
+
+        /// JSON file with accept rules, including per-peer size caps and directories
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
     },
-    Info,
+    /// Push files to another peer's drop box
+    Push {
+        #[arg(value_name = "DROP_TICKET")]
+        drop_ticket: String,
+
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Show which files were added, removed or changed between two shares,
+    /// without downloading either
+    Diff {
+        /// Ticket, ticket file or domain of the older share
+        #[arg(value_name = "BEFORE")]
+        before: String,
+
+        /// Ticket, ticket file or domain of the newer share
+        #[arg(value_name = "AFTER")]
+        after: String,
+    },
+    /// Print the DNS TXT record that publishes a ticket under a domain
+    DnsRecord {
+        #[arg(value_name = "DOMAIN")]
+        domain: String,
+
+        #[arg(value_name = "TICKET")]
+        ticket: String,
+    },
+    /// Watch a folder and automatically receive every `.ginseng-ticket` file dropped into it
+    Watch {
+        #[arg(value_name = "DIR")]
+        directory: PathBuf,
+    },
+    /// List finished shares and downloads of the app, newest first
+    History {
+        /// Only list this many transfers
+        #[arg(long, value_name = "COUNT", default_value_t = 20)]
+        limit: usize,
+
+        /// Remove every transfer from the history instead of listing them
+        #[arg(long)]
+        clear: bool,
+    },
+    /// List the shares served by running `send` commands
+    Shares,
+    /// Stop a share served by a running `send`, or list the running shares if none is given
+    Stop {
+        /// The share ID or ticket printed by `send`
+        #[arg(value_name = "SHARE")]
+        share: Option<String>,
+    },
+}
+
+impl Commands {
+    /// Checks whether the command was asked for JSON output
+    fn json(&self) -> bool {
+        match self {
+            Commands::Send { json, .. }
+            | Commands::Receive { json, .. }
+            | Commands::Info { json } => *json,
+            _ => false,
+        }
+    }
+}
+
+/// Outcome of a command, reported through the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Success,
+    Failed,
+    PartialFailure,
+    InvalidTicket,
+    PeerOffline,
+    ShareExpired,
+    Cancelled,
+}
+
+impl Outcome {
+    fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Success => 0,
+            Outcome::Failed => 1,
+            Outcome::PartialFailure => 2,
+            Outcome::InvalidTicket => 3,
+            Outcome::PeerOffline => 4,
+            Outcome::ShareExpired => 5,
+            Outcome::Cancelled => 130,
+        }
+    }
+
+    fn from_error(error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<Cancelled>().is_some() {
+            return Outcome::Cancelled;
+        }
+        match error.downcast_ref::<DownloadError>() {
+            Some(DownloadError::InvalidTicket(_)) => Outcome::InvalidTicket,
+            Some(DownloadError::PeerOffline(_)) => Outcome::PeerOffline,
+            Some(DownloadError::ShareExpired) => Outcome::ShareExpired,
+            Some(DownloadError::PartialFailure { .. }) => Outcome::PartialFailure,
+            None => Outcome::Failed,
+        }
+    }
+}
+
+/// Error returned when the user interrupts a download with Ctrl+C
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Machine-readable summary printed with `--json`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Summary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    share_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_root: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped_files: Vec<SkippedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    download_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed_files: Vec<SkippedFile>,
+    /// Metadata of the received shares, with every file; only with `--json`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shares: Vec<ShareMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node: Option<NodeInfo>,
+}
+
+impl Summary {
+    /// Keeps what was downloaded before a download failed partway
+    fn from_error(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<DownloadError>() {
+            Some(DownloadError::PartialFailure {
+                download_path,
+                downloaded,
+                failed,
+            }) => Summary {
+                download_path: Some(download_path.to_string_lossy().to_string()),
+                files: Some(*downloaded),
+                failed_files: failed.clone(),
+                ..Summary::default()
+            },
+            _ => Summary::default(),
+        }
+    }
+}
+
+/// The summary line with the outcome it belongs to
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryLine {
+    status: Outcome,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten)]
+    summary: Summary,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    logging::init(args.verbose);
+    let json = args.command.json();
 
-    if let Err(error) = run(args).await {
-        eprintln!("Error: {}", error);
-        std::process::exit(1);
+    let (outcome, error, summary) = match run(args).await {
+        Ok(summary) => (Outcome::Success, None, summary),
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            let summary = Summary::from_error(&error);
+            (Outcome::from_error(&error), Some(error), summary)
+        }
+    };
+
+    if json {
+        display_summary_line(outcome, error.as_ref(), summary);
     }
+    std::process::exit(outcome.exit_code());
 }
 
-async fn run(args: Args) -> Result<()> {
-    let ginseng = GinsengCore::new().await?;
+async fn run(args: Args) -> Result<Summary> {
+    let relays = RelayPreference {
+        regions: (!args.relay_regions.is_empty()).then_some(args.relay_regions),
+        custom_urls: args.relays,
+    };
+    let ginseng = GinsengCore::with_relays(&relays).await?;
+    ginseng.set_bandwidth_limits(BandwidthLimits {
+        upload: args.upload_limit,
+        download: args.download_limit,
+    });
+    if let Some(download_dir) = &args.download_dir {
+        validate_download_directory(download_dir).await?;
+        ginseng.set_download_directory(Some(download_dir.clone()));
+    }
+    ginseng.set_port_mapping(args.port_mapping);
 
     match args.command {
-        Commands::Send { paths, files_only } => handle_send(ginseng, paths, files_only).await,
-        Commands::Receive { ticket } => handle_receive(ginseng, ticket).await,
-        Commands::Info => handle_info(ginseng).await,
+        Commands::Send {
+            paths,
+            files_only,
+            ticket_file,
+            note,
+            timeout,
+            expire,
+            once,
+            compress,
+            archive,
+            custom_fields,
+            exclude,
+            ignore_files,
+            preview,
+            json,
+        } => {
+            let options = SendOptions {
+                files_only,
+                ticket_file,
+                note,
+                timeout,
+                expire,
+                once,
+                compress,
+                archive,
+                custom_fields: custom_fields.into_iter().collect(),
+                exclude: ExcludeRules {
+                    patterns: exclude,
+                    ignore_files,
+                },
+                preview,
+            };
+            handle_send(ginseng, paths, options, json).await
+        }
+        Commands::Receive {
+            ticket,
+            raw: true,
+            output,
+            json,
+            ..
+        } => until_cancelled(handle_receive_raw(ginseng, ticket, output, json)).await,
+        Commands::Receive {
+            ticket,
+            output,
+            watch,
+            interval,
+            json,
+            ..
+        } => {
+            if let Some(output) = &output {
+                validate_download_directory(output).await?;
+            }
+            if watch {
+                handle_receive_watch(ginseng, ticket, interval, output, json).await
+            } else {
+                until_cancelled(handle_receive(ginseng, ticket, output, json)).await
+            }
+        }
+        Commands::Info { json } => handle_info(ginseng, json).await,
+        Commands::Watch { directory } => handle_watch(ginseng, directory)
+            .await
+            .map(|()| Summary::default()),
+        Commands::DropBox {
+            name,
+            allowed_peers,
+            max_size,
+            output,
+            rules,
+        } => {
+            let mut rules = match rules {
+                Some(path) => AcceptRules::load(&path).await?,
+                None => AcceptRules::default(),
+            };
+            rules.allowed_peers.extend(allowed_peers);
+            rules.max_share_size = max_size.or(rules.max_share_size);
+            rules.target_directory = output.or(rules.target_directory).or(args.download_dir);
+            handle_drop_box(ginseng, name, rules)
+                .await
+                .map(|()| Summary::default())
+        }
+        Commands::Push { drop_ticket, paths } => handle_push(ginseng, drop_ticket, paths)
+            .await
+            .map(|()| Summary::default()),
+        Commands::Diff { before, after } => handle_diff(ginseng, before, after)
+            .await
+            .map(|()| Summary::default()),
+        Commands::DnsRecord { domain, ticket } => {
+            handle_dns_record(&domain, &ticket);
+            Ok(Summary::default())
+        }
+        Commands::History { limit, clear } => handle_history(limit, clear)
+            .await
+            .map(|()| Summary::default()),
+        Commands::Shares => handle_shares().await.map(|()| Summary::default()),
+        Commands::Stop { share } => handle_stop(share).await.map(|()| Summary::default()),
+    }
+}
+
+/// Runs a download until it finishes or the user presses Ctrl+C.
+async fn until_cancelled<F>(download: F) -> Result<Summary>
+where
+    F: std::future::Future<Output = Result<Summary>>,
+{
+    tokio::select! {
+        result = download => result,
+        _ = tokio::signal::ctrl_c() => Err(Cancelled.into()),
     }
 }
 
-async fn handle_send(ginseng: GinsengCore, paths: Vec<PathBuf>, files_only: bool) -> Result<()> {
+/// Progress bars of a transfer, drawn from its progress events
+///
+/// A bar per file is shown while the file is transferred, above a bar for
+/// the whole transfer with its rate and the time left. Nothing is drawn when
+/// stderr is not a terminal.
+#[derive(Clone)]
+struct ProgressDisplay {
+    bars: MultiProgress,
+    total: ProgressBar,
+    files: Arc<Mutex<HashMap<String, ProgressBar>>>,
+}
+
+impl ProgressDisplay {
+    fn new() -> Self {
+        let bars = MultiProgress::new();
+        let total = bars.add(
+            ProgressBar::new(0)
+                .with_style(bar_style("{bar:40.cyan/blue} {bytes}/{total_bytes} {msg}")),
+        );
+        Self {
+            bars,
+            total,
+            files: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns a channel that updates the bars with the events sent to it.
+    fn channel(&self) -> Channel<ProgressEvent> {
+        let display = self.clone();
+        Channel::new(move |body| {
+            if let InvokeResponseBody::Json(json) = body {
+                if let Ok(event) = serde_json::from_str(&json) {
+                    display.update(event);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn update(&self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::TransferStarted { transfer }
+            | ProgressEvent::TransferProgress { transfer } => self.show_transfer(&transfer),
+            ProgressEvent::FileProgress { file, .. } => self.show_file(&file),
+            ProgressEvent::StageChanged {
+                message: Some(message),
+                ..
+            } => self.total.set_message(message),
+            ProgressEvent::StageProgress {
+                message,
+                processed_bytes,
+                ..
+            } => self.total.set_message(format!(
+                "{} ({})",
+                message,
+                format_file_size(processed_bytes)
+            )),
+            ProgressEvent::SourceChanged { path, .. } => {
+                self.bars
+                    .println(format!("⚠️  {} changed while being shared", path))
+                    .ok();
+            }
+            ProgressEvent::TransferCompleted { transfer }
+            | ProgressEvent::TransferCancelled { transfer }
+            | ProgressEvent::TransferFailed { transfer, .. } => {
+                self.show_transfer(&transfer);
+                self.clear_files();
+                self.total.finish();
+            }
+            ProgressEvent::StageChanged { .. } | ProgressEvent::CleanupCompleted { .. } => {}
+        }
+    }
+
+    fn show_transfer(&self, transfer: &TransferProgress) {
+        self.total.set_length(transfer.total_bytes);
+        self.total.set_position(transfer.transferred_bytes);
+        if transfer.total_files > 0 {
+            let mut message = format!(
+                "{}/{} files",
+                transfer.completed_files, transfer.total_files
+            );
+            if let Some(rate) = transfer.transfer_rate {
+                message.push_str(&format!(" · {}/s", format_file_size(rate)));
+            }
+            if let Some(eta) = transfer.eta_seconds {
+                message.push_str(&format!(
+                    " · {} left",
+                    HumanDuration(Duration::from_secs(eta))
+                ));
+            }
+            self.total.set_message(message);
+        }
+        for file in &transfer.files {
+            self.show_file(file);
+        }
+    }
+
+    fn show_file(&self, file: &FileProgress) {
+        let Ok(mut files) = self.files.lock() else {
+            return;
+        };
+        match file.status {
+            FileStatus::Pending => {}
+            FileStatus::Transferring | FileStatus::Writing => {
+                let bar = files.entry(file.file_id.clone()).or_insert_with(|| {
+                    let bar = self.bars.insert_before(
+                        &self.total,
+                        ProgressBar::new(file.total_bytes)
+                            .with_style(bar_style("  {bar:40} {bytes}/{total_bytes} {wide_msg}")),
+                    );
+                    bar.set_message(file.relative_path.clone());
+                    bar
+                });
+                bar.set_length(file.total_bytes);
+                bar.set_position(file.transferred_bytes);
+            }
+            FileStatus::Completed | FileStatus::Failed | FileStatus::Skipped => {
+                if let Some(bar) = files.remove(&file.file_id) {
+                    bar.finish_and_clear();
+                    self.bars.remove(&bar);
+                }
+            }
+        }
+    }
+
+    fn clear_files(&self) {
+        if let Ok(mut files) = self.files.lock() {
+            for (_, bar) in files.drain() {
+                bar.finish_and_clear();
+                self.bars.remove(&bar);
+            }
+        }
+    }
+
+    /// Removes the bars of a transfer that ended without a final event,
+    /// e.g. because it failed before it started.
+    fn finish(&self) {
+        self.clear_files();
+        if !self.total.is_finished() {
+            self.total.finish_and_clear();
+        }
+    }
+}
+
+fn bar_style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ")
+}
+
+/// Downloads a share, showing its progress while it runs.
+///
+/// The share is saved in `output` if given, and the download folder otherwise.
+async fn download_with_progress(
+    ginseng: &GinsengCore,
+    ticket: String,
+    output: Option<&Path>,
+) -> Result<(ShareMetadata, PathBuf)> {
+    let progress = ProgressDisplay::new();
+    let result = ginseng
+        .download_files_with_progress(progress.channel(), ticket, output.map(Path::to_path_buf))
+        .await;
+    progress.finish();
+    result
+}
+
+/// Options of the send command
+struct SendOptions {
+    files_only: bool,
+    ticket_file: Option<PathBuf>,
+    note: Option<String>,
+    timeout: Option<Duration>,
+    expire: Option<u64>,
+    once: bool,
+    compress: bool,
+    archive: bool,
+    custom_fields: BTreeMap<String, String>,
+    exclude: ExcludeRules,
+    preview: bool,
+}
+
+async fn handle_send(
+    ginseng: GinsengCore,
+    paths: Vec<PathBuf>,
+    options: SendOptions,
+    json: bool,
+) -> Result<Summary> {
     validate_paths_exist(&paths)?;
 
-    if files_only {
+    if options.files_only {
         validate_paths_are_files(&paths)?;
     }
 
-    display_sharing_summary(&paths);
+    if !json {
+        display_sharing_summary(&paths);
+    }
 
-    println!("\nGenerating share ticket...");
-    let ticket = ginseng.share_files_cli(paths).await?;
+    notice(json, "\nGenerating share ticket...");
+    let progress = ProgressDisplay::new();
+    let share = ginseng
+        .share_files_with_progress(
+            progress.channel(),
+            paths.clone(),
+            options.custom_fields,
+            ShareOptions {
+                expires_in: options.timeout,
+                single_use: options.once,
+                compression: if options.compress {
+                    Compression::Zstd
+                } else {
+                    Compression::None
+                },
+                archive: options.archive,
+                delta: false,
+                exclude: options.exclude,
+                max_size: None,
+                preview: options.preview,
+            },
+        )
+        .await;
+    progress.finish();
+    let share = share?;
+    let ticket = share.ticket;
 
-    display_share_ticket(&ticket);
+    let summary = Summary {
+        ticket: Some(ticket.clone()),
+        share_id: Some(share.share_id.clone()),
+        content_root: Some(share.content_root.clone()),
+        skipped_files: share.skipped_files,
+        ..Summary::default()
+    };
+    if json {
+        // Printed right away, as sharing goes on until stopped
+        display_summary_line(Outcome::Success, None, summary.clone());
+    } else {
+        display_skipped_files(&summary.skipped_files);
+        display_share_ticket(&ticket, &share.share_id, &share.content_root);
+    }
 
-    tokio::signal::ctrl_c().await?;
-    println!("\nStopped sharing.");
+    if let Some(ticket_file) = options.ticket_file {
+        let mut contents = TicketFile::new(ticket.clone());
+        contents.note = options.note;
+        contents.expires_at = options.timeout.map(|timeout| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                + timeout.as_secs()
+        });
+        save_ticket_file(&ticket_file, &contents).await?;
+        notice(
+            json,
+            format!("💾 Ticket saved to {}", ticket_file.display()),
+        );
+    }
 
+    let timeout = async {
+        match options.timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    // A one-time ticket is of no use after its download, so stop sharing then too
+    let downloads = options.expire.or(options.once.then_some(1));
+    let expired = async {
+        match downloads {
+            Some(downloads) => stop_after_downloads(&ginseng, &share.share_id, downloads).await,
+            None => std::future::pending().await,
+        }
+    };
+    // Never finishes once unlocked, so sharing goes on as usual
+    let unlocked = async {
+        if options.preview {
+            notice(
+                json,
+                "🔒 Only the file list is shared. Press Enter to unlock the files.",
+            );
+            wait_for_enter().await;
+            ginseng.unlock_share(&share.share_id).await?;
+            notice(json, "🔓 Files unlocked.");
+        }
+        std::future::pending::<Result<&str>>().await
+    };
+
+    let running = RunningShares::open_default()?;
+    running
+        .add(&RunningShare {
+            share_id: share.share_id.clone(),
+            ticket: ticket.clone(),
+            paths,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+        .await?;
+
+    let outcome = tokio::select! {
+        result = tokio::signal::ctrl_c() => result
+            .map(|()| "Stopped sharing.")
+            .map_err(anyhow::Error::from),
+        _ = timeout => Ok("⏱️  Timeout reached, stopped sharing."),
+        result = expired => result.map(|()| "✅ Download limit reached, stopped sharing."),
+        result = unlocked => result,
+        _ = wait_for_stop(&running, &share.share_id) => ginseng
+            .stop_share(&share.share_id)
+            .await
+            .map(|()| "🛑 Stopped sharing on request."),
+    };
+
+    running.remove(&share.share_id).await.ok();
+    notice(json, format!("\n{}", outcome?));
+    Ok(summary)
+}
+
+/// Waits until a line is entered on stdin.
+///
+/// Never returns if stdin is closed or cannot be read, e.g. when running
+/// without a terminal.
+async fn wait_for_enter() {
+    let read = tokio::task::spawn_blocking(|| std::io::stdin().read_line(&mut String::new())).await;
+    match read {
+        Ok(Ok(bytes)) if bytes > 0 => {}
+        _ => std::future::pending().await,
+    }
+}
+
+/// Waits until `ginseng-cli stop` removes the record of a running share.
+async fn wait_for_stop(running: &RunningShares, share_id: &str) {
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        if !running.contains(share_id).await {
+            return;
+        }
+    }
+}
+
+/// Stops sharing once a share has been downloaded completely the given number
+/// of times, so no further download starts before the CLI exits.
+async fn stop_after_downloads(ginseng: &GinsengCore, share_id: &str, downloads: u64) -> Result<()> {
+    wait_for_downloads(ginseng, share_id, downloads).await;
+    ginseng.stop_share(share_id).await
+}
+
+/// Waits until a share has been downloaded completely the given number of times.
+async fn wait_for_downloads(ginseng: &GinsengCore, share_id: &str, downloads: u64) {
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let completed = ginseng
+            .shares
+            .activity(share_id)
+            .await
+            .map_or(0, |activity| activity.completed_downloads);
+        if completed as u64 >= downloads {
+            return;
+        }
+    }
+}
+
+async fn handle_receive(
+    ginseng: GinsengCore,
+    ticket: String,
+    output: Option<PathBuf>,
+    json: bool,
+) -> Result<Summary> {
+    if let Some(tickets) = split_pasted_tickets(&ticket) {
+        return handle_receive_many(ginseng, tickets, output, json).await;
+    }
+
+    notice(json, "🔄 Downloading files from ticket...");
+
+    let ticket = resolve_ticket_input(&ticket).await?;
+    let (metadata, download_path) =
+        match download_with_progress(&ginseng, ticket, output.as_deref()).await {
+            Ok(result) => result,
+            Err(error) if error.downcast_ref::<NotAGinsengShare>().is_some() => {
+                anyhow::bail!(
+                    "{}\nRun `ginseng-cli receive --raw <TICKET>` to save it as a single file.",
+                    error
+                )
+            }
+            Err(error) => {
+                if let Some(DownloadError::PartialFailure { failed, .. }) = error.downcast_ref() {
+                    if !json {
+                        display_failed_files(failed);
+                    }
+                }
+                return Err(error);
+            }
+        };
+
+    if !json {
+        display_download_summary(&metadata, &download_path);
+    }
+
+    Ok(Summary {
+        download_path: Some(download_path.to_string_lossy().to_string()),
+        files: Some(metadata.files.len()),
+        shares: json.then_some(metadata).into_iter().collect(),
+        ..Summary::default()
+    })
+}
+
+/// Downloads several shares one after another, continuing past failures.
+async fn handle_receive_many(
+    ginseng: GinsengCore,
+    tickets: Vec<String>,
+    output: Option<PathBuf>,
+    json: bool,
+) -> Result<Summary> {
+    notice(json, format!("🎟️  Found {} tickets", tickets.len()));
+
+    let mut summary = Summary::default();
+    let mut failed = 0;
+    for (index, ticket) in tickets.iter().enumerate() {
+        notice(
+            json,
+            format!(
+                "\n🔄 Downloading share {} of {}...",
+                index + 1,
+                tickets.len()
+            ),
+        );
+        match download_with_progress(&ginseng, ticket.clone(), output.as_deref()).await {
+            Ok((metadata, download_path)) => {
+                if !json {
+                    display_download_summary(&metadata, &download_path);
+                }
+                summary.download_path = Some(download_path.to_string_lossy().to_string());
+                summary.files = Some(summary.files.unwrap_or(0) + metadata.files.len());
+                summary.shares.extend(json.then_some(metadata));
+            }
+            Err(error) => {
+                if let Some(DownloadError::PartialFailure { failed, .. }) = error.downcast_ref() {
+                    if !json {
+                        display_failed_files(failed);
+                    }
+                    summary.failed_files.extend(failed.iter().cloned());
+                }
+                eprintln!("❌ Share {} failed: {}", index + 1, error);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} shares could not be received",
+            failed,
+            tickets.len()
+        );
+    }
+    Ok(summary)
+}
+
+async fn handle_receive_watch(
+    ginseng: GinsengCore,
+    source: String,
+    interval: Duration,
+    output: Option<PathBuf>,
+    json: bool,
+) -> Result<Summary> {
+    if !Path::new(&source).is_file() && !looks_like_domain(&source) {
+        anyhow::bail!(
+            "Watch mode needs a domain or ticket file the sender can update, not a ticket string"
+        );
+    }
+
+    notice(json, format!("👀 Watching {} for new content...", source));
+    notice(json, "Press Ctrl+C to stop watching.");
+
+    let mut current_ticket = None;
+    let mut summary = Summary::default();
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        }
+
+        let ticket = match resolve_ticket_input(&source).await {
+            Ok(ticket) => ticket,
+            Err(error) => {
+                notice(
+                    json,
+                    format!("⚠️  Could not check for new content: {}", error),
+                );
+                continue;
+            }
+        };
+        if current_ticket.as_ref() == Some(&ticket) {
+            continue;
+        }
+
+        notice(json, "🔄 New content published, syncing...");
+        let result = tokio::select! {
+            result = download_with_progress(&ginseng, ticket.clone(), output.as_deref()) => result,
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        };
+
+        match result {
+            Ok((metadata, download_path)) => {
+                notice(
+                    json,
+                    format!(
+                        "✅ Synced {} files ({}) into {}",
+                        metadata.files.len(),
+                        format_file_size(metadata.total_size),
+                        download_path.display()
+                    ),
+                );
+                summary.download_path = Some(download_path.to_string_lossy().to_string());
+                summary.files = Some(summary.files.unwrap_or(0) + metadata.files.len());
+                summary.shares.extend(json.then_some(metadata));
+                current_ticket = Some(ticket);
+            }
+            Err(error) => {
+                notice(json, format!("❌ Sync failed: {}", error));
+                // Keep partially synced content instead of downloading it again
+                if let Some(DownloadError::PartialFailure { failed, .. }) = error.downcast_ref() {
+                    if !json {
+                        display_failed_files(failed);
+                    }
+                    summary.failed_files.extend(failed.iter().cloned());
+                    current_ticket = Some(ticket);
+                }
+            }
+        }
+    }
+
+    notice(json, "\nStopped watching.");
+    Ok(summary)
+}
+
+async fn handle_receive_raw(
+    ginseng: GinsengCore,
+    ticket: String,
+    output: Option<PathBuf>,
+    json: bool,
+) -> Result<Summary> {
+    notice(json, "🔄 Downloading raw blob from ticket...");
+
+    let ticket = resolve_ticket_input(&ticket).await?;
+    let output = output.unwrap_or_else(|| PathBuf::from("."));
+    let path = ginseng.download_raw_blob(&ticket, &output).await?;
+
+    notice(json, format!("✅ Saved blob to {}", path.display()));
+
+    Ok(Summary {
+        download_path: Some(path.to_string_lossy().to_string()),
+        files: Some(1),
+        ..Summary::default()
+    })
+}
+
+async fn handle_info(ginseng: GinsengCore, json: bool) -> Result<Summary> {
+    let info = ginseng.node_details();
+    if !json {
+        println!("🔧 Node Information:");
+        println!("{}", info);
+    }
+    Ok(Summary {
+        node: Some(info),
+        ..Summary::default()
+    })
+}
+
+async fn handle_drop_box(ginseng: GinsengCore, name: String, rules: AcceptRules) -> Result<()> {
+    let drop_ticket = ginseng.dropbox.open(name, rules).await?;
+    let mut events = ginseng.dropbox.subscribe();
+
+    println!("\n📥 Drop Ticket:");
+    println!("{}", drop_ticket);
+    println!("\nSenders can push files with: ginseng-cli push <DROP_TICKET> <PATH>...");
+    println!("Press Ctrl+C to close the drop box.");
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => display_drop_event(event),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        }
+    }
+
+    ginseng.dropbox.close().await;
+    println!("\nClosed drop box.");
     Ok(())
 }
 
-async fn handle_receive(ginseng: GinsengCore, ticket: String) -> Result<()> {
-    println!("🔄 Downloading files from ticket...");
+fn display_drop_event(event: DropEvent) {
+    match event {
+        DropEvent::Incoming { peer } => println!("🔄 Incoming push from {}", peer),
+        DropEvent::Received {
+            peer,
+            metadata,
+            download_path,
+        } => println!(
+            "✅ Received {} files ({}) from {} into {}",
+            metadata.files.len(),
+            format_file_size(metadata.total_size),
+            peer,
+            download_path
+        ),
+        DropEvent::Rejected { peer, reason } => {
+            println!("🚫 Rejected push from {}: {}", peer, reason)
+        }
+        DropEvent::Failed { peer, error } => println!("❌ Push from {} failed: {}", peer, error),
+    }
+}
 
-    let (metadata, download_path) = ginseng.download_files_cli(ticket).await?;
+async fn handle_push(ginseng: GinsengCore, drop_ticket: String, paths: Vec<PathBuf>) -> Result<()> {
+    validate_paths_exist(&paths)?;
+    display_sharing_summary(&paths);
 
-    display_download_summary(&metadata, &download_path);
+    println!("\nPushing to drop box...");
+    match ginseng.send_to_drop_box(&drop_ticket, paths).await? {
+        DropResponse::Delivered {
+            file_count,
+            total_size,
+            ..
+        } => {
+            println!(
+                "✅ Delivered {} files ({}), receipt recorded",
+                file_count,
+                format_file_size(total_size)
+            );
+            Ok(())
+        }
+        DropResponse::Rejected { reason } => {
+            anyhow::bail!("Drop box rejected the files: {}", reason)
+        }
+        DropResponse::Failed { error } => {
+            anyhow::bail!("Drop box failed to receive the files: {}", error)
+        }
+    }
+}
+
+async fn handle_diff(ginseng: GinsengCore, before: String, after: String) -> Result<()> {
+    println!("🔍 Fetching both file lists...");
+    let before = resolve_ticket_input(&before).await?;
+    let after = resolve_ticket_input(&after).await?;
+    let diff = ginseng.diff_tickets(&before, &after).await?;
 
+    if diff.is_empty() {
+        println!("✅ Both shares hold the same {} files.", diff.unchanged);
+        return Ok(());
+    }
+    for file in &diff.added {
+        println!(
+            "  + {} ({})",
+            file.relative_path,
+            format_file_size(file.size)
+        );
+    }
+    for file in &diff.removed {
+        println!(
+            "  - {} ({})",
+            file.relative_path,
+            format_file_size(file.size)
+        );
+    }
+    for file in &diff.changed {
+        println!(
+            "  ~ {} ({} -> {})",
+            file.relative_path,
+            format_file_size(file.before.size),
+            format_file_size(file.after.size)
+        );
+    }
+    println!(
+        "\n📊 {} added, {} removed, {} changed, {} unchanged",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged
+    );
     Ok(())
 }
 
-async fn handle_info(ginseng: GinsengCore) -> Result<()> {
-    let info = ginseng.node_info().await?;
-    println!("🔧 Node Information:");
-    println!("{}", info);
+fn handle_dns_record(domain: &str, ticket: &str) {
+    println!("🌐 Add this TXT record to the zone of {}:", domain);
+    println!("{}", format_txt_record(domain, ticket));
+    println!("\nRecipients can then run: ginseng-cli receive {}", domain);
+}
+
+async fn handle_history(limit: usize, clear: bool) -> Result<()> {
+    let path = TransferHistory::default_path()
+        .ok_or_else(|| anyhow::anyhow!("Could not determine the config directory"))?;
+    let history = TransferHistory::open(&path)?;
+
+    if clear {
+        let removed = history.clear().await?;
+        println!("🧹 Removed {} transfers from the history", removed);
+        return Ok(());
+    }
+
+    let entries = history.list(Some(limit)).await?;
+    if entries.is_empty() {
+        println!("No transfers recorded yet.");
+    }
+    for entry in entries {
+        let direction = match entry.transfer_type {
+            TransferType::Upload => "📤 Shared",
+            TransferType::Download => "📥 Downloaded",
+        };
+        let outcome = match entry.outcome {
+            HistoryOutcome::Completed => "completed",
+            HistoryOutcome::PartiallyFailed => "some files failed",
+            HistoryOutcome::Failed => "failed",
+            HistoryOutcome::Cancelled => "cancelled",
+        };
+        let finished = format_timestamp(entry.finished_at);
+        println!(
+            "{} {} files ({}) · {} · {}",
+            direction,
+            entry.files.len(),
+            format_file_size(entry.total_size),
+            outcome,
+            finished
+        );
+        if let Some(peer) = &entry.peer {
+            println!("   Peer: {}", peer);
+        }
+        if let Some(error) = &entry.error {
+            println!("   Error: {}", error);
+        }
+    }
+    Ok(())
+}
+
+async fn handle_shares() -> Result<()> {
+    let shares = RunningShares::open_default()?.list().await;
+    if shares.is_empty() {
+        println!("No shares are running.");
+    }
+    for share in shares {
+        println!(
+            "🆔 {} · since {}",
+            share.share_id,
+            format_timestamp(share.started_at)
+        );
+        for path in &share.paths {
+            println!("   {}", path.display());
+        }
+        println!("   Ticket: {}", share.ticket);
+    }
     Ok(())
 }
 
+async fn handle_stop(share: Option<String>) -> Result<()> {
+    let Some(share) = share else {
+        return handle_shares().await;
+    };
+    let running = RunningShares::open_default()?;
+
+    match running.remove(&share).await? {
+        Some(stopped) => {
+            println!("🛑 Stopped sharing {}", stopped.share_id);
+            Ok(())
+        }
+        None => anyhow::bail!("No running share matches '{}'", share),
+    }
+}
+
+async fn handle_watch(ginseng: GinsengCore, directory: PathBuf) -> Result<()> {
+    println!("👀 Watching {} for ticket files...", directory.display());
+    println!("Press Ctrl+C to stop watching.");
+
+    tokio::select! {
+        result = watch_ticket_folder(&ginseng, &directory, display_watch_event) => result?,
+        result = tokio::signal::ctrl_c() => result?,
+    }
+
+    println!("\nStopped watching.");
+    Ok(())
+}
+
+fn display_watch_event(event: WatchEvent) {
+    match event {
+        WatchEvent::TicketDetected { path } => println!("🎫 Found ticket: {}", path),
+        WatchEvent::DownloadCompleted {
+            path,
+            download_path,
+        } => println!("✅ Received {} into {}", path, download_path),
+        WatchEvent::DownloadFailed { path, error } => {
+            println!("❌ Failed to receive {}: {}", path, error)
+        }
+        WatchEvent::WatcherFailed { error } => println!("❌ Watcher stopped: {}", error),
+    }
+}
+
 fn validate_paths_exist(paths: &[PathBuf]) -> Result<()> {
     for path in paths {
         if !path.exists() {
@@ -143,10 +1373,72 @@ fn display_multiple_paths_summary(paths: &[PathBuf]) {
     }
 }
 
-fn display_share_ticket(ticket: &str) {
+fn display_skipped_files(skipped_files: &[SkippedFile]) {
+    if skipped_files.is_empty() {
+        return;
+    }
+
+    println!(
+        "\n⚠️  {} files could not be read and were skipped:",
+        skipped_files.len()
+    );
+    for skipped in skipped_files {
+        println!("   {} — {}", skipped.relative_path, skipped.error);
+    }
+}
+
+fn display_failed_files(failed_files: &[SkippedFile]) {
+    println!(
+        "\n⚠️  {} files could not be downloaded:",
+        failed_files.len()
+    );
+    for failed in failed_files {
+        println!("   {} — {}", failed.relative_path, failed.error);
+    }
+}
+
+/// Prints a message for people; with `--json` it goes to stderr, so stdout
+/// only carries JSON.
+fn notice(json: bool, message: impl std::fmt::Display) {
+    if json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Whether the summary line has been printed, so stdout carries only one
+static SUMMARY_PRINTED: AtomicBool = AtomicBool::new(false);
+
+/// Prints the summary as a single JSON line for scripts.
+///
+/// Only the first call prints anything: `send` reports its ticket as soon as
+/// the share is open, and its final outcome only through the exit code.
+fn display_summary_line(outcome: Outcome, error: Option<&anyhow::Error>, summary: Summary) {
+    if SUMMARY_PRINTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let line = SummaryLine {
+        status: outcome,
+        exit_code: outcome.exit_code(),
+        error: error.map(|error| error.to_string()),
+        summary,
+    };
+    if let Ok(line) = serde_json::to_string(&line) {
+        println!("{}", line);
+    }
+}
+
+fn display_share_ticket(ticket: &str, share_id: &str, content_root: &str) {
     println!("\n🎫 Share Ticket:");
     println!("{}", ticket);
-    println!("\nShare this ticket with the recipient. Press Ctrl+C to stop sharing.");
+    println!("\n🆔 Share ID: {}", share_id);
+    println!("🧮 Content root: {}", content_root);
+    println!(
+        "\nShare this ticket with the recipient. Press Ctrl+C or run `ginseng-cli stop {}` \
+         to stop sharing.",
+        share_id
+    );
 }
 
 fn display_download_summary(metadata: &ShareMetadata, download_path: &Path) {
@@ -155,15 +1447,24 @@ fn display_download_summary(metadata: &ShareMetadata, download_path: &Path) {
 
     display_share_type_info(&metadata.share_type);
     println!("📊 Total size: {}", format_file_size(metadata.total_size));
+    println!("🧮 Content root: {}", content_root(&metadata.files));
+    display_custom_fields(&metadata.custom_fields);
 
     display_file_listing(&metadata.files);
 }
 
+fn display_custom_fields(custom_fields: &BTreeMap<String, String>) {
+    for (key, value) in custom_fields {
+        println!("🏷️  {}: {}", key, value);
+    }
+}
+
 fn display_share_type_info(share_type: &ShareType) {
     let type_description = match share_type {
         ShareType::SingleFile => "Single file".to_string(),
         ShareType::MultipleFiles => "Multiple files".to_string(),
         ShareType::Directory { name } => format!("Directory ({})", name),
+        ShareType::Archive { name } => format!("Directory archive ({})", name),
     };
     println!("📄 Type: {}", type_description);
 }
@@ -217,6 +1518,81 @@ fn calculate_directory_summary(dir: &PathBuf) -> Result<DirectorySummary> {
     })
 }
 
+/// Parses a custom field given as `KEY=VALUE`.
+fn parse_custom_field(input: &str) -> Result<(String, String), String> {
+    let (key, value) = input
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid field '{}', expected KEY=VALUE", input))?;
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+/// Parses a duration such as `90`, `90s`, `30m`, `2h`, or `1d`.
+///
+/// A number without a unit is taken as seconds.
+fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let (number, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, "s"),
+    };
+
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}'", input))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Unknown duration unit '{}', use s, m, h or d",
+                unit
+            ))
+        }
+    };
+
+    Ok(Duration::from_secs(value.saturating_mul(multiplier)))
+}
+
+/// Parses a rate in bytes per second such as `65536`, `500K`, `2M`, or `1G`.
+///
+/// Units are powers of 1024, matching the sizes printed by the CLI.
+fn parse_rate(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let (number, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, ""),
+    };
+
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid rate '{}'", input))?;
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown rate unit '{}', use K, M or G", unit)),
+    };
+
+    match value.saturating_mul(multiplier) {
+        0 => Err("Rate must be greater than zero".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+/// Formats a Unix timestamp as local date and time, e.g. 2024-05-01 14:30
+fn format_timestamp(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
 // Keep in sync with formatFileSize in FileTransfer.tsx
 fn format_file_size(bytes: u64) -> String {
     if bytes == 0 {
@@ -228,3 +1604,50 @@ fn format_file_size(bytes: u64) -> String {
     let size = bytes as f64 / k.pow(i as u32) as f64;
     format!("{:.2} {}", size, sizes[i])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_share_stops_after_download_limit() {
+        let sender = GinsengCore::new().await.unwrap();
+        // Tickets reach the sender without a relay once it knows its addresses
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while sender.node_details().direct_addresses.is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let source = TempDir::new().unwrap();
+        let file_path = source.path().join("notes.txt");
+        tokio::fs::write(&file_path, "limited").await.unwrap();
+        let share = sender
+            .share_files_cli(vec![file_path], BTreeMap::new(), ShareOptions::default())
+            .await
+            .unwrap();
+
+        let limit = stop_after_downloads(&sender, &share.share_id, 1);
+        tokio::pin!(limit);
+        assert!(futures::poll!(limit.as_mut()).is_pending());
+
+        let receiver = GinsengCore::new().await.unwrap();
+        let target = TempDir::new().unwrap();
+        receiver
+            .download_files_with_progress(
+                Channel::new(|_| Ok(())),
+                share.ticket,
+                Some(target.path().to_path_buf()),
+            )
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), limit)
+            .await
+            .expect("download limit was not reached")
+            .unwrap();
+        assert!(!sender.shares.is_open(&share.share_id).await);
+    }
+}
@@ -0,0 +1,141 @@
+//! What happens when a downloaded file already exists
+//!
+//! A download saving `report.pdf` into a folder that already holds a
+//! `report.pdf` resolves the collision with the policy chosen for the
+//! transfer: keep both by saving the new file as `report (1).pdf`, keep the
+//! existing file, replace it, or leave the decision to the user. Each
+//! collision is reported with the download result, so the user can see which
+//! files were renamed or left out, and download them again with another
+//! policy.
+
+use crate::paths::unique_path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How a download handles files that already exist at their target path
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CollisionPolicy {
+    /// Save the new file under a free name, e.g. "report (1).pdf"
+    #[default]
+    Rename,
+    /// Keep the existing file and leave out the new one
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Leave out the new file until the user decides what to do
+    Ask,
+}
+
+/// How a collision was resolved
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "resolution"
+)]
+pub enum CollisionResolution {
+    /// The new file was saved under another name
+    Renamed { path: PathBuf },
+    /// The existing file was kept and the new one left out
+    Skipped,
+    /// The existing file was replaced
+    Overwritten,
+    /// The new file was left out until the user decides
+    Asked,
+}
+
+/// A downloaded file that already existed at its target path
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileCollision {
+    /// Path of the file within the share
+    pub relative_path: String,
+    /// Content hash of the file, or of the archive it was unpacked from, for
+    /// downloading it again
+    pub hash: String,
+    /// Path of the existing file
+    pub existing_path: PathBuf,
+    /// What happened to the new file
+    #[serde(flatten)]
+    pub resolution: CollisionResolution,
+}
+
+impl FileCollision {
+    /// Returns where the new file is written, or None if it is left out
+    pub fn written_to(&self) -> Option<&Path> {
+        match &self.resolution {
+            CollisionResolution::Renamed { path } => Some(path),
+            CollisionResolution::Overwritten => Some(&self.existing_path),
+            CollisionResolution::Skipped | CollisionResolution::Asked => None,
+        }
+    }
+}
+
+/// Applies a collision policy to the target path of a downloaded file.
+///
+/// # Arguments
+///
+/// * `target` - Where the file would be saved
+/// * `policy` - How to handle an existing file at that path
+///
+/// # Returns
+///
+/// How the collision is resolved, or None if nothing exists at the path
+pub fn resolve_collision(target: &Path, policy: CollisionPolicy) -> Option<CollisionResolution> {
+    if !target.exists() {
+        return None;
+    }
+
+    Some(match policy {
+        CollisionPolicy::Rename => CollisionResolution::Renamed {
+            path: unique_path(target, Path::exists),
+        },
+        CollisionPolicy::Skip => CollisionResolution::Skipped,
+        CollisionPolicy::Overwrite => CollisionResolution::Overwritten,
+        CollisionPolicy::Ask => CollisionResolution::Asked,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_collision() {
+        let dir = TempDir::new().unwrap();
+        let report = dir.path().join("report.pdf");
+        assert_eq!(resolve_collision(&report, CollisionPolicy::Rename), None);
+
+        std::fs::write(&report, "old").unwrap();
+        std::fs::write(dir.path().join("report (1).pdf"), "older").unwrap();
+        assert_eq!(
+            resolve_collision(&report, CollisionPolicy::Rename),
+            Some(CollisionResolution::Renamed {
+                path: dir.path().join("report (2).pdf")
+            })
+        );
+        assert_eq!(
+            resolve_collision(&report, CollisionPolicy::Skip),
+            Some(CollisionResolution::Skipped)
+        );
+        assert_eq!(
+            resolve_collision(&report, CollisionPolicy::Overwrite),
+            Some(CollisionResolution::Overwritten)
+        );
+        assert_eq!(
+            resolve_collision(&report, CollisionPolicy::Ask),
+            Some(CollisionResolution::Asked)
+        );
+
+        let notes = dir.path().join(".notes");
+        std::fs::write(&notes, "old").unwrap();
+        assert_eq!(
+            resolve_collision(&notes, CollisionPolicy::Rename),
+            Some(CollisionResolution::Renamed {
+                path: dir.path().join(".notes (1)")
+            })
+        );
+    }
+}
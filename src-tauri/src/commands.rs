@@ -1,8 +1,70 @@
-use crate::progress::ProgressEvent;
-use crate::state::{AppState, DownloadResult};
+use crate::bandwidth::{BandwidthLimits, BandwidthWindow};
+use crate::collisions::CollisionPolicy;
+use crate::companion::{
+    bind_companion, new_companion_token, serve_companion, CompanionEvent, DEFAULT_COMPANION_PORT,
+};
+use crate::compression::Compression;
+use crate::concurrency::ConcurrencyLimits;
+use crate::context_menu;
+use crate::core::{
+    DownloadOptions, Followup, ScannedFile, ShareMetadata, ShareOptions, ShareResult, ShareTooLarge,
+};
+use crate::devices::{DeviceDelivery, DeviceGroup, GroupDelivery, PairedDevice, QueuedPush};
+use crate::disk::validate_download_directory;
+use crate::dns::{format_txt_record, resolve_ticket};
+use crate::dropbox::{AcceptRules, DropResponse};
+use crate::errors::GinsengError;
+use crate::exclude::ExcludeRules;
+use crate::folder_names::validate_template;
+use crate::history::HistoryEntry;
+use crate::inspector::{self, BlobEntry, BlobPreview, TagEntry, DEFAULT_PREVIEW_BYTES};
+use crate::library::LibraryEntry;
+use crate::logging;
+use crate::netstats::NetworkStats;
+use crate::organize::OrganizeRule;
+use crate::peers::PeerStats;
+use crate::progress::{new_transfer_id, ProgressEvent, TransferId};
+use crate::queue::QueueEntry;
+use crate::receipts::Receipt;
+use crate::relays::RelayPreference;
+use crate::settings::{pinned_first, Settings, SettingsStore};
+use crate::share_diff::ShareDiff;
+use crate::shares::{ShareActivity, ShareSummary};
+use crate::sinks::OutputSink;
+use crate::state::{AppState, CoreStatus, DownloadResult};
+use crate::templates::{ShareTemplate, TemplateRun, TemplateStore};
+use crate::tickets::{load_ticket_file, TicketFile};
 use crate::utils::validate_and_canonicalize_paths;
+use crate::watcher::{watch_ticket_folder, WatchEvent};
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Name of the app event carrying ticket watcher updates
+const TICKET_WATCHER_EVENT: &str = "ticket-watcher";
+
+/// Name of the app event carrying drop box updates
+const DROP_BOX_EVENT: &str = "drop-box";
+
+/// Name of the app event carrying companion endpoint events
+const COMPANION_EVENT: &str = "companion";
+
+/// Outcome of sharing files from the frontend
+#[derive(Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "status"
+)]
+pub enum ShareOutcome {
+    /// The files were shared
+    Shared(ShareResult),
+    /// The files exceed the maximum share size and were not read
+    TooLarge { total_size: u64, max_size: u64 },
+}
 
 #[derive(Clone, Serialize)]
 #[serde(
@@ -35,7 +97,7 @@ pub async fn share_files(
     channel: Channel<DownloadEvent<'_>>,
     state: tauri::State<'_, AppState>,
     paths: Vec<String>,
-) -> Result<String, String> {
+) -> Result<String, GinsengError> {
     channel
         .send(DownloadEvent::Started {
             detail: "Preparing to share files",
@@ -48,7 +110,7 @@ pub async fn share_files(
 
     core.share_files(&channel, validated_paths)
         .await
-        .map_err(|error| error.to_string())
+        .map_err(GinsengError::from)
 }
 
 /// Download files using a ticket
@@ -66,17 +128,19 @@ pub async fn share_files(
 pub async fn download_files(
     state: tauri::State<'_, AppState>,
     ticket: String,
-) -> Result<DownloadResult, String> {
+) -> Result<DownloadResult, GinsengError> {
     let core = state.get_core()?;
 
     let (metadata, target_dir) = core
         .download_files(ticket)
         .await
-        .map_err(|error| error.to_string())?;
+        .map_err(GinsengError::from)?;
 
     Ok(DownloadResult {
         metadata,
         download_path: target_dir.to_string_lossy().to_string(),
+        collisions: Vec::new(),
+        failed_files: Vec::new(),
     })
 }
 
@@ -91,10 +155,30 @@ pub async fn download_files(
 /// # Errors
 /// Returns an error if core is not initialized or node info retrieval fails
 #[tauri::command]
-pub async fn node_info(state: tauri::State<'_, AppState>) -> Result<String, String> {
+pub async fn node_info(state: tauri::State<'_, AppState>) -> Result<String, GinsengError> {
+    let core = state.get_core()?;
+
+    core.node_info().await.map_err(GinsengError::from)
+}
+
+/// Get the traffic of the node since it started
+///
+/// The byte counters only grow; polling them shows the network activity of
+/// all transfers together.
+///
+/// # Returns
+/// Bytes sent and received, how much of them went through relays, and the
+/// open connections
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn network_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<NetworkStats, GinsengError> {
     let core = state.get_core()?;
 
-    core.node_info().await.map_err(|error| error.to_string())
+    Ok(core.network_stats())
 }
 
 /// Share a single file (convenience wrapper around share_files)
@@ -113,7 +197,7 @@ pub async fn share_file(
     channel: Channel<DownloadEvent<'_>>,
     state: tauri::State<'_, AppState>,
     path: String,
-) -> Result<String, String> {
+) -> Result<String, GinsengError> {
     share_files(channel, state, vec![path]).await
 }
 
@@ -134,42 +218,1960 @@ pub async fn download_file(
     state: tauri::State<'_, AppState>,
     ticket: String,
     _target: String,
-) -> Result<(), String> {
+) -> Result<(), GinsengError> {
     let _result = download_files(state, ticket).await?;
     Ok(())
 }
 
 /// Share files with parallel progress tracking
+///
+/// The caller may provide the transfer ID so it can refer to the transfer
+/// before the first progress event arrives. A random ID is used otherwise.
+/// An optional `include` list of directory-relative paths (from `scan_directory`)
+/// limits which files of shared directories are included. Optional custom
+/// fields (e.g., a project ID) are attached to the share and shown to the receiver.
+/// With `expires_in_secs`, the share stops being served after that many seconds
+/// and receivers are told it expired. A `single_use` share stops being served
+/// once it has been downloaded completely. With `compression`, file data is
+/// compressed before it is stored and decompressed by the receiver. An
+/// `archive` share sends a single directory as one tar archive, which is much
+/// faster for directories of many small files, and is unpacked by the receiver.
+/// A `delta` share reuses the blobs of files that have not changed since an
+/// open share stored them, instead of reading them again. Files and folders of
+/// shared directories matching the `exclude` rules are left out. A `preview`
+/// share only serves its file list until `unlock_share` is called.
+///
+/// Files adding up to more than the maximum share size in the settings are not
+/// read; the outcome is `tooLarge` with their total size instead, and sharing
+/// them anyway takes `force`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn share_files_parallel(
     channel: Channel<ProgressEvent>,
     state: tauri::State<'_, AppState>,
     paths: Vec<String>,
-) -> Result<String, String> {
+    transfer_id: Option<TransferId>,
+    include: Option<Vec<String>>,
+    custom_fields: Option<BTreeMap<String, String>>,
+    expires_in_secs: Option<u64>,
+    single_use: Option<bool>,
+    compression: Option<Compression>,
+    archive: Option<bool>,
+    delta: Option<bool>,
+    exclude: Option<ExcludeRules>,
+    force: Option<bool>,
+    preview: Option<bool>,
+) -> Result<ShareOutcome, GinsengError> {
     let core = state.get_core()?;
     let validated_paths = validate_and_canonicalize_paths(paths)?;
+    let transfer_id = transfer_id.unwrap_or_else(new_transfer_id);
+    let max_size = if force.unwrap_or_default() {
+        None
+    } else {
+        // Unreadable settings fall back to the defaults rather than failing the share
+        load_settings().await.unwrap_or_default().max_share_size
+    };
+
+    let result = core
+        .share_files_parallel(
+            channel,
+            transfer_id,
+            validated_paths,
+            include,
+            custom_fields.unwrap_or_default(),
+            ShareOptions {
+                expires_in: expires_in_secs.map(Duration::from_secs),
+                single_use: single_use.unwrap_or_default(),
+                compression: compression.unwrap_or_default(),
+                archive: archive.unwrap_or_default(),
+                delta: delta.unwrap_or_default(),
+                exclude: exclude.unwrap_or_default(),
+                max_size,
+                preview: preview.unwrap_or_default(),
+            },
+        )
+        .await;
+
+    match result {
+        Ok(share) => Ok(ShareOutcome::Shared(share)),
+        Err(error) => match error.downcast_ref::<ShareTooLarge>() {
+            Some(too_large) => Ok(ShareOutcome::TooLarge {
+                total_size: too_large.total_size,
+                max_size: too_large.max_size,
+            }),
+            None => Err(error.into()),
+        },
+    }
+}
+
+/// Set the largest total size shared without confirmation and save it in the settings
+///
+/// # Arguments
+/// * `max_size` - The size in bytes, or None for no limit
+///
+/// # Errors
+/// Returns an error if the settings cannot be written
+#[tauri::command]
+pub async fn set_max_share_size(max_size: Option<u64>) -> Result<(), GinsengError> {
+    update_settings(|settings| settings.max_share_size = max_size).await?;
+    Ok(())
+}
 
-    core.share_files_parallel(channel, validated_paths)
+/// List the files in a directory for selecting a subset to share
+///
+/// # Arguments
+/// * `path` - The directory to scan
+///
+/// # Returns
+/// Every file in the directory with its relative path and size
+///
+/// # Errors
+/// Returns an error if the path is not a readable directory
+#[tauri::command]
+pub async fn scan_directory(path: String) -> Result<Vec<ScannedFile>, GinsengError> {
+    crate::core::scan_directory(Path::new(&path))
         .await
-        .map_err(|error| error.to_string())
+        .map_err(GinsengError::from)
 }
 
 /// Download files with parallel progress tracking
+///
+/// The caller may provide the transfer ID so it can refer to the transfer
+/// before the first progress event arrives. A random ID is used otherwise.
+/// Files are saved to the download folder unless another folder or an output
+/// sink such as an S3 bucket or WebDAV endpoint is given. Organization rules
+/// given here replace the default rules from the settings for this download
+/// only. Files that already exist are saved under a new name unless another
+/// collision policy is given, and are listed in the result.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn download_files_parallel(
     channel: Channel<ProgressEvent>,
     state: tauri::State<'_, AppState>,
     ticket: String,
-) -> Result<DownloadResult, String> {
+    transfer_id: Option<TransferId>,
+    sink: Option<OutputSink>,
+    organize: Option<Vec<OrganizeRule>>,
+    collisions: Option<CollisionPolicy>,
+    target_dir: Option<String>,
+) -> Result<DownloadResult, GinsengError> {
     let core = state.get_core()?;
+    let transfer_id = transfer_id.unwrap_or_else(new_transfer_id);
 
-    let (metadata, target_dir) = core
-        .download_files_parallel(channel, ticket)
+    let options = DownloadOptions {
+        sink: sink.unwrap_or_default(),
+        organize,
+        collisions: collisions.unwrap_or_default(),
+        target_directory: chosen_download_directory(target_dir).await?,
+    };
+    core.download_files_parallel(channel, transfer_id, ticket, options)
         .await
-        .map_err(|error| error.to_string())?;
+        .map(DownloadResult::from)
+        .map_err(GinsengError::from)
+}
 
-    Ok(DownloadResult {
-        metadata,
-        download_path: target_dir.to_string_lossy().to_string(),
+/// Fetch the file list of a share without downloading its files
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `ticket` - The ticket string received from the sender
+///
+/// # Returns
+/// The share metadata, listing every file with its hash
+///
+/// # Errors
+/// Returns an error if core is not initialized, the ticket is invalid, or the
+/// share cannot be fetched
+#[tauri::command]
+pub async fn preview_share(
+    state: tauri::State<'_, AppState>,
+    ticket: String,
+) -> Result<ShareMetadata, GinsengError> {
+    let core = state.get_core()?;
+    core.preview_share(&ticket)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Compare the file lists of two shares without downloading either
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `before` - Ticket of the older share
+/// * `after` - Ticket of the newer share
+///
+/// # Returns
+/// The files added, removed and changed from the older share to the newer one
+///
+/// # Errors
+/// Returns an error if core is not initialized, a ticket is invalid, or a
+/// share cannot be fetched
+#[tauri::command]
+pub async fn diff_tickets(
+    state: tauri::State<'_, AppState>,
+    before: String,
+    after: String,
+) -> Result<ShareDiff, GinsengError> {
+    let core = state.get_core()?;
+    core.diff_tickets(&before, &after)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Download only the chosen files of a share with parallel progress tracking
+///
+/// # Arguments
+/// * `channel` - Channel for sending progress events of the download
+/// * `state` - The Tauri application state
+/// * `ticket` - The ticket string received from the sender
+/// * `file_hashes` - Content hashes of the files to download
+/// * `transfer_id` - Optional caller-chosen ID for the download
+/// * `sink` - Optional output sink to write the files to
+/// * `organize` - Optional organization rules replacing the defaults
+/// * `collisions` - How files that already exist are handled, renamed by default
+/// * `target_dir` - Optional folder to save the files to instead of the download folder
+///
+/// # Returns
+/// Download result with the share metadata, the download path, and the files
+/// that already existed
+///
+/// # Errors
+/// Returns an error if core is not initialized, none of the files are part of
+/// the share, or the download fails
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn download_selected_files(
+    channel: Channel<ProgressEvent>,
+    state: tauri::State<'_, AppState>,
+    ticket: String,
+    file_hashes: Vec<String>,
+    transfer_id: Option<TransferId>,
+    sink: Option<OutputSink>,
+    organize: Option<Vec<OrganizeRule>>,
+    collisions: Option<CollisionPolicy>,
+    target_dir: Option<String>,
+) -> Result<DownloadResult, GinsengError> {
+    let core = state.get_core()?;
+    let transfer_id = transfer_id.unwrap_or_else(new_transfer_id);
+
+    let options = DownloadOptions {
+        sink: sink.unwrap_or_default(),
+        organize,
+        collisions: collisions.unwrap_or_default(),
+        target_directory: chosen_download_directory(target_dir).await?,
+    };
+    core.download_selected_files(channel, transfer_id, ticket, file_hashes, options)
+        .await
+        .map(DownloadResult::from)
+        .map_err(GinsengError::from)
+}
+
+/// Cancel a running share or download
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `transfer_id` - ID of the transfer to cancel
+///
+/// # Returns
+/// true if the transfer was running and has been cancelled
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn cancel_transfer(
+    state: tauri::State<'_, AppState>,
+    transfer_id: TransferId,
+) -> Result<bool, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.cancel_transfer(&transfer_id))
+}
+
+/// List the transfers that are running or waiting in the transfer queue
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// Running transfers first, then waiting transfers in the order they start
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn list_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<QueueEntry>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.queue.list())
+}
+
+/// Move a waiting transfer to a new place in the transfer queue
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `transfer_id` - ID of the waiting transfer
+/// * `position` - New place among the waiting transfers, starting at 0
+///
+/// # Errors
+/// Returns an error if core is not initialized or the transfer is not waiting
+#[tauri::command]
+pub async fn move_in_queue(
+    state: tauri::State<'_, AppState>,
+    transfer_id: TransferId,
+    position: usize,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+    if core.queue.move_to(&transfer_id, position) {
+        Ok(())
+    } else {
+        Err(GinsengError::Other(format!(
+            "Transfer '{}' is not waiting in the queue",
+            transfer_id
+        )))
+    }
+}
+
+/// Create a follow-up transfer covering only the failed files of a transfer
+///
+/// # Arguments
+/// * `channel` - Channel for sending progress events of the follow-up transfer
+/// * `state` - The Tauri application state
+/// * `failed_transfer_id` - The share or download that had failed files
+/// * `transfer_id` - Optional caller-chosen ID for the follow-up transfer
+///
+/// # Returns
+/// The new share for a failed share, or the downloaded files for a failed download
+///
+/// # Errors
+/// Returns an error if core is not initialized, the transfer had no failed files,
+/// or the follow-up transfer fails
+#[tauri::command]
+pub async fn create_followup(
+    channel: Channel<ProgressEvent>,
+    state: tauri::State<'_, AppState>,
+    failed_transfer_id: String,
+    transfer_id: Option<TransferId>,
+) -> Result<Followup, GinsengError> {
+    let core = state.get_core()?;
+    let transfer_id = transfer_id.unwrap_or_else(new_transfer_id);
+
+    core.create_followup(channel, &failed_transfer_id, transfer_id)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Retry the failed files of a download within the same transfer
+///
+/// # Arguments
+/// * `channel` - Channel for sending progress events of the transfer
+/// * `state` - The Tauri application state
+/// * `transfer_id` - The download that had failed files
+///
+/// # Returns
+/// Download result with the share metadata, the download path, and the files
+/// that already existed
+///
+/// # Errors
+/// Returns an error if core is not initialized, the transfer is not a download
+/// with failed files, or the retry is cancelled
+#[tauri::command]
+pub async fn retry_failed_files(
+    channel: Channel<ProgressEvent>,
+    state: tauri::State<'_, AppState>,
+    transfer_id: TransferId,
+) -> Result<DownloadResult, GinsengError> {
+    let core = state.get_core()?;
+
+    core.retry_failed_files(channel, &transfer_id)
+        .await
+        .map(DownloadResult::from)
+        .map_err(GinsengError::from)
+}
+
+/// Find the tickets in input holding several of them, e.g. pasted together
+///
+/// # Arguments
+/// * `input` - The text entered as ticket
+///
+/// # Returns
+/// Every ticket in the input, or None if it is a single ticket or not made of tickets only
+#[tauri::command]
+pub fn split_pasted_tickets(input: String) -> Option<Vec<String>> {
+    crate::core::split_pasted_tickets(&input)
+}
+
+/// Download a single raw blob from a plain iroh-blobs ticket
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `ticket` - A raw blob ticket, e.g. created by another iroh-blobs tool
+/// * `target` - File or directory to save to (defaults to the Downloads folder)
+///
+/// # Returns
+/// The path the blob was saved to
+///
+/// # Errors
+/// Returns an error if core is not initialized, the ticket is invalid, or download fails
+#[tauri::command]
+pub async fn download_raw_blob(
+    state: tauri::State<'_, AppState>,
+    ticket: String,
+    target: Option<String>,
+) -> Result<String, GinsengError> {
+    let core = state.get_core()?;
+    let target = match target {
+        Some(target) => PathBuf::from(target),
+        None => core.download_directory().map_err(GinsengError::from)?,
+    };
+
+    core.download_raw_blob(&ticket, &target)
+        .await
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(GinsengError::from)
+}
+
+/// Start watching a folder for `.ginseng-ticket` files and download them automatically
+///
+/// Progress is reported through `ticket-watcher` app events. Starting a new
+/// watcher replaces any previously running one.
+///
+/// # Arguments
+/// * `app` - The Tauri application handle
+/// * `state` - The Tauri application state
+/// * `directory` - Folder to watch for ticket files
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn start_ticket_watcher(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    directory: String,
+) -> Result<(), GinsengError> {
+    state.get_core()?;
+
+    let directory = PathBuf::from(directory);
+    let handle = tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let Ok(core) = state.get_core() else {
+            return;
+        };
+
+        let result = watch_ticket_folder(core, &directory, |event| {
+            app.emit(TICKET_WATCHER_EVENT, event).ok();
+        })
+        .await;
+
+        if let Err(error) = result {
+            app.emit(
+                TICKET_WATCHER_EVENT,
+                WatchEvent::WatcherFailed {
+                    error: error.to_string(),
+                },
+            )
+            .ok();
+        }
+    });
+
+    state.ticket_watcher.replace(handle).await;
+    Ok(())
+}
+
+/// Stop the running ticket folder watcher
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// true if a watcher was running and has been stopped
+#[tauri::command]
+pub async fn stop_ticket_watcher(state: tauri::State<'_, AppState>) -> Result<bool, GinsengError> {
+    Ok(state.ticket_watcher.stop().await)
+}
+
+/// Address and token a companion browser extension connects with
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanionInfo {
+    pub port: u16,
+    pub token: String,
+}
+
+/// Start the localhost endpoint a companion browser extension hands files and tickets to
+///
+/// Shares and downloads started by the extension are reported through
+/// `companion` app events. Starting the endpoint again replaces the running one.
+///
+/// # Arguments
+/// * `app` - The Tauri application handle
+/// * `state` - The Tauri application state
+/// * `port` - Port to listen on (defaults to 47615)
+///
+/// # Returns
+/// The port and the token the extension must send with every request
+///
+/// # Errors
+/// Returns an error if core is not initialized, the token cannot be saved, or the port is in use
+#[tauri::command]
+pub async fn start_companion_endpoint(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    port: Option<u16>,
+) -> Result<CompanionInfo, GinsengError> {
+    state.get_core()?;
+
+    let settings = update_settings(|settings| {
+        settings
+            .companion_token
+            .get_or_insert_with(new_companion_token);
+    })
+    .await?;
+    let token = settings.companion_token.unwrap_or_default();
+
+    // Stop the running endpoint first so its port can be bound again
+    state.companion.stop_and_wait().await;
+    let port = port.unwrap_or(DEFAULT_COMPANION_PORT);
+    let listener = bind_companion(port).await.map_err(GinsengError::from)?;
+
+    let info = CompanionInfo {
+        port,
+        token: token.clone(),
+    };
+    let handle = tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let Ok(core) = state.get_core() else {
+            return;
+        };
+
+        let events = app.clone();
+        let result = serve_companion(core.clone(), listener, token, move |event| {
+            events.emit(COMPANION_EVENT, event).ok();
+        })
+        .await;
+
+        if let Err(error) = result {
+            app.emit(
+                COMPANION_EVENT,
+                CompanionEvent::EndpointFailed {
+                    error: error.to_string(),
+                },
+            )
+            .ok();
+        }
+    });
+
+    state.companion.replace(handle).await;
+    Ok(info)
+}
+
+/// Stop the companion endpoint
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// true if the endpoint was running and has been stopped
+#[tauri::command]
+pub async fn stop_companion_endpoint(
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, GinsengError> {
+    Ok(state.companion.stop().await)
+}
+
+/// Replace the companion token, e.g. because it was leaked
+///
+/// The running endpoint is stopped, since it still accepts the old token.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// The new token
+///
+/// # Errors
+/// Returns an error if the settings cannot be saved
+#[tauri::command]
+pub async fn reset_companion_token(
+    state: tauri::State<'_, AppState>,
+) -> Result<String, GinsengError> {
+    state.companion.stop().await;
+    let settings = update_settings(|settings| {
+        settings.companion_token = Some(new_companion_token());
     })
+    .await?;
+    Ok(settings.companion_token.unwrap_or_default())
+}
+
+/// Add "Share with Ginseng" to the context menu of the system file manager
+///
+/// The entries launch this app with the selected files, which are then
+/// reported through `context-menu-share` app events.
+///
+/// # Errors
+/// Returns an error if the entries cannot be written or the platform is not supported
+#[tauri::command]
+pub async fn install_context_menu() -> Result<(), GinsengError> {
+    let launcher = context_menu::launcher_path().map_err(GinsengError::from)?;
+    context_menu::install(&launcher).map_err(GinsengError::from)
+}
+
+/// Remove "Share with Ginseng" from the context menu of the system file manager
+///
+/// # Errors
+/// Returns an error if an installed entry cannot be removed
+#[tauri::command]
+pub async fn uninstall_context_menu() -> Result<(), GinsengError> {
+    context_menu::uninstall().map_err(GinsengError::from)
+}
+
+/// Check whether the file manager context-menu entries are installed
+#[tauri::command]
+pub async fn context_menu_installed() -> bool {
+    context_menu::is_installed()
+}
+
+/// Get the folder log files are written to, for attaching them to bug reports
+///
+/// # Returns
+/// Path of the log folder, or None if the platform has no data directory
+#[tauri::command]
+pub fn log_directory() -> Option<String> {
+    logging::log_directory().map(|directory| directory.to_string_lossy().to_string())
+}
+
+/// Take the files picked from the file manager context menu since the last call
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// Paths of the picked files and folders, empty if there are none
+#[tauri::command]
+pub fn take_context_menu_paths(state: tauri::State<'_, AppState>) -> Vec<String> {
+    state
+        .take_context_menu_paths()
+        .into_iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Save the ticket of an open share to a file so it can be handed over or received later
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The ID returned when the share was created
+/// * `path` - Destination path of the ticket file
+/// * `note` - Optional note stored alongside the ticket
+/// * `expires_in_secs` - Optional lifetime of the ticket file in seconds, defaults to the share's
+///
+/// # Errors
+/// Returns an error if core is not initialized, the share is unknown or no longer open,
+/// or the ticket file cannot be written
+#[tauri::command]
+pub async fn export_ticket(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+    path: String,
+    note: Option<String>,
+    expires_in_secs: Option<u64>,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+
+    core.export_ticket(
+        &share_id,
+        Path::new(&path),
+        note,
+        expires_in_secs.map(Duration::from_secs),
+    )
+    .await
+    .map_err(GinsengError::from)
+}
+
+/// Load a ticket file, e.g. one dropped onto the window
+///
+/// # Arguments
+/// * `path` - Path of the ticket file
+///
+/// # Returns
+/// The ticket file contents including any note and expiry
+///
+/// # Errors
+/// Returns an error if the file cannot be read, contains no ticket, or has expired
+#[tauri::command]
+pub async fn load_ticket(path: String) -> Result<TicketFile, GinsengError> {
+    let ticket_file = load_ticket_file(&PathBuf::from(path))
+        .await
+        .map_err(GinsengError::from)?;
+
+    if ticket_file.is_expired() {
+        return Err(GinsengError::ShareExpired(
+            "This ticket file has expired".to_string(),
+        ));
+    }
+
+    Ok(ticket_file)
+}
+
+/// Format a ticket as a DNS TXT record for publishing under a domain
+///
+/// # Arguments
+/// * `ticket` - The ticket string to publish
+/// * `domain` - The domain recipients will receive from (e.g., "files.example.com")
+///
+/// # Returns
+/// A zone file line for the `_ginseng.<domain>` TXT record
+#[tauri::command]
+pub fn format_dns_record(ticket: String, domain: String) -> String {
+    format_txt_record(&domain, &ticket)
+}
+
+/// Look up the ticket published in DNS for a domain
+///
+/// # Arguments
+/// * `domain` - The domain publishing a Ginseng TXT record
+///
+/// # Returns
+/// The ticket string found in the TXT record
+///
+/// # Errors
+/// Returns an error if the lookup fails or no ticket is published
+#[tauri::command]
+pub async fn resolve_dns_ticket(domain: String) -> Result<String, GinsengError> {
+    resolve_ticket(&domain).await.map_err(GinsengError::from)
+}
+
+/// Open the drop box so other peers can push files to this device
+///
+/// Incoming pushes are reported through `drop-box` app events.
+///
+/// # Arguments
+/// * `app` - The Tauri application handle
+/// * `state` - The Tauri application state
+/// * `name` - Human-readable name shown to senders
+/// * `rules` - Rules deciding which pushes are accepted
+///
+/// # Returns
+/// The drop ticket senders use to push files
+///
+/// # Errors
+/// Returns an error if core is not initialized or the drop box cannot be opened
+#[tauri::command]
+pub async fn open_drop_box(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    name: String,
+    mut rules: AcceptRules,
+) -> Result<String, GinsengError> {
+    let core = state.get_core()?;
+    rules
+        .folder_name_template
+        .get_or_insert_with(|| core.folder_name_template());
+    if rules.target_directory.is_none() {
+        rules.target_directory = core.download_directory().ok();
+    }
+
+    let drop_ticket = core
+        .dropbox
+        .open(name, rules)
+        .await
+        .map_err(GinsengError::from)?;
+
+    let mut events = core.dropbox.subscribe();
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    app.emit(DROP_BOX_EVENT, event).ok();
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    state.drop_box_events.replace(handle).await;
+
+    Ok(drop_ticket)
+}
+
+/// Close the drop box so further pushes are rejected
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn close_drop_box(state: tauri::State<'_, AppState>) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+    core.dropbox.close().await;
+    state.drop_box_events.stop().await;
+    Ok(())
+}
+
+/// Send files to another peer's drop box
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `drop_ticket` - The drop ticket published by the receiver
+/// * `paths` - Vector of file paths to send
+///
+/// # Returns
+/// The drop box response telling whether the files were delivered
+///
+/// # Errors
+/// Returns an error if core is not initialized, paths are invalid, or the drop box is unreachable
+#[tauri::command]
+pub async fn send_to_drop_box(
+    state: tauri::State<'_, AppState>,
+    drop_ticket: String,
+    paths: Vec<String>,
+) -> Result<DropResponse, GinsengError> {
+    let core = state.get_core()?;
+    let validated_paths = validate_and_canonicalize_paths(paths)?;
+
+    core.send_to_drop_box(&drop_ticket, validated_paths)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Pair a device so files can be sent to it by name
+///
+/// Pairing a name again replaces the device's drop ticket.
+///
+/// # Arguments
+/// * `name` - Name to send to the device by
+/// * `drop_ticket` - The drop ticket published by the device
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the name is empty, the drop ticket is invalid, or the
+/// settings cannot be written
+#[tauri::command]
+pub async fn pair_device(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    drop_ticket: String,
+) -> Result<Settings, GinsengError> {
+    let device = PairedDevice {
+        name: name.trim().to_string(),
+        drop_ticket: drop_ticket.trim().to_string(),
+    };
+    if device.name.is_empty() {
+        return Err(GinsengError::Other(
+            "Device name cannot be empty".to_string(),
+        ));
+    }
+    device.peer().map_err(GinsengError::from)?;
+
+    let settings = update_settings(|settings| {
+        settings
+            .paired_devices
+            .retain(|paired| paired.name != device.name);
+        settings.paired_devices.push(device);
+    })
+    .await?;
+    refresh_warm_devices(&state, &settings);
+
+    Ok(settings)
+}
+
+/// Forget a paired device
+///
+/// The device is also removed from its groups. Pushes already queued for the
+/// device are still delivered.
+///
+/// # Arguments
+/// * `name` - Name of the device
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the settings cannot be written
+#[tauri::command]
+pub async fn unpair_device(
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> Result<Settings, GinsengError> {
+    let settings = update_settings(|settings| {
+        settings.paired_devices.retain(|paired| paired.name != name);
+        for group in &mut settings.device_groups {
+            group.devices.retain(|device| *device != name);
+        }
+    })
+    .await?;
+    refresh_warm_devices(&state, &settings);
+
+    Ok(settings)
+}
+
+/// Keeps connections warm to the paired devices of the settings.
+///
+/// Nothing is refreshed while core is starting; it reads the settings itself
+/// once started.
+fn refresh_warm_devices(state: &tauri::State<'_, AppState>, settings: &Settings) {
+    if let Ok(core) = state.get_core() {
+        core.warmer.set_devices(&settings.paired_devices);
+    }
+}
+
+/// Create or replace a group of paired devices
+///
+/// # Arguments
+/// * `name` - Name of the group, e.g. "Family"
+/// * `devices` - Names of the paired devices in the group
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the name is empty, a device is not paired, or the
+/// settings cannot be written
+#[tauri::command]
+pub async fn save_device_group(
+    name: String,
+    devices: Vec<String>,
+) -> Result<Settings, GinsengError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(GinsengError::Other(
+            "Group name cannot be empty".to_string(),
+        ));
+    }
+
+    let settings = load_settings().await?;
+    if let Some(unknown) = devices.iter().find(|device| {
+        !settings
+            .paired_devices
+            .iter()
+            .any(|paired| &paired.name == *device)
+    }) {
+        return Err(GinsengError::Other(format!(
+            "Device '{}' is not paired",
+            unknown
+        )));
+    }
+
+    update_settings(|settings| {
+        settings.device_groups.retain(|group| group.name != name);
+        settings.device_groups.push(DeviceGroup { name, devices });
+    })
+    .await
+}
+
+/// Delete a group of paired devices; the devices stay paired
+///
+/// # Arguments
+/// * `name` - Name of the group
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the settings cannot be written
+#[tauri::command]
+pub async fn delete_device_group(name: String) -> Result<Settings, GinsengError> {
+    update_settings(|settings| settings.device_groups.retain(|group| group.name != name)).await
+}
+
+/// Send files to a paired device, queueing them if the device is offline
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `device` - Name of the paired device
+/// * `paths` - Vector of file paths to send
+///
+/// # Returns
+/// The device's answer, or the ID of the push queued until it comes online
+///
+/// # Errors
+/// Returns an error if core is not initialized, the device is not paired,
+/// paths are invalid, or sharing fails
+#[tauri::command]
+pub async fn send_to_device(
+    state: tauri::State<'_, AppState>,
+    device: String,
+    paths: Vec<String>,
+) -> Result<DeviceDelivery, GinsengError> {
+    let core = state.get_core()?;
+    let settings = load_settings().await?;
+    let device = settings
+        .paired_devices
+        .iter()
+        .find(|paired| paired.name == device)
+        .ok_or_else(|| format!("Unknown device '{}'", device))?;
+    let validated_paths = validate_and_canonicalize_paths(paths)?;
+
+    core.send_to_device(device, validated_paths)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Send files to every device of a group at once
+///
+/// Devices that are offline get the push queued until they come online.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `paths` - Vector of file paths to send
+/// * `group` - Name of the device group
+///
+/// # Returns
+/// What happened to the push for each device of the group
+///
+/// # Errors
+/// Returns an error if core is not initialized, the group is unknown or
+/// empty, paths are invalid, or sharing fails
+#[tauri::command]
+pub async fn share_to_group(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    group: String,
+) -> Result<Vec<GroupDelivery>, GinsengError> {
+    let core = state.get_core()?;
+    let settings = load_settings().await?;
+    let group = settings
+        .device_groups
+        .iter()
+        .find(|device_group| device_group.name == group)
+        .ok_or_else(|| format!("Unknown device group '{}'", group))?;
+    let validated_paths = validate_and_canonicalize_paths(paths)?;
+
+    core.share_to_group(group, &settings.paired_devices, validated_paths)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// List the pushes waiting for their devices to come online
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// Every queued push, oldest first
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn list_outbox(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<QueuedPush>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.outbox.list())
+}
+
+/// Stop waiting to deliver a queued push
+///
+/// The share itself keeps being served.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `push_id` - The ID returned when the push was queued
+///
+/// # Errors
+/// Returns an error if core is not initialized or the push is not queued
+#[tauri::command]
+pub async fn cancel_queued_push(
+    state: tauri::State<'_, AppState>,
+    push_id: String,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+    if core.outbox.remove(&push_id) {
+        Ok(())
+    } else {
+        Err(GinsengError::Other(format!("Unknown push '{}'", push_id)))
+    }
+}
+
+/// List receipts of shares delivered to drop boxes and paired devices
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - Only list the receipts of this share, if given
+///
+/// # Returns
+/// Who received which share and when, oldest first
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn list_receipts(
+    state: tauri::State<'_, AppState>,
+    share_id: Option<String>,
+) -> Result<Vec<Receipt>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.receipts.list(share_id.as_deref()).await)
+}
+
+/// List finished shares and downloads, newest first
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `limit` - Largest number of transfers to list, all if not given
+///
+/// # Returns
+/// The recorded transfers with their ticket, peer, files and outcome
+///
+/// # Errors
+/// Returns an error if core is not initialized or the history cannot be read
+#[tauri::command]
+pub async fn list_history(
+    state: tauri::State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, GinsengError> {
+    let core = state.get_core()?;
+    core.history.list(limit).await.map_err(GinsengError::from)
+}
+
+/// Remove every finished share and download from the history
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// The number of transfers removed
+///
+/// # Errors
+/// Returns an error if core is not initialized or the history cannot be written
+#[tauri::command]
+pub async fn clear_history(state: tauri::State<'_, AppState>) -> Result<usize, GinsengError> {
+    let core = state.get_core()?;
+    core.history.clear().await.map_err(GinsengError::from)
+}
+
+/// List the shares currently being served
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// Every open share, oldest first
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn list_shares(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ShareSummary>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.shares.list().await)
+}
+
+/// Get who has fetched a share and how much they received
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The ID returned when the share was created
+///
+/// # Returns
+/// Every redemption of the share along with totals
+///
+/// # Errors
+/// Returns an error if core is not initialized or the share is unknown
+#[tauri::command]
+pub async fn share_activity(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+) -> Result<ShareActivity, GinsengError> {
+    let core = state.get_core()?;
+
+    core.shares
+        .activity(&share_id)
+        .await
+        .ok_or_else(|| GinsengError::Other(format!("Unknown share '{}'", share_id)))
+}
+
+/// Stop serving a share and remove its files from the store
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The ID returned when the share was created
+///
+/// # Errors
+/// Returns an error if core is not initialized or the share is unknown or
+/// already stopped
+#[tauri::command]
+pub async fn stop_share(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+
+    core.stop_share(&share_id).await.map_err(GinsengError::from)
+}
+
+/// Start serving the files of a preview share
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The ID returned when the share was created
+///
+/// # Errors
+/// Returns an error if core is not initialized or the share is unknown or
+/// already unlocked
+#[tauri::command]
+pub async fn unlock_share(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+
+    core.unlock_share(&share_id)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Save a printable transfer slip for a share
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The ID returned when the share was created
+/// * `destination` - File to save the card to, a PNG image if it ends in
+///   `.png` and a PDF otherwise
+///
+/// # Errors
+/// Returns an error if core is not initialized, the share is unknown or no
+/// longer open, or the card cannot be written
+#[tauri::command]
+pub async fn export_share_card(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+    destination: String,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+
+    core.export_share_card(&share_id, Path::new(&destination))
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// List every share received by this node, pinned shares first, then most
+/// recent first
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized or settings cannot be read
+#[tauri::command]
+pub async fn library_shares(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<LibraryEntry>, GinsengError> {
+    let core = state.get_core()?;
+    let settings = load_settings().await?;
+
+    let mut entries = core.library.list().await;
+    pinned_first(&mut entries, &settings.pinned_shares, |entry| {
+        &entry.share_id
+    });
+    Ok(entries)
+}
+
+/// Get the transfer statistics of a peer
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `node_id` - Endpoint ID of the peer
+///
+/// # Returns
+/// The peer's statistics, or None if the peer has never been seen
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn peer_stats(
+    state: tauri::State<'_, AppState>,
+    node_id: String,
+) -> Result<Option<PeerStats>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.peer_stats.get(&node_id).await)
+}
+
+/// List the transfer statistics of every known peer, pinned peers first, then
+/// most recently seen first
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized or settings cannot be read
+#[tauri::command]
+pub async fn list_peer_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PeerStats>, GinsengError> {
+    let core = state.get_core()?;
+    let settings = load_settings().await?;
+
+    let mut stats = core.peer_stats.list().await;
+    pinned_first(&mut stats, &settings.pinned_peers, |stats| &stats.peer);
+    Ok(stats)
+}
+
+/// Get the user's settings
+///
+/// # Errors
+/// Returns an error if the settings file cannot be read
+#[tauri::command]
+pub async fn get_settings() -> Result<Settings, GinsengError> {
+    load_settings().await
+}
+
+/// Pin or unpin a peer so it is listed first in device lists
+///
+/// # Arguments
+/// * `node_id` - Endpoint ID of the peer
+/// * `pinned` - Whether the peer should be pinned
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the settings cannot be read or written
+#[tauri::command]
+pub async fn set_peer_pinned(node_id: String, pinned: bool) -> Result<Settings, GinsengError> {
+    update_settings(|settings| {
+        if pinned {
+            settings.pinned_peers.insert(node_id);
+        } else {
+            settings.pinned_peers.remove(&node_id);
+        }
+    })
+    .await
+}
+
+/// Pin or unpin a share so it is listed first in the library
+///
+/// # Arguments
+/// * `share_id` - The share to pin or unpin
+/// * `pinned` - Whether the share should be pinned
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if the settings cannot be read or written
+#[tauri::command]
+pub async fn set_share_pinned(share_id: String, pinned: bool) -> Result<Settings, GinsengError> {
+    update_settings(|settings| {
+        if pinned {
+            settings.pinned_shares.insert(share_id);
+        } else {
+            settings.pinned_shares.remove(&share_id);
+        }
+    })
+    .await
+}
+
+/// Get the upload and download rate limits applied outside of scheduled windows
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn get_bandwidth_limits(
+    state: tauri::State<'_, AppState>,
+) -> Result<BandwidthLimits, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.bandwidth_limits())
+}
+
+/// Set the upload and download rate limits and save them in the settings
+///
+/// The limits apply immediately, including to running transfers.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `limits` - Caps in bytes per second, null for unlimited
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be written
+#[tauri::command]
+pub async fn set_bandwidth_limits(
+    state: tauri::State<'_, AppState>,
+    limits: BandwidthLimits,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+    core.set_bandwidth_limits(limits);
+
+    update_settings(|settings| settings.bandwidth = limits).await?;
+    Ok(())
+}
+
+/// Get the times of day with their own upload and download rate limits
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn get_bandwidth_schedule(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<BandwidthWindow>, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.bandwidth_schedule())
+}
+
+/// Set the times of day with their own rate limits and save them in the
+/// settings
+///
+/// The schedule applies immediately, including to running transfers.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `schedule` - Windows with a start and end time as HH:MM and their caps;
+///   the first window covering a time wins
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be written
+#[tauri::command]
+pub async fn set_bandwidth_schedule(
+    state: tauri::State<'_, AppState>,
+    schedule: Vec<BandwidthWindow>,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+    core.set_bandwidth_schedule(schedule.clone());
+
+    update_settings(|settings| settings.bandwidth_schedule = schedule).await?;
+    Ok(())
+}
+
+/// Get the disk and network concurrency limits
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Errors
+/// Returns an error if core is not initialized
+#[tauri::command]
+pub async fn get_concurrency_limits(
+    state: tauri::State<'_, AppState>,
+) -> Result<ConcurrencyLimits, GinsengError> {
+    let core = state.get_core()?;
+    Ok(core.concurrency_limits())
+}
+
+/// Set the disk and network concurrency limits and save them in the settings
+///
+/// Raised limits apply immediately; lowered limits apply as running file
+/// reads, writes and downloads finish.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `limits` - Files read or written at once, and files downloaded at once
+///
+/// # Errors
+/// Returns an error if core is not initialized, a limit is zero, or the
+/// settings cannot be written
+#[tauri::command]
+pub async fn set_concurrency_limits(
+    state: tauri::State<'_, AppState>,
+    limits: ConcurrencyLimits,
+) -> Result<(), GinsengError> {
+    if limits.disk == 0 || limits.network == 0 {
+        return Err(GinsengError::Other(
+            "Concurrency limits must be at least 1".to_string(),
+        ));
+    }
+
+    let core = state.get_core()?;
+    core.set_concurrency_limits(limits);
+
+    update_settings(|settings| settings.concurrency = limits).await?;
+    Ok(())
+}
+
+/// Set or clear the folder shares are saved to in place of Downloads
+///
+/// Devices with a download folder of their own keep using it.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `directory` - Folder to save shares to, or None for Downloads
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized, the folder does not exist or
+/// is not writable, or the settings cannot be written
+#[tauri::command]
+pub async fn set_download_directory(
+    state: tauri::State<'_, AppState>,
+    directory: Option<String>,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+
+    let directory = directory.map(PathBuf::from);
+    if let Some(directory) = &directory {
+        validate_download_directory(directory)
+            .await
+            .map_err(GinsengError::from)?;
+    }
+
+    let settings =
+        update_settings(|settings| settings.download_directory = directory.clone()).await?;
+
+    core.set_download_directory(directory);
+    Ok(settings)
+}
+
+/// Set or clear the download folder for shares received from a device
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `node_id` - Endpoint ID of the sending device
+/// * `folder` - Folder to save the device's shares to, or None for Downloads
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized, the folder is not a directory,
+/// or the settings cannot be written
+#[tauri::command]
+pub async fn set_device_folder(
+    state: tauri::State<'_, AppState>,
+    node_id: String,
+    folder: Option<String>,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+
+    let folder = match folder {
+        Some(folder) => {
+            let folder = PathBuf::from(folder);
+            if !folder.is_dir() {
+                return Err(GinsengError::Other(format!(
+                    "'{}' is not a directory",
+                    folder.display()
+                )));
+            }
+            Some(folder)
+        }
+        None => None,
+    };
+
+    let settings = update_settings(|settings| match folder {
+        Some(folder) => {
+            settings.device_folders.insert(node_id, folder);
+        }
+        None => {
+            settings.device_folders.remove(&node_id);
+        }
+    })
+    .await?;
+
+    core.set_device_folders(settings.device_folders.clone());
+    Ok(settings)
+}
+
+/// Set the default rules received shares are organized into subfolders by
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `rules` - Subfolder levels to add to the download folder, outermost first
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be written
+#[tauri::command]
+pub async fn set_organize_rules(
+    state: tauri::State<'_, AppState>,
+    rules: Vec<OrganizeRule>,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+    core.set_organize_rules(rules.clone());
+
+    update_settings(|settings| settings.organize_rules = rules).await
+}
+
+/// Set the template naming the folders shares of several files are saved in
+///
+/// `{date}`, `{time}` and `{timestamp}` in the template are replaced with
+/// when the share is received.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `template` - The naming template, or None for `Ginseng {date} {time}`
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized, the template is empty, or the
+/// settings cannot be written
+#[tauri::command]
+pub async fn set_folder_name_template(
+    state: tauri::State<'_, AppState>,
+    template: Option<String>,
+) -> Result<Settings, GinsengError> {
+    if let Some(template) = &template {
+        validate_template(template).map_err(GinsengError::from)?;
+    }
+    let core = state.get_core()?;
+    core.set_folder_name_template(template.clone());
+
+    update_settings(|settings| settings.folder_name_template = template).await
+}
+
+/// Set the relay servers the endpoint connects through
+///
+/// The endpoint keeps its current relays until the app is restarted.
+///
+/// # Arguments
+/// * `relays` - Public relay regions to use and further relay servers
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if a relay URL is invalid, no relay is selected, or the
+/// settings cannot be written
+#[tauri::command]
+pub async fn set_relay_preference(relays: RelayPreference) -> Result<Settings, GinsengError> {
+    relays.relay_mode().map_err(GinsengError::from)?;
+
+    update_settings(|settings| settings.relays = relays).await
+}
+
+/// Set whether connections to paired devices are kept open
+///
+/// Warm connections let pushes and downloads from paired devices start
+/// without first finding a path to the device.
+///
+/// # Arguments
+/// * `enabled` - Whether to keep connections warm
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be
+/// written
+#[tauri::command]
+pub async fn set_warm_connections(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+    core.warmer.set_enabled(enabled);
+
+    update_settings(|settings| settings.warm_connections = enabled).await
+}
+
+/// Set whether the endpoint's port is mapped on the router
+///
+/// A mapped port lets peers behind other routers connect directly instead
+/// of through a relay. Whether the router mapped it shows in the node info.
+///
+/// # Arguments
+/// * `enabled` - Whether to ask the router to map the port
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be
+/// written
+#[tauri::command]
+pub async fn set_port_mapping(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+    core.set_port_mapping(enabled);
+
+    update_settings(|settings| settings.port_mapping = enabled).await
+}
+
+/// Set whether downloaded files are removed from the store once saved
+///
+/// Released shares are not added to the library, so they cannot be exported
+/// again or served to other peers without downloading them again.
+///
+/// # Arguments
+/// * `release` - Whether to remove downloaded files from the store
+///
+/// # Returns
+/// The updated settings
+///
+/// # Errors
+/// Returns an error if core is not initialized or the settings cannot be
+/// written
+#[tauri::command]
+pub async fn set_release_downloads(
+    state: tauri::State<'_, AppState>,
+    release: bool,
+) -> Result<Settings, GinsengError> {
+    let core = state.get_core()?;
+    core.set_release_downloads(release);
+
+    update_settings(|settings| settings.release_downloads = release).await
+}
+
+/// Checks a folder chosen for a single download.
+///
+/// # Errors
+/// Returns an error if the folder does not exist or is not writable
+async fn chosen_download_directory(
+    target_dir: Option<String>,
+) -> Result<Option<PathBuf>, GinsengError> {
+    let Some(target_dir) = target_dir else {
+        return Ok(None);
+    };
+    let target_dir = PathBuf::from(target_dir);
+    validate_download_directory(&target_dir)
+        .await
+        .map_err(GinsengError::from)?;
+    Ok(Some(target_dir))
+}
+
+/// Loads the settings from the user's config directory.
+async fn load_settings() -> Result<Settings, GinsengError> {
+    let store = SettingsStore::open_default().map_err(GinsengError::from)?;
+    store.load().await.map_err(GinsengError::from)
+}
+
+/// Changes the settings in the user's config directory.
+async fn update_settings(updater: impl FnOnce(&mut Settings)) -> Result<Settings, GinsengError> {
+    let store = SettingsStore::open_default().map_err(GinsengError::from)?;
+    store.update(updater).await.map_err(GinsengError::from)
+}
+
+/// Export a received share from the library to a folder
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The library share to export
+/// * `destination` - Folder to export the share into
+///
+/// # Returns
+/// The path the files were exported to
+///
+/// # Errors
+/// Returns an error if core is not initialized, the share is not in the library,
+/// or the files cannot be written
+#[tauri::command]
+pub async fn export_from_library(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+    destination: String,
+) -> Result<String, GinsengError> {
+    let core = state.get_core()?;
+
+    let path = core
+        .export_from_library(&share_id, Path::new(&destination))
+        .await
+        .map_err(GinsengError::from)?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Remove a share from the library so its files can be garbage collected
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `share_id` - The library share to remove
+///
+/// # Errors
+/// Returns an error if core is not initialized or the share is not in the library
+#[tauri::command]
+pub async fn remove_from_library(
+    state: tauri::State<'_, AppState>,
+    share_id: String,
+) -> Result<(), GinsengError> {
+    let core = state.get_core()?;
+
+    core.library
+        .remove(&share_id)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// List every blob in the store for the storage inspector
+///
+/// # Returns
+/// Each blob with its size and the tags referencing it
+///
+/// # Errors
+/// Returns an error if core is not initialized or the store cannot be listed
+#[tauri::command]
+pub async fn list_store_blobs(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<BlobEntry>, GinsengError> {
+    let core = state.get_core()?;
+
+    inspector::list_blobs(&core.store)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// List every tag in the store for the storage inspector
+///
+/// # Errors
+/// Returns an error if core is not initialized or the tags cannot be listed
+#[tauri::command]
+pub async fn list_store_tags(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TagEntry>, GinsengError> {
+    let core = state.get_core()?;
+
+    inspector::list_tags(&core.store)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Read the start of a blob for preview in the storage inspector
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `hash` - Hash of the blob to read
+/// * `max_bytes` - Maximum number of bytes to return, 64 KiB by default
+///
+/// # Errors
+/// Returns an error if core is not initialized or the blob cannot be read
+#[tauri::command]
+pub async fn read_store_blob(
+    state: tauri::State<'_, AppState>,
+    hash: String,
+    max_bytes: Option<usize>,
+) -> Result<BlobPreview, GinsengError> {
+    let core = state.get_core()?;
+
+    inspector::read_blob(
+        &core.store,
+        &hash,
+        max_bytes.unwrap_or(DEFAULT_PREVIEW_BYTES),
+    )
+    .await
+    .map_err(GinsengError::from)
+}
+
+/// Delete a blob and the tags protecting it from the store
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+/// * `hash` - Hash of the blob to delete
+///
+/// # Returns
+/// The number of tags that were removed
+///
+/// # Errors
+/// Returns an error if core is not initialized or the blob cannot be deleted
+#[tauri::command]
+pub async fn delete_store_blob(
+    state: tauri::State<'_, AppState>,
+    hash: String,
+) -> Result<usize, GinsengError> {
+    let core = state.get_core()?;
+
+    inspector::delete_blob(&core.store, &hash)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// List the saved share templates
+///
+/// # Errors
+/// Returns an error if the templates file cannot be read
+#[tauri::command]
+pub async fn list_share_templates() -> Result<Vec<ShareTemplate>, GinsengError> {
+    let store = TemplateStore::open_default().map_err(GinsengError::from)?;
+    store.list().await.map_err(GinsengError::from)
+}
+
+/// Save a share template, replacing any template with the same name
+///
+/// # Arguments
+/// * `template` - The template to save
+///
+/// # Errors
+/// Returns an error if the template is invalid or cannot be written
+#[tauri::command]
+pub async fn save_share_template(template: ShareTemplate) -> Result<(), GinsengError> {
+    let store = TemplateStore::open_default().map_err(GinsengError::from)?;
+    store.save(template).await.map_err(GinsengError::from)
+}
+
+/// Delete a saved share template
+///
+/// # Arguments
+/// * `name` - Name of the template to delete
+///
+/// # Errors
+/// Returns an error if the template does not exist or cannot be removed
+#[tauri::command]
+pub async fn delete_share_template(name: String) -> Result<(), GinsengError> {
+    let store = TemplateStore::open_default().map_err(GinsengError::from)?;
+    store.remove(&name).await.map_err(GinsengError::from)
+}
+
+/// Run a saved share template
+///
+/// Shares the template's paths, writes its ticket file, and delivers the
+/// ticket to its recipient drop boxes.
+///
+/// # Arguments
+/// * `channel` - Channel for sending progress events of the share
+/// * `state` - The Tauri application state
+/// * `name` - Name of the template to run
+/// * `transfer_id` - Optional caller-chosen ID for the share transfer
+///
+/// # Returns
+/// The share and the outcome of each delivery
+///
+/// # Errors
+/// Returns an error if core is not initialized, the template does not exist,
+/// or sharing fails
+#[tauri::command]
+pub async fn run_share_template(
+    channel: Channel<ProgressEvent>,
+    state: tauri::State<'_, AppState>,
+    name: String,
+    transfer_id: Option<TransferId>,
+) -> Result<TemplateRun, GinsengError> {
+    let core = state.get_core()?;
+    let store = TemplateStore::open_default().map_err(GinsengError::from)?;
+    let template = store.get(&name).await.map_err(GinsengError::from)?;
+    let transfer_id = transfer_id.unwrap_or_else(new_transfer_id);
+
+    core.run_share_template(channel, transfer_id, &template)
+        .await
+        .map_err(GinsengError::from)
+}
+
+/// Get the initialization status of the Ginseng core
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// Whether the core is initializing, ready, or failed to initialize
+#[tauri::command]
+pub fn core_status(state: tauri::State<'_, AppState>) -> CoreStatus {
+    state.core_status()
+}
+
+/// Retry initializing the Ginseng core right away
+///
+/// Skips the remaining backoff delay after a failed initialization. Does
+/// nothing if the core is already initialized or currently initializing.
+///
+/// # Arguments
+/// * `state` - The Tauri application state
+///
+/// # Returns
+/// The initialization status at the time of the call
+#[tauri::command]
+pub fn retry_initialization(state: tauri::State<'_, AppState>) -> CoreStatus {
+    let status = state.core_status();
+    if matches!(status, CoreStatus::Failed { .. }) {
+        state.retry_requested.notify_one();
+    }
+    status
 }
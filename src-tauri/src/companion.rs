@@ -0,0 +1,440 @@
+//! Local endpoint for a companion browser extension
+//!
+//! A browser extension or file manager integration can hand Ginseng a file to
+//! send or a ticket to receive without going through the main window, by
+//! calling a small HTTP endpoint on localhost. The endpoint only listens on the
+//! loopback interface, and every request must carry the endpoint's token as
+//! `Authorization: Bearer <token>`, so other local software and web pages
+//! cannot use it. Every connection is handled on a task of its own, and a
+//! client that does not send its request within [`REQUEST_TIMEOUT`] is
+//! dropped, so a stalled client or a running download never holds up others.
+//!
+//! Routes:
+//!
+//! - `GET /` answers with the app name and version, to check the connection
+//! - `POST /send` with `{"paths": [...]}` shares the files and answers with
+//!   the share ID and ticket
+//! - `POST /receive` with `{"ticket": "..."}` answers right away and then
+//!   downloads the share, or every share if several tickets were pasted
+
+use crate::core::{split_pasted_tickets, GinsengCore, ShareOptions};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use uuid::Uuid;
+
+/// Port the endpoint listens on unless another one is configured
+pub const DEFAULT_COMPANION_PORT: u16 = 47615;
+
+/// Largest request head (request line and headers) accepted, in bytes
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+/// Largest request body accepted, in bytes
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// How long a client may take to send its request or read the response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events emitted while serving the companion endpoint
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "event",
+    content = "data"
+)]
+pub enum CompanionEvent {
+    /// Files handed over by the extension were shared
+    ShareCreated {
+        paths: Vec<String>,
+        share_id: String,
+        ticket: String,
+    },
+    /// A ticket handed over by the extension is being received
+    DownloadStarted { ticket: String },
+    /// A share handed over by the extension was downloaded
+    DownloadCompleted { download_path: String },
+    /// A request could not be carried out
+    RequestFailed { error: String },
+    /// The endpoint itself stopped because of an error
+    EndpointFailed { error: String },
+}
+
+/// Files the extension asks to send
+#[derive(Debug, Deserialize)]
+struct SendRequest {
+    paths: Vec<PathBuf>,
+}
+
+/// Ticket the extension asks to receive
+#[derive(Debug, Deserialize)]
+struct ReceiveRequest {
+    ticket: String,
+}
+
+/// A parsed HTTP request
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+/// An HTTP response with a JSON body
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: u16,
+    body: serde_json::Value,
+}
+
+impl Response {
+    fn json(status: u16, body: impl Serialize) -> Self {
+        Self {
+            status,
+            body: serde_json::to_value(body).unwrap_or_default(),
+        }
+    }
+
+    fn error(status: u16, error: impl ToString) -> Self {
+        Self::json(status, serde_json::json!({ "error": error.to_string() }))
+    }
+}
+
+/// Work left to do after the response has been sent
+enum AfterResponse {
+    Nothing,
+    Receive(Vec<String>),
+}
+
+/// Creates a new random token for the endpoint
+pub fn new_companion_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Starts listening for the extension on a localhost port.
+///
+/// # Errors
+///
+/// Returns an error if the port is already in use.
+pub async fn bind_companion(port: u16) -> Result<TcpListener> {
+    TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to listen on port {}: {}", port, error))
+}
+
+/// Serves the companion endpoint until accepting connections fails.
+///
+/// # Arguments
+///
+/// * `core` - The Ginseng core used to share and receive
+/// * `listener` - Listener returned by [`bind_companion`]
+/// * `token` - Token every request must carry
+/// * `on_event` - Callback invoked for every endpoint event
+///
+/// # Errors
+///
+/// Returns an error if connections can no longer be accepted.
+pub async fn serve_companion<F>(
+    core: GinsengCore,
+    listener: TcpListener,
+    token: String,
+    on_event: F,
+) -> Result<()>
+where
+    F: Fn(CompanionEvent) + Clone + Send + Sync + 'static,
+{
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let core = core.clone();
+        let token = token.clone();
+        let on_event = on_event.clone();
+        tokio::spawn(async move { handle_connection(&core, stream, &token, &on_event).await });
+    }
+}
+
+/// Answers the request of a connection, then receives the shares it handed over.
+///
+/// Only reading the request and writing the response are limited by
+/// [`REQUEST_TIMEOUT`]; sharing large files may take longer than that.
+async fn handle_connection<F>(core: &GinsengCore, mut stream: TcpStream, token: &str, on_event: &F)
+where
+    F: Fn(CompanionEvent),
+{
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out waiting for the request")));
+    let (response, after) = match request {
+        Ok(request) => handle_request(core, token, request, on_event).await,
+        Err(error) => (Response::error(400, error), AfterResponse::Nothing),
+    };
+    let _ = tokio::time::timeout(REQUEST_TIMEOUT, write_response(&mut stream, &response)).await;
+    drop(stream);
+
+    if let AfterResponse::Receive(tickets) = after {
+        for ticket in tickets {
+            receive(core, ticket, on_event).await;
+        }
+    }
+}
+
+/// Checks the token of a request and carries out what it asks for.
+async fn handle_request<F>(
+    core: &GinsengCore,
+    token: &str,
+    request: Request,
+    on_event: &F,
+) -> (Response, AfterResponse)
+where
+    F: Fn(CompanionEvent),
+{
+    if !request
+        .token
+        .as_deref()
+        .is_some_and(|given| tokens_match(given, token))
+    {
+        return (
+            Response::error(401, "Missing or invalid token"),
+            AfterResponse::Nothing,
+        );
+    }
+
+    let response = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response::json(
+            200,
+            serde_json::json!({ "name": "ginseng", "version": env!("CARGO_PKG_VERSION") }),
+        ),
+        ("POST", "/send") => match serde_json::from_slice::<SendRequest>(&request.body) {
+            Ok(send) => share(core, send.paths, on_event).await,
+            Err(error) => Response::error(400, error),
+        },
+        ("POST", "/receive") => match serde_json::from_slice::<ReceiveRequest>(&request.body) {
+            Ok(receive) => {
+                let tickets = split_pasted_tickets(&receive.ticket)
+                    .unwrap_or_else(|| vec![receive.ticket.trim().to_string()]);
+                let response = Response::json(202, serde_json::json!({ "tickets": tickets.len() }));
+                return (response, AfterResponse::Receive(tickets));
+            }
+            Err(error) => Response::error(400, error),
+        },
+        _ => Response::error(404, "Unknown route"),
+    };
+    (response, AfterResponse::Nothing)
+}
+
+/// Shares files handed over by the extension.
+async fn share<F>(core: &GinsengCore, paths: Vec<PathBuf>, on_event: &F) -> Response
+where
+    F: Fn(CompanionEvent),
+{
+    let display_paths = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    match core
+        .share_files_cli(paths, BTreeMap::new(), ShareOptions::default())
+        .await
+    {
+        Ok(share) => {
+            on_event(CompanionEvent::ShareCreated {
+                paths: display_paths,
+                share_id: share.share_id.clone(),
+                ticket: share.ticket.clone(),
+            });
+            Response::json(
+                200,
+                serde_json::json!({ "shareId": share.share_id, "ticket": share.ticket }),
+            )
+        }
+        Err(error) => {
+            on_event(CompanionEvent::RequestFailed {
+                error: error.to_string(),
+            });
+            Response::error(500, error)
+        }
+    }
+}
+
+/// Downloads a share handed over by the extension.
+async fn receive<F>(core: &GinsengCore, ticket: String, on_event: &F)
+where
+    F: Fn(CompanionEvent),
+{
+    on_event(CompanionEvent::DownloadStarted {
+        ticket: ticket.clone(),
+    });
+
+    match core.download_files_cli(ticket).await {
+        Ok((_, download_path)) => on_event(CompanionEvent::DownloadCompleted {
+            download_path: download_path.to_string_lossy().to_string(),
+        }),
+        Err(error) => on_event(CompanionEvent::RequestFailed {
+            error: error.to_string(),
+        }),
+    }
+}
+
+/// Reads an HTTP request from a connection.
+async fn read_request<S>(stream: &mut S) -> Result<Request>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::new();
+    let head_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        if buffer.len() > MAX_HEAD_SIZE {
+            anyhow::bail!("Request head is too large");
+        }
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            anyhow::bail!("Connection closed before the request was complete");
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = std::str::from_utf8(&buffer[..head_end])?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut token = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse()?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            token = value.strip_prefix("Bearer ").map(str::to_string);
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        anyhow::bail!("Request body is too large");
+    }
+
+    let mut body = buffer.split_off(head_end + 4);
+    if body.len() < content_length {
+        let mut rest = vec![0u8; content_length - body.len()];
+        stream.read_exact(&mut rest).await?;
+        body.extend_from_slice(&rest);
+    }
+    body.truncate(content_length);
+
+    Ok(Request {
+        method,
+        path,
+        token,
+        body,
+    })
+}
+
+/// Writes an HTTP response and closes the connection.
+async fn write_response<S>(stream: &mut S, response: &Response) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let body = serde_json::to_vec(&response.body)?;
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Returns the reason phrase for the status codes the endpoint answers with.
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Compares tokens in constant time, so the token cannot be guessed from how
+/// long a comparison takes.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request_with_token_and_body() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client
+            .write_all(
+                b"POST /receive HTTP/1.1\r\nHost: localhost\r\nauthorization: Bearer secret\r\nContent-Length: 17\r\n\r\n{\"ticket\":\"blob\"}",
+            )
+            .await
+            .unwrap();
+
+        let request = read_request(&mut server).await.unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: "POST".to_string(),
+                path: "/receive".to_string(),
+                token: Some("secret".to_string()),
+                body: br#"{"ticket":"blob"}"#.to_vec(),
+            }
+        );
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"POST /send HTTP/1.1\r\nContent-Length: 999999\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(read_request(&mut server).await.is_err());
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        let token = new_companion_token();
+        assert!(tokens_match(&token, &token));
+        assert!(!tokens_match(&new_companion_token(), &token));
+        assert!(!tokens_match("", &token));
+    }
+
+    #[tokio::test]
+    async fn test_write_response() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        write_response(
+            &mut server,
+            &Response::error(401, "Missing or invalid token"),
+        )
+        .await
+        .unwrap();
+
+        let mut written = String::new();
+        client.read_to_string(&mut written).await.unwrap();
+        assert!(written.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(written.ends_with(r#"{"error":"Missing or invalid token"}"#));
+    }
+}
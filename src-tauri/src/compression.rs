@@ -0,0 +1,287 @@
+//! Optional compression of shared file data
+//!
+//! A share can compress its files with zstd before they are stored as blobs,
+//! so text-heavy folders cross slow links much faster. Each file records how
+//! its blob is compressed in its [`FileInfo`](crate::core::FileInfo), and the
+//! receiver restores the original content when it exports the file. Files that
+//! do not get smaller, such as photos or archives, are stored as they are.
+//!
+//! Restored content is capped at the size the file declares, so a small blob
+//! from a peer cannot expand into an unbounded amount of data.
+
+use anyhow::{ensure, Result};
+use async_compression::tokio::bufread::ZstdDecoder;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+/// zstd level used when compressing files; favors speed over ratio
+const ZSTD_LEVEL: i32 = 3;
+
+/// How the blob of a shared file is compressed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// The blob is the file content as it is
+    #[default]
+    None,
+    /// The blob is the file content compressed with zstd
+    Zstd,
+}
+
+impl Compression {
+    /// Checks whether the blob is stored uncompressed
+    pub fn is_none(&self) -> bool {
+        *self == Compression::None
+    }
+}
+
+/// Compresses the content of a file for storing it as a blob.
+///
+/// # Arguments
+///
+/// * `content` - The content of the file
+///
+/// # Returns
+///
+/// The compressed content, or None if compressing does not make it smaller
+///
+/// # Errors
+///
+/// Returns an error if the compressor fails.
+pub fn compress(content: &[u8]) -> Result<Option<Vec<u8>>> {
+    let compressed = zstd::bulk::compress(content, ZSTD_LEVEL)
+        .map_err(|error| anyhow::anyhow!("Failed to compress file: {}", error))?;
+    Ok((compressed.len() < content.len()).then_some(compressed))
+}
+
+/// Restores the content of a file from its blob.
+///
+/// # Arguments
+///
+/// * `compression` - How the blob is compressed
+/// * `blob` - The content of the blob
+/// * `max_size` - The largest size the original content may have
+///
+/// # Returns
+///
+/// The original content of the file; borrowed from `blob` if it is not compressed
+///
+/// # Errors
+///
+/// Returns an error if the blob is not valid compressed data or the content
+/// is larger than `max_size`.
+pub fn decompress(compression: Compression, blob: &[u8], max_size: u64) -> Result<Cow<'_, [u8]>> {
+    let content = match compression {
+        Compression::None => Cow::Borrowed(blob),
+        Compression::Zstd => {
+            let mut content = Vec::new();
+            zstd::stream::read::Decoder::new(blob)
+                .and_then(|decoder| {
+                    decoder
+                        .take(max_size.saturating_add(1))
+                        .read_to_end(&mut content)
+                })
+                .map_err(|error| anyhow::anyhow!("Failed to decompress file: {}", error))?;
+            Cow::Owned(content)
+        }
+    };
+    ensure!(
+        content.len() as u64 <= max_size,
+        "File content is larger than its declared {} bytes",
+        max_size
+    );
+    Ok(content)
+}
+
+/// Restores the content of a file while its blob is read.
+///
+/// Only a small window of the content is held in memory at a time, so files
+/// of any size can be written to disk as they are read from the store.
+///
+/// # Arguments
+///
+/// * `compression` - How the blob is compressed
+/// * `blob` - Reader of the blob
+/// * `max_size` - The largest size the original content may have
+///
+/// # Returns
+///
+/// Reader of the original content of the file; reading fails if the blob is
+/// not valid compressed data or the content is larger than `max_size`
+pub fn decompress_reader<'a>(
+    compression: Compression,
+    blob: impl AsyncRead + Send + 'a,
+    max_size: u64,
+) -> Pin<Box<dyn AsyncRead + Send + 'a>> {
+    let content: Pin<Box<dyn AsyncRead + Send + 'a>> = match compression {
+        Compression::None => Box::pin(blob),
+        Compression::Zstd => Box::pin(ZstdDecoder::new(BufReader::new(blob))),
+    };
+    Box::pin(SizeLimit {
+        inner: content,
+        max_size,
+        remaining: max_size,
+    })
+}
+
+/// Reader that fails once its inner reader produces more than `max_size` bytes
+struct SizeLimit<R> {
+    inner: R,
+    max_size: u64,
+    remaining: u64,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for SizeLimit<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - filled) as u64;
+        match self.remaining.checked_sub(read) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Poll::Ready(Ok(()))
+            }
+            None => {
+                buf.set_filled(filled);
+                Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "file content is larger than its declared {} bytes",
+                        self.max_size
+                    ),
+                )))
+            }
+        }
+    }
+}
+
+/// Restores the content of a file from its blob on the blocking thread pool.
+///
+/// Decompressing a large file would otherwise hold up the async runtime, and
+/// with it progress events and other transfers, until it is done.
+///
+/// # Errors
+///
+/// Returns an error if the blob is not valid compressed data or the content
+/// is larger than `max_size`.
+pub async fn decompress_blocking<B>(
+    compression: Compression,
+    blob: B,
+    max_size: u64,
+) -> Result<Vec<u8>>
+where
+    B: AsRef<[u8]> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        decompress(compression, blob.as_ref(), max_size).map(Cow::into_owned)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let content = "line of text\n".repeat(1000).into_bytes();
+
+        let compressed = compress(&content).unwrap().unwrap();
+        assert!(compressed.len() < content.len());
+        assert_eq!(
+            decompress(Compression::Zstd, &compressed, content.len() as u64).unwrap(),
+            content.as_slice()
+        );
+        assert!(decompress(Compression::Zstd, b"not zstd", 100).is_err());
+    }
+
+    #[test]
+    fn test_incompressible_content_is_kept() {
+        assert_eq!(compress(b"abc").unwrap(), None);
+        assert!(matches!(
+            decompress(Compression::None, b"abc", 3).unwrap(),
+            Cow::Borrowed(b"abc")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decompress_reader() {
+        use tokio::io::AsyncReadExt;
+
+        let content = "line of text\n".repeat(1000).into_bytes();
+        let compressed = compress(&content).unwrap().unwrap();
+
+        let mut restored = Vec::new();
+        decompress_reader(
+            Compression::Zstd,
+            compressed.as_slice(),
+            content.len() as u64,
+        )
+        .read_to_end(&mut restored)
+        .await
+        .unwrap();
+        assert_eq!(restored, content);
+
+        let mut invalid = Vec::new();
+        assert!(decompress_reader(Compression::Zstd, &b"not zstd"[..], 100)
+            .read_to_end(&mut invalid)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decompress_blocking() {
+        let content = "line of text\n".repeat(1000).into_bytes();
+        let compressed = compress(&content).unwrap().unwrap();
+
+        assert_eq!(
+            decompress_blocking(Compression::Zstd, compressed, content.len() as u64)
+                .await
+                .unwrap(),
+            content
+        );
+        assert!(
+            decompress_blocking(Compression::Zstd, b"not zstd".to_vec(), 100)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_content_larger_than_declared_is_refused() {
+        use tokio::io::AsyncReadExt;
+
+        let content = "line of text\n".repeat(1000).into_bytes();
+        let compressed = compress(&content).unwrap().unwrap();
+        let declared = content.len() as u64 - 1;
+
+        assert!(decompress(Compression::Zstd, &compressed, declared).is_err());
+        assert!(decompress(Compression::None, &content, declared).is_err());
+        assert!(
+            decompress_blocking(Compression::Zstd, compressed.clone(), declared)
+                .await
+                .is_err()
+        );
+
+        let mut restored = Vec::new();
+        let error = decompress_reader(Compression::Zstd, compressed.as_slice(), declared)
+            .read_to_end(&mut restored)
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(
+            decompress_reader(Compression::None, content.as_slice(), declared)
+                .read_to_end(&mut Vec::new())
+                .await
+                .is_err()
+        );
+    }
+}
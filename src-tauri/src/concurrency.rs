@@ -0,0 +1,227 @@
+//! Concurrency budgets for disk IO and network streams
+//!
+//! Reading files into the blob store and writing received files to disk share
+//! one budget, downloading file data from peers another. Keeping them apart
+//! means a slow disk only holds back other disk work, while downloads keep
+//! using the network, and a slow network does not leave the disk idle. Both
+//! budgets can be resized while transfers are running: a larger budget lets
+//! waiting work start right away, a smaller one takes effect as running work
+//! finishes.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Files read or written at once by default, across all transfers
+pub const DEFAULT_DISK_CONCURRENCY: usize = 2;
+
+/// Files downloaded at once by default, across all transfers
+pub const DEFAULT_NETWORK_CONCURRENCY: usize = 4;
+
+/// Sizes of the disk and network budgets
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcurrencyLimits {
+    /// Files read from or written to disk at once
+    #[serde(default = "default_disk")]
+    pub disk: usize,
+    /// Files downloaded from peers at once
+    #[serde(default = "default_network")]
+    pub network: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            disk: DEFAULT_DISK_CONCURRENCY,
+            network: DEFAULT_NETWORK_CONCURRENCY,
+        }
+    }
+}
+
+fn default_disk() -> usize {
+    DEFAULT_DISK_CONCURRENCY
+}
+
+fn default_network() -> usize {
+    DEFAULT_NETWORK_CONCURRENCY
+}
+
+/// A resizable number of slots, shared by every transfer
+#[derive(Debug, Clone)]
+pub struct Budget {
+    slots: Arc<Semaphore>,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+/// Size of a budget guarded by its lock
+#[derive(Debug)]
+struct BudgetState {
+    /// Number of slots
+    size: usize,
+    /// Slots in use that are given up instead of returned, after shrinking
+    debt: usize,
+}
+
+impl Budget {
+    /// Creates a budget with the given number of slots, at least one
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(size)),
+            state: Arc::new(Mutex::new(BudgetState { size, debt: 0 })),
+        }
+    }
+
+    /// Returns the number of slots
+    pub fn size(&self) -> usize {
+        self.state.lock().map_or(1, |state| state.size)
+    }
+
+    /// Changes the number of slots, at least one
+    ///
+    /// Slots in use when shrinking stay in use until their permits are dropped.
+    pub fn set_size(&self, size: usize) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let size = size.max(1);
+
+        if size > state.size {
+            let added = size - state.size;
+            let repaid = added.min(state.debt);
+            state.debt -= repaid;
+            self.slots.add_permits(added - repaid);
+        } else {
+            let removed = state.size - size;
+            let forgotten = self.slots.forget_permits(removed);
+            state.debt += removed - forgotten;
+        }
+        state.size = size;
+    }
+
+    /// Waits for a free slot and holds it until the permit is dropped
+    pub async fn acquire(&self) -> BudgetPermit {
+        let permit = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("budget semaphore is never closed");
+        BudgetPermit {
+            permit: Some(permit),
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// A slot of a budget, given back when dropped
+#[derive(Debug)]
+pub struct BudgetPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl Drop for BudgetPermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        if let Ok(mut state) = self.state.lock() {
+            if state.debt > 0 {
+                state.debt -= 1;
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Disk and network budgets of a node
+#[derive(Debug, Clone)]
+pub struct Concurrency {
+    /// Limits files read into the blob store and written to disk
+    pub disk: Budget,
+    /// Limits files downloaded from peers
+    pub network: Budget,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        let limits = ConcurrencyLimits::default();
+        Self {
+            disk: Budget::new(limits.disk),
+            network: Budget::new(limits.network),
+        }
+    }
+}
+
+impl Concurrency {
+    /// Returns the current limits
+    pub fn limits(&self) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            disk: self.disk.size(),
+            network: self.network.size(),
+        }
+    }
+
+    /// Changes both limits
+    pub fn set_limits(&self, limits: ConcurrencyLimits) {
+        self.disk.set_size(limits.disk);
+        self.network.set_size(limits.network);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    async fn try_acquire(budget: &Budget) -> Option<BudgetPermit> {
+        timeout(Duration::from_millis(20), budget.acquire())
+            .await
+            .ok()
+    }
+
+    #[tokio::test]
+    async fn test_budget_grows_and_shrinks_while_in_use() {
+        let budget = Budget::new(2);
+        let first = budget.acquire().await;
+        let second = budget.acquire().await;
+        assert!(try_acquire(&budget).await.is_none());
+
+        budget.set_size(3);
+        let third = try_acquire(&budget).await.unwrap();
+
+        // Shrinking below the slots in use waits for them to be dropped
+        budget.set_size(1);
+        assert_eq!(budget.size(), 1);
+        drop(first);
+        drop(second);
+        assert!(try_acquire(&budget).await.is_none());
+        drop(third);
+        let only = try_acquire(&budget).await.unwrap();
+        assert!(try_acquire(&budget).await.is_none());
+        drop(only);
+
+        budget.set_size(0);
+        assert_eq!(budget.size(), 1);
+        assert!(try_acquire(&budget).await.is_some());
+    }
+
+    #[test]
+    fn test_limits_of_budgets_are_independent() {
+        let concurrency = Concurrency::default();
+        assert_eq!(concurrency.limits(), ConcurrencyLimits::default());
+
+        concurrency.set_limits(ConcurrencyLimits {
+            disk: 1,
+            network: 8,
+        });
+        assert_eq!(concurrency.disk.size(), 1);
+        assert_eq!(concurrency.network.size(), 8);
+
+        let limits: ConcurrencyLimits = serde_json::from_str(r#"{"disk":3}"#).unwrap();
+        assert_eq!(limits.network, DEFAULT_NETWORK_CONCURRENCY);
+    }
+}
@@ -0,0 +1,493 @@
+//! File manager context-menu integration
+//!
+//! Adds a "Share with Ginseng" entry to the context menu of files and folders
+//! in the system file manager:
+//!
+//! - Windows: shell verbs for files and folders under the current user's
+//!   registry classes
+//! - macOS: a Quick Action in `~/Library/Services`, shown for Finder items
+//! - Linux: a Dolphin service menu and a Nautilus script
+//!
+//! Every entry launches the app with [`SHARE_ARG`] followed by the selected
+//! paths. If the app is already running, the arguments are handed to the
+//! running instance instead of starting a second one.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Argument the context-menu entries launch the app with, followed by the
+/// selected paths
+pub const SHARE_ARG: &str = "--share";
+
+/// Label of the context-menu entry
+pub const MENU_LABEL: &str = "Share with Ginseng";
+
+/// Extracts the paths to share from the arguments the app was launched with.
+///
+/// Relative paths are resolved against the working directory of the launch.
+///
+/// # Arguments
+///
+/// * `args` - The launch arguments, starting with the executable
+/// * `cwd` - Working directory of the launch
+///
+/// # Returns
+///
+/// The paths following [`SHARE_ARG`], or None if the app was not launched
+/// from the context menu or no paths were selected
+pub fn share_paths_from_args<I>(args: I, cwd: &Path) -> Option<Vec<PathBuf>>
+where
+    I: IntoIterator,
+    I::Item: Into<PathBuf>,
+{
+    let mut args = args.into_iter().map(Into::into).skip(1);
+    args.by_ref().find(|arg| arg.as_os_str() == SHARE_ARG)?;
+
+    let paths: Vec<PathBuf> = args.map(|path| cwd.join(path)).collect();
+    (!paths.is_empty()).then_some(paths)
+}
+
+/// Returns the executable the context-menu entries should launch.
+///
+/// Inside an AppImage the running executable lives in a temporary mount, so
+/// the AppImage itself is launched instead.
+///
+/// # Errors
+///
+/// Returns an error if the path of the running executable cannot be determined.
+pub fn launcher_path() -> Result<PathBuf> {
+    if let Some(appimage) = std::env::var_os("APPIMAGE") {
+        return Ok(PathBuf::from(appimage));
+    }
+    Ok(std::env::current_exe()?)
+}
+
+/// Adds the context-menu entries for the current user, replacing earlier ones.
+///
+/// # Arguments
+///
+/// * `launcher` - The executable the entries launch
+///
+/// # Errors
+///
+/// Returns an error if the entries cannot be written or the platform is not
+/// supported.
+pub fn install(launcher: &Path) -> Result<()> {
+    platform::install(launcher)
+}
+
+/// Removes the context-menu entries of the current user.
+///
+/// Entries that are not installed are skipped.
+///
+/// # Errors
+///
+/// Returns an error if an installed entry cannot be removed or the platform
+/// is not supported.
+pub fn uninstall() -> Result<()> {
+    platform::uninstall()
+}
+
+/// Checks whether the context-menu entries are installed for the current user.
+pub fn is_installed() -> bool {
+    platform::is_installed()
+}
+
+/// Quotes an argument for a shell script.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Quotes an argument for the `Exec` key of a desktop entry.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn desktop_exec_quote(arg: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    // The whole value is unescaped once more when the entry is read
+    quoted.replace('\\', r"\\")
+}
+
+/// Writes a menu entry file, creating its parent directories.
+#[cfg(unix)]
+fn write_entry(path: &Path, contents: &str, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    if executable {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Removes a file or directory if it exists.
+#[cfg(unix)]
+fn remove_if_exists(path: &Path) -> Result<()> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match result {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{desktop_exec_quote, remove_if_exists, shell_quote, write_entry};
+    use super::{MENU_LABEL, SHARE_ARG};
+    use anyhow::Result;
+    use std::path::{Path, PathBuf};
+
+    /// Returns the Dolphin service menu and the Nautilus script
+    fn entry_paths() -> Result<[PathBuf; 2]> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine the data directory"))?;
+        Ok([
+            data_dir.join("kio/servicemenus/ginseng-share.desktop"),
+            data_dir.join("nautilus/scripts").join(MENU_LABEL),
+        ])
+    }
+
+    pub fn install(launcher: &Path) -> Result<()> {
+        let launcher = launcher.to_string_lossy();
+        let [service_menu, nautilus_script] = entry_paths()?;
+
+        // Dolphin only runs service menus that are executable
+        write_entry(
+            &service_menu,
+            &format!(
+                "[Desktop Entry]\n\
+                 Type=Service\n\
+                 MimeType=all/all;\n\
+                 Actions=share;\n\
+                 X-KDE-Priority=TopLevel\n\
+                 \n\
+                 [Desktop Action share]\n\
+                 Name={MENU_LABEL}\n\
+                 Icon=ginseng\n\
+                 Exec={} {SHARE_ARG} %F\n",
+                desktop_exec_quote(&launcher)
+            ),
+            true,
+        )?;
+        write_entry(
+            &nautilus_script,
+            &format!(
+                "#!/bin/sh\nexec {} {SHARE_ARG} \"$@\"\n",
+                shell_quote(&launcher)
+            ),
+            true,
+        )?;
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<()> {
+        for path in entry_paths()? {
+            remove_if_exists(&path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_installed() -> bool {
+        entry_paths().is_ok_and(|paths| paths.iter().any(|path| path.exists()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{remove_if_exists, shell_quote, write_entry, MENU_LABEL, SHARE_ARG};
+    use anyhow::Result;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Returns the Quick Action bundle
+    fn workflow_path() -> Result<PathBuf> {
+        let home = dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine the home directory"))?;
+        Ok(home
+            .join("Library/Services")
+            .join(format!("{MENU_LABEL}.workflow")))
+    }
+
+    /// Escapes text for an XML property list.
+    fn xml_escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    /// Asks the system to pick up changed services; best effort, since they
+    /// are also picked up on the next login.
+    fn refresh_services() {
+        let _ = Command::new("/System/Library/CoreServices/pbs")
+            .arg("-update")
+            .status();
+    }
+
+    pub fn install(launcher: &Path) -> Result<()> {
+        let contents = workflow_path()?.join("Contents");
+        let command = format!(
+            "exec {} {SHARE_ARG} \"$@\"",
+            shell_quote(&launcher.to_string_lossy())
+        );
+
+        write_entry(
+            &contents.join("Info.plist"),
+            &format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>{MENU_LABEL}</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.item</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#
+            ),
+            false,
+        )?;
+        write_entry(
+            &contents.join("document.wflow"),
+            &format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.string</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>AMParameterProperties</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<dict/>
+					<key>CheckedForUserDefaultShell</key>
+					<dict/>
+					<key>inputMethod</key>
+					<dict/>
+					<key>shell</key>
+					<dict/>
+					<key>source</key>
+					<dict/>
+				</dict>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>{}</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+				<key>Class Name</key>
+				<string>RunShellScriptAction</string>
+			</dict>
+		</dict>
+	</array>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceApplicationBundleID</key>
+		<string>com.apple.finder</string>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#,
+                xml_escape(&command)
+            ),
+            false,
+        )?;
+        refresh_services();
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<()> {
+        remove_if_exists(&workflow_path()?)?;
+        refresh_services();
+        Ok(())
+    }
+
+    pub fn is_installed() -> bool {
+        workflow_path().is_ok_and(|path| path.exists())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{MENU_LABEL, SHARE_ARG};
+    use anyhow::Result;
+    use std::path::Path;
+    use std::process::{Command, Stdio};
+
+    /// Registry keys of the shell verbs for files and for folders
+    const VERB_KEYS: [&str; 2] = [
+        r"HKCU\Software\Classes\*\shell\Ginseng",
+        r"HKCU\Software\Classes\Directory\shell\Ginseng",
+    ];
+
+    /// Runs `reg.exe` with the given arguments.
+    fn reg(args: &[&str]) -> Result<bool> {
+        let status = Command::new("reg")
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(status.success())
+    }
+
+    /// Sets a registry value, failing if it cannot be written.
+    fn set_value(key: &str, name: Option<&str>, data: &str) -> Result<()> {
+        let mut args = vec!["add", key];
+        match name {
+            Some(name) => args.extend(["/v", name]),
+            None => args.push("/ve"),
+        }
+        args.extend(["/d", data, "/f"]);
+        if !reg(&args)? {
+            anyhow::bail!("Could not write registry key '{}'", key);
+        }
+        Ok(())
+    }
+
+    pub fn install(launcher: &Path) -> Result<()> {
+        let launcher = launcher.to_string_lossy();
+        // Explorer starts one process per selected item, and each is handed
+        // to the running instance
+        let command = format!("\"{launcher}\" {SHARE_ARG} \"%1\"");
+        for key in VERB_KEYS {
+            set_value(key, None, MENU_LABEL)?;
+            set_value(key, Some("Icon"), &launcher)?;
+            set_value(&format!(r"{key}\command"), None, &command)?;
+        }
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<()> {
+        for key in VERB_KEYS {
+            if reg(&["query", key])? && !reg(&["delete", key, "/f"])? {
+                anyhow::bail!("Could not remove registry key '{}'", key);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_installed() -> bool {
+        VERB_KEYS
+            .iter()
+            .any(|key| reg(&["query", key]).unwrap_or(false))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    use anyhow::Result;
+    use std::path::Path;
+
+    pub fn install(_launcher: &Path) -> Result<()> {
+        anyhow::bail!("Context-menu integration is not supported on this platform")
+    }
+
+    pub fn uninstall() -> Result<()> {
+        anyhow::bail!("Context-menu integration is not supported on this platform")
+    }
+
+    pub fn is_installed() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_paths_from_args() {
+        let cwd = Path::new("/home/user");
+        assert_eq!(share_paths_from_args(["ginseng"], cwd), None);
+        assert_eq!(share_paths_from_args(["ginseng", SHARE_ARG], cwd), None);
+        assert_eq!(
+            share_paths_from_args(["ginseng", SHARE_ARG, "/tmp/a.txt", "notes"], cwd),
+            Some(vec![
+                PathBuf::from("/tmp/a.txt"),
+                PathBuf::from("/home/user/notes"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(shell_quote("/opt/it's here"), r"'/opt/it'\''s here'");
+        assert_eq!(
+            desktop_exec_quote("/opt/My App/$ginseng"),
+            r#""/opt/My App/\\$ginseng""#
+        );
+    }
+}
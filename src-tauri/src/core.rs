@@ -1,22 +1,80 @@
+use crate::archive::{list_files, ArchiveBuilder, ARCHIVE_EXTENSION};
+use crate::bandwidth::{Bandwidth, BandwidthLimiter, BandwidthLimits, BandwidthWindow};
+use crate::bookmarks::{create_bookmarks, AccessScopes};
+use crate::capabilities::{
+    exchange_capabilities, Capabilities, CapabilityExchange, CAPABILITIES_ALPN,
+};
+use crate::cleanup::{drop_blob_tags, TransferCleanup};
+use crate::collisions::{resolve_collision, CollisionPolicy, CollisionResolution, FileCollision};
 use crate::commands::DownloadEvent;
+use crate::compression::{compress, decompress_blocking, decompress_reader, Compression};
+use crate::concurrency::{Concurrency, ConcurrencyLimits};
+use crate::delta::IngestCache;
+use crate::devices::{DeviceDelivery, DeviceGroup, GroupDelivery, Outbox, PairedDevice};
+use crate::disk::{remove_stale_partials, write_file};
+use crate::dropbox::{parse_drop_ticket, push_ticket, DropBox, DropResponse, DROPBOX_ALPN};
+use crate::exclude::{walk_directory, ExcludeRules};
+use crate::folder_names::{folder_name, unique_folder, DEFAULT_TEMPLATE};
+use crate::hashing;
+use crate::history::{HistoryEntry, Outcome, TransferHistory};
+use crate::identity;
+use crate::lanes::DownloadLanes;
+use crate::library::Library;
+use crate::netstats::{network_stats, NetworkStats};
+use crate::organize::{organized_directory, OrganizeRule};
+use crate::paths::{
+    calculate_relative_path, local_path, sanitize_component, unique_relative_paths,
+    validate_relative_path,
+};
+use crate::peers::{Direction, PeerStatsStore, TransferRecord};
+use crate::portmap::{PortMapper, PortMapping};
 use crate::progress::{
-    FileProgress, FileStatus, ProgressEvent, ProgressTracker, RateLimiter, TransferStage,
-    TransferType,
+    derive_file_id, format_bytes, FileCounter, FileProgress, FileStatus, ProgressEvent,
+    ProgressTracker, RateLimiter, TransferId, TransferStage, TransferType,
 };
+use crate::provider::spawn_event_handler;
+use crate::queue::TransferQueue;
+use crate::receipts::{Receipt, ReceiptLog};
+use crate::relays::RelayPreference;
+use crate::saved_shares::{SavedShare, SavedShares};
+use crate::share_card::{CardFormat, ShareCard};
+use crate::share_diff::{diff_shares, ShareDiff};
+use crate::shares::{ShareId, ShareRegistry};
+use crate::sinks::{OutputSink, RemoteSink};
+use crate::sources::{SourceWatcher, SourceWatchers};
+use crate::templates::{Delivery, ShareTemplate, TemplateRun};
+use crate::tickets::{save_ticket_file, split_tickets, TicketFile};
+use crate::transfers::{ActiveTransfers, FailedTransfer, TransferLog};
 use crate::utils::{
-    calculate_relative_path, calculate_total_size, extract_directory_name, extract_file_name,
-    get_downloads_directory, validate_paths_not_empty,
+    calculate_total_size, extract_directory_name, extract_file_name, get_downloads_directory,
+    is_file_in_use, validate_paths_not_empty,
 };
+use crate::warmup::{ConnectionWarmer, Warmup, WARMUP_ALPN};
 use anyhow::Result;
 
-use iroh::{endpoint::Connection, protocol::Router, Endpoint, RelayMode};
-use iroh_blobs::{store::mem::MemStore, ticket::BlobTicket, BlobsProtocol, Hash};
+use futures::StreamExt;
+use iroh::{endpoint::Connection, protocol::Router, Endpoint, EndpointId, RelayMode, SecretKey};
+use iroh_blobs::{
+    api::{
+        downloader::{DownloadProgressItem, Downloader},
+        Store,
+    },
+    store::mem::MemStore,
+    ticket::BlobTicket,
+    BlobFormat, BlobsProtocol, Hash,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::ipc::Channel;
 use tokio::fs;
-use walkdir::WalkDir;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use uuid::Uuid;
 
 /// Information about a file being shared or downloaded.
 ///
@@ -32,6 +90,9 @@ pub struct FileInfo {
     pub size: u64,
     /// Content-addressed hash for retrieving the file from the blob store
     pub hash: String,
+    /// How the blob is compressed; the size above is always the uncompressed size
+    #[serde(default, skip_serializing_if = "Compression::is_none")]
+    pub compression: Compression,
 }
 
 /// The type of content being shared, which affects how files are organized on download.
@@ -46,6 +107,12 @@ pub enum ShareType {
         /// The name of the directory being shared
         name: String,
     },
+    /// A directory is being shared as a single tar archive, which is unpacked
+    /// on download (will preserve the directory name)
+    Archive {
+        /// The name of the directory being shared
+        name: String,
+    },
 }
 
 /// Metadata describing what is being shared.
@@ -60,26 +127,334 @@ pub struct ShareMetadata {
     pub share_type: ShareType,
     /// Total size of all files in bytes
     pub total_size: u64,
+    /// Files that were selected but could not be read, and were left out
+    #[serde(default)]
+    pub skipped_files: Vec<SkippedFile>,
+    /// Custom key-value fields attached by the sender (e.g., a project ID)
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, String>,
+    /// Unix timestamp after which the sender stops serving the share
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// ID the sender assigned to the share, so logs of both peers can be matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<ShareId>,
+    /// Root hash over the hashes of all files (see [`content_root`]), a single
+    /// value both peers can compare to confirm they hold the same content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_root: Option<String>,
+}
+
+/// A file left out of a share because it could not be read.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SkippedFile {
+    /// The relative path the file would have had in the share
+    pub relative_path: String,
+    /// Why the file could not be read (e.g., "Permission denied")
+    pub error: String,
 }
 
 /// A complete share bundle containing metadata and its verification hash.
 ///
 /// This is the top-level structure that gets stored as a blob and referenced
 /// by the share ticket. It enables integrity verification of the metadata.
+///
+/// Shares with more than [`MANIFEST_PAGE_SIZE`] files are paged: the metadata
+/// only lists the first page of files, and the remaining pages are stored as
+/// separate blobs so the receiver can start downloading before it has fetched
+/// the whole file list.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShareBundle {
     /// The share metadata containing file information
     pub metadata: ShareMetadata,
     /// Hash of the metadata for integrity verification
     pub metadata_hash: String,
+    /// Hashes of the blobs holding the remaining pages of the file list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manifest_pages: Vec<String>,
+}
+
+/// The files of a share to download, and where to put them.
+struct DownloadSelection {
+    /// The files to download
+    files: SelectedFiles,
+    /// Directory to save the files to, or None to choose it as for a whole share
+    target_directory: Option<PathBuf>,
+}
+
+/// Files picked from a share, by path or by content.
+enum SelectedFiles {
+    /// Relative paths of the files within the share
+    Paths(HashSet<String>),
+    /// Content hashes of the files
+    Hashes(HashSet<String>),
+}
+
+impl SelectedFiles {
+    /// Whether a file of the share is selected
+    fn contains(&self, file_info: &FileInfo) -> bool {
+        match self {
+            SelectedFiles::Paths(paths) => paths.contains(&file_info.relative_path),
+            SelectedFiles::Hashes(hashes) => hashes.contains(&file_info.hash),
+        }
+    }
+}
+
+/// A follow-up transfer created for the failed files of an earlier transfer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Followup {
+    /// The unreadable files were shared again under a new ticket
+    Share(ShareResult),
+    /// The failed files were downloaded again
+    Download {
+        /// Metadata of the share the files belong to
+        metadata: ShareMetadata,
+        /// Directory the files were saved to
+        download_path: PathBuf,
+        /// Files that already existed where they were to be saved
+        collisions: Vec<FileCollision>,
+    },
+}
+
+/// A file found while scanning a directory for selective sharing.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ScannedFile {
+    /// The path relative to the scanned directory (e.g., "photos/beach.jpg")
+    pub relative_path: String,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Network configuration of this node, for diagnostics.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeInfo {
+    /// The endpoint ID peers connect to
+    pub endpoint_id: String,
+    /// Addresses the endpoint can be reached at without a relay
+    pub direct_addresses: Vec<String>,
+    /// The relay the endpoint can be reached through
+    pub relay_url: Option<String>,
+    /// Protocol version and features of this build
+    pub capabilities: Capabilities,
+    /// Whether the endpoint's port is mapped on the router
+    pub port_mapping: PortMapping,
+}
+
+impl std::fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Endpoint ID: {}\nDirect addresses: {:?}\nRelay URL: {:?}\nProtocol: {}\nPort mapping: {}",
+            self.endpoint_id,
+            self.direct_addresses,
+            self.relay_url,
+            self.capabilities,
+            self.port_mapping
+        )
+    }
+}
+
+/// A newly published share.
+///
+/// The ticket is handed to recipients, while the share ID is kept by the
+/// sender to look up who has fetched the share.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareResult {
+    /// Identifier for looking up the share's activity
+    pub share_id: ShareId,
+    /// Ticket others use to download the share
+    pub ticket: String,
+    /// Files that could not be read and were left out of the share
+    pub skipped_files: Vec<SkippedFile>,
+    /// Root hash over the hashes of the shared files
+    pub content_root: String,
+}
+
+/// Number of times ingesting a file locked by another application is retried
+const LOCKED_FILE_RETRIES: u32 = 3;
+
+/// Delay between attempts to ingest a locked file
+const LOCKED_FILE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Number of times ingesting a file that changed while being read is retried
+const CHANGED_FILE_RETRIES: u32 = 3;
+
+/// Number of files per manifest page of a share
+const MANIFEST_PAGE_SIZE: usize = 5_000;
+
+/// Largest share bundle or manifest page a receiver fetches before parsing it
+const MAX_CONTROL_BLOB_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Number of consecutive attempts without progress before a file download fails
+const DOWNLOAD_RESUME_ATTEMPTS: u32 = 3;
+
+/// Delay before resuming an interrupted file download
+const DOWNLOAD_RESUME_DELAY: Duration = Duration::from_secs(1);
+
+/// Error returned when a ticket points at a blob that is not a Ginseng share.
+///
+/// This usually means the ticket was created by another iroh-blobs tool. Such
+/// tickets can still be received with [`GinsengCore::download_raw_blob`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAGinsengShare;
+
+impl std::fmt::Display for NotAGinsengShare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "This looks like a raw iroh ticket, not a Ginseng share. \
+             It can still be received as a raw download."
+        )
+    }
+}
+
+impl std::error::Error for NotAGinsengShare {}
+
+/// Error returned when a transfer was cancelled with [`GinsengCore::cancel_transfer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferCancelled;
+
+impl std::fmt::Display for TransferCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transfer was cancelled")
+    }
+}
+
+impl std::error::Error for TransferCancelled {}
+
+/// Error returned when the files to share add up to more than
+/// [`ShareOptions::max_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShareTooLarge {
+    /// Total size of the files to share in bytes
+    pub total_size: u64,
+    /// The largest total size allowed in bytes
+    pub max_size: u64,
+}
+
+impl std::fmt::Display for ShareTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The files to share total {}, more than the limit of {}",
+            format_bytes(self.total_size),
+            format_bytes(self.max_size)
+        )
+    }
+}
+
+impl std::error::Error for ShareTooLarge {}
+
+/// Options for how a share is stored and how long it is served.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShareOptions {
+    /// How long the share is served for, unlimited if None
+    pub expires_in: Option<Duration>,
+    /// Whether the share closes after its first complete download
+    pub single_use: bool,
+    /// How file data is compressed before it is stored
+    pub compression: Compression,
+    /// Whether a single shared directory is stored as one tar archive
+    pub archive: bool,
+    /// Whether files unchanged since an earlier share reuse its blobs instead
+    /// of being read again; ignored for archive shares
+    pub delta: bool,
+    /// Files and folders of shared directories that are left out
+    pub exclude: ExcludeRules,
+    /// Largest total size of the files shared, unlimited if None; checked
+    /// before any file is read
+    pub max_size: Option<u64>,
+    /// Whether only the file list is served until the sender unlocks the
+    /// files with [`GinsengCore::unlock_share`]
+    pub preview: bool,
 }
 
+/// Options for where and how a download saves its files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadOptions {
+    /// Where to write the files, e.g. the local disk or an S3 bucket
+    pub sink: OutputSink,
+    /// Organization rules replacing the defaults for this download; they only
+    /// apply to files saved to the local disk
+    pub organize: Option<Vec<OrganizeRule>>,
+    /// How files that already exist on the local disk are handled
+    pub collisions: CollisionPolicy,
+    /// Folder replacing the peer's and the default download folder for this
+    /// download; the share is laid out within it as usual. Only applies to
+    /// files saved to the local disk
+    pub target_directory: Option<PathBuf>,
+}
+
+/// Files of a share saved by a download.
+#[derive(Debug, Clone)]
+pub struct DownloadedShare {
+    /// Metadata of the share
+    pub metadata: ShareMetadata,
+    /// Where the files were saved (a URL for remote sinks)
+    pub location: PathBuf,
+    /// Files that already existed where they were to be saved, and how each
+    /// was handled
+    pub collisions: Vec<FileCollision>,
+    /// Files that could not be downloaded, with the reason
+    pub failed: Vec<SkippedFile>,
+    /// Root hash over the hashes of the files received, if every file of the
+    /// share was received
+    pub content_root: Option<String>,
+}
+
+/// Errors a download can end with that callers may want to handle separately.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadError {
+    /// The ticket could not be parsed
+    InvalidTicket(String),
+    /// The peer sharing the files could not be reached
+    PeerOffline(String),
+    /// The sender no longer serves the share because it expired
+    ShareExpired,
+    /// Some files could not be downloaded, the rest were saved
+    PartialFailure {
+        /// Directory the downloaded files were saved to
+        download_path: PathBuf,
+        /// Number of files that were saved
+        downloaded: usize,
+        /// Files that could not be downloaded, with the reason
+        failed: Vec<SkippedFile>,
+    },
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::InvalidTicket(error) => write!(f, "Failed to parse ticket: {}", error),
+            DownloadError::PeerOffline(error) => {
+                write!(f, "Failed to establish connection: {}", error)
+            }
+            DownloadError::ShareExpired => write!(f, "This share has expired"),
+            DownloadError::PartialFailure {
+                downloaded, failed, ..
+            } => write!(
+                f,
+                "{} of {} files could not be downloaded",
+                failed.len(),
+                downloaded + failed.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
 /// Core functionality for peer-to-peer file sharing using Iroh.
 ///
 /// This struct encapsulates all the networking and storage components needed
 /// for sharing and downloading files in a decentralized manner. It handles
 /// the entire lifecycle from file ingestion to ticket generation for sharing,
 /// and from ticket parsing to file reconstruction for downloading.
+///
+/// Every component is a handle to shared state, so a clone is cheap and acts
+/// on the same node, e.g. from a task spawned per request.
+#[derive(Clone)]
 pub struct GinsengCore {
     /// Iroh endpoint for P2P networking
     pub endpoint: Endpoint,
@@ -89,31 +464,417 @@ pub struct GinsengCore {
     pub blobs: BlobsProtocol,
     /// Router for handling incoming connections and protocol routing
     pub router: Router,
+    /// Drop box receiving shares pushed by other peers
+    pub dropbox: DropBox,
+    /// Shares published by this node and who has fetched them
+    pub shares: ShareRegistry,
+    /// Transfers that finished with failed files, for follow-up transfers
+    pub transfers: TransferLog,
+    /// Shares received by this node, kept for exporting again later
+    pub library: Library,
+    /// Download lanes fetching control blobs ahead of file data
+    pub lanes: DownloadLanes,
+    /// Transfers currently running, for cancellation
+    pub active_transfers: ActiveTransfers,
+    /// Transfers running or waiting for their turn
+    pub queue: TransferQueue,
+    /// Long-term transfer statistics of known peers
+    pub peer_stats: PeerStatsStore,
+    /// Upload and download rate limits shared by all transfers
+    pub bandwidth: Bandwidth,
+    /// Disk and network concurrency budgets shared by all transfers
+    pub concurrency: Concurrency,
+    /// Files stored for shares, reused by delta shares while unchanged
+    pub ingested: IngestCache,
+    /// Pushes to paired devices waiting for the devices to come online
+    pub outbox: Outbox,
+    /// Receipts of shares delivered to drop boxes
+    pub receipts: ReceiptLog,
+    /// Every finished share and download
+    pub history: TransferHistory,
+    /// Connections kept open to paired devices so transfers start right away
+    pub warmer: ConnectionWarmer,
+    /// Mapping of the endpoint's port on the router, while enabled
+    pub port_mapper: PortMapper,
+    /// Folder shares are saved to in place of Downloads, if configured
+    download_directory: Arc<RwLock<Option<PathBuf>>>,
+    /// Download folders of peers whose shares are not saved to Downloads
+    device_folders: Arc<RwLock<BTreeMap<String, PathBuf>>>,
+    /// Subfolders received shares are organized into by default
+    organize_rules: Arc<RwLock<Vec<OrganizeRule>>>,
+    /// Template naming the folders shares of several files are saved in, if
+    /// not the default
+    folder_name_template: Arc<RwLock<Option<String>>>,
+    /// Whether downloaded files leave the store once saved, instead of being
+    /// kept in the library
+    release_downloads: Arc<RwLock<bool>>,
+    /// Watchers warning about shared files that change while being served
+    source_watchers: SourceWatchers,
+    /// Sandbox access to the paths of the shares being served
+    access_scopes: AccessScopes,
+    /// Open shares, saved so they can be served again after a restart
+    saved_shares: SavedShares,
 }
 
 impl GinsengCore {
     /// Creates a new GinsengCore instance with default configuration.
     ///
-    /// Sets up the Iroh endpoint with relay discovery, creates an in-memory blob store,
+    /// Sets up the Iroh endpoint with relay discovery, creates an in-memory blob store
+    /// that hashes and verifies data on its own threads (see [`crate::hashing`]),
     /// and initializes the protocol router for handling P2P connections.
     ///
     /// # Errors
     ///
     /// Returns an error if the endpoint cannot be created or bound to a port.
     pub async fn new() -> Result<Self> {
-        let endpoint = create_endpoint().await?;
-        let store = MemStore::new();
-        let blobs = BlobsProtocol::new(&store, None);
-        let router = create_router(&endpoint, &blobs);
+        Self::with_relays(&RelayPreference::default()).await
+    }
+
+    /// Creates a new GinsengCore instance connecting through the preferred relays.
+    ///
+    /// Like [`GinsengCore::new`], but the endpoint chooses its home relay
+    /// among the relays of `relays` (see [`crate::relays`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a relay URL is invalid, no relay is selected, or
+    /// the endpoint cannot be created or bound to a port.
+    pub async fn with_relays(relays: &RelayPreference) -> Result<Self> {
+        Self::build(relays, None, SavedShares::in_memory()).await
+    }
+
+    /// Creates a GinsengCore instance that keeps its identity and open shares
+    /// across restarts.
+    ///
+    /// Like [`GinsengCore::with_relays`], but the endpoint uses the secret key
+    /// saved in the user's config directory, saving a new one on first start
+    /// (see [`crate::identity`]), and open shares are saved so
+    /// [`GinsengCore::restore_saved_shares`] can serve them again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a relay URL is invalid, no relay is selected, or
+    /// the endpoint cannot be created or bound to a port.
+    pub async fn persistent(relays: &RelayPreference) -> Result<Self> {
+        let key_path = identity::default_path();
+        let secret_key = match &key_path {
+            Some(path) => identity::load_secret_key(path)
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::warn!(%error, "saved secret key unusable, creating a new one");
+                    None
+                }),
+            None => None,
+        };
+        let saved_key = secret_key.is_some();
+
+        let core = Self::build(relays, secret_key, SavedShares::open_default().await).await?;
+        if let Some(path) = key_path.filter(|_| !saved_key) {
+            if let Err(error) = identity::save_secret_key(&path, core.endpoint.secret_key()).await {
+                tracing::warn!(%error, "secret key could not be saved");
+            }
+        }
+        Ok(core)
+    }
+
+    /// Creates the endpoint, store and protocols of a GinsengCore instance.
+    async fn build(
+        relays: &RelayPreference,
+        secret_key: Option<SecretKey>,
+        saved_shares: SavedShares,
+    ) -> Result<Self> {
+        let endpoint = create_endpoint(relays.relay_mode()?, secret_key).await?;
+        let store = hashing::new_store();
+        let shares = ShareRegistry::new();
+        let peer_stats = PeerStatsStore::open_default().await;
+        let bandwidth = Bandwidth::default();
+        bandwidth.spawn_schedule();
+        let outbox = Outbox::new();
+        let blobs = BlobsProtocol::new(
+            &store,
+            Some(spawn_event_handler(
+                shares.clone(),
+                peer_stats.clone(),
+                bandwidth.upload.clone(),
+                outbox.clone(),
+            )),
+        );
+        let dropbox = DropBox::new(
+            endpoint.clone(),
+            blobs.clone(),
+            store.clone(),
+            bandwidth.download.clone(),
+        );
+        let router = create_router(&endpoint, &blobs, &dropbox);
+        let library = Library::new(store.clone());
+        let concurrency = Concurrency::default();
+        let lanes = DownloadLanes::new(&store, &endpoint, concurrency.network.clone());
+        let receipts = ReceiptLog::open_default().await;
+        outbox.spawn_retries(endpoint.clone(), shares.clone(), receipts.clone());
+        let warmer = ConnectionWarmer::new();
+        warmer.spawn(endpoint.clone());
 
         Ok(Self {
             endpoint,
             store,
             blobs,
             router,
+            dropbox,
+            shares,
+            transfers: TransferLog::new(),
+            library,
+            lanes,
+            active_transfers: ActiveTransfers::new(),
+            queue: TransferQueue::default(),
+            peer_stats,
+            bandwidth,
+            concurrency,
+            ingested: IngestCache::new(),
+            outbox,
+            receipts,
+            history: TransferHistory::open_default(),
+            warmer,
+            port_mapper: PortMapper::new(),
+            download_directory: Arc::default(),
+            device_folders: Arc::default(),
+            organize_rules: Arc::default(),
+            folder_name_template: Arc::default(),
+            release_downloads: Arc::default(),
+            source_watchers: SourceWatchers::new(),
+            access_scopes: AccessScopes::new(),
+            saved_shares,
         })
     }
 
+    /// Returns the folder shares are saved to unless their peer has its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no folder is configured and the Downloads folder
+    /// cannot be determined
+    pub fn download_directory(&self) -> Result<PathBuf> {
+        let configured = self
+            .download_directory
+            .read()
+            .ok()
+            .and_then(|directory| directory.clone());
+        match configured {
+            Some(directory) => Ok(directory),
+            None => get_downloads_directory(),
+        }
+    }
+
+    /// Replaces the folder shares are saved to unless their peer has its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - The folder, or None for the Downloads folder
+    pub fn set_download_directory(&self, directory: Option<PathBuf>) {
+        if let Ok(mut current) = self.download_directory.write() {
+            *current = directory;
+        }
+    }
+
+    /// Returns the download folder configured for a peer, if any.
+    pub fn device_folder(&self, peer: &EndpointId) -> Option<PathBuf> {
+        let folders = self.device_folders.read().ok()?;
+        folders.get(&peer.to_string()).cloned()
+    }
+
+    /// Replaces the download folders of all peers.
+    ///
+    /// Shares received from a peer with a folder are saved there instead of
+    /// the Downloads folder, keeping the usual layout within it.
+    ///
+    /// # Arguments
+    ///
+    /// * `folders` - Download folders by endpoint ID of the sending peer
+    pub fn set_device_folders(&self, folders: BTreeMap<String, PathBuf>) {
+        if let Ok(mut current) = self.device_folders.write() {
+            *current = folders;
+        }
+    }
+
+    /// Removes partial files left behind by downloads a crash interrupted.
+    ///
+    /// The download folder and the download folders of peers are searched in
+    /// the background.
+    pub fn remove_stale_partials(&self) {
+        let mut folders: Vec<PathBuf> = self
+            .device_folders
+            .read()
+            .map(|folders| folders.values().cloned().collect())
+            .unwrap_or_default();
+        folders.extend(self.download_directory().ok());
+
+        tokio::spawn(async move {
+            for folder in folders {
+                let removed = remove_stale_partials(folder.clone()).await;
+                if removed > 0 {
+                    tracing::info!(folder = %folder.display(), removed, "removed stale partial files");
+                }
+            }
+        });
+    }
+
+    /// Returns the default rules received shares are organized by.
+    pub fn organize_rules(&self) -> Vec<OrganizeRule> {
+        self.organize_rules
+            .read()
+            .map(|rules| rules.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the default rules received shares are organized by.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - Subfolder levels to add to the download folder, outermost first
+    pub fn set_organize_rules(&self, rules: Vec<OrganizeRule>) {
+        if let Ok(mut current) = self.organize_rules.write() {
+            *current = rules;
+        }
+    }
+
+    /// Returns the template naming the folders shares of several files are saved in.
+    pub fn folder_name_template(&self) -> String {
+        self.folder_name_template
+            .read()
+            .ok()
+            .and_then(|template| template.clone())
+            .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string())
+    }
+
+    /// Replaces the template naming the folders shares of several files are saved in.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The naming template, or None for the default
+    pub fn set_folder_name_template(&self, template: Option<String>) {
+        if let Ok(mut current) = self.folder_name_template.write() {
+            *current = template;
+        }
+    }
+
+    /// Returns whether downloaded files leave the store once saved.
+    pub fn releases_downloads(&self) -> bool {
+        self.release_downloads
+            .read()
+            .map(|release| *release)
+            .unwrap_or_default()
+    }
+
+    /// Sets whether downloaded files leave the store once saved.
+    ///
+    /// By default received shares are kept in the library, so they can be
+    /// exported again and fetched from this node without another download.
+    /// Releasing them keeps the store down to the transfers still running;
+    /// the library only gains shares received while this is off.
+    ///
+    /// # Arguments
+    ///
+    /// * `release` - Whether to remove downloaded files from the store
+    pub fn set_release_downloads(&self, release: bool) {
+        if let Ok(mut current) = self.release_downloads.write() {
+            *current = release;
+        }
+    }
+
+    /// Determines where to save a share received from a peer.
+    ///
+    /// Starts from the given folder, else the peer's download folder, else
+    /// the download folder, and adds the subfolders of the organization rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the received share
+    /// * `sender` - The peer the share is received from
+    /// * `rules` - Rules replacing the default organization rules, if any
+    /// * `folder` - Folder chosen for this download only, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if neither the peer nor the app has a folder configured
+    /// and the Downloads folder cannot be determined
+    fn target_directory_for(
+        &self,
+        metadata: &ShareMetadata,
+        sender: &EndpointId,
+        rules: Option<&[OrganizeRule]>,
+        folder: Option<&Path>,
+    ) -> Result<PathBuf> {
+        let base = match folder
+            .map(Path::to_path_buf)
+            .or_else(|| self.device_folder(sender))
+        {
+            Some(base) => base,
+            None => self.download_directory()?,
+        };
+        let default_rules = self.organize_rules();
+        let rules = rules.unwrap_or(&default_rules);
+
+        let base = organized_directory(
+            &base,
+            rules,
+            metadata,
+            &sender.to_string(),
+            chrono::Local::now(),
+        );
+        determine_target_directory(metadata, Some(&base), &self.folder_name_template())
+    }
+
+    /// Returns the upload and download rate limits applied outside of
+    /// scheduled windows.
+    pub fn bandwidth_limits(&self) -> BandwidthLimits {
+        self.bandwidth.limits()
+    }
+
+    /// Changes the upload and download rate limits applied outside of
+    /// scheduled windows.
+    ///
+    /// The limits apply to all transfers, including those already running.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - The new limits in bytes per second, None for unlimited
+    pub fn set_bandwidth_limits(&self, limits: BandwidthLimits) {
+        self.bandwidth.set_limits(limits);
+    }
+
+    /// Returns the windows of the day with their own rate limits.
+    pub fn bandwidth_schedule(&self) -> Vec<BandwidthWindow> {
+        self.bandwidth.schedule()
+    }
+
+    /// Replaces the windows of the day with their own rate limits.
+    ///
+    /// Running transfers switch limits along with new ones as each window
+    /// starts and ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` - The windows, the first one covering a time winning
+    pub fn set_bandwidth_schedule(&self, schedule: Vec<BandwidthWindow>) {
+        self.bandwidth.set_schedule(schedule);
+    }
+
+    /// Returns the current disk and network concurrency limits.
+    pub fn concurrency_limits(&self) -> ConcurrencyLimits {
+        self.concurrency.limits()
+    }
+
+    /// Changes the disk and network concurrency limits.
+    ///
+    /// Raised limits let waiting files start right away; lowered limits take
+    /// effect as files already being read, written or downloaded finish.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - The number of files handled at once by each budget
+    pub fn set_concurrency_limits(&self, limits: ConcurrencyLimits) {
+        self.concurrency.set_limits(limits);
+    }
+
     /// Shares the specified files or directories and returns a ticket string.
     ///
     /// This function processes the provided paths, creates metadata describing
@@ -149,7 +910,13 @@ impl GinsengCore {
             })
             .unwrap();
 
-        let metadata = create_share_metadata(&self.blobs, &paths).await?;
+        let metadata = create_share_metadata(
+            &self.blobs,
+            &paths,
+            Compression::None,
+            &ExcludeRules::default(),
+        )
+        .await?;
 
         channel
             .send(DownloadEvent::Progress {
@@ -157,34 +924,9 @@ impl GinsengCore {
             })
             .unwrap();
 
-        let metadata_hash = store_metadata_as_blob(&self.blobs, &metadata).await?;
-
-        channel
-            .send(DownloadEvent::Progress {
-                detail: "Generating share ticket",
-            })
-            .unwrap();
-
-        let bundle = ShareBundle {
-            metadata,
-            metadata_hash,
-        };
-
-        channel
-            .send(DownloadEvent::Progress {
-                detail: "Storing bundle as blob",
-            })
-            .unwrap();
-
-        let (bundle_hash, bundle_format) = store_bundle_as_blob(&self.blobs, &bundle).await?;
-
-        channel
-            .send(DownloadEvent::Progress {
-                detail: "Creating share ticket",
-            })
-            .unwrap();
-
-        let ticket = create_share_ticket(&self.endpoint, &bundle_hash, &bundle_format);
+        let share = self
+            .publish_share(metadata, &paths, &ShareOptions::default(), None)
+            .await?;
 
         channel
             .send(DownloadEvent::Completed {
@@ -192,7 +934,7 @@ impl GinsengCore {
             })
             .unwrap();
 
-        ticket
+        Ok(share.ticket)
     }
 
     /// Downloads files from a ticket and returns metadata and download location.
@@ -222,7 +964,8 @@ impl GinsengCore {
         let ticket = parse_ticket(&ticket_str)?;
         let bundle =
             download_and_parse_bundle(&self.endpoint, &self.blobs, &self.store, &ticket).await?;
-        let target_directory = determine_target_directory(&bundle.metadata)?;
+        let target_directory =
+            self.target_directory_for(&bundle.metadata, &ticket.addr().id, None, None)?;
 
         download_all_files(
             &self.endpoint,
@@ -230,6 +973,7 @@ impl GinsengCore {
             &bundle.metadata,
             &target_directory,
             &ticket,
+            &self.bandwidth.download,
         )
         .await?;
 
@@ -241,7 +985,39 @@ impl GinsengCore {
     /// Provides details about the node ID, direct addresses, and relay URL
     /// for debugging and network diagnostics.
     pub async fn node_info(&self) -> Result<String> {
-        format_node_info(&self.endpoint)
+        Ok(self.node_details().to_string())
+    }
+
+    /// Returns this node's network configuration as structured data.
+    pub fn node_details(&self) -> NodeInfo {
+        let endpoint_addr = self.endpoint.addr();
+        NodeInfo {
+            endpoint_id: self.endpoint.id().to_string(),
+            direct_addresses: endpoint_addr
+                .ip_addrs()
+                .map(|addr| addr.to_string())
+                .collect(),
+            relay_url: endpoint_addr.relay_urls().next().map(|url| url.to_string()),
+            capabilities: Capabilities::local(),
+            port_mapping: self.port_mapper.status(),
+        }
+    }
+
+    /// Returns the traffic of the endpoint since it started.
+    ///
+    /// The counters cover every connection, so polling them gives the
+    /// network activity of the node as a whole.
+    pub fn network_stats(&self) -> NetworkStats {
+        network_stats(&self.endpoint)
+    }
+
+    /// Turns mapping the endpoint's port on the router on or off.
+    ///
+    /// With the port mapped over UPnP, NAT-PMP or PCP, peers can connect to
+    /// this node directly instead of through a relay. Whether the router
+    /// mapped the port is reported in [`GinsengCore::node_details`].
+    pub fn set_port_mapping(&self, enabled: bool) {
+        self.port_mapper.set_enabled(&self.endpoint, enabled);
     }
 
     /// Shares files with parallel processing and real-time progress updates
@@ -252,24 +1028,69 @@ impl GinsengCore {
     /// # Arguments
     ///
     /// * `channel` - Channel for sending progress events to the frontend
+    /// * `transfer_id` - Identifier for the transfer, used in all progress events
     /// * `paths` - Vector of file or directory paths to share
+    /// * `include` - Optional selection of directory-relative file paths (as returned
+    ///   by [`scan_directory`]); files in shared directories that are not listed are
+    ///   left out, while the directory structure of included files is preserved
+    /// * `custom_fields` - Key-value fields attached to the share metadata
+    /// * `options` - How file data is compressed, and when the share stops being
+    ///   served and its blobs are removed
     ///
     /// # Returns
     ///
-    /// A ticket string that can be shared to download the files
+    /// The share ID and a ticket string that can be shared to download the files
     ///
     /// # Errors
     ///
-    /// Returns an error if paths are invalid, files cannot be read, or blob storage fails
+    /// Returns an error if paths are invalid, custom fields are invalid, files
+    /// cannot be read, blob storage fails, or the transfer is cancelled
     pub async fn share_files_parallel(
         &self,
         channel: Channel<ProgressEvent>,
+        transfer_id: TransferId,
         paths: Vec<PathBuf>,
-    ) -> Result<String> {
+        include: Option<Vec<String>>,
+        custom_fields: BTreeMap<String, String>,
+        options: ShareOptions,
+    ) -> Result<ShareResult> {
+        let tracker = ProgressTracker::new(transfer_id, TransferType::Upload);
+        let share = self.share_files_tracked(
+            channel.clone(),
+            tracker.clone(),
+            paths,
+            include,
+            custom_fields,
+            options,
+        );
+        let result = self.run_cancellable(&channel, &tracker, share).await;
+
+        let ticket = result.as_ref().ok().map(|share| share.ticket.clone());
+        self.record_history(&tracker, &result, ticket, None).await;
+        result
+    }
+
+    /// Shares files, reporting progress through the given tracker.
+    async fn share_files_tracked(
+        &self,
+        channel: Channel<ProgressEvent>,
+        tracker: ProgressTracker,
+        paths: Vec<PathBuf>,
+        include: Option<Vec<String>>,
+        custom_fields: BTreeMap<String, String>,
+        options: ShareOptions,
+    ) -> Result<ShareResult> {
         validate_paths_not_empty(&paths)?;
+        validate_custom_fields(&custom_fields)?;
+        let mut archive = if options.archive {
+            let name = extract_directory_name(archive_directory(&paths)?);
+            Some((ArchiveBuilder::new(), name))
+        } else {
+            None
+        };
 
-        let tracker = ProgressTracker::new(uuid::Uuid::new_v4().to_string(), TransferType::Upload);
         let rate_limiter = RateLimiter::new(Duration::from_millis(100));
+        let mut cleanup = self.transfer_cleanup(&tracker, &channel).await;
 
         // Send initial event
         channel
@@ -280,18 +1101,67 @@ impl GinsengCore {
 
         tracker.set_stage(TransferStage::Initializing).await;
 
-        // Collect file paths to process
-        let file_paths = collect_file_paths(&paths).await?;
+        // Collect file paths to process, stopping the walk as soon as the
+        // share is cancelled
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+        let cancel = self
+            .active_transfers
+            .token(&transfer_id)
+            .unwrap_or_default();
+        let mut file_paths = collect_file_paths(&paths, &options.exclude, &cancel).await?;
+        if let Some(include) = include {
+            file_paths = filter_selected_files(file_paths, &include.into_iter().collect());
+            if file_paths.is_empty() {
+                anyhow::bail!("No files selected to share");
+            }
+        }
+
+        // Watch the files from before they are read, so changes while reading count too
+        let source_watcher = {
+            let channel = channel.clone();
+            let transfer_id = transfer_id.clone();
+            let files = file_paths
+                .iter()
+                .map(|(file_path, _)| file_path.clone())
+                .collect();
+            // Sharing works without the warnings, e.g. when the OS runs out of watches
+            SourceWatcher::start(&paths, files, move |path| {
+                channel
+                    .send(ProgressEvent::SourceChanged {
+                        transfer_id: transfer_id.clone(),
+                        path: path.to_string_lossy().to_string(),
+                    })
+                    .ok();
+            })
+            .ok()
+        };
 
-        // Initialize file progress entries
-        for (file_path, base_path) in &file_paths {
+        // Initialize file progress entries, reporting how many files were
+        // scanned so far since statting a large tree takes a while. Files
+        // with the same name from different folders get paths of their own.
+        let relative_paths = unique_relative_paths(&file_paths)?;
+        let mut counters = Vec::with_capacity(file_paths.len());
+        let mut total_size = 0;
+        for ((file_path, _), relative_path) in file_paths.iter().zip(relative_paths) {
             let name = extract_file_name(file_path);
-            let relative_path = calculate_relative_path(file_path, base_path)?;
-            let size = get_file_size(file_path).await?;
-            tracker
-                .add_file(FileProgress::new(name, relative_path, size))
-                .await;
+            let size = get_file_size(file_path).await.unwrap_or_default();
+            total_size += size;
+            counters.push(
+                tracker
+                    .add_file(FileProgress::new(name, relative_path, size))
+                    .await,
+            );
+            tracker.file_scanned();
+
+            if rate_limiter.should_emit() {
+                channel
+                    .send(ProgressEvent::TransferProgress {
+                        transfer: tracker.get_snapshot().await,
+                    })
+                    .ok();
+            }
         }
+        check_share_size(total_size, options.max_size)?;
 
         channel
             .send(ProgressEvent::TransferProgress {
@@ -303,6 +1173,9 @@ impl GinsengCore {
 
         // Process files sequentially with progress updates
         let mut file_infos = Vec::new();
+        let mut skipped_files = Vec::new();
+        let mut failed_paths = Vec::new();
+        let mut sources = BTreeMap::new();
 
         for (idx, (file_path, base_path)) in file_paths.iter().enumerate() {
             let snapshot = tracker.get_snapshot().await;
@@ -321,44 +1194,118 @@ impl GinsengCore {
                 })
                 .ok();
 
-            // Store file as blob
-            let file_info = create_file_info(&self.blobs, file_path, base_path).await?;
+            // Store file as blob or add it to the archive, recording unreadable
+            // files instead of failing the share
+            let disk = self.concurrency.disk.acquire().await;
+            let stored = match archive.as_mut() {
+                Some((archive, _)) => add_to_archive(archive, file_path, base_path)
+                    .await
+                    .map(|size| (size, None)),
+                None => self
+                    .store_share_file(file_path, base_path, &options)
+                    .await
+                    .map(|(file_info, reused)| (file_info.size, Some((file_info, reused)))),
+            };
+            drop(disk);
+            let (size, file_info) = match stored {
+                Ok(stored) => stored,
+                Err(error) => {
+                    let error = error.to_string();
+                    tracing::warn!(path = %file_path.display(), %error, "file could not be read");
+                    tracker
+                        .update_file(&file_id, |f| {
+                            f.status = FileStatus::Failed;
+                            f.error = Some(error.clone());
+                        })
+                        .await;
+                    skipped_files.push(SkippedFile {
+                        relative_path: snapshot.files[idx].relative_path.clone(),
+                        error,
+                    });
+                    failed_paths.push((file_path.clone(), base_path.clone()));
+                    continue;
+                }
+            };
+            if let Some((mut file_info, reused)) = file_info {
+                file_info.relative_path = snapshot.files[idx].relative_path.clone();
+                // Reused blobs still belong to the earlier share if this one fails
+                if !reused {
+                    cleanup.track_blob(file_info.hash.clone(), file_info.size);
+                }
+                sources.insert(file_info.hash.clone(), file_path.clone());
+                file_infos.push(file_info);
+            }
 
+            counters[idx].set(size);
+            tracker.add_hashed_bytes(size);
             tracker
                 .update_file(&file_id, |f| {
                     f.status = FileStatus::Completed;
-                    f.transferred_bytes = f.total_bytes;
                 })
                 .await;
 
-            if rate_limiter.should_emit().await {
+            if rate_limiter.should_emit() {
                 let snapshot = tracker.get_snapshot().await;
                 channel
                     .send(ProgressEvent::TransferProgress { transfer: snapshot })
                     .ok();
             }
+        }
+
+        // An archive is built from its files, so it cannot be restored by
+        // reading a single file again
+        let sources = archive.is_none().then_some(sources);
+        let share_type = match archive {
+            Some((archive, name)) => {
+                if !archive.is_empty() {
+                    let file_info =
+                        store_archive(&self.blobs, archive, &name, options.compression).await?;
+                    cleanup.track_blob(file_info.hash.clone(), file_info.size);
+                    file_infos.push(file_info);
+                }
+                ShareType::Archive { name }
+            }
+            None => determine_share_type(&paths, &file_infos),
+        };
 
-            file_infos.push(file_info);
+        if file_infos.is_empty() {
+            anyhow::bail!("None of the selected files could be read");
         }
 
         let total_size = calculate_total_size(file_infos.iter().map(|f| f.size));
-        let share_type = determine_share_type(&paths, &file_infos);
 
         let metadata = ShareMetadata {
             files: file_infos,
             share_type,
             total_size,
+            skipped_files,
+            custom_fields: custom_fields.clone(),
+            expires_at: expiry_time(options.expires_in),
+            share_id: None,
+            content_root: None,
         };
 
         tracker.set_stage(TransferStage::Finalizing).await;
 
-        let metadata_hash = store_metadata_as_blob(&self.blobs, &metadata).await?;
-        let bundle = ShareBundle {
-            metadata,
-            metadata_hash,
-        };
-        let (bundle_hash, bundle_format) = store_bundle_as_blob(&self.blobs, &bundle).await?;
-        let ticket = create_share_ticket(&self.endpoint, &bundle_hash, &bundle_format)?;
+        let share = self
+            .publish_share(metadata, &paths, &options, sources)
+            .await?;
+        tracker.set_share_id(share.share_id.clone()).await;
+        cleanup.disarm();
+
+        if let Some(source_watcher) = source_watcher {
+            self.source_watchers
+                .insert(share.share_id.clone(), source_watcher);
+        }
+
+        if !failed_paths.is_empty() {
+            let failed = FailedTransfer::Upload {
+                failed: failed_paths,
+                custom_fields,
+                compression: options.compression,
+            };
+            self.transfers.record(&tracker, failed).await;
+        }
 
         tracker.complete().await;
         channel
@@ -367,7 +1314,7 @@ impl GinsengCore {
             })
             .ok();
 
-        Ok(ticket)
+        Ok(share)
     }
 
     /// Downloads files with parallel processing and real-time progress updates
@@ -378,11 +1325,14 @@ impl GinsengCore {
     /// # Arguments
     ///
     /// * `channel` - Channel for sending progress events to the frontend
+    /// * `transfer_id` - Identifier for the transfer, used in all progress events
     /// * `ticket_str` - The ticket string received from the sender
+    /// * `options` - Where and how to save the files
     ///
     /// # Returns
     ///
-    /// A tuple containing the share metadata and the path where files were saved
+    /// The share metadata, the location where files were saved, and the files
+    /// that already existed there
     ///
     /// # Errors
     ///
@@ -390,37 +1340,251 @@ impl GinsengCore {
     pub async fn download_files_parallel(
         &self,
         channel: Channel<ProgressEvent>,
+        transfer_id: TransferId,
         ticket_str: String,
-    ) -> Result<(ShareMetadata, PathBuf)> {
-        let tracker =
-            ProgressTracker::new(uuid::Uuid::new_v4().to_string(), TransferType::Download);
-        let rate_limiter = RateLimiter::new(Duration::from_millis(100));
-
-        channel
-            .send(ProgressEvent::TransferStarted {
-                transfer: tracker.get_snapshot().await,
-            })
-            .ok();
-
-        tracker.set_stage(TransferStage::Connecting).await;
+        options: DownloadOptions,
+    ) -> Result<DownloadedShare> {
+        self.download_share(channel, transfer_id, ticket_str, None, options)
+            .await
+    }
 
-        let ticket = parse_ticket(&ticket_str)?;
+    /// Fetches the file list of a share without downloading its files.
+    ///
+    /// Lets the receiver pick files for [`GinsengCore::download_selected_files`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ticket_str` - The ticket string received from the sender
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ticket is invalid or the share cannot be fetched
+    pub async fn preview_share(&self, ticket_str: &str) -> Result<ShareMetadata> {
+        let ticket = parse_ticket(ticket_str)?;
         let bundle =
             download_and_parse_bundle(&self.endpoint, &self.blobs, &self.store, &ticket).await?;
+        Ok(bundle.metadata)
+    }
+
+    /// Compares the file lists of two shares without downloading their files.
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - Ticket of the older share
+    /// * `after` - Ticket of the newer share
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a ticket is invalid or a share cannot be fetched
+    pub async fn diff_tickets(&self, before: &str, after: &str) -> Result<ShareDiff> {
+        let (before, after) =
+            tokio::try_join!(self.preview_share(before), self.preview_share(after))?;
+        Ok(diff_shares(&before, &after))
+    }
+
+    /// Downloads only the chosen files of a share with progress updates.
+    ///
+    /// Progress is tracked for the selected files alone, and the files are
+    /// saved where the whole share would have been.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Channel for sending progress events to the frontend
+    /// * `transfer_id` - Identifier for the transfer, used in all progress events
+    /// * `ticket_str` - The ticket string received from the sender
+    /// * `file_hashes` - Content hashes of the files to download
+    /// * `options` - Where and how to save the files
+    ///
+    /// # Returns
+    ///
+    /// The share metadata, the location where files were saved, and the files
+    /// that already existed there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no files are selected, none of them are part of the
+    /// share, or the download fails
+    pub async fn download_selected_files(
+        &self,
+        channel: Channel<ProgressEvent>,
+        transfer_id: TransferId,
+        ticket_str: String,
+        file_hashes: Vec<String>,
+        options: DownloadOptions,
+    ) -> Result<DownloadedShare> {
+        if file_hashes.is_empty() {
+            anyhow::bail!("No files selected to download");
+        }
 
-        let target_directory = determine_target_directory(&bundle.metadata)?;
+        let selection = DownloadSelection {
+            files: SelectedFiles::Hashes(file_hashes.into_iter().collect()),
+            target_directory: None,
+        };
+        self.download_share(channel, transfer_id, ticket_str, Some(selection), options)
+            .await
+    }
 
-        // Initialize file progress
-        for file_info in &bundle.metadata.files {
-            tracker
-                .add_file(FileProgress::new(
-                    file_info.name.clone(),
-                    file_info.relative_path.clone(),
-                    file_info.size,
-                ))
-                .await;
+    /// Creates a follow-up transfer covering only the failed files of a transfer.
+    ///
+    /// For a share, the files that could not be read are shared again under a
+    /// new ticket, keeping their directory structure. For a download, the files
+    /// that failed are downloaded again from the same ticket into the same folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Channel for sending progress events of the follow-up transfer
+    /// * `failed_transfer_id` - The transfer whose failed files should be retried
+    /// * `transfer_id` - Identifier for the follow-up transfer
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transfer is unknown or had no failed files, or
+    /// if the follow-up transfer fails.
+    pub async fn create_followup(
+        &self,
+        channel: Channel<ProgressEvent>,
+        failed_transfer_id: &str,
+        transfer_id: TransferId,
+    ) -> Result<Followup> {
+        let failed = self
+            .transfers
+            .get(failed_transfer_id)
+            .await
+            .ok_or_else(|| {
+                anyhow::anyhow!("Transfer '{}' has no failed files", failed_transfer_id)
+            })?;
+
+        if let FailedTransfer::Upload {
+            custom_fields,
+            compression,
+            ..
+        } = &failed
+        {
+            let (paths, include) = failed.upload_selection().unwrap_or_default();
+            let share = self
+                .share_files_parallel(
+                    channel,
+                    transfer_id,
+                    paths,
+                    Some(include),
+                    custom_fields.clone(),
+                    ShareOptions {
+                        compression: *compression,
+                        ..ShareOptions::default()
+                    },
+                )
+                .await?;
+            return Ok(Followup::Share(share));
         }
 
+        let FailedTransfer::Download {
+            ticket,
+            failed,
+            target_directory,
+            sink,
+            collisions,
+            ..
+        } = failed
+        else {
+            unreachable!("upload transfers are handled above");
+        };
+
+        let selection = DownloadSelection {
+            files: SelectedFiles::Paths(failed.into_iter().collect()),
+            target_directory: Some(target_directory),
+        };
+        let options = DownloadOptions {
+            sink,
+            organize: None,
+            collisions,
+            target_directory: None,
+        };
+        let download = self
+            .download_share(channel, transfer_id, ticket, Some(selection), options)
+            .await?;
+
+        Ok(Followup::Download {
+            metadata: download.metadata,
+            download_path: download.location,
+            collisions: download.collisions,
+        })
+    }
+
+    /// Downloads the failed files of a download again within the same transfer.
+    ///
+    /// Unlike [`GinsengCore::create_followup`], no new transfer is started: the
+    /// share metadata recorded with the transfer is reused, and only the files
+    /// marked as failed are downloaded again, reporting through the transfer's
+    /// original progress tracker. Files that fail again stay recorded, so the
+    /// retry can be repeated.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Channel for sending progress events of the transfer
+    /// * `transfer_id` - The download whose failed files should be retried
+    ///
+    /// # Returns
+    ///
+    /// The share metadata, the location where files were saved, and the files
+    /// that already existed there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transfer is unknown, had no failed files, or is
+    /// not a download.
+    pub async fn retry_failed_files(
+        &self,
+        channel: Channel<ProgressEvent>,
+        transfer_id: &str,
+    ) -> Result<DownloadedShare> {
+        let (failed, tracker) = self
+            .transfers
+            .get_with_tracker(transfer_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Transfer '{}' has no failed files", transfer_id))?;
+
+        let ticket = match &failed {
+            FailedTransfer::Download { ticket, .. } => Some(ticket.clone()),
+            FailedTransfer::Upload { .. } => None,
+        };
+        let peer = ticket
+            .as_deref()
+            .and_then(|ticket| parse_ticket(ticket).ok())
+            .map(|ticket| ticket.addr().id.to_string());
+
+        let retry = self.retry_failed_files_tracked(channel.clone(), tracker.clone(), failed);
+        let result = self.run_cancellable(&channel, &tracker, retry).await;
+
+        self.record_history(&tracker, &result, ticket, peer).await;
+        result
+    }
+
+    /// Retries the failed files of a download, reporting through its tracker.
+    async fn retry_failed_files_tracked(
+        &self,
+        channel: Channel<ProgressEvent>,
+        tracker: ProgressTracker,
+        failed: FailedTransfer,
+    ) -> Result<DownloadedShare> {
+        let FailedTransfer::Download {
+            ticket: ticket_str,
+            failed,
+            metadata,
+            whole_share,
+            target_directory,
+            sink,
+            collisions,
+        } = failed
+        else {
+            anyhow::bail!("Only failed downloads can be retried");
+        };
+
+        let ticket = parse_ticket(&ticket_str)?;
+        let remote = sink.connect()?;
+        let rate_limiter = RateLimiter::new(Duration::from_millis(100));
+        let mut cleanup = self.transfer_cleanup(&tracker, &channel).await;
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+
         tracker.set_stage(TransferStage::Transferring).await;
         channel
             .send(ProgressEvent::TransferProgress {
@@ -428,41 +1592,66 @@ impl GinsengCore {
             })
             .ok();
 
-        // Download files (sequentially for now - parallel version needs more careful lifetime management)
-        let downloader = self.blobs.store().downloader(&self.endpoint);
-
-        for (idx, file_info) in bundle.metadata.files.iter().enumerate() {
-            let snapshot = tracker.get_snapshot().await;
-            let file_id = snapshot.files[idx].file_id.clone();
+        let mut still_failed = Vec::new();
+        let mut file_collisions = Vec::new();
+        let retried = metadata
+            .files
+            .iter()
+            .filter(|file_info| failed.contains(&file_info.relative_path));
+        self.active_transfers.claim_blobs(
+            &transfer_id,
+            retried.clone().map(|file_info| file_info.hash.clone()),
+        );
+
+        for file_info in retried {
+            let file_id = derive_file_id(&file_info.hash, &file_info.relative_path);
+            let Some(counter) = tracker.file_counter(&file_id).await else {
+                continue;
+            };
 
             tracker
                 .update_file(&file_id, |f| {
                     f.status = FileStatus::Transferring;
+                    f.error = None;
                 })
                 .await;
 
-            let file_hash: Hash = file_info
-                .hash
-                .parse()
-                .map_err(|e| anyhow::anyhow!("Invalid hash: {}", e))?;
-
-            // Download file
-            downloader
-                .download(file_hash, Some(ticket.addr().id))
-                .await
-                .map_err(|e| anyhow::anyhow!("Download failed: {}", e))?;
-
-            // Export to file system
-            export_individual_file(&self.blobs, file_info, &target_directory).await?;
-
-            tracker
-                .update_file(&file_id, |f| {
-                    f.status = FileStatus::Completed;
-                    f.transferred_bytes = f.total_bytes;
-                })
+            let result = self
+                .download_one_file(
+                    ticket.addr().id,
+                    &metadata.share_type,
+                    file_info,
+                    &counter,
+                    &target_directory,
+                    remote.as_ref(),
+                    collisions,
+                    &mut cleanup,
+                )
                 .await;
 
-            if rate_limiter.should_emit().await {
+            match result {
+                Ok(collisions) => {
+                    counter.set(file_info.size);
+                    let status = saved_file_status(file_info, &collisions);
+                    tracker.update_file(&file_id, |f| f.status = status).await;
+                    file_collisions.extend(collisions);
+                }
+                Err(error) => {
+                    tracing::warn!(file = %file_info.relative_path, %error, "file failed again");
+                    tracker
+                        .update_file(&file_id, |f| {
+                            f.status = FileStatus::Failed;
+                            f.error = Some(error.to_string());
+                        })
+                        .await;
+                    still_failed.push(SkippedFile {
+                        relative_path: file_info.relative_path.clone(),
+                        error: error.to_string(),
+                    });
+                }
+            }
+
+            if rate_limiter.should_emit() {
                 channel
                     .send(ProgressEvent::TransferProgress {
                         transfer: tracker.get_snapshot().await,
@@ -471,6 +1660,41 @@ impl GinsengCore {
             }
         }
 
+        cleanup.disarm();
+
+        let release = self.releases_downloads();
+        if release {
+            let saved = saved_file_hashes(
+                metadata
+                    .files
+                    .iter()
+                    .filter(|file_info| failed.contains(&file_info.relative_path)),
+                &still_failed,
+                &file_collisions,
+            );
+            self.release_downloaded_blobs(&transfer_id, saved).await;
+        }
+        if still_failed.is_empty() {
+            self.transfers.remove(&transfer_id).await;
+            if whole_share && !release {
+                self.add_to_library(&ticket, &ticket_str, &metadata).await;
+            }
+        } else {
+            let failed = FailedTransfer::Download {
+                ticket: ticket_str,
+                failed: still_failed
+                    .iter()
+                    .map(|file| file.relative_path.clone())
+                    .collect(),
+                metadata: metadata.clone(),
+                whole_share,
+                target_directory: target_directory.clone(),
+                sink,
+                collisions,
+            };
+            self.transfers.record(&tracker, failed).await;
+        }
+
         tracker.complete().await;
         channel
             .send(ProgressEvent::TransferCompleted {
@@ -478,37 +1702,1519 @@ impl GinsengCore {
             })
             .ok();
 
-        Ok((bundle.metadata, target_directory))
+        let location = match &remote {
+            Some(remote) => PathBuf::from(remote.location(&target_directory.to_string_lossy())),
+            None => target_directory,
+        };
+        let content_root =
+            (whole_share && still_failed.is_empty()).then(|| received_content_root(&metadata));
+        Ok(DownloadedShare {
+            metadata,
+            location,
+            collisions: file_collisions,
+            failed: still_failed,
+            content_root,
+        })
     }
 
-    /// CLI version - share files without progress tracking
-    pub async fn share_files_cli(&self, paths: Vec<PathBuf>) -> Result<String> {
-        validate_paths_not_empty(&paths)?;
-        let metadata = create_share_metadata(&self.blobs, &paths).await?;
-        let metadata_hash = store_metadata_as_blob(&self.blobs, &metadata).await?;
-        let bundle = ShareBundle {
-            metadata,
-            metadata_hash,
+    /// Downloads one file of a share and writes it to the target directory or sink.
+    ///
+    /// A file that already exists on the local disk is first handled by the
+    /// collision policy; if the new file is left out, nothing is downloaded.
+    /// Archives are unpacked into the target directory, with the policy applied
+    /// to each file in them. The file is downloaded on the bulk lane at the
+    /// download rate limit, continuing from any data already verified locally,
+    /// and written once the disk budget has a free slot. Local files are written
+    /// under a temporary name and renamed once complete, so a failed file never
+    /// leaves a truncated copy behind.
+    ///
+    /// # Returns
+    ///
+    /// The collisions with existing files; at most one unless an archive was
+    /// unpacked
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the relative path of the file would leave the
+    /// target directory, or the file cannot be downloaded or written.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_one_file(
+        &self,
+        provider: EndpointId,
+        share_type: &ShareType,
+        file_info: &FileInfo,
+        counter: &FileCounter,
+        target_directory: &Path,
+        remote: Option<&RemoteSink>,
+        collisions: CollisionPolicy,
+        cleanup: &mut TransferCleanup,
+    ) -> Result<Vec<FileCollision>> {
+        validate_relative_path(&file_info.relative_path)?;
+        let mut target_file_path = local_path(target_directory, &file_info.relative_path);
+        let collision = match (remote, share_type) {
+            (Some(_), _) | (None, ShareType::Archive { .. }) => None,
+            (None, _) => {
+                resolve_collision(&target_file_path, collisions).map(|resolution| FileCollision {
+                    relative_path: file_info.relative_path.clone(),
+                    hash: file_info.hash.clone(),
+                    existing_path: target_file_path.clone(),
+                    resolution,
+                })
+            }
         };
-        let (bundle_hash, bundle_format) = store_bundle_as_blob(&self.blobs, &bundle).await?;
-        create_share_ticket(&self.endpoint, &bundle_hash, &bundle_format)
+        if let Some(collision) = &collision {
+            match collision.written_to() {
+                Some(path) => target_file_path = path.to_path_buf(),
+                None => return Ok(vec![collision.clone()]),
+            }
+        }
+
+        if remote.is_none() {
+            cleanup.track_file(target_file_path.clone(), file_info.size);
+        }
+        counter.reset_written();
+
+        async {
+            let file_hash: Hash = file_info
+                .hash
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid hash: {}", e))?;
+
+            let lane = self.lanes.bulk().await;
+            download_file_resumable(
+                &self.store,
+                lane.downloader(),
+                &self.bandwidth.download,
+                file_hash,
+                provider,
+                |bytes| counter.set(bytes),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Download failed: {}", e))?;
+            drop(lane);
+
+            // Export to file system or upload to the sink
+            match remote {
+                Some(remote) => {
+                    let share_prefix = target_directory.to_string_lossy();
+                    let uploaded = match share_type {
+                        ShareType::Archive { .. } => {
+                            remote
+                                .upload_archive(&self.store, file_info, &share_prefix)
+                                .await
+                        }
+                        _ => remote.upload(&self.store, file_info, &share_prefix).await,
+                    };
+                    uploaded.map(|()| Vec::new())
+                }
+                None => {
+                    let _disk = self.concurrency.disk.acquire().await;
+                    export_file_to(
+                        &self.blobs,
+                        share_type,
+                        file_info,
+                        target_directory,
+                        &target_file_path,
+                        collisions,
+                        |bytes, elapsed| counter.set_written(bytes, elapsed),
+                    )
+                    .await
+                }
+            }
+        }
+        .await
+        .map(|unpacked| collision.into_iter().chain(unpacked).collect())
     }
 
-    /// CLI version - download files without progress tracking
-    pub async fn download_files_cli(&self, ticket_str: String) -> Result<(ShareMetadata, PathBuf)> {
+    /// Removes the blobs of saved files from the store.
+    ///
+    /// Blobs an open share serves, a library share keeps or another running
+    /// transfer still needs stay in the store.
+    async fn release_downloaded_blobs(
+        &self,
+        transfer_id: &str,
+        hashes: impl IntoIterator<Item = String>,
+    ) {
+        let mut released = Vec::new();
+        for hash in hashes {
+            if self.shares.is_open_blob(&hash).await
+                || self.library.contains_blob(&hash).await
+                || self
+                    .active_transfers
+                    .is_claimed_by_other(transfer_id, &hash)
+            {
+                continue;
+            }
+            if let Ok(hash) = hash.parse::<Hash>() {
+                released.push(hash);
+            }
+        }
+        if released.is_empty() {
+            return;
+        }
+
+        tracing::debug!(blobs = released.len(), "releasing downloaded blobs");
+        if let Err(error) = self.store.blobs().delete(released).await {
+            tracing::warn!(%error, "downloaded blobs not released");
+        }
+    }
+
+    /// Keeps a fully downloaded share in the library.
+    async fn add_to_library(
+        &self,
+        ticket: &BlobTicket,
+        ticket_str: &str,
+        metadata: &ShareMetadata,
+    ) {
+        let bundle_hash = ticket.hash().to_string();
+        // The files are already saved, so failing to keep them is not an error
+        self.library
+            .add(bundle_hash, ticket_str.to_string(), metadata.clone())
+            .await
+            .ok();
+    }
+
+    /// Exports a share from the library to a folder.
+    ///
+    /// The files are taken from the local blob store, so the sender does not
+    /// need to be online. The share is laid out in the folder the same way as
+    /// when it was first downloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The library share to export
+    /// * `destination` - Folder to export the share into
+    ///
+    /// # Returns
+    ///
+    /// The path the files were exported to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is not in the library or a file cannot be exported.
+    pub async fn export_from_library(&self, share_id: &str, destination: &Path) -> Result<PathBuf> {
+        let entry = self
+            .library
+            .get(share_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Share '{}' is not in the library", share_id))?;
+
+        let target_directory =
+            target_directory_in(destination, &entry.metadata, &self.folder_name_template());
+        let mut cleanup = TransferCleanup::new(self.store.clone());
+        if !target_directory.exists() {
+            cleanup.track_directory(target_directory.clone());
+        }
+
+        for file_info in &entry.metadata.files {
+            cleanup.track_file(
+                local_path(&target_directory, &file_info.relative_path),
+                file_info.size,
+            );
+            let disk = self.concurrency.disk.acquire().await;
+            let exported = export_individual_file(
+                &self.blobs,
+                &entry.metadata.share_type,
+                file_info,
+                &target_directory,
+                |_, _| {},
+            )
+            .await;
+            drop(disk);
+            if let Err(error) = exported {
+                cleanup.run().await;
+                return Err(error);
+            }
+        }
+
+        cleanup.disarm();
+        Ok(target_directory)
+    }
+
+    /// Downloads a share, or only selected files of it, with progress updates.
+    ///
+    /// Files that fail to download or export are marked as failed and recorded
+    /// in the transfer log instead of aborting the whole transfer. The download
+    /// can be cancelled with [`GinsengCore::cancel_transfer`].
+    async fn download_share(
+        &self,
+        channel: Channel<ProgressEvent>,
+        transfer_id: TransferId,
+        ticket_str: String,
+        selection: Option<DownloadSelection>,
+        options: DownloadOptions,
+    ) -> Result<DownloadedShare> {
+        let peer = parse_ticket(&ticket_str)
+            .ok()
+            .map(|ticket| ticket.addr().id.to_string());
+        let started = Instant::now();
+
+        let tracker = ProgressTracker::new(transfer_id, TransferType::Download);
+        if let Some(peer) = &peer {
+            let expected_rate = self
+                .peer_stats
+                .get(peer)
+                .await
+                .and_then(|stats| stats.average_speed);
+            tracker.set_expected_rate(expected_rate).await;
+        }
+        let download = self.download_share_tracked(
+            channel.clone(),
+            tracker.clone(),
+            ticket_str.clone(),
+            selection,
+            options,
+        );
+        let result = self.run_cancellable(&channel, &tracker, download).await;
+        self.record_history(&tracker, &result, Some(ticket_str), peer.clone())
+            .await;
+
+        // Cancelling says nothing about the peer, so it is left out of its statistics
+        let cancelled = matches!(&result, Err(error) if error.is::<TransferCancelled>());
+        if let (Some(peer), false) = (peer, cancelled) {
+            let snapshot = tracker.get_snapshot().await;
+            self.peer_stats
+                .record(
+                    &peer,
+                    TransferRecord {
+                        direction: Direction::Received,
+                        bytes: snapshot.transferred_bytes,
+                        duration: started.elapsed(),
+                        succeeded: result.is_ok() && snapshot.failed_files == 0,
+                    },
+                )
+                .await;
+        }
+
+        result
+    }
+
+    /// Downloads a share, reporting progress through the given tracker.
+    async fn download_share_tracked(
+        &self,
+        channel: Channel<ProgressEvent>,
+        tracker: ProgressTracker,
+        ticket_str: String,
+        selection: Option<DownloadSelection>,
+        options: DownloadOptions,
+    ) -> Result<DownloadedShare> {
+        let DownloadOptions {
+            sink,
+            organize,
+            collisions,
+            target_directory: folder,
+        } = options;
+        let rate_limiter = RateLimiter::new(Duration::from_millis(100));
+        let mut cleanup = self.transfer_cleanup(&tracker, &channel).await;
+
+        channel
+            .send(ProgressEvent::TransferStarted {
+                transfer: tracker.get_snapshot().await,
+            })
+            .ok();
+
+        tracker.set_stage(TransferStage::Connecting).await;
+
         let ticket = parse_ticket(&ticket_str)?;
-        let bundle =
-            download_and_parse_bundle(&self.endpoint, &self.blobs, &self.store, &ticket).await?;
-        let target_directory = determine_target_directory(&bundle.metadata)?;
-        download_all_files(
-            &self.endpoint,
-            &self.blobs,
-            &bundle.metadata,
-            &target_directory,
-            &ticket,
-        )
-        .await?;
-        Ok((bundle.metadata, target_directory))
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+        self.active_transfers
+            .claim_blobs(&transfer_id, [ticket.hash().to_string()]);
+        // Capabilities are exchanged while the bundle is fetched, so peers
+        // without the exchange do not hold up the download
+        let (capabilities, bundle) = tokio::join!(
+            exchange_capabilities(&self.endpoint, ticket.addr().clone()),
+            self.fetch_bundle(&channel, &tracker, &rate_limiter, &ticket)
+        );
+        tracker.set_capabilities(capabilities).await;
+        let ShareBundle {
+            mut metadata,
+            manifest_pages,
+            ..
+        } = bundle?;
+        let mut control_hashes = manifest_pages.clone();
+        control_hashes.push(ticket.hash().to_string());
+        self.active_transfers
+            .claim_blobs(&transfer_id, control_hashes.iter().cloned());
+        if let Some(share_id) = &metadata.share_id {
+            tracing::Span::current().record("share_id", share_id.as_str());
+            tracker.set_share_id(share_id.clone()).await;
+        }
+        tracing::info!(
+            peer = %ticket.addr().id,
+            files = metadata.files.len(),
+            "share manifest received"
+        );
+
+        // Later pages of the file list are fetched while the first files download
+        let mut pending_pages = spawn_manifest_page_fetcher(
+            self.lanes.clone(),
+            self.store.clone(),
+            ticket.clone(),
+            manifest_pages,
+        );
+
+        // For remote sinks the target directory is the key prefix within the sink
+        let remote = sink.connect()?;
+        let (selected, target_directory) = match selection {
+            Some(DownloadSelection {
+                files,
+                target_directory: Some(target_directory),
+            }) => (Some(files), target_directory),
+            selection => (
+                selection.map(|selection| selection.files),
+                match &remote {
+                    Some(remote) => {
+                        PathBuf::from(remote.share_prefix(&metadata, &self.folder_name_template()))
+                    }
+                    None => self.target_directory_for(
+                        &metadata,
+                        &ticket.addr().id,
+                        organize.as_deref(),
+                        folder.as_deref(),
+                    )?,
+                },
+            ),
+        };
+        if remote.is_none() && !target_directory.exists() {
+            cleanup.track_directory(target_directory.clone());
+        }
+
+        tracker.set_stage(TransferStage::Transferring).await;
+
+        // Download files (sequentially for now - parallel version needs more careful lifetime management)
+        let mut files = Vec::new();
+        let mut file_ids = Vec::new();
+        let mut counters = Vec::new();
+        let mut failed_files = Vec::new();
+        let mut file_collisions = Vec::new();
+        let mut page = metadata.files.clone();
+
+        loop {
+            // Initialize file progress for the files of this page
+            let first_of_page = files.len();
+            for file_info in page {
+                if selected
+                    .as_ref()
+                    .is_some_and(|selected| !selected.contains(&file_info))
+                {
+                    continue;
+                }
+                let file = FileProgress::for_content(
+                    &file_info.hash,
+                    file_info.name.clone(),
+                    file_info.relative_path.clone(),
+                    file_info.size,
+                );
+                file_ids.push(file.file_id.clone());
+                counters.push(tracker.add_file(file).await);
+                files.push(file_info);
+            }
+            self.active_transfers.claim_blobs(
+                &transfer_id,
+                files[first_of_page..]
+                    .iter()
+                    .map(|file_info| file_info.hash.clone()),
+            );
+
+            channel
+                .send(ProgressEvent::TransferProgress {
+                    transfer: tracker.get_snapshot().await,
+                })
+                .ok();
+
+            for (idx, file_info) in files.iter().enumerate().skip(first_of_page) {
+                let file_id = file_ids[idx].clone();
+
+                tracker
+                    .update_file(&file_id, |f| {
+                        f.status = FileStatus::Transferring;
+                    })
+                    .await;
+
+                let result = self
+                    .download_one_file(
+                        ticket.addr().id,
+                        &metadata.share_type,
+                        file_info,
+                        &counters[idx],
+                        &target_directory,
+                        remote.as_ref(),
+                        collisions,
+                        &mut cleanup,
+                    )
+                    .await;
+
+                let collisions = match result {
+                    Ok(collisions) => collisions,
+                    Err(error) => {
+                        tracing::warn!(
+                            file = %file_info.relative_path,
+                            hash = %file_info.hash,
+                            %error,
+                            "file download failed"
+                        );
+                        tracker
+                            .update_file(&file_id, |f| {
+                                f.status = FileStatus::Failed;
+                                f.error = Some(error.to_string());
+                            })
+                            .await;
+                        failed_files.push(SkippedFile {
+                            relative_path: file_info.relative_path.clone(),
+                            error: error.to_string(),
+                        });
+                        continue;
+                    }
+                };
+
+                counters[idx].set(file_info.size);
+                let status = saved_file_status(file_info, &collisions);
+                tracker.update_file(&file_id, |f| f.status = status).await;
+                file_collisions.extend(collisions);
+
+                if rate_limiter.should_emit() {
+                    channel
+                        .send(ProgressEvent::TransferProgress {
+                            transfer: tracker.get_snapshot().await,
+                        })
+                        .ok();
+                }
+            }
+
+            page = match pending_pages.recv().await {
+                Some(next_page) => next_page?,
+                None => break,
+            };
+            metadata.files.extend(page.iter().cloned());
+        }
+
+        if selected.is_some() && files.is_empty() {
+            anyhow::bail!("None of the selected files are part of the share");
+        }
+        if failed_files.len() == files.len() && !files.is_empty() {
+            anyhow::bail!("None of the files could be downloaded");
+        }
+
+        cleanup.disarm();
+
+        let whole_share = files.len() == metadata.files.len();
+        if self.releases_downloads() {
+            // Failed downloads are retried from the recorded metadata, so the
+            // manifest is not needed again
+            let saved = saved_file_hashes(&files, &failed_files, &file_collisions);
+            self.release_downloaded_blobs(&transfer_id, saved.into_iter().chain(control_hashes))
+                .await;
+        } else if failed_files.is_empty() && whole_share {
+            self.add_to_library(&ticket, &ticket_str, &metadata).await;
+        }
+
+        if !failed_files.is_empty() {
+            let failed = FailedTransfer::Download {
+                ticket: ticket_str,
+                failed: failed_files
+                    .iter()
+                    .map(|file| file.relative_path.clone())
+                    .collect(),
+                metadata: metadata.clone(),
+                whole_share,
+                target_directory: target_directory.clone(),
+                sink,
+                collisions,
+            };
+            self.transfers.record(&tracker, failed).await;
+        }
+
+        tracker.complete().await;
+        channel
+            .send(ProgressEvent::TransferCompleted {
+                transfer: tracker.get_snapshot().await,
+            })
+            .ok();
+
+        let location = match &remote {
+            Some(remote) => PathBuf::from(remote.location(&target_directory.to_string_lossy())),
+            None => target_directory,
+        };
+        let content_root =
+            (whole_share && failed_files.is_empty()).then(|| received_content_root(&metadata));
+        Ok(DownloadedShare {
+            metadata,
+            location,
+            collisions: file_collisions,
+            failed: failed_files,
+            content_root,
+        })
+    }
+
+    /// CLI version - share files without progress tracking
+    pub async fn share_files_cli(
+        &self,
+        paths: Vec<PathBuf>,
+        custom_fields: BTreeMap<String, String>,
+        options: ShareOptions,
+    ) -> Result<ShareResult> {
+        self.share_files_with_progress(Channel::new(|_| Ok(())), paths, custom_fields, options)
+            .await
+    }
+
+    /// CLI version - share files, reporting progress through the channel
+    ///
+    /// Unlike [`GinsengCore::share_files_parallel`], the share cannot be
+    /// cancelled and is not recorded in the history.
+    pub async fn share_files_with_progress(
+        &self,
+        channel: Channel<ProgressEvent>,
+        paths: Vec<PathBuf>,
+        custom_fields: BTreeMap<String, String>,
+        options: ShareOptions,
+    ) -> Result<ShareResult> {
+        let tracker = ProgressTracker::new(new_transfer_id(), TransferType::Upload);
+        self.share_files_tracked(channel, tracker, paths, None, custom_fields, options)
+            .await
+    }
+
+    /// CLI version - download files without progress tracking
+    ///
+    /// Files that fail to download do not abort the download. The files that
+    /// were saved are kept and [`DownloadError::PartialFailure`] lists the rest.
+    pub async fn download_files_cli(&self, ticket_str: String) -> Result<(ShareMetadata, PathBuf)> {
+        self.download_files_with_progress(Channel::new(|_| Ok(())), ticket_str, None)
+            .await
+    }
+
+    /// CLI version - download files, reporting progress through the channel
+    ///
+    /// The share is saved in `target_directory` if given, laid out as usual,
+    /// and in the download folder otherwise. Files that already exist are
+    /// replaced. Files that fail to download do not abort the download. The
+    /// files that were saved are kept and [`DownloadError::PartialFailure`]
+    /// lists the rest.
+    pub async fn download_files_with_progress(
+        &self,
+        channel: Channel<ProgressEvent>,
+        ticket_str: String,
+        target_directory: Option<PathBuf>,
+    ) -> Result<(ShareMetadata, PathBuf)> {
+        let tracker = ProgressTracker::new(new_transfer_id(), TransferType::Download);
+        let options = DownloadOptions {
+            collisions: CollisionPolicy::Overwrite,
+            target_directory,
+            ..DownloadOptions::default()
+        };
+        let downloaded = self
+            .download_share_tracked(channel, tracker, ticket_str, None, options)
+            .await?;
+
+        if !downloaded.failed.is_empty() {
+            return Err(DownloadError::PartialFailure {
+                download_path: downloaded.location,
+                downloaded: downloaded.metadata.files.len() - downloaded.failed.len(),
+                failed: downloaded.failed,
+            }
+            .into());
+        }
+
+        Ok((downloaded.metadata, downloaded.location))
+    }
+
+    /// Downloads a single raw blob from a plain blob ticket.
+    ///
+    /// Unlike `download_files`, the ticket does not need to point at a Ginseng
+    /// share bundle, so tickets created by other iroh-blobs tools can be
+    /// received as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticket_str` - A raw blob ticket
+    /// * `target` - File path to write to, or a directory to write the blob into
+    ///   (named after its hash)
+    ///
+    /// # Returns
+    ///
+    /// The path the blob was written to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ticket is invalid or refers to a hash sequence,
+    /// the download fails, or the blob cannot be written to disk.
+    pub async fn download_raw_blob(&self, ticket_str: &str, target: &Path) -> Result<PathBuf> {
+        let ticket = parse_ticket(ticket_str)?;
+        if ticket.format() != BlobFormat::Raw {
+            anyhow::bail!("Ticket refers to a collection, only single raw blobs are supported");
+        }
+
+        download_blob(&self.endpoint, &self.store, &ticket, None).await?;
+
+        let target_path = std::path::absolute(raw_blob_target_path(target, &ticket.hash()))?;
+        ensure_parent_directory_exists(&target_path).await?;
+
+        let expected_hash = blake3::Hash::from_bytes(*ticket.hash().as_bytes());
+        write_file(
+            &target_path,
+            self.store.reader(ticket.hash()),
+            Some(expected_hash),
+            |_, _| {},
+        )
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to export blob to '{}': {}",
+                target_path.display(),
+                error
+            )
+        })?;
+
+        Ok(target_path)
+    }
+
+    /// Shares files and pushes them to another peer's drop box.
+    ///
+    /// The files are shared as usual, then the share ticket is handed to the
+    /// drop box, which downloads the files before responding.
+    ///
+    /// # Arguments
+    ///
+    /// * `drop_ticket` - The drop ticket published by the receiver
+    /// * `paths` - Vector of file or directory paths to send
+    ///
+    /// # Returns
+    ///
+    /// The drop box response telling whether the share was delivered
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the drop ticket is invalid, sharing fails, or the
+    /// drop box cannot be reached.
+    pub async fn send_to_drop_box(
+        &self,
+        drop_ticket: &str,
+        paths: Vec<PathBuf>,
+    ) -> Result<DropResponse> {
+        let drop_ticket = parse_drop_ticket(drop_ticket)?;
+        let share = self
+            .share_files_cli(paths, BTreeMap::new(), ShareOptions::default())
+            .await?;
+        self.push_share(&drop_ticket, &share, None).await
+    }
+
+    /// Pushes a share to a drop box, keeping a receipt if it is delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `drop_ticket` - The drop ticket of the receiver
+    /// * `share` - The share to push
+    /// * `receiver_name` - Name of the receiver for the receipt, if known
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the drop box cannot be reached or responds with an
+    /// invalid message.
+    async fn push_share(
+        &self,
+        drop_ticket: &BlobTicket,
+        share: &ShareResult,
+        receiver_name: Option<&str>,
+    ) -> Result<DropResponse> {
+        let receiver = drop_ticket.addr().id;
+        tracing::info!(share_id = %share.share_id, %receiver, "pushing share to drop box");
+        let response = push_ticket(&self.endpoint, drop_ticket, share.ticket.clone()).await?;
+        tracing::info!(share_id = %share.share_id, %receiver, ?response, "drop box answered");
+        if let Some(receipt) = Receipt::for_push(
+            &share.share_id,
+            receiver.to_string(),
+            receiver_name.map(str::to_string),
+            &response,
+        ) {
+            self.receipts.record(receipt).await;
+        }
+        Ok(response)
+    }
+
+    /// Shares files and pushes them to a paired device.
+    ///
+    /// If the device cannot be reached, e.g. because it is switched off, the
+    /// push is queued in the outbox and delivered once the device comes
+    /// online, as long as the share is still open.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The paired device to send the files to
+    /// * `paths` - Vector of file or directory paths to send
+    ///
+    /// # Returns
+    ///
+    /// The device's answer, or the ID of the queued push
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device's drop ticket is invalid or sharing fails.
+    pub async fn send_to_device(
+        &self,
+        device: &PairedDevice,
+        paths: Vec<PathBuf>,
+    ) -> Result<DeviceDelivery> {
+        parse_drop_ticket(&device.drop_ticket)?;
+        let share = self
+            .share_files_cli(paths, BTreeMap::new(), ShareOptions::default())
+            .await?;
+
+        Ok(self.push_to_device(device, &share).await)
+    }
+
+    /// Shares files once and pushes them to every device of a group.
+    ///
+    /// The devices are pushed to at once. Devices that cannot be reached get
+    /// the push queued, as with [`GinsengCore::send_to_device`], and members
+    /// that are not paired are reported as failed without stopping delivery
+    /// to the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `group` - The group to send the files to
+    /// * `paired` - The paired devices the group's members are looked up in
+    /// * `paths` - Vector of file or directory paths to send
+    ///
+    /// # Returns
+    ///
+    /// What happened to the push for each member, in group order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the group is empty or sharing fails.
+    pub async fn share_to_group(
+        &self,
+        group: &DeviceGroup,
+        paired: &[PairedDevice],
+        paths: Vec<PathBuf>,
+    ) -> Result<Vec<GroupDelivery>> {
+        if group.devices.is_empty() {
+            anyhow::bail!("Group '{}' has no devices", group.name);
+        }
+        let share = self
+            .share_files_cli(paths, BTreeMap::new(), ShareOptions::default())
+            .await?;
+
+        let deliveries = group.members(paired).into_iter().map(|(name, device)| {
+            let share = &share;
+            async move {
+                let delivery = match device {
+                    Some(device) => self.push_to_device(device, share).await,
+                    None => DeviceDelivery::Failed {
+                        error: format!("Device '{}' is not paired", name),
+                    },
+                };
+                GroupDelivery {
+                    device: name.to_string(),
+                    delivery,
+                }
+            }
+        });
+        Ok(futures::future::join_all(deliveries).await)
+    }
+
+    /// Pushes a share to a paired device, queueing the push if the device
+    /// cannot be reached.
+    async fn push_to_device(&self, device: &PairedDevice, share: &ShareResult) -> DeviceDelivery {
+        let drop_ticket = match parse_drop_ticket(&device.drop_ticket) {
+            Ok(drop_ticket) => drop_ticket,
+            Err(error) => {
+                return DeviceDelivery::Failed {
+                    error: error.to_string(),
+                }
+            }
+        };
+
+        match self
+            .push_share(&drop_ticket, share, Some(&device.name))
+            .await
+        {
+            Ok(response) => DeviceDelivery::Answered { response },
+            Err(error) => {
+                let push_id = self.outbox.queue(
+                    device.clone(),
+                    drop_ticket.addr().id.to_string(),
+                    share.share_id.clone(),
+                    share.ticket.clone(),
+                    error.to_string(),
+                );
+                DeviceDelivery::Queued { push_id }
+            }
+        }
+    }
+
+    /// Runs a share template: shares its paths and delivers the ticket.
+    ///
+    /// The ticket is written to the template's ticket file, if any, and pushed
+    /// to every recipient drop box. A recipient that cannot be reached does not
+    /// stop delivery to the others and is reported as failed.
+    /// Files that have not changed since an earlier run, whose share is still
+    /// open, are not read again.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - Channel for sending progress events of the share
+    /// * `transfer_id` - Identifier for the share transfer
+    /// * `template` - The template to run
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid, sharing fails, or the
+    /// ticket file cannot be written.
+    pub async fn run_share_template(
+        &self,
+        channel: Channel<ProgressEvent>,
+        transfer_id: TransferId,
+        template: &ShareTemplate,
+    ) -> Result<TemplateRun> {
+        template.validate()?;
+
+        let share = self
+            .share_files_parallel(
+                channel,
+                transfer_id,
+                template.paths.clone(),
+                template.include.clone(),
+                template.custom_fields.clone(),
+                ShareOptions {
+                    expires_in: template.expires_in_secs.map(Duration::from_secs),
+                    single_use: false,
+                    compression: Compression::None,
+                    archive: false,
+                    delta: true,
+                    exclude: ExcludeRules::default(),
+                    max_size: None,
+                    preview: false,
+                },
+            )
+            .await?;
+
+        if let Some(path) = &template.ticket_file {
+            let mut ticket_file = TicketFile::new(share.ticket.clone());
+            ticket_file.note = Some(template.name.clone());
+            ticket_file.expires_at = template
+                .expires_in_secs
+                .map(|secs| chrono::Utc::now().timestamp() as u64 + secs);
+            save_ticket_file(path, &ticket_file).await?;
+        }
+
+        let mut deliveries = Vec::with_capacity(template.recipients.len());
+        for recipient in &template.recipients {
+            let response = match parse_drop_ticket(recipient) {
+                Ok(drop_ticket) => self
+                    .push_share(&drop_ticket, &share, None)
+                    .await
+                    .unwrap_or_else(|error| DropResponse::Failed {
+                        error: error.to_string(),
+                    }),
+                Err(error) => DropResponse::Failed {
+                    error: error.to_string(),
+                },
+            };
+            deliveries.push(Delivery {
+                recipient: recipient.clone(),
+                response,
+            });
+        }
+
+        Ok(TemplateRun {
+            share,
+            ticket_file: template.ticket_file.clone(),
+            deliveries,
+        })
+    }
+
+    /// Fetches and parses a share bundle, reporting progress on the channel.
+    ///
+    /// Bytes received are reported under the `Connecting` stage and parsing
+    /// under the `Initializing` stage, so shares with very large manifests do
+    /// not leave the receiver without feedback.
+    async fn fetch_bundle(
+        &self,
+        channel: &Channel<ProgressEvent>,
+        tracker: &ProgressTracker,
+        rate_limiter: &RateLimiter,
+        ticket: &BlobTicket,
+    ) -> Result<ShareBundle> {
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+        let stage_changed = |stage: TransferStage, message: String| {
+            channel
+                .send(ProgressEvent::StageChanged {
+                    transfer_id: transfer_id.clone(),
+                    stage,
+                    message: Some(message),
+                })
+                .ok();
+        };
+
+        stage_changed(TransferStage::Connecting, "Connecting to peer".to_string());
+        let _connection = establish_connection(&self.endpoint, ticket).await?;
+
+        stage_changed(
+            TransferStage::Connecting,
+            "Fetching share manifest".to_string(),
+        );
+        let lane = self.lanes.control();
+        stream_download(
+            lane.downloader(),
+            None,
+            Some(MAX_CONTROL_BLOB_SIZE),
+            ticket.hash(),
+            ticket.addr().id,
+            |processed_bytes| {
+                if rate_limiter.should_emit() {
+                    channel
+                        .send(ProgressEvent::StageProgress {
+                            transfer_id: transfer_id.clone(),
+                            stage: TransferStage::Connecting,
+                            message: "Fetching share manifest".to_string(),
+                            processed_bytes,
+                        })
+                        .ok();
+                }
+            },
+        )
+        .await
+        .map_err(bundle_unavailable)?;
+        drop(lane);
+        rate_limiter.force_emit();
+
+        tracker.set_stage(TransferStage::Initializing).await;
+        stage_changed(
+            TransferStage::Initializing,
+            "Parsing share manifest".to_string(),
+        );
+        let bundle = parse_bundle_from_blob(&self.blobs, ticket).await?;
+        let found = match bundle.manifest_pages.len() {
+            0 => format!("Found {} files", bundle.metadata.files.len()),
+            pages => format!(
+                "Found {} files, {} more manifest pages to fetch",
+                bundle.metadata.files.len(),
+                pages
+            ),
+        };
+        stage_changed(TransferStage::Initializing, found);
+
+        Ok(bundle)
+    }
+
+    /// Cancels a running transfer.
+    ///
+    /// The transfer stops at its next await point, its partial state is
+    /// removed, and a final snapshot in the `Cancelled` stage is sent on its
+    /// progress channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `transfer_id` - Identifier of the transfer to cancel
+    ///
+    /// # Returns
+    ///
+    /// `true` if the transfer was running, `false` if it is unknown or has
+    /// already finished
+    pub fn cancel_transfer(&self, transfer_id: &str) -> bool {
+        self.active_transfers.cancel(transfer_id)
+    }
+
+    /// Adds a finished transfer to the history.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracker` - Progress tracker of the transfer
+    /// * `result` - What the transfer ended with
+    /// * `ticket` - The ticket created or downloaded from, if any
+    /// * `peer` - Endpoint ID of the peer downloaded from, if known
+    async fn record_history<T>(
+        &self,
+        tracker: &ProgressTracker,
+        result: &Result<T>,
+        ticket: Option<String>,
+        peer: Option<String>,
+    ) {
+        let snapshot = tracker.get_snapshot().await;
+        let (outcome, error) = match result {
+            Ok(_) if snapshot.failed_files > 0 => (Outcome::PartiallyFailed, None),
+            Ok(_) => (Outcome::Completed, None),
+            Err(error) if error.is::<TransferCancelled>() => (Outcome::Cancelled, None),
+            Err(error) => (Outcome::Failed, Some(error.to_string())),
+        };
+
+        let mut entry = HistoryEntry::from_snapshot(&snapshot, outcome, error);
+        entry.ticket = ticket;
+        entry.peer = peer;
+        self.history.record(entry).await;
+    }
+
+    /// Runs a transfer until it finishes or is cancelled.
+    ///
+    /// The transfer first waits for its turn in the transfer queue, in the
+    /// `Queued` stage. A cancelled transfer future is dropped, which aborts
+    /// its downloads and runs its cleanup guard, and a final cancelled
+    /// snapshot is sent.
+    async fn run_cancellable<T>(
+        &self,
+        channel: &Channel<ProgressEvent>,
+        tracker: &ProgressTracker,
+        transfer: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let snapshot = tracker.get_snapshot().await;
+        let span = tracing::info_span!(
+            "transfer",
+            transfer_id = %snapshot.transfer_id,
+            transfer_type = ?snapshot.transfer_type,
+            share_id = snapshot.share_id.as_deref(),
+        );
+        let active = self.active_transfers.start(snapshot.transfer_id.clone());
+        let slot = self
+            .queue
+            .enqueue(snapshot.transfer_id.clone(), snapshot.transfer_type);
+
+        let transfer = async {
+            if !slot.is_running() {
+                tracker.set_stage(TransferStage::Queued).await;
+                channel
+                    .send(ProgressEvent::StageChanged {
+                        transfer_id: snapshot.transfer_id.clone(),
+                        stage: TransferStage::Queued,
+                        message: Some("Waiting for other transfers to finish".to_string()),
+                    })
+                    .ok();
+                slot.ready().await;
+                tracker.restart_clock().await;
+            }
+            transfer.await
+        };
+
+        async {
+            tracing::info!("transfer started");
+            let result = tokio::select! {
+                result = transfer => result,
+                _ = active.token().cancelled() => {
+                    tracker.cancel().await;
+                    channel
+                        .send(ProgressEvent::TransferCancelled {
+                            transfer: tracker.get_snapshot().await,
+                        })
+                        .ok();
+                    Err(TransferCancelled.into())
+                }
+            };
+            match &result {
+                Ok(_) => tracing::info!("transfer finished"),
+                Err(error) => tracing::warn!(%error, "transfer failed"),
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Creates a cleanup guard for a parallel transfer.
+    ///
+    /// If the transfer fails or is cancelled before the guard is disarmed, the
+    /// partial state is removed and the reclaimed space is reported on the
+    /// progress channel.
+    async fn transfer_cleanup(
+        &self,
+        tracker: &ProgressTracker,
+        channel: &Channel<ProgressEvent>,
+    ) -> TransferCleanup {
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+        let channel = channel.clone();
+
+        TransferCleanup::new(self.store.clone()).on_report(move |report| {
+            channel
+                .send(ProgressEvent::CleanupCompleted {
+                    transfer_id,
+                    report,
+                })
+                .ok();
+        })
+    }
+
+    /// Stores a file as a blob for a share.
+    ///
+    /// In delta mode, a file that has not changed since an earlier share
+    /// stored it reuses that blob, as long as a share that is still open keeps
+    /// it in the store. Otherwise the file is stored again and checked against
+    /// the recorded hash. Every file stored here is recorded for later delta
+    /// shares, unless it changed while being read.
+    ///
+    /// # Returns
+    ///
+    /// The stored file, and whether the blob of an earlier share was reused
+    async fn store_share_file(
+        &self,
+        file_path: &Path,
+        base_path: &Path,
+        options: &ShareOptions,
+    ) -> Result<(FileInfo, bool)> {
+        let version = match file_version(file_path).await? {
+            (size, Some(modified)) => Some((size, modified)),
+            (_, None) => None,
+        };
+
+        let recorded = version.filter(|_| options.delta).and_then(|version| {
+            self.ingested
+                .lookup(file_path, version, options.compression)
+        });
+        if let (Some(version), Some((hash, compression))) = (version, recorded.clone()) {
+            if self.shares.is_open_blob(&hash).await {
+                let file_info = FileInfo {
+                    name: extract_file_name(file_path),
+                    relative_path: calculate_relative_path(file_path, base_path)?,
+                    size: version.0,
+                    hash,
+                    compression,
+                };
+                return Ok((file_info, true));
+            }
+        }
+
+        let file_info =
+            create_file_info(&self.blobs, file_path, base_path, options.compression).await?;
+        if let Some((hash, _)) = recorded.filter(|(hash, _)| *hash != file_info.hash) {
+            // Same size and modification time, but other content
+            tracing::warn!(
+                path = %file_path.display(),
+                recorded = %hash,
+                stored = %file_info.hash,
+                "file changed without a new modification time"
+            );
+            self.ingested.forget(file_path);
+        }
+        if let Some((size, modified)) = version {
+            if file_version(file_path).await.ok() == Some((size, Some(modified))) {
+                self.ingested
+                    .record(file_path, (size, modified), options.compression, &file_info);
+            }
+        }
+        Ok((file_info, false))
+    }
+
+    /// Stores the bundle for a share, creates its ticket, and registers it.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata for files that are already stored as blobs
+    /// * `paths` - Files and directories the share was created from
+    /// * `options` - Whether the share is single-use or a locked preview
+    /// * `sources` - File each blob was read from, by hash, if the share is
+    ///   to be served again after a restart
+    ///
+    /// # Returns
+    ///
+    /// The ID and ticket of the new share
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata or bundle cannot be stored.
+    async fn publish_share(
+        &self,
+        mut metadata: ShareMetadata,
+        paths: &[PathBuf],
+        options: &ShareOptions,
+        sources: Option<BTreeMap<String, PathBuf>>,
+    ) -> Result<ShareResult> {
+        let share_id = Uuid::new_v4().to_string();
+        tracing::Span::current().record("share_id", share_id.as_str());
+        metadata.share_id = Some(share_id.clone());
+        let root = content_root(&metadata.files);
+        metadata.content_root = Some(root.clone());
+
+        let (bundle_hash, bundle_format, control_hashes) =
+            store_control_blobs(&self.blobs, &metadata).await?;
+        let ticket = create_share_ticket(&self.endpoint, &bundle_hash, &bundle_format)?;
+
+        self.shares
+            .register(
+                ticket.clone(),
+                bundle_hash.to_string(),
+                control_hashes,
+                &metadata,
+                options.single_use,
+            )
+            .await;
+        self.shares.set_paths(&share_id, paths.to_vec()).await;
+        // Locked before the ticket is handed out, so no file is served early
+        if options.preview {
+            self.shares.lock(&share_id).await;
+        }
+        // Keeps access to the paths under the macOS sandbox once the dialog
+        // that granted it is gone
+        let bookmarks = create_bookmarks(paths);
+        if !bookmarks.is_empty() {
+            if let Err(error) = self.access_scopes.insert(share_id.clone(), &bookmarks) {
+                tracing::warn!(%share_id, %error, "sandbox access not kept");
+            }
+        }
+        if let Some(sources) = sources {
+            self.saved_shares
+                .save(SavedShare {
+                    share_id: share_id.clone(),
+                    ticket: ticket.clone(),
+                    metadata: metadata.clone(),
+                    paths: paths.to_vec(),
+                    bookmarks,
+                    sources,
+                    single_use: options.single_use,
+                    locked: options.preview,
+                })
+                .await;
+        }
+        tracing::info!(%share_id, %bundle_hash, files = metadata.files.len(), "share published");
+        if metadata.expires_at.is_some() || options.single_use {
+            self.schedule_close(share_id.clone(), metadata.expires_at);
+        }
+
+        Ok(ShareResult {
+            share_id,
+            ticket,
+            skipped_files: metadata.skipped_files,
+            content_root: root,
+        })
+    }
+
+    /// Serves the shares saved before the last restart again under their old
+    /// tickets.
+    ///
+    /// Each file is stored again from where it was read. Shares that expired
+    /// in the meantime, or whose files were moved or changed since, are
+    /// dropped.
+    ///
+    /// # Returns
+    ///
+    /// The number of shares restored
+    pub async fn restore_saved_shares(&self) -> usize {
+        let mut restored = 0;
+        for saved in self.saved_shares.list().await {
+            match self.restore_share(&saved).await {
+                Ok(()) => restored += 1,
+                Err(error) => {
+                    tracing::warn!(share_id = %saved.share_id, %error, "saved share dropped");
+                    self.access_scopes.remove(&saved.share_id);
+                    self.saved_shares.remove(&saved.share_id).await;
+                }
+            }
+        }
+        restored
+    }
+
+    /// Stores a saved share's blobs again and registers it under its ticket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share expired, a file cannot be read or no
+    /// longer matches its hash, or the bundle no longer matches the ticket.
+    async fn restore_share(&self, saved: &SavedShare) -> Result<()> {
+        let metadata = &saved.metadata;
+        if metadata
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= current_unix_time())
+        {
+            anyhow::bail!("Share has expired");
+        }
+        // The files can only be read again under the sandbox through the
+        // bookmarks made when the share was created
+        self.access_scopes
+            .insert(saved.share_id.clone(), &saved.bookmarks)?;
+
+        for file in &metadata.files {
+            let path = saved.sources.get(&file.hash).ok_or_else(|| {
+                anyhow::anyhow!("No source file known for '{}'", file.relative_path)
+            })?;
+            let (hash, _, _) = store_file_as_blob(&self.blobs, path, file.compression).await?;
+            if hash != file.hash {
+                anyhow::bail!("File '{}' changed since it was shared", path.display());
+            }
+        }
+
+        let (bundle_hash, _, control_hashes) = store_control_blobs(&self.blobs, metadata).await?;
+        if parse_ticket(&saved.ticket)?.hash() != bundle_hash {
+            anyhow::bail!("Share bundle no longer matches its ticket");
+        }
+
+        self.shares
+            .register(
+                saved.ticket.clone(),
+                bundle_hash.to_string(),
+                control_hashes,
+                metadata,
+                saved.single_use,
+            )
+            .await;
+        self.shares
+            .set_paths(&saved.share_id, saved.paths.clone())
+            .await;
+        if saved.locked {
+            self.shares.lock(&saved.share_id).await;
+        }
+        tracing::info!(share_id = %saved.share_id, %bundle_hash, "share restored");
+        if metadata.expires_at.is_some() || saved.single_use {
+            self.schedule_close(saved.share_id.clone(), metadata.expires_at);
+        }
+        Ok(())
+    }
+
+    /// Removes a share's blobs from the store once it expires, is used up or
+    /// is stopped.
+    ///
+    /// Requests for the share are refused from the moment it closes; this
+    /// only frees the memory its blobs take up and stops watching its files.
+    fn schedule_close(&self, share_id: ShareId, expires_at: Option<u64>) {
+        let shares = self.shares.clone();
+        let store = self.store.clone();
+        let source_watchers = self.source_watchers.clone();
+        let saved_shares = self.saved_shares.clone();
+        let access_scopes = self.access_scopes.clone();
+
+        tokio::spawn(async move {
+            let expired = async {
+                match expires_at {
+                    Some(expires_at) => {
+                        let remaining = expires_at.saturating_sub(current_unix_time());
+                        tokio::time::sleep(Duration::from_secs(remaining)).await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = expired => {}
+                _ = shares.closed(&share_id) => {}
+            }
+
+            remove_closed_share(
+                &store,
+                &shares,
+                &source_watchers,
+                &access_scopes,
+                &saved_shares,
+                &share_id,
+            )
+            .await;
+        });
+    }
+
+    /// Stops serving a share before it expires or is used up.
+    ///
+    /// Peers are refused the share's blobs from now on, including peers in the
+    /// middle of downloading it, and the blobs no other share uses are removed
+    /// from the store. The share stays registered so its activity can still be
+    /// looked up.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The ID returned when the share was created
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is unknown or was already stopped.
+    pub async fn stop_share(&self, share_id: &str) -> Result<()> {
+        if !self.shares.stop(share_id).await {
+            anyhow::bail!("Unknown or already stopped share '{}'", share_id);
+        }
+
+        remove_closed_share(
+            &self.store,
+            &self.shares,
+            &self.source_watchers,
+            &self.access_scopes,
+            &self.saved_shares,
+            share_id,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Starts serving the files of a preview share.
+    ///
+    /// Until then receivers can only fetch the share's file list; downloads of
+    /// its files are refused.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The ID returned when the share was created
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is unknown or not locked.
+    pub async fn unlock_share(&self, share_id: &str) -> Result<()> {
+        if !self.shares.unlock(share_id).await {
+            anyhow::bail!("Unknown or already unlocked share '{}'", share_id);
+        }
+        self.saved_shares.set_locked(share_id, false).await;
+        tracing::info!(%share_id, "share unlocked");
+        Ok(())
+    }
+
+    /// Saves a printable transfer slip for an open share.
+    ///
+    /// The card shows how to receive the share, its ticket as a QR code, a
+    /// short code to refer to it by, and when it expires. It is written as a
+    /// PNG image if the destination ends in `.png`, and as a PDF otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The ID returned when the share was created
+    /// * `destination` - File to save the card to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is unknown or no longer open, or the card
+    /// cannot be rendered or written.
+    pub async fn export_share_card(&self, share_id: &str, destination: &Path) -> Result<()> {
+        let ticket = self
+            .shares
+            .ticket(share_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Unknown share '{}'", share_id))?;
+        if !self.shares.is_open(share_id).await {
+            anyhow::bail!("Share '{}' is no longer open", share_id);
+        }
+
+        let card = ShareCard::new(share_id, ticket, self.shares.expires_at(share_id).await);
+        let bytes = card.render(CardFormat::from_path(destination))?;
+        fs::write(destination, bytes).await?;
+        Ok(())
+    }
+
+    /// Saves the ticket of an open share to a ticket file.
+    ///
+    /// Without a lifetime of its own, the ticket file expires with the share.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The ID returned when the share was created
+    /// * `destination` - File to save the ticket to
+    /// * `note` - Optional note stored alongside the ticket
+    /// * `expires_in` - Optional lifetime of the ticket file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is unknown or no longer open, or the
+    /// ticket file cannot be written.
+    pub async fn export_ticket(
+        &self,
+        share_id: &str,
+        destination: &Path,
+        note: Option<String>,
+        expires_in: Option<Duration>,
+    ) -> Result<()> {
+        let ticket = self
+            .shares
+            .ticket(share_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Unknown share '{}'", share_id))?;
+        if !self.shares.is_open(share_id).await {
+            anyhow::bail!("Share '{}' is no longer open", share_id);
+        }
+
+        let mut ticket_file = TicketFile::new(ticket);
+        ticket_file.note = note;
+        ticket_file.expires_at = match expires_in {
+            Some(expires_in) => expiry_time(Some(expires_in)),
+            None => self.shares.expires_at(share_id).await,
+        };
+        save_ticket_file(destination, &ticket_file).await
     }
 
     /// Gracefully shuts down the router and endpoint.
@@ -528,24 +3234,38 @@ impl GinsengCore {
 
 /// Creates and configures an Iroh endpoint for P2P networking.
 ///
-/// Sets up the endpoint with blob protocol support, default relay mode,
-/// and n0 discovery for finding peers on the network.
-async fn create_endpoint() -> Result<Endpoint> {
-    Endpoint::builder()
-        .alpns(vec![iroh_blobs::protocol::ALPN.to_vec()])
-        .relay_mode(RelayMode::Default)
+/// Sets up the endpoint with blob protocol support, the given relay mode,
+/// and n0 discovery for finding peers on the network. Without a secret key
+/// the endpoint gets a new identity.
+async fn create_endpoint(relay_mode: RelayMode, secret_key: Option<SecretKey>) -> Result<Endpoint> {
+    let mut builder = Endpoint::builder()
+        .alpns(vec![
+            iroh_blobs::protocol::ALPN.to_vec(),
+            DROPBOX_ALPN.to_vec(),
+            WARMUP_ALPN.to_vec(),
+            CAPABILITIES_ALPN.to_vec(),
+        ])
+        .relay_mode(relay_mode);
+    if let Some(secret_key) = secret_key {
+        builder = builder.secret_key(secret_key);
+    }
+    builder
         .bind()
         .await
         .map_err(|error| anyhow::anyhow!("Failed to create endpoint: {}", error))
 }
 
-/// Creates a protocol router that handles incoming blob protocol connections.
+/// Creates a protocol router that handles incoming blob, drop box, warm and
+/// capability exchange connections.
 ///
-/// The router accepts connections using the blob protocol ALPN and routes
-/// them to the appropriate blob protocol handler.
-fn create_router(endpoint: &Endpoint, blobs: &BlobsProtocol) -> Router {
+/// The router accepts connections using the blob protocol, drop box, warm-up
+/// and capabilities ALPNs and routes them to the appropriate protocol handler.
+fn create_router(endpoint: &Endpoint, blobs: &BlobsProtocol, dropbox: &DropBox) -> Router {
     iroh::protocol::Router::builder(endpoint.clone())
         .accept(iroh_blobs::protocol::ALPN, blobs.clone())
+        .accept(DROPBOX_ALPN, dropbox.clone())
+        .accept(WARMUP_ALPN, Warmup)
+        .accept(CAPABILITIES_ALPN, CapabilityExchange)
         .spawn()
 }
 
@@ -554,11 +3274,16 @@ fn create_router(endpoint: &Endpoint, blobs: &BlobsProtocol) -> Router {
 /// Uses different strategies:
 /// - Single path: Detects if it's a file or directory and handles accordingly
 /// - Multiple paths: Validates all are files and creates a multiple files share
-async fn create_share_metadata(blobs: &BlobsProtocol, paths: &[PathBuf]) -> Result<ShareMetadata> {
+async fn create_share_metadata(
+    blobs: &BlobsProtocol,
+    paths: &[PathBuf],
+    compression: Compression,
+    exclude: &ExcludeRules,
+) -> Result<ShareMetadata> {
     if paths.len() == 1 {
-        create_single_path_metadata(blobs, &paths[0]).await
+        create_single_path_metadata(blobs, &paths[0], compression, exclude).await
     } else {
-        create_multiple_files_metadata(blobs, paths).await
+        create_multiple_files_metadata(blobs, paths, compression).await
     }
 }
 
@@ -566,12 +3291,19 @@ async fn create_share_metadata(blobs: &BlobsProtocol, paths: &[PathBuf]) -> Resu
 ///
 /// Canonicalizes the path and determines whether it's a file or directory,
 /// then delegates to the appropriate metadata creation function.
-async fn create_single_path_metadata(blobs: &BlobsProtocol, path: &Path) -> Result<ShareMetadata> {
+async fn create_single_path_metadata(
+    blobs: &BlobsProtocol,
+    path: &Path,
+    compression: Compression,
+    exclude: &ExcludeRules,
+) -> Result<ShareMetadata> {
     let canonical_path = fs::canonicalize(path).await?;
 
     match (canonical_path.is_file(), canonical_path.is_dir()) {
-        (true, false) => create_single_file_metadata(blobs, &canonical_path).await,
-        (false, true) => create_directory_metadata(blobs, &canonical_path).await,
+        (true, false) => create_single_file_metadata(blobs, &canonical_path, compression).await,
+        (false, true) => {
+            create_directory_metadata(blobs, &canonical_path, compression, exclude).await
+        }
         _ => anyhow::bail!("Path is neither a file nor a directory"),
     }
 }
@@ -582,13 +3314,19 @@ async fn create_single_path_metadata(blobs: &BlobsProtocol, path: &Path) -> Resu
 async fn create_single_file_metadata(
     blobs: &BlobsProtocol,
     file_path: &Path,
+    compression: Compression,
 ) -> Result<ShareMetadata> {
-    let file_info = create_file_info(blobs, file_path, file_path).await?;
+    let file_info = create_file_info(blobs, file_path, file_path, compression).await?;
 
     Ok(ShareMetadata {
         files: vec![file_info.clone()],
         share_type: ShareType::SingleFile,
         total_size: file_info.size,
+        skipped_files: Vec::new(),
+        custom_fields: BTreeMap::new(),
+        expires_at: None,
+        share_id: None,
+        content_root: None,
     })
 }
 
@@ -599,9 +3337,12 @@ async fn create_single_file_metadata(
 async fn create_directory_metadata(
     blobs: &BlobsProtocol,
     dir_path: &Path,
+    compression: Compression,
+    exclude: &ExcludeRules,
 ) -> Result<ShareMetadata> {
     let directory_name = extract_directory_name(dir_path);
-    let file_infos = collect_directory_files(blobs, dir_path).await?;
+    let (file_infos, skipped_files) =
+        collect_directory_files(blobs, dir_path, compression, exclude).await?;
     let total_size = calculate_total_size(file_infos.iter().map(|f| f.size));
 
     Ok(ShareMetadata {
@@ -610,6 +3351,11 @@ async fn create_directory_metadata(
             name: directory_name,
         },
         total_size,
+        skipped_files,
+        custom_fields: BTreeMap::new(),
+        expires_at: None,
+        share_id: None,
+        content_root: None,
     })
 }
 
@@ -617,17 +3363,40 @@ async fn create_directory_metadata(
 ///
 /// Validates that all paths are files (no directories allowed in multi-file shares),
 /// stores each file as a blob, and creates metadata with MultipleFiles type.
+/// Files with the same name are told apart by the folder they are in, see
+/// [`unique_relative_paths`].
 async fn create_multiple_files_metadata(
     blobs: &BlobsProtocol,
     paths: &[PathBuf],
+    compression: Compression,
 ) -> Result<ShareMetadata> {
     validate_all_paths_are_files(paths).await?;
 
-    let mut file_infos = Vec::new();
+    let mut canonical_paths = Vec::with_capacity(paths.len());
     for path in paths {
         let canonical_path = fs::canonicalize(path).await?;
-        let file_info = create_file_info(blobs, &canonical_path, &canonical_path).await?;
-        file_infos.push(file_info);
+        canonical_paths.push((canonical_path.clone(), canonical_path));
+    }
+    // Files with the same name from different folders get paths of their own
+    let relative_paths = unique_relative_paths(&canonical_paths)?;
+
+    let mut file_infos = Vec::new();
+    let mut skipped_files = Vec::new();
+    for ((canonical_path, _), relative_path) in canonical_paths.iter().zip(relative_paths) {
+        match create_file_info(blobs, canonical_path, canonical_path, compression).await {
+            Ok(mut file_info) => {
+                file_info.relative_path = relative_path;
+                file_infos.push(file_info);
+            }
+            Err(error) => skipped_files.push(SkippedFile {
+                relative_path,
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    if file_infos.is_empty() {
+        anyhow::bail!("None of the selected files could be read");
     }
 
     let total_size = calculate_total_size(file_infos.iter().map(|f| f.size));
@@ -636,6 +3405,11 @@ async fn create_multiple_files_metadata(
         files: file_infos,
         share_type: ShareType::MultipleFiles,
         total_size,
+        skipped_files,
+        custom_fields: BTreeMap::new(),
+        expires_at: None,
+        share_id: None,
+        content_root: None,
     })
 }
 
@@ -665,21 +3439,24 @@ async fn validate_all_paths_are_files(paths: &[PathBuf]) -> Result<()> {
 ///
 /// * `file_path` - The absolute path to the file
 /// * `base_path` - The base path for calculating relative paths
+/// * `compression` - How to compress the file content before storing it
 async fn create_file_info(
     blobs: &BlobsProtocol,
     file_path: &Path,
     base_path: &Path,
+    compression: Compression,
 ) -> Result<FileInfo> {
     let file_name = extract_file_name(file_path);
     let relative_path = calculate_relative_path(file_path, base_path)?;
-    let file_size = get_file_size(file_path).await?;
-    let file_hash = store_file_as_blob(blobs, file_path).await?;
+    let (file_hash, file_size, compression) =
+        store_file_as_blob(blobs, file_path, compression).await?;
 
     Ok(FileInfo {
         name: file_name,
         relative_path,
         size: file_size,
         hash: file_hash,
+        compression,
     })
 }
 
@@ -697,62 +3474,390 @@ async fn get_file_size(file_path: &Path) -> Result<u64> {
         })
 }
 
-/// Stores a file as a content-addressed blob and returns its hash.
+/// Stores a snapshot of a file as a content-addressed blob.
 ///
-/// The file is read and stored in the blob store, returning a hash
-/// that can be used to retrieve the content later.
-async fn store_file_as_blob(blobs: &BlobsProtocol, file_path: &Path) -> Result<String> {
-    blobs
-        .store()
-        .add_path(file_path)
-        .await
-        .map(|tag| tag.hash.to_string())
-        .map_err(|error| {
-            anyhow::anyhow!(
+/// The file's bytes are copied into the blob store, so the share keeps serving
+/// exactly the content read here even if the file changes later. A file that
+/// is modified while being read would give a blob matching no version of it,
+/// so its size and modification time are compared before and after reading
+/// and the read is repeated if they differ. Files locked by another
+/// application (common on Windows while a document is open) are retried a
+/// few times before giving up with a specific "in use" error.
+///
+/// With compression, the file is read into memory and stored compressed,
+/// unless that does not make it smaller.
+///
+/// # Returns
+///
+/// The hash of the stored blob, the size of the snapshot in bytes, and how
+/// the blob is compressed.
+async fn store_file_as_blob(
+    blobs: &BlobsProtocol,
+    file_path: &Path,
+    compression: Compression,
+) -> Result<(String, u64, Compression)> {
+    let mut locked_attempts = 0;
+    let mut changed_attempts = 0;
+
+    loop {
+        let before = file_version(file_path).await?;
+        let stored = match compression {
+            Compression::None => blobs
+                .store()
+                .add_path(file_path)
+                .await
+                .map(|tag| (tag.hash, Compression::None))
+                .map_err(|error| anyhow::anyhow!("{}", error)),
+            Compression::Zstd => store_compressed(blobs, file_path).await,
+        };
+        let error = match stored {
+            Ok((hash, compression)) => {
+                if file_version(file_path).await? == before {
+                    return Ok((hash.to_string(), before.0, compression));
+                }
+
+                changed_attempts += 1;
+                if changed_attempts > CHANGED_FILE_RETRIES {
+                    anyhow::bail!(
+                        "File '{}' kept changing while being read. Wait until it is saved and try again.",
+                        file_path.display()
+                    );
+                }
+                continue;
+            }
+            Err(error) => error,
+        };
+
+        if !is_file_in_use(file_path) {
+            anyhow::bail!(
                 "Failed to store file '{}' as blob: {}",
                 file_path.display(),
                 error
-            )
+            );
+        }
+
+        locked_attempts += 1;
+        if locked_attempts > LOCKED_FILE_RETRIES {
+            anyhow::bail!(
+                "File '{}' is in use by another application. Close it and try again.",
+                file_path.display()
+            );
+        }
+
+        tokio::time::sleep(LOCKED_FILE_RETRY_DELAY).await;
+    }
+}
+
+/// Stores the content of a file compressed with zstd.
+///
+/// Falls back to storing the content as it is if compressing does not make
+/// it smaller.
+async fn store_compressed(blobs: &BlobsProtocol, file_path: &Path) -> Result<(Hash, Compression)> {
+    let content = fs::read(file_path).await?;
+    let (blob, compression) = compress_content(content).await?;
+
+    let tag = blobs
+        .store()
+        .add_bytes(blob)
+        .await
+        .map_err(|error| anyhow::anyhow!("{}", error))?;
+    Ok((tag.hash, compression))
+}
+
+/// Compresses content with zstd, unless that does not make it smaller.
+///
+/// Compressing runs off the async runtime, as it can take a while for large
+/// content.
+///
+/// # Returns
+///
+/// The content to store and how it is compressed.
+async fn compress_content(content: Vec<u8>) -> Result<(Vec<u8>, Compression)> {
+    tokio::task::spawn_blocking(move || {
+        Ok(match compress(&content)? {
+            Some(compressed) => (compressed, Compression::Zstd),
+            None => (content, Compression::None),
         })
+    })
+    .await?
+}
+
+/// Returns the directory shared in archive mode.
+///
+/// # Errors
+///
+/// Returns an error unless exactly one path is shared and it is a directory.
+fn archive_directory(paths: &[PathBuf]) -> Result<&Path> {
+    match paths {
+        [path] if path.is_dir() => Ok(path),
+        _ => anyhow::bail!("Archive mode shares a single directory"),
+    }
+}
+
+/// Reads a file into an archive under its path relative to the base path.
+///
+/// # Returns
+///
+/// The size of the file in bytes.
+async fn add_to_archive(
+    archive: &mut ArchiveBuilder,
+    file_path: &Path,
+    base_path: &Path,
+) -> Result<u64> {
+    let relative_path = calculate_relative_path(file_path, base_path)?;
+    let content = fs::read(file_path)
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to read '{}': {}", file_path.display(), error))?;
+    let modified = file_version(file_path).await?.1;
+
+    archive.append(&relative_path, &content, modified)?;
+    Ok(content.len() as u64)
+}
+
+/// Stores a finished archive as a blob and describes it as the file of a share.
+///
+/// # Arguments
+///
+/// * `archive` - The archive with every file of the share
+/// * `name` - The name of the shared directory, used for the archive file name
+/// * `compression` - How to compress the archive before storing it
+async fn store_archive(
+    blobs: &BlobsProtocol,
+    archive: ArchiveBuilder,
+    name: &str,
+    compression: Compression,
+) -> Result<FileInfo> {
+    let archive = archive.finish()?;
+    let size = archive.len() as u64;
+    let (blob, compression) = match compression {
+        Compression::None => (archive, Compression::None),
+        Compression::Zstd => compress_content(archive).await?,
+    };
+
+    let tag = blobs
+        .store()
+        .add_bytes(blob)
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to store archive as blob: {}", error))?;
+
+    let file_name = format!("{}.{}", name, ARCHIVE_EXTENSION);
+    Ok(FileInfo {
+        name: file_name.clone(),
+        relative_path: file_name,
+        size,
+        hash: tag.hash.to_string(),
+        compression,
+    })
+}
+
+/// Returns the size and modification time identifying a version of a file.
+async fn file_version(file_path: &Path) -> Result<(u64, Option<SystemTime>)> {
+    let metadata = fs::metadata(file_path).await.map_err(|error| {
+        anyhow::anyhow!(
+            "Failed to read metadata of '{}': {}",
+            file_path.display(),
+            error
+        )
+    })?;
+    Ok((metadata.len(), metadata.modified().ok()))
 }
 
 /// Recursively collects all files in a directory and creates FileInfo for each.
 ///
 /// Uses WalkDir to traverse the directory tree and processes only regular files,
 /// creating FileInfo structures with paths relative to the directory root.
-async fn collect_directory_files(blobs: &BlobsProtocol, dir_path: &Path) -> Result<Vec<FileInfo>> {
+/// Excluded files and folders are left out. Files and folders that cannot be
+/// read are returned as skipped files.
+async fn collect_directory_files(
+    blobs: &BlobsProtocol,
+    dir_path: &Path,
+    compression: Compression,
+    exclude: &ExcludeRules,
+) -> Result<(Vec<FileInfo>, Vec<SkippedFile>)> {
     let mut file_infos = Vec::new();
+    let mut skipped_files = Vec::new();
+
+    for entry in walk_directory(dir_path, exclude)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                let relative_path = error
+                    .path()
+                    .and_then(|path| calculate_relative_path(path, dir_path).ok())
+                    .unwrap_or_default();
+                skipped_files.push(SkippedFile {
+                    relative_path,
+                    error: error.to_string(),
+                });
+                continue;
+            }
+        };
 
-    for entry in WalkDir::new(dir_path).into_iter().filter_map(Result::ok) {
         let path = entry.path();
         if path.is_file() {
-            let file_info = create_file_info(blobs, path, dir_path).await?;
-            file_infos.push(file_info);
+            match create_file_info(blobs, path, dir_path, compression).await {
+                Ok(file_info) => file_infos.push(file_info),
+                Err(error) => skipped_files.push(SkippedFile {
+                    relative_path: calculate_relative_path(path, dir_path)?,
+                    error: error.to_string(),
+                }),
+            }
         }
     }
 
-    Ok(file_infos)
+    Ok((file_infos, skipped_files))
 }
 
-/// Collects all file paths from the given paths (files and directories)
-async fn collect_file_paths(paths: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>> {
-    let mut file_paths = Vec::new();
+/// Fails with [`ShareTooLarge`] if the files to share exceed the size limit.
+fn check_share_size(total_size: u64, max_size: Option<u64>) -> Result<()> {
+    match max_size {
+        Some(max_size) if total_size > max_size => Err(ShareTooLarge {
+            total_size,
+            max_size,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
 
-    for path in paths {
-        let canonical = fs::canonicalize(path).await?;
-        if canonical.is_file() {
-            file_paths.push((canonical.clone(), canonical.clone()));
-        } else if canonical.is_dir() {
-            for entry in WalkDir::new(&canonical).into_iter().filter_map(Result::ok) {
-                let entry_path = entry.path();
-                if entry_path.is_file() {
-                    file_paths.push((entry_path.to_path_buf(), canonical.clone()));
+/// Collects all file paths from the given paths (files and directories)
+///
+/// Excluded files and folders of directories are left out; files given
+/// directly are always collected. Directories are walked on a blocking
+/// thread that checks `cancel` between entries, so cancelling a share of a
+/// huge folder stops the walk right away instead of after it finished.
+///
+/// # Errors
+///
+/// Returns [`TransferCancelled`] if `cancel` is cancelled during the walk, or
+/// an error if a path or an exclude pattern is invalid.
+async fn collect_file_paths(
+    paths: &[PathBuf],
+    exclude: &ExcludeRules,
+    cancel: &CancellationToken,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let paths = paths.to_vec();
+    let exclude = exclude.clone();
+    let cancel = cancel.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut file_paths = Vec::new();
+
+        for path in &paths {
+            let canonical = std::fs::canonicalize(path)?;
+            if canonical.is_file() {
+                file_paths.push((canonical.clone(), canonical.clone()));
+            } else if canonical.is_dir() {
+                for entry in walk_directory(&canonical, &exclude)?.filter_map(Result::ok) {
+                    if cancel.is_cancelled() {
+                        return Err(TransferCancelled.into());
+                    }
+                    let entry_path = entry.path();
+                    if entry_path.is_file() {
+                        file_paths.push((entry_path.to_path_buf(), canonical.clone()));
+                    }
                 }
             }
         }
+
+        Ok(file_paths)
+    })
+    .await
+    .map_err(|error| anyhow::anyhow!("Failed to collect files: {}", error))?
+}
+
+/// Maximum number of custom fields on a share
+const MAX_CUSTOM_FIELDS: usize = 32;
+
+/// Maximum length of a custom field key in bytes
+const MAX_CUSTOM_FIELD_KEY_LENGTH: usize = 64;
+
+/// Maximum length of a custom field value in bytes
+const MAX_CUSTOM_FIELD_VALUE_LENGTH: usize = 1024;
+
+/// Validates custom fields attached to a share.
+///
+/// Keys must be non-empty, and the number and length of fields are limited
+/// so the share bundle stays small.
+fn validate_custom_fields(custom_fields: &BTreeMap<String, String>) -> Result<()> {
+    if custom_fields.len() > MAX_CUSTOM_FIELDS {
+        anyhow::bail!(
+            "A share can have at most {} custom fields",
+            MAX_CUSTOM_FIELDS
+        );
+    }
+
+    for (key, value) in custom_fields {
+        if key.trim().is_empty() {
+            anyhow::bail!("Custom field names cannot be empty");
+        }
+        if key.len() > MAX_CUSTOM_FIELD_KEY_LENGTH {
+            anyhow::bail!(
+                "Custom field name '{}' is longer than {} bytes",
+                key,
+                MAX_CUSTOM_FIELD_KEY_LENGTH
+            );
+        }
+        if value.len() > MAX_CUSTOM_FIELD_VALUE_LENGTH {
+            anyhow::bail!(
+                "Custom field '{}' is longer than {} bytes",
+                key,
+                MAX_CUSTOM_FIELD_VALUE_LENGTH
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps only the selected files from shared directories.
+///
+/// Files shared directly (not through a directory) are always kept.
+fn filter_selected_files(
+    file_paths: Vec<(PathBuf, PathBuf)>,
+    include: &HashSet<String>,
+) -> Vec<(PathBuf, PathBuf)> {
+    file_paths
+        .into_iter()
+        .filter(|(file_path, base_path)| {
+            file_path == base_path
+                || calculate_relative_path(file_path, base_path)
+                    .is_ok_and(|relative_path| include.contains(&relative_path))
+        })
+        .collect()
+}
+
+/// Lists every file in a directory with its relative path and size.
+///
+/// Intended for building a file picker where the user selects a subset of
+/// the directory to share. The relative paths match the `include` selection
+/// accepted by [`GinsengCore::share_files_parallel`].
+///
+/// # Errors
+///
+/// Returns an error if the path is not a directory or cannot be read.
+pub async fn scan_directory(directory: &Path) -> Result<Vec<ScannedFile>> {
+    let canonical = fs::canonicalize(directory).await?;
+    if !canonical.is_dir() {
+        anyhow::bail!("'{}' is not a directory", directory.display());
+    }
+
+    let mut files = Vec::new();
+    let file_paths = collect_file_paths(
+        &[canonical],
+        &ExcludeRules::default(),
+        &CancellationToken::new(),
+    )
+    .await?;
+    for (file_path, base_path) in file_paths {
+        files.push(ScannedFile {
+            relative_path: calculate_relative_path(&file_path, &base_path)?,
+            size: get_file_size(&file_path).await?,
+        });
     }
 
-    Ok(file_paths)
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(files)
 }
 
 /// Determines share type from paths and file infos
@@ -773,6 +3878,89 @@ fn determine_share_type(paths: &[PathBuf], file_infos: &[FileInfo]) -> ShareType
     }
 }
 
+/// Stores the metadata, file list pages and bundle of a share as blobs.
+///
+/// The same metadata always yields the same blobs, so a share restored after
+/// a restart keeps its ticket.
+///
+/// # Returns
+///
+/// The hash and format of the bundle, and the hashes of the other blobs
+async fn store_control_blobs(
+    blobs: &BlobsProtocol,
+    metadata: &ShareMetadata,
+) -> Result<(Hash, iroh_blobs::BlobFormat, Vec<String>)> {
+    let metadata_hash = store_metadata_as_blob(blobs, metadata).await?;
+    let (first_page, manifest_pages) = store_manifest_pages(blobs, &metadata.files).await?;
+    let bundle = ShareBundle {
+        metadata: ShareMetadata {
+            files: first_page,
+            ..metadata.clone()
+        },
+        metadata_hash,
+        manifest_pages,
+    };
+    let (bundle_hash, bundle_format) = store_bundle_as_blob(blobs, &bundle).await?;
+
+    let mut control_hashes = bundle.manifest_pages;
+    control_hashes.push(bundle.metadata_hash);
+    Ok((bundle_hash, bundle_format, control_hashes))
+}
+
+/// Computes the root hash over the hashes of a share's files.
+///
+/// The file hashes are the leaves of a binary merkle tree, in the order the
+/// files are listed; each pair of nodes is hashed together and a node left
+/// without a pair moves up a level as it is. The root of a single file is its
+/// own hash, and the root of no files the hash of no data. Since every blob is
+/// verified against its hash, two peers with the same root hold the same
+/// content.
+pub fn content_root(files: &[FileInfo]) -> String {
+    let mut level: Vec<Hash> = files
+        .iter()
+        .map(|file| {
+            file.hash
+                .parse()
+                .unwrap_or_else(|_| Hash::new(file.hash.as_bytes()))
+        })
+        .collect();
+    if level.is_empty() {
+        return Hash::new(b"").to_string();
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut node = [0u8; 64];
+                    node[..32].copy_from_slice(left.as_bytes());
+                    node[32..].copy_from_slice(right.as_bytes());
+                    Hash::new(node)
+                }
+                _ => pair[0],
+            })
+            .collect();
+    }
+    level[0].to_string()
+}
+
+/// Computes the content root of a share whose files were all received.
+///
+/// A root announced by the sender that does not match the file list is
+/// logged; the root computed from the verified files is returned either way.
+fn received_content_root(metadata: &ShareMetadata) -> String {
+    let root = content_root(&metadata.files);
+    if let Some(announced) = metadata
+        .content_root
+        .as_ref()
+        .filter(|announced| **announced != root)
+    {
+        tracing::warn!(%announced, computed = %root, "content root differs from the sender's");
+    }
+    root
+}
+
 /// Serializes share metadata to JSON and stores it as a blob.
 async fn store_metadata_as_blob(blobs: &BlobsProtocol, metadata: &ShareMetadata) -> Result<String> {
     let metadata_json = serde_json::to_string(metadata)?;
@@ -794,6 +3982,26 @@ async fn store_bundle_as_blob(
     Ok((tag.hash, tag.format))
 }
 
+/// Stores all but the first page of a share's file list as separate blobs.
+///
+/// Returns the files of the first page, which go into the bundle itself, and
+/// the hashes of the remaining pages in order.
+async fn store_manifest_pages(
+    blobs: &BlobsProtocol,
+    files: &[FileInfo],
+) -> Result<(Vec<FileInfo>, Vec<String>)> {
+    let mut pages = files.chunks(MANIFEST_PAGE_SIZE);
+    let first_page = pages.next().unwrap_or_default().to_vec();
+
+    let mut page_hashes = Vec::new();
+    for page in pages {
+        let page_json = serde_json::to_string(page)?;
+        page_hashes.push(store_json_as_blob(blobs, &page_json).await?);
+    }
+
+    Ok((first_page, page_hashes))
+}
+
 /// Stores a JSON string as a blob and returns its hash.
 async fn store_json_as_blob(blobs: &BlobsProtocol, json: &str) -> Result<String> {
     let add_progress = blobs.store().add_bytes(json.as_bytes().to_vec());
@@ -818,25 +4026,105 @@ fn create_share_ticket(
 }
 
 /// Parses a ticket string into a BlobTicket structure.
-fn parse_ticket(ticket_str: &str) -> Result<BlobTicket> {
+pub(crate) fn parse_ticket(ticket_str: &str) -> Result<BlobTicket> {
     ticket_str
         .parse::<BlobTicket>()
-        .map_err(|error| anyhow::anyhow!("Failed to parse ticket: {}", error))
+        .map_err(|error| DownloadError::InvalidTicket(error.to_string()).into())
+}
+
+/// Finds the tickets in input holding several of them, e.g. pasted together.
+///
+/// # Returns
+///
+/// Every ticket in the input, in order, or None if the input is a single
+/// ticket or anything other than tickets
+pub fn split_pasted_tickets(input: &str) -> Option<Vec<String>> {
+    split_tickets(input, |ticket| parse_ticket(ticket).is_ok()).filter(|tickets| tickets.len() > 1)
 }
 
 /// Downloads a bundle from a peer and parses it into a ShareBundle.
 ///
 /// Establishes a connection to the peer, downloads the bundle blob,
 /// exports it to a temporary file, parses the JSON, and cleans up.
-async fn download_and_parse_bundle(
+pub(crate) async fn download_and_parse_bundle(
     endpoint: &Endpoint,
     blobs: &BlobsProtocol,
     store: &MemStore,
     ticket: &BlobTicket,
 ) -> Result<ShareBundle> {
     let _connection = establish_connection(endpoint, ticket).await?;
-    download_blob(endpoint, store, ticket).await?;
-    parse_bundle_from_blob(blobs, ticket).await
+    download_blob(endpoint, store, ticket, Some(MAX_CONTROL_BLOB_SIZE))
+        .await
+        .map_err(bundle_unavailable)?;
+    let mut bundle = parse_bundle_from_blob(blobs, ticket).await?;
+
+    let downloader = store.downloader(endpoint);
+    for page in std::mem::take(&mut bundle.manifest_pages) {
+        let files = fetch_manifest_page(&downloader, store, ticket, &page).await?;
+        bundle.metadata.files.extend(files);
+    }
+
+    Ok(bundle)
+}
+
+/// Fetches the remaining manifest pages of a share in the background.
+///
+/// Pages are fetched one after another and sent as they arrive, at most one
+/// page ahead of the receiver, so files of earlier pages can be downloaded
+/// while later pages are still being fetched. Fetching stops at the first
+/// page that fails. Pages are control blobs, so they are fetched on the
+/// control lane ahead of file data.
+fn spawn_manifest_page_fetcher(
+    lanes: DownloadLanes,
+    store: MemStore,
+    ticket: BlobTicket,
+    pages: Vec<String>,
+) -> mpsc::Receiver<Result<Vec<FileInfo>>> {
+    let (sender, receiver) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        for page in pages {
+            let lane = lanes.control();
+            let result = fetch_manifest_page(lane.downloader(), &store, &ticket, &page).await;
+            drop(lane);
+            let failed = result.is_err();
+            if sender.send(result).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Downloads a manifest page from the share's provider and parses its files.
+async fn fetch_manifest_page(
+    downloader: &Downloader,
+    store: &Store,
+    ticket: &BlobTicket,
+    page_hash: &str,
+) -> Result<Vec<FileInfo>> {
+    let hash: Hash = page_hash
+        .parse()
+        .map_err(|error| anyhow::anyhow!("Invalid manifest page hash: {}", error))?;
+
+    stream_download(
+        downloader,
+        None,
+        Some(MAX_CONTROL_BLOB_SIZE),
+        hash,
+        ticket.addr().id,
+        |_| {},
+    )
+    .await
+    .map_err(|error| anyhow::anyhow!("Failed to fetch manifest page: {}", error))?;
+    let bytes = store
+        .get_bytes(hash)
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to read manifest page: {}", error))?;
+
+    serde_json::from_slice(&bytes)
+        .map_err(|error| anyhow::anyhow!("Invalid manifest page: {}", error))
 }
 
 /// Establishes a P2P connection to the node specified in the ticket.
@@ -844,53 +4132,309 @@ async fn establish_connection(endpoint: &Endpoint, ticket: &BlobTicket) -> Resul
     endpoint
         .connect(ticket.addr().clone(), iroh_blobs::protocol::ALPN)
         .await
-        .map_err(|error| anyhow::anyhow!("Failed to establish connection: {}", error))
+        .map_err(|error| DownloadError::PeerOffline(error.to_string()).into())
 }
 
 /// Downloads a blob from a peer into the local store.
-async fn download_blob(endpoint: &Endpoint, store: &MemStore, ticket: &BlobTicket) -> Result<()> {
+///
+/// The download fails once more than `max_size` bytes arrive, if given.
+async fn download_blob(
+    endpoint: &Endpoint,
+    store: &MemStore,
+    ticket: &BlobTicket,
+    max_size: Option<u64>,
+) -> Result<()> {
     let downloader = store.downloader(endpoint);
-    downloader
-        .download(ticket.hash(), Some(ticket.addr().id))
+    stream_download(
+        &downloader,
+        None,
+        max_size,
+        ticket.hash(),
+        ticket.addr().id,
+        |_| {},
+    )
+    .await
+}
+
+/// Explains a failed bundle download once the peer could be reached.
+///
+/// Senders refuse requests for expired and used up shares, which the receiver
+/// cannot tell apart from a share that was never served, so all are mentioned.
+fn bundle_unavailable(error: anyhow::Error) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{}. The share may have expired, already been downloaded once, or is no longer shared",
+        error
+    )
+}
+
+/// Downloads a file blob, resuming from the data already verified in the store.
+///
+/// The downloader only requests the chunk ranges missing locally, so a file
+/// interrupted by a dropped connection continues from the last verified range
+/// instead of starting over. Interrupted downloads are retried as long as they
+/// keep making progress; the partial data also stays in the store, so a
+/// re-issued download of the same share picks up where this one stopped.
+/// Received data counts against the given download rate limit.
+async fn download_file_resumable(
+    store: &Store,
+    downloader: &Downloader,
+    limiter: &BandwidthLimiter,
+    hash: Hash,
+    provider: EndpointId,
+    mut on_progress: impl FnMut(u64),
+) -> Result<()> {
+    let mut attempts_without_progress = 0;
+
+    loop {
+        let local_bytes = local_blob_bytes(store, hash).await;
+        on_progress(local_bytes);
+
+        let result = stream_download(
+            downloader,
+            Some(limiter),
+            None,
+            hash,
+            provider,
+            &mut on_progress,
+        )
+        .await;
+        let error = match result {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        if local_blob_bytes(store, hash).await > local_bytes {
+            attempts_without_progress = 0;
+        } else {
+            attempts_without_progress += 1;
+        }
+        if attempts_without_progress >= DOWNLOAD_RESUME_ATTEMPTS {
+            return Err(error);
+        }
+        tokio::time::sleep(DOWNLOAD_RESUME_DELAY).await;
+    }
+}
+
+/// Returns the number of verified bytes of a blob present in the store.
+async fn local_blob_bytes(store: &Store, hash: Hash) -> u64 {
+    store
+        .remote()
+        .local(hash)
+        .await
+        .map(|local| local.local_bytes())
+        .unwrap_or(0)
+}
+
+/// Runs a download, reporting the bytes present so far including local data.
+///
+/// With a limiter, the stream is paused until the limiter allows the bytes
+/// received since the previous progress report. The first report only sets
+/// the baseline, so data already present locally is not counted. With a
+/// maximum size, the download fails once more bytes than that are present.
+async fn stream_download(
+    downloader: &Downloader,
+    limiter: Option<&BandwidthLimiter>,
+    max_size: Option<u64>,
+    hash: Hash,
+    provider: EndpointId,
+    mut on_progress: impl FnMut(u64),
+) -> Result<()> {
+    #[cfg(feature = "fault-injection")]
+    crate::faults::download_started().await;
+
+    let mut progress = downloader
+        .download(hash, Some(provider))
+        .stream()
         .await
-        .map_err(|error| anyhow::anyhow!("Failed to download blob: {}", error))
+        .map_err(|error| anyhow::anyhow!("Failed to download blob: {}", error))?;
+
+    let mut reported = None;
+    while let Some(item) = progress.next().await {
+        match item {
+            DownloadProgressItem::Progress(processed_bytes) => {
+                if let Some(max_size) = max_size.filter(|&max_size| processed_bytes > max_size) {
+                    anyhow::bail!("Blob is larger than the {} bytes allowed", max_size);
+                }
+                on_progress(processed_bytes);
+                if let (Some(limiter), Some(reported)) = (limiter, reported) {
+                    limiter
+                        .acquire(processed_bytes.saturating_sub(reported))
+                        .await;
+                }
+                #[cfg(feature = "fault-injection")]
+                if let Some(reported) = reported {
+                    crate::faults::bytes_received(processed_bytes.saturating_sub(reported))
+                        .await
+                        .map_err(|error| anyhow::anyhow!("Failed to download blob: {}", error))?;
+                }
+                reported = Some(processed_bytes);
+            }
+            DownloadProgressItem::Error(error) => {
+                anyhow::bail!("Failed to download blob: {}", error)
+            }
+            DownloadProgressItem::DownloadError => {
+                anyhow::bail!("Failed to download blob: no provider could serve it")
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
 }
 
 /// Exports a blob to a temporary file, parses it as JSON, and cleans up.
+///
+/// # Errors
+///
+/// Returns [`NotAGinsengShare`] if the blob is not a Ginseng share bundle.
 async fn parse_bundle_from_blob(blobs: &BlobsProtocol, ticket: &BlobTicket) -> Result<ShareBundle> {
+    if ticket.format() != BlobFormat::Raw {
+        return Err(NotAGinsengShare.into());
+    }
+
     let temp_bundle_path = create_temp_bundle_path(ticket);
     blobs.export(ticket.hash(), &temp_bundle_path).await?;
 
-    let bundle_json = fs::read_to_string(&temp_bundle_path).await?;
-    let bundle = serde_json::from_str(&bundle_json)?;
-
+    let bundle_bytes = fs::read(&temp_bundle_path).await;
     fs::remove_file(&temp_bundle_path).await?;
+
+    let bundle = parse_bundle(&bundle_bytes?)?;
+    ensure_not_expired(&bundle.metadata)?;
     Ok(bundle)
 }
 
-/// Creates a temporary file path for bundle extraction using the ticket hash.
-fn create_temp_bundle_path(ticket: &BlobTicket) -> PathBuf {
-    std::env::temp_dir().join(format!("ginseng_bundle_{}", ticket.hash()))
+/// Parses share bundle JSON, reporting anything else as a non-Ginseng blob.
+fn parse_bundle(bytes: &[u8]) -> Result<ShareBundle> {
+    serde_json::from_slice(bytes).map_err(|_| NotAGinsengShare.into())
+}
+
+/// Fails with [`DownloadError::ShareExpired`] if a share's expiry has passed.
+///
+/// The sender refuses requests for expired shares, but a bundle fetched
+/// earlier may still be in the local store.
+fn ensure_not_expired(metadata: &ShareMetadata) -> Result<()> {
+    match metadata.expires_at {
+        Some(expires_at) if current_unix_time() >= expires_at => {
+            Err(DownloadError::ShareExpired.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the Unix timestamp a share given the lifetime expires at.
+fn expiry_time(expires_in: Option<Duration>) -> Option<u64> {
+    expires_in.map(|expires_in| current_unix_time().saturating_add(expires_in.as_secs()))
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Creates a temporary file path for bundle extraction using the ticket hash.
+fn create_temp_bundle_path(ticket: &BlobTicket) -> PathBuf {
+    std::env::temp_dir().join(format!("ginseng_bundle_{}", ticket.hash()))
+}
+
+/// Determines where to save downloaded files based on the share type.
+///
+/// - Single file: Downloads directory
+/// - Multiple files: Subdirectory in Downloads named by `folder_name_template`
+///   (see [`crate::folder_names`])
+/// - Directory: Named subdirectory in Downloads
+///
+/// If the sending device has its own download folder, that folder is used in
+/// place of Downloads.
+pub(crate) fn determine_target_directory(
+    metadata: &ShareMetadata,
+    device_folder: Option<&Path>,
+    folder_name_template: &str,
+) -> Result<PathBuf> {
+    match device_folder {
+        Some(device_folder) => Ok(target_directory_in(
+            device_folder,
+            metadata,
+            folder_name_template,
+        )),
+        None => {
+            let downloads_dir = get_downloads_directory()?;
+            Ok(target_directory_in(
+                &downloads_dir,
+                metadata,
+                folder_name_template,
+            ))
+        }
+    }
+}
+
+/// Returns the hashes of the downloaded files that were saved.
+///
+/// Files that failed, and files left out until the user decides about a
+/// collision, are not saved yet and keep their blobs for a later attempt.
+fn saved_file_hashes<'a>(
+    files: impl IntoIterator<Item = &'a FileInfo>,
+    failed: &[SkippedFile],
+    collisions: &[FileCollision],
+) -> Vec<String> {
+    files
+        .into_iter()
+        .filter(|file_info| {
+            !failed
+                .iter()
+                .any(|file| file.relative_path == file_info.relative_path)
+                && !collisions.iter().any(|collision| {
+                    collision.hash == file_info.hash
+                        && collision.resolution == CollisionResolution::Asked
+                })
+        })
+        .map(|file_info| file_info.hash.clone())
+        .collect()
+}
+
+/// Removes the blobs of a closed share from the store, stops watching and
+/// accessing its files and forgets it was open.
+///
+/// Does nothing to the store if the share is still open or its blobs were
+/// already removed.
+async fn remove_closed_share(
+    store: &MemStore,
+    shares: &ShareRegistry,
+    source_watchers: &SourceWatchers,
+    access_scopes: &AccessScopes,
+    saved_shares: &SavedShares,
+    share_id: &str,
+) {
+    saved_shares.remove(share_id).await;
+    if let Some(blobs) = shares.take_closed_blobs(share_id).await {
+        drop_blob_tags(store, &blobs).await.ok();
+        let hashes: Vec<Hash> = blobs.keys().filter_map(|hash| hash.parse().ok()).collect();
+        store.blobs().delete(hashes).await.ok();
+    }
+    source_watchers.remove(share_id);
+    access_scopes.remove(share_id);
 }
 
-/// Determines where to save downloaded files based on the share type.
+/// Determines where to save downloaded files within a base directory.
 ///
-/// - Single file: Downloads directory
-/// - Multiple files: Timestamped subdirectory in Downloads
-/// - Directory: Named subdirectory in Downloads
-fn determine_target_directory(metadata: &ShareMetadata) -> Result<PathBuf> {
-    let downloads_dir = get_downloads_directory()?;
-
-    let target_dir = match &metadata.share_type {
-        ShareType::SingleFile => downloads_dir,
-        ShareType::MultipleFiles => {
-            let timestamp = chrono::Utc::now().timestamp();
-            downloads_dir.join(format!("ginseng_files_{}", timestamp))
+/// Follows the same layout as [`determine_target_directory`] does in Downloads.
+pub(crate) fn target_directory_in(
+    base: &Path,
+    metadata: &ShareMetadata,
+    folder_name_template: &str,
+) -> PathBuf {
+    match &metadata.share_type {
+        ShareType::SingleFile => base.to_path_buf(),
+        ShareType::MultipleFiles => unique_folder(
+            base,
+            &folder_name(folder_name_template, chrono::Local::now()),
+        ),
+        ShareType::Directory { name } | ShareType::Archive { name } => {
+            base.join(sanitize_component(name))
         }
-        ShareType::Directory { name } => downloads_dir.join(name),
-    };
-
-    Ok(target_dir)
+    }
 }
 
 /// Downloads all files referenced in the metadata to the target directory.
@@ -898,81 +4442,319 @@ fn determine_target_directory(metadata: &ShareMetadata) -> Result<PathBuf> {
 /// Uses a two-phase approach:
 /// 1. Download all file blobs to ensure they're available
 /// 2. Export all files to their target locations with proper directory structure
-async fn download_all_files(
+///
+/// If either phase fails, files already exported and directories created for
+/// them are removed again. Files whose data goes past their listed size, or
+/// whose paths would leave the target directory, are refused, so a peer
+/// cannot write more or elsewhere than the metadata it sent says.
+pub(crate) async fn download_all_files(
     endpoint: &Endpoint,
     blobs: &BlobsProtocol,
     metadata: &ShareMetadata,
     target_dir: &Path,
     ticket: &BlobTicket,
+    limiter: &BandwidthLimiter,
 ) -> Result<()> {
+    let mut cleanup = TransferCleanup::new(blobs.store().clone());
+    if !target_dir.exists() {
+        cleanup.track_directory(target_dir.to_path_buf());
+    }
+
+    let failed = download_and_export_files(
+        endpoint,
+        blobs,
+        metadata,
+        target_dir,
+        ticket,
+        limiter,
+        &mut cleanup,
+    )
+    .await;
+
+    match failed.first() {
+        None => {
+            cleanup.disarm();
+            Ok(())
+        }
+        Some(first) => {
+            let error = anyhow::anyhow!("{}", first.error);
+            cleanup.run().await;
+            Err(error)
+        }
+    }
+}
+
+/// Downloads all files of a share and exports them, recording each export
+/// with the cleanup guard so partial downloads can be removed.
+///
+/// A file that fails is removed again and does not stop the other files.
+/// Returns the files that failed, with the reason.
+async fn download_and_export_files(
+    endpoint: &Endpoint,
+    blobs: &BlobsProtocol,
+    metadata: &ShareMetadata,
+    target_dir: &Path,
+    ticket: &BlobTicket,
+    limiter: &BandwidthLimiter,
+    cleanup: &mut TransferCleanup,
+) -> Vec<SkippedFile> {
     let downloader = blobs.store().downloader(endpoint);
+    let mut failed = Vec::new();
 
     for file_info in &metadata.files {
-        let file_hash: Hash = file_info.hash.parse::<Hash>().map_err(|error| {
-            anyhow::anyhow!("Invalid hash for file '{}': {}", file_info.name, error)
-        })?;
+        let result = async {
+            validate_relative_path(&file_info.relative_path)?;
+            let file_hash: Hash = file_info.hash.parse::<Hash>().map_err(|error| {
+                anyhow::anyhow!("Invalid hash for file '{}': {}", file_info.name, error)
+            })?;
 
-        downloader
-            .download(file_hash, Some(ticket.addr().id))
-            .await
-            .map_err(|error| {
-                anyhow::anyhow!(
-                    "Failed to download file '{}' ({}): {}",
+            // Blobs are never larger than the file they hold, compressed or not
+            let oversized = CancellationToken::new();
+            let download = download_file_resumable(
+                blobs.store(),
+                &downloader,
+                limiter,
+                file_hash,
+                ticket.addr().id,
+                |bytes| {
+                    if bytes > file_info.size {
+                        oversized.cancel();
+                    }
+                },
+            );
+            tokio::select! {
+                result = download => result.map_err(|error| {
+                    anyhow::anyhow!(
+                        "Failed to download file '{}' ({}): {}",
+                        file_info.name,
+                        file_hash,
+                        error
+                    )
+                })?,
+                () = oversized.cancelled() => anyhow::bail!(
+                    "File '{}' is larger than the {} bytes the sender listed",
                     file_info.name,
-                    file_hash,
-                    error
-                )
-            })?;
-    }
+                    file_info.size
+                ),
+            }
 
-    for file_info in &metadata.files {
-        export_individual_file(blobs, file_info, target_dir)
-            .await
-            .map_err(|error| {
+            let target_file_path = local_path(target_dir, &file_info.relative_path);
+            let existed = target_file_path.exists();
+            cleanup.track_file(target_file_path.clone(), file_info.size);
+
+            let exported = export_individual_file(
+                blobs,
+                &metadata.share_type,
+                file_info,
+                target_dir,
+                |_, _| {},
+            )
+            .await;
+            if exported.is_err() && !existed {
+                let _ = fs::remove_file(&target_file_path).await;
+            }
+            exported.map_err(|error| {
                 anyhow::anyhow!("Failed to export file '{}': {}", file_info.name, error)
-            })?;
+            })
+        }
+        .await;
+
+        if let Err(error) = result {
+            failed.push(SkippedFile {
+                relative_path: file_info.relative_path.clone(),
+                error: error.to_string(),
+            });
+        }
     }
 
-    Ok(())
+    failed
+}
+
+/// Returns the status of a downloaded file, which is skipped if the collision
+/// with an existing file left it out.
+///
+/// An archive counts as completed once unpacked, even if some of the files in
+/// it were left out.
+fn saved_file_status(file_info: &FileInfo, collisions: &[FileCollision]) -> FileStatus {
+    let left_out = collisions.iter().any(|collision| {
+        collision.relative_path == file_info.relative_path && collision.written_to().is_none()
+    });
+    if left_out {
+        FileStatus::Skipped
+    } else {
+        FileStatus::Completed
+    }
 }
 
 /// Exports a single file from the blob store to its target location.
 ///
-/// Creates necessary parent directories and exports the file using
-/// its relative path to maintain directory structure.
+/// Creates necessary parent directories and writes the file using its
+/// relative path to maintain directory structure. Compressed blobs are
+/// decompressed as they are written, and the archive of an archive share
+/// is unpacked into the target directory. `on_write` is called with the bytes
+/// written and the time spent writing as the file is written.
 async fn export_individual_file(
     blobs: &BlobsProtocol,
+    share_type: &ShareType,
     file_info: &FileInfo,
     target_dir: &Path,
+    on_write: impl FnMut(u64, Duration),
 ) -> Result<()> {
+    let target_file_path = local_path(target_dir, &file_info.relative_path);
+    export_file_to(
+        blobs,
+        share_type,
+        file_info,
+        target_dir,
+        &target_file_path,
+        CollisionPolicy::Overwrite,
+        on_write,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Exports a single file from the blob store to the given path.
+///
+/// Like [`export_individual_file`], but the file is written to
+/// `target_file_path` instead of its own path in the target directory, e.g.
+/// under another name because a file of that name already exists. The written
+/// file is checked before it replaces an existing one (see [`write_file`]), so
+/// disk corruption is caught instead of leaving a damaged copy behind.
+async fn export_file_to(
+    blobs: &BlobsProtocol,
+    share_type: &ShareType,
+    file_info: &FileInfo,
+    target_dir: &Path,
+    target_file_path: &Path,
+    collisions: CollisionPolicy,
+    on_write: impl FnMut(u64, Duration),
+) -> Result<Vec<FileCollision>> {
     let file_hash: Hash = file_info.hash.parse::<Hash>().map_err(|error| {
         anyhow::anyhow!("Invalid hash for file '{}': {}", file_info.name, error)
     })?;
-    let target_file_path = target_dir.join(&file_info.relative_path);
+    if let ShareType::Archive { .. } = share_type {
+        // Entries are found by their offsets, so the archive is read whole;
+        // the sender builds it in memory as well
+        let blob = blobs.store().get_bytes(file_hash).await.map_err(|error| {
+            anyhow::anyhow!("Failed to read '{}': {}", file_info.relative_path, error)
+        })?;
+        let archive = decompress_blocking(file_info.compression, blob, file_info.size)
+            .await
+            .map_err(|error| {
+                anyhow::anyhow!("Failed to read '{}': {}", file_info.relative_path, error)
+            })?;
+        return unpack_archive(&archive, &file_info.hash, target_dir, collisions, on_write)
+            .await
+            .map_err(|error| anyhow::anyhow!("Failed to unpack '{}': {}", file_info.name, error));
+    }
 
-    ensure_parent_directory_exists(&target_file_path)
+    ensure_parent_directory_exists(target_file_path)
         .await
         .map_err(|error| {
-            anyhow::anyhow!(
+            let message = format!(
                 "Failed to create directory for '{}': {}",
-                file_info.relative_path,
+                file_info.relative_path, error
+            );
+            // Keeps the I/O error in the chain, so callers can tell e.g. a full disk
+            error.context(message)
+        })?;
+    let content = decompress_reader(
+        file_info.compression,
+        blobs.store().reader(file_hash),
+        file_info.size,
+    );
+    // A blob hash is the BLAKE3 hash of its content, so uncompressed files
+    // can be checked against the hash the sender recorded
+    let expected_hash = file_info
+        .compression
+        .is_none()
+        .then(|| blake3::Hash::from_bytes(*file_hash.as_bytes()));
+    write_file(target_file_path, content, expected_hash, on_write)
+        .await
+        .map_err(|error| {
+            let message = format!(
+                "Failed to export '{}' to '{}': {}",
+                file_info.name,
+                target_file_path.display(),
                 error
-            )
+            );
+            error.context(message)
         })?;
 
-    blobs
-        .export(file_hash, &target_file_path)
+    Ok(Vec::new())
+}
+
+/// Writes every file of an archive into the target directory.
+///
+/// The paths stored in the archive are placed with the same rules as the
+/// paths of regular shares, and files that already exist are handled by the
+/// collision policy. `on_write` is called with the bytes written across all
+/// files and the time spent writing.
+///
+/// # Returns
+///
+/// The collisions with existing files, reported with the hash of the archive
+async fn unpack_archive(
+    archive: &[u8],
+    archive_hash: &str,
+    target_dir: &Path,
+    collisions: CollisionPolicy,
+    mut on_write: impl FnMut(u64, Duration),
+) -> Result<Vec<FileCollision>> {
+    let started = Instant::now();
+    let mut written = 0;
+    let mut file_collisions = Vec::new();
+
+    for file in list_files(archive)? {
+        let mut target_file_path = local_path(target_dir, &file.relative_path);
+        if let Some(resolution) = resolve_collision(&target_file_path, collisions) {
+            let collision = FileCollision {
+                relative_path: file.relative_path.clone(),
+                hash: archive_hash.to_string(),
+                existing_path: target_file_path.clone(),
+                resolution,
+            };
+            let written_to = collision.written_to().map(Path::to_path_buf);
+            file_collisions.push(collision);
+            match written_to {
+                Some(path) => target_file_path = path,
+                None => continue,
+            }
+        }
+        ensure_parent_directory_exists(&target_file_path).await?;
+
+        let stats = write_file(
+            &target_file_path,
+            &archive[file.content],
+            None,
+            |bytes, _| on_write(written + bytes, started.elapsed()),
+        )
         .await
         .map_err(|error| {
             anyhow::anyhow!(
-                "Failed to export '{}' to '{}': {}",
-                file_info.name,
+                "Failed to write '{}' to '{}': {}",
+                file.relative_path,
                 target_file_path.display(),
                 error
             )
         })?;
+        written += stats.bytes;
+    }
 
-    Ok(())
+    Ok(file_collisions)
+}
+
+/// Determines where a raw blob is written.
+///
+/// Existing directories receive the blob as a file named after its hash;
+/// any other target is used as the file path itself.
+fn raw_blob_target_path(target: &Path, hash: &Hash) -> PathBuf {
+    if target.is_dir() {
+        target.join(hash.to_string())
+    } else {
+        target.to_path_buf()
+    }
 }
 
 /// Ensures that the parent directory of a file path exists.
@@ -985,19 +4767,6 @@ async fn ensure_parent_directory_exists(file_path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Formats node information for display, including ID, addresses, and relay.
-fn format_node_info(endpoint: &Endpoint) -> Result<String> {
-    let endpoint_id = endpoint.id();
-    let endpoint_addr = endpoint.addr();
-
-    Ok(format!(
-        "Endpoint ID: {}\nDirect addresses: {:?}\nRelay URL: {:?}",
-        endpoint_id,
-        endpoint_addr.ip_addrs().collect::<Vec<_>>(),
-        endpoint_addr.relay_urls().next()
-    ))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1009,9 +4778,14 @@ mod tests {
             files: vec![],
             share_type: ShareType::SingleFile,
             total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
         };
 
-        let result = determine_target_directory(&metadata);
+        let result = determine_target_directory(&metadata, None, DEFAULT_TEMPLATE);
         assert!(result.is_ok());
     }
 
@@ -1021,11 +4795,21 @@ mod tests {
             files: vec![],
             share_type: ShareType::MultipleFiles,
             total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
         };
 
-        let result = determine_target_directory(&metadata);
+        let result = determine_target_directory(&metadata, None, DEFAULT_TEMPLATE);
         assert!(result.is_ok());
-        assert!(result.unwrap().to_string_lossy().contains("ginseng_files_"));
+        assert!(result
+            .unwrap()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("Ginseng "));
     }
 
     #[test]
@@ -1036,13 +4820,85 @@ mod tests {
                 name: "test_folder".to_string(),
             },
             total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
         };
 
-        let result = determine_target_directory(&metadata);
+        let result = determine_target_directory(&metadata, None, DEFAULT_TEMPLATE);
         assert!(result.is_ok());
         assert!(result.unwrap().to_string_lossy().ends_with("test_folder"));
     }
 
+    #[test]
+    fn test_determine_target_directory_uses_device_folder() {
+        let metadata = ShareMetadata {
+            files: vec![],
+            share_type: ShareType::Directory {
+                name: "reports".to_string(),
+            },
+            total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        };
+
+        let device_folder = PathBuf::from("/transfers/work");
+        let result =
+            determine_target_directory(&metadata, Some(&device_folder), DEFAULT_TEMPLATE).unwrap();
+        assert_eq!(result, device_folder.join("reports"));
+    }
+
+    #[test]
+    fn test_selected_files_by_path_and_hash() {
+        let file = FileInfo {
+            name: "a.txt".to_string(),
+            relative_path: "docs/a.txt".to_string(),
+            size: 1,
+            hash: "abc".to_string(),
+            compression: Compression::None,
+        };
+
+        let by_path = SelectedFiles::Paths(HashSet::from(["docs/a.txt".to_string()]));
+        let by_hash = SelectedFiles::Hashes(HashSet::from(["abc".to_string()]));
+        let other = SelectedFiles::Hashes(HashSet::from(["docs/a.txt".to_string()]));
+
+        assert!(by_path.contains(&file));
+        assert!(by_hash.contains(&file));
+        assert!(!other.contains(&file));
+    }
+
+    #[test]
+    fn test_saved_file_hashes_leave_out_unsaved_files() {
+        let file = |path: &str| FileInfo {
+            name: path.to_string(),
+            relative_path: path.to_string(),
+            size: 1,
+            hash: format!("hash-{}", path),
+            compression: Compression::None,
+        };
+        let files = vec![file("a.txt"), file("b.txt"), file("c.txt")];
+        let failed = vec![SkippedFile {
+            relative_path: "b.txt".to_string(),
+            error: "peer went away".to_string(),
+        }];
+        let collisions = vec![FileCollision {
+            relative_path: "c.txt".to_string(),
+            hash: "hash-c.txt".to_string(),
+            existing_path: PathBuf::from("/downloads/c.txt"),
+            resolution: CollisionResolution::Asked,
+        }];
+
+        assert_eq!(
+            saved_file_hashes(&files, &failed, &collisions),
+            vec!["hash-a.txt".to_string()]
+        );
+    }
+
     #[test]
     fn test_create_temp_bundle_path() {
         let ticket_str = "blobafkfrvhakfhakfhakfhakfhakfhakfhfkafkafkafka";
@@ -1061,10 +4917,188 @@ mod tests {
         assert!(path.to_string_lossy().contains("ginseng_bundle_"));
     }
 
+    #[test]
+    fn test_parse_bundle_rejects_non_ginseng_blobs() {
+        let error = parse_bundle(b"\x89PNG raw image bytes").unwrap_err();
+        assert!(error.downcast_ref::<NotAGinsengShare>().is_some());
+
+        let error = parse_bundle(br#"{"unrelated":"json"}"#).unwrap_err();
+        assert!(error.downcast_ref::<NotAGinsengShare>().is_some());
+    }
+
+    #[test]
+    fn test_parse_bundle_accepts_share_bundle() {
+        let bundle = ShareBundle {
+            metadata: ShareMetadata {
+                files: vec![],
+                share_type: ShareType::SingleFile,
+                total_size: 0,
+                skipped_files: vec![],
+                custom_fields: BTreeMap::new(),
+                expires_at: None,
+                share_id: None,
+                content_root: None,
+            },
+            metadata_hash: "hash".to_string(),
+            manifest_pages: vec![],
+        };
+        let json = serde_json::to_vec(&bundle).unwrap();
+
+        assert_eq!(parse_bundle(&json).unwrap().metadata, bundle.metadata);
+    }
+
+    #[test]
+    fn test_ensure_not_expired() {
+        let metadata = |expires_at| ShareMetadata {
+            files: vec![],
+            share_type: ShareType::SingleFile,
+            total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at,
+            share_id: None,
+            content_root: None,
+        };
+
+        assert!(ensure_not_expired(&metadata(None)).is_ok());
+        assert!(ensure_not_expired(&metadata(expiry_time(Some(Duration::from_secs(60))))).is_ok());
+        assert!(matches!(
+            ensure_not_expired(&metadata(Some(0)))
+                .unwrap_err()
+                .downcast_ref::<DownloadError>(),
+            Some(DownloadError::ShareExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_store_manifest_pages_splits_large_file_lists() {
+        let blobs = BlobsProtocol::new(&MemStore::new(), None);
+        let files: Vec<FileInfo> = (0..MANIFEST_PAGE_SIZE * 2 + 1)
+            .map(|i| FileInfo {
+                name: format!("{}.txt", i),
+                relative_path: format!("{}.txt", i),
+                size: 1,
+                hash: Hash::new(i.to_string()).to_string(),
+                compression: Compression::None,
+            })
+            .collect();
+
+        let (first_page, pages) = store_manifest_pages(&blobs, &files).await.unwrap();
+        assert_eq!(first_page.len(), MANIFEST_PAGE_SIZE);
+        assert_eq!(pages.len(), 2);
+
+        let last_page = blobs
+            .store()
+            .get_bytes(pages[1].parse::<Hash>().unwrap())
+            .await
+            .unwrap();
+        let last_page: Vec<FileInfo> = serde_json::from_slice(&last_page).unwrap();
+        assert_eq!(last_page, files[MANIFEST_PAGE_SIZE * 2..]);
+
+        let (first_page, pages) = store_manifest_pages(&blobs, &files[..3]).await.unwrap();
+        assert_eq!(first_page.len(), 3);
+        assert!(pages.is_empty());
+    }
+
+    #[test]
+    fn test_raw_blob_target_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let hash = iroh_blobs::Hash::new(b"raw blob");
+
+        assert_eq!(
+            raw_blob_target_path(temp_dir.path(), &hash),
+            temp_dir.path().join(hash.to_string())
+        );
+
+        let file_path = temp_dir.path().join("blob.bin");
+        assert_eq!(raw_blob_target_path(&file_path, &hash), file_path);
+    }
+
+    #[test]
+    fn test_check_share_size() {
+        assert!(check_share_size(u64::MAX, None).is_ok());
+        assert!(check_share_size(100, Some(100)).is_ok());
+
+        let error = check_share_size(101, Some(100)).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ShareTooLarge>(),
+            Some(&ShareTooLarge {
+                total_size: 101,
+                max_size: 100,
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_selected_files() {
+        let root = PathBuf::from("/share/photos");
+        let single = PathBuf::from("/share/notes.txt");
+        let file_paths = vec![
+            (root.join("beach.jpg"), root.clone()),
+            (root.join("2024").join("city.jpg"), root.clone()),
+            (root.join("2024").join("park.jpg"), root.clone()),
+            (single.clone(), single.clone()),
+        ];
+        let include: HashSet<String> = [
+            "beach.jpg".to_string(),
+            Path::new("2024")
+                .join("park.jpg")
+                .to_string_lossy()
+                .to_string(),
+        ]
+        .into_iter()
+        .collect();
+
+        let selected = filter_selected_files(file_paths, &include);
+        let selected: Vec<&PathBuf> = selected.iter().map(|(path, _)| path).collect();
+
+        assert_eq!(
+            selected,
+            vec![
+                &root.join("beach.jpg"),
+                &root.join("2024").join("park.jpg"),
+                &single
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), b"abc").unwrap();
+        std::fs::write(nested.join("b.txt"), b"hello").unwrap();
+
+        let files = scan_directory(temp_dir.path()).await.unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].relative_path, "a.txt");
+        assert_eq!(files[0].size, 3);
+        assert_eq!(files[1].size, 5);
+    }
+
+    #[test]
+    fn test_validate_custom_fields() {
+        let mut fields = BTreeMap::new();
+        fields.insert("project".to_string(), "ginseng".to_string());
+        assert!(validate_custom_fields(&fields).is_ok());
+
+        fields.insert(" ".to_string(), "value".to_string());
+        assert!(validate_custom_fields(&fields).is_err());
+
+        let too_long = BTreeMap::from([("order".to_string(), "x".repeat(2000))]);
+        assert!(validate_custom_fields(&too_long).is_err());
+    }
+
     #[test]
     fn test_parse_ticket_invalid() {
         let result = parse_ticket("invalid_ticket");
         assert!(result.is_err());
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<DownloadError>(),
+            Some(DownloadError::InvalidTicket(_))
+        ));
     }
 
     #[tokio::test]
@@ -1077,6 +5111,26 @@ mod tests {
         assert!(!result.unwrap().is_empty());
     }
 
+    #[tokio::test]
+    async fn test_store_file_as_blob_keeps_snapshot() {
+        let core = GinsengCore::new().await.unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("test.txt");
+        tokio::fs::write(&temp_file, "original").await.unwrap();
+
+        let (hash, size, compression) =
+            store_file_as_blob(&core.blobs, &temp_file, Compression::None)
+                .await
+                .unwrap();
+        tokio::fs::write(&temp_file, "changed later").await.unwrap();
+
+        let hash: Hash = hash.parse().unwrap();
+        let bytes = core.store.get_bytes(hash).await.unwrap();
+        assert_eq!(size, 8);
+        assert_eq!(compression, Compression::None);
+        assert_eq!(bytes.as_ref(), b"original");
+    }
+
     #[tokio::test]
     async fn test_create_single_file_metadata_with_temp_file() {
         let core = GinsengCore::new().await.unwrap();
@@ -1084,7 +5138,7 @@ mod tests {
         let temp_file = temp_dir.path().join("test.txt");
         tokio::fs::write(&temp_file, "test content").await.unwrap();
 
-        let result = create_single_file_metadata(&core.blobs, &temp_file).await;
+        let result = create_single_file_metadata(&core.blobs, &temp_file, Compression::None).await;
         assert!(result.is_ok());
 
         let metadata = result.unwrap();
@@ -1106,7 +5160,13 @@ mod tests {
         tokio::fs::write(&file1, "content1").await.unwrap();
         tokio::fs::write(&file2, "content2").await.unwrap();
 
-        let result = create_directory_metadata(&core.blobs, temp_dir.path()).await;
+        let result = create_directory_metadata(
+            &core.blobs,
+            temp_dir.path(),
+            Compression::None,
+            &ExcludeRules::default(),
+        )
+        .await;
         assert!(result.is_ok());
 
         let metadata = result.unwrap();
@@ -1114,4 +5174,257 @@ mod tests {
         assert_eq!(metadata.files.len(), 2);
         assert_eq!(metadata.total_size, 16);
     }
+
+    #[tokio::test]
+    async fn test_archive_share_is_unpacked_on_export() {
+        let core = GinsengCore::new().await.unwrap();
+        let source = TempDir::new().unwrap();
+        let sub_dir = source.path().join("subdir");
+        tokio::fs::create_dir(&sub_dir).await.unwrap();
+        tokio::fs::write(source.path().join("a.txt"), "first")
+            .await
+            .unwrap();
+        tokio::fs::write(sub_dir.join("b.txt"), "second".repeat(100))
+            .await
+            .unwrap();
+
+        let mut archive = ArchiveBuilder::new();
+        let files = collect_file_paths(
+            &[source.path().to_path_buf()],
+            &ExcludeRules::default(),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        for (file_path, base_path) in files {
+            add_to_archive(&mut archive, &file_path, &base_path)
+                .await
+                .unwrap();
+        }
+        let file_info = store_archive(&core.blobs, archive, "source", Compression::Zstd)
+            .await
+            .unwrap();
+        assert_eq!(file_info.compression, Compression::Zstd);
+
+        let share_type = ShareType::Archive {
+            name: "source".to_string(),
+        };
+        let target = TempDir::new().unwrap();
+        export_individual_file(
+            &core.blobs,
+            &share_type,
+            &file_info,
+            target.path(),
+            |_, _| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("a.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("subdir").join("b.txt")).unwrap(),
+            "second".repeat(100)
+        );
+        assert!(!target.path().join(&file_info.relative_path).exists());
+
+        std::fs::write(target.path().join("a.txt"), "mine").unwrap();
+        let collisions = export_file_to(
+            &core.blobs,
+            &share_type,
+            &file_info,
+            target.path(),
+            &target.path().join(&file_info.relative_path),
+            CollisionPolicy::Skip,
+            |_, _| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("a.txt")).unwrap(),
+            "mine"
+        );
+        assert_eq!(collisions.len(), 2);
+        assert!(collisions.iter().all(|collision| {
+            collision.hash == file_info.hash && collision.resolution == CollisionResolution::Skipped
+        }));
+    }
+
+    /// Waits until an endpoint knows its local addresses, so tickets created
+    /// afterwards reach it without a relay.
+    async fn wait_for_direct_addresses(core: &GinsengCore) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while core.endpoint.addr().ip_addrs().next().is_none() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("endpoint has no direct addresses");
+    }
+
+    #[tokio::test]
+    async fn test_single_use_share_refuses_second_redemption() {
+        let sender = GinsengCore::new().await.unwrap();
+        wait_for_direct_addresses(&sender).await;
+        let source = TempDir::new().unwrap();
+        let file_path = source.path().join("notes.txt");
+        tokio::fs::write(&file_path, "only once").await.unwrap();
+        let share = sender
+            .share_files_cli(
+                vec![file_path],
+                BTreeMap::new(),
+                ShareOptions {
+                    single_use: true,
+                    ..ShareOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let first = GinsengCore::new().await.unwrap();
+        let target = TempDir::new().unwrap();
+        first
+            .download_files_with_progress(
+                Channel::new(|_| Ok(())),
+                share.ticket.clone(),
+                Some(target.path().to_path_buf()),
+            )
+            .await
+            .unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            sender.shares.closed(&share.share_id),
+        )
+        .await
+        .expect("single-use share was not used up");
+        assert!(!sender.shares.is_open(&share.share_id).await);
+
+        // Another receiver, since the first one has the blobs in its own store
+        let second = GinsengCore::new().await.unwrap();
+        let target = TempDir::new().unwrap();
+        let redeemed_again = tokio::time::timeout(
+            Duration::from_secs(30),
+            second.download_files_with_progress(
+                Channel::new(|_| Ok(())),
+                share.ticket,
+                Some(target.path().to_path_buf()),
+            ),
+        )
+        .await
+        .expect("second redemption did not finish");
+        assert!(redeemed_again.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delta_share_reuses_blobs_of_unchanged_files() {
+        let core = GinsengCore::new().await.unwrap();
+        let source = TempDir::new().unwrap();
+        let file_path = source.path().join("notes.txt");
+        tokio::fs::write(&file_path, "first").await.unwrap();
+        let options = ShareOptions {
+            delta: true,
+            ..ShareOptions::default()
+        };
+
+        let (stored, reused) = core
+            .store_share_file(&file_path, source.path(), &options)
+            .await
+            .unwrap();
+        assert!(!reused);
+
+        // Blobs are only reused while a share keeps them in the store
+        let (_, reused) = core
+            .store_share_file(&file_path, source.path(), &options)
+            .await
+            .unwrap();
+        assert!(!reused);
+        let metadata = ShareMetadata {
+            files: vec![stored.clone()],
+            share_type: ShareType::SingleFile,
+            total_size: stored.size,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        };
+        core.shares
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata,
+                false,
+            )
+            .await;
+
+        let (again, reused) = core
+            .store_share_file(&file_path, source.path(), &options)
+            .await
+            .unwrap();
+        assert!(reused);
+        assert_eq!(again, stored);
+
+        tokio::fs::write(&file_path, "changed").await.unwrap();
+        let (changed, reused) = core
+            .store_share_file(&file_path, source.path(), &options)
+            .await
+            .unwrap();
+        assert!(!reused);
+        assert_ne!(changed.hash, stored.hash);
+    }
+
+    #[tokio::test]
+    async fn test_published_share_carries_its_id_to_receivers() {
+        let core = GinsengCore::new().await.unwrap();
+        let hash = store_json_as_blob(&core.blobs, "hello").await.unwrap();
+        let metadata = ShareMetadata {
+            files: vec![FileInfo {
+                name: "hello.txt".to_string(),
+                relative_path: "hello.txt".to_string(),
+                size: 5,
+                hash,
+                compression: Compression::None,
+            }],
+            share_type: ShareType::SingleFile,
+            total_size: 5,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        };
+
+        let share = core
+            .publish_share(metadata, &[], &ShareOptions::default(), None)
+            .await
+            .unwrap();
+        let ticket = parse_ticket(&share.ticket).unwrap();
+        let bundle = parse_bundle_from_blob(&core.blobs, &ticket).await.unwrap();
+        assert_eq!(bundle.metadata.share_id, Some(share.share_id.clone()));
+        assert_eq!(bundle.metadata.content_root, Some(share.content_root));
+        assert!(core.shares.is_open(&share.share_id).await);
+    }
+
+    #[test]
+    fn test_content_root_covers_file_hashes_in_order() {
+        let file = |content: &[u8]| FileInfo {
+            name: "file.txt".to_string(),
+            relative_path: "file.txt".to_string(),
+            size: content.len() as u64,
+            hash: Hash::new(content).to_string(),
+            compression: Compression::None,
+        };
+        let (a, b, c) = (file(b"a"), file(b"b"), file(b"c"));
+
+        assert_eq!(content_root(std::slice::from_ref(&a)), a.hash);
+        assert_eq!(content_root(&[]), Hash::new(b"").to_string());
+
+        let root = content_root(&[a.clone(), b.clone(), c.clone()]);
+        assert_eq!(root, content_root(&[a.clone(), b.clone(), c.clone()]));
+        assert_ne!(root, content_root(&[b.clone(), a.clone(), c.clone()]));
+        assert_ne!(root, content_root(&[a, b]));
+    }
 }
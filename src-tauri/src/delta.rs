@@ -0,0 +1,159 @@
+//! Reuse of stored blobs when files are shared again
+//!
+//! Re-sharing a directory after editing a few of its files would otherwise
+//! read and hash every file again. Every file stored for a share is recorded
+//! here with the size and modification time it had when it was read. A delta
+//! share looks its files up first: a file that still has the same size and
+//! modification time, and is shared with the same compression, reuses the
+//! blob stored earlier instead of being read again.
+//!
+//! The cache lives only as long as the process and is deliberately not saved
+//! to disk. A hash alone cannot be served: the blob it names must still be in
+//! the store, and the store is in memory, so after a restart every file has to
+//! be read into it again anyway. For the same reason, a file is only reused
+//! while a share that is still open keeps its blob in the store; otherwise it
+//! is read again, and the new hash is checked against the recorded one.
+
+use crate::compression::Compression;
+use crate::core::FileInfo;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Size and modification time identifying a version of a file
+pub type FileVersion = (u64, SystemTime);
+
+/// A file as it was stored for a share
+#[derive(Debug, Clone, PartialEq)]
+struct StoredFile {
+    /// Version of the file whose content was stored
+    version: FileVersion,
+    /// Compression the share asked for
+    requested: Compression,
+    /// Hash of the stored blob
+    hash: String,
+    /// How the stored blob is compressed
+    compression: Compression,
+}
+
+/// Thread-safe record of the files stored for shares in this process, by
+/// absolute path
+#[derive(Debug, Clone, Default)]
+pub struct IngestCache {
+    files: Arc<Mutex<HashMap<PathBuf, StoredFile>>>,
+}
+
+impl IngestCache {
+    /// Creates an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the blob a file was stored as
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Absolute path of the file
+    /// * `version` - Version of the file whose content was stored
+    /// * `requested` - Compression the share asked for
+    /// * `file_info` - The stored file, with the hash and compression of its blob
+    pub fn record(
+        &self,
+        file_path: &Path,
+        version: FileVersion,
+        requested: Compression,
+        file_info: &FileInfo,
+    ) {
+        if let Ok(mut files) = self.files.lock() {
+            files.insert(
+                file_path.to_path_buf(),
+                StoredFile {
+                    version,
+                    requested,
+                    hash: file_info.hash.clone(),
+                    compression: file_info.compression,
+                },
+            );
+        }
+    }
+
+    /// Returns the blob a file was stored as, if it has not changed since
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Absolute path of the file
+    /// * `version` - Current version of the file
+    /// * `requested` - Compression the share asks for
+    ///
+    /// # Returns
+    ///
+    /// The hash of the blob and how it is compressed, or None if the file was
+    /// not stored before, has changed, or was stored with other compression
+    pub fn lookup(
+        &self,
+        file_path: &Path,
+        version: FileVersion,
+        requested: Compression,
+    ) -> Option<(String, Compression)> {
+        let files = self.files.lock().ok()?;
+        files
+            .get(file_path)
+            .filter(|stored| stored.version == version && stored.requested == requested)
+            .map(|stored| (stored.hash.clone(), stored.compression))
+    }
+
+    /// Forgets a file, e.g. because its blob is no longer kept in the store
+    pub fn forget(&self, file_path: &Path) {
+        if let Ok(mut files) = self.files.lock() {
+            files.remove(file_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_only_unchanged_files_are_reused() {
+        let cache = IngestCache::new();
+        let path = Path::new("/shared/notes.txt");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let file_info = FileInfo {
+            name: "notes.txt".to_string(),
+            relative_path: "notes.txt".to_string(),
+            size: 10,
+            hash: "hash".to_string(),
+            compression: Compression::None,
+        };
+        cache.record(path, (10, modified), Compression::Zstd, &file_info);
+
+        assert_eq!(
+            cache.lookup(path, (10, modified), Compression::Zstd),
+            Some(("hash".to_string(), Compression::None))
+        );
+        assert_eq!(cache.lookup(path, (11, modified), Compression::Zstd), None);
+        assert_eq!(
+            cache.lookup(
+                path,
+                (10, modified + Duration::from_secs(1)),
+                Compression::Zstd
+            ),
+            None
+        );
+        assert_eq!(cache.lookup(path, (10, modified), Compression::None), None);
+        assert_eq!(
+            cache.lookup(
+                Path::new("/shared/other.txt"),
+                (10, modified),
+                Compression::Zstd
+            ),
+            None
+        );
+
+        cache.forget(path);
+        assert_eq!(cache.lookup(path, (10, modified), Compression::Zstd), None);
+    }
+}
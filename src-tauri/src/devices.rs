@@ -0,0 +1,381 @@
+//! Paired devices and the shares waiting to reach them
+//!
+//! A device is paired by saving its drop ticket under a name, so files can be
+//! sent to it again without asking for a ticket each time. Sending pushes the
+//! share ticket to the device's drop box. A device that cannot be reached,
+//! such as a phone that is switched off, gets the push queued in the outbox
+//! instead. Queued pushes are retried periodically, and right away when the
+//! device connects to this node, until the device answers or the share closes.
+//! Paired devices can be put into groups (e.g., "Family") to send a share to
+//! all of them at once.
+
+use crate::dropbox::{parse_drop_ticket, push_ticket, DropResponse};
+use crate::receipts::{Receipt, ReceiptLog};
+use crate::shares::{ShareId, ShareRegistry};
+use anyhow::Result;
+use iroh::Endpoint;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// How often queued pushes are retried while their devices stay unreachable
+pub const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A device files can be sent to by name
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedDevice {
+    /// Name the device is sent to by (e.g., "phone")
+    pub name: String,
+    /// Drop ticket of the device's drop box
+    pub drop_ticket: String,
+}
+
+impl PairedDevice {
+    /// Returns the endpoint ID of the device
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the drop ticket is invalid
+    pub fn peer(&self) -> Result<String> {
+        Ok(parse_drop_ticket(&self.drop_ticket)?.addr().id.to_string())
+    }
+}
+
+/// A named set of paired devices a share can be sent to at once
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceGroup {
+    /// Name of the group (e.g., "All my devices")
+    pub name: String,
+    /// Names of the paired devices in the group
+    pub devices: Vec<String>,
+}
+
+impl DeviceGroup {
+    /// Looks up the members of the group among the paired devices
+    ///
+    /// # Returns
+    ///
+    /// Every member name with its paired device, or None if no device of
+    /// that name is paired
+    pub fn members<'a>(
+        &'a self,
+        paired: &'a [PairedDevice],
+    ) -> Vec<(&'a str, Option<&'a PairedDevice>)> {
+        self.devices
+            .iter()
+            .map(|name| {
+                let device = paired.iter().find(|device| &device.name == name);
+                (name.as_str(), device)
+            })
+            .collect()
+    }
+}
+
+/// Outcome of sending a share to a paired device
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "status"
+)]
+pub enum DeviceDelivery {
+    /// The device's drop box answered the push
+    Answered { response: DropResponse },
+    /// The device could not be reached, so the push waits in the outbox
+    Queued { push_id: String },
+    /// No push was made, e.g. because the device is not paired
+    Failed { error: String },
+}
+
+/// Outcome of sending a share to one device of a group
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupDelivery {
+    /// Name of the device
+    pub device: String,
+    /// What happened to the push
+    pub delivery: DeviceDelivery,
+}
+
+/// A push waiting for its device to come online
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedPush {
+    /// Identifier of the push, for cancelling it
+    pub id: String,
+    /// The device the share is sent to
+    pub device: PairedDevice,
+    /// Endpoint ID of the device
+    pub peer: String,
+    /// The share being sent
+    pub share_id: ShareId,
+    /// Ticket of the share, pushed to the device's drop box
+    pub ticket: String,
+    /// Unix timestamp when the push was queued
+    pub queued_at: u64,
+    /// Number of times the device could not be reached
+    pub attempts: u32,
+    /// Why the device could not be reached the last time
+    pub last_error: String,
+}
+
+/// Thread-safe queue of pushes to devices that could not be reached
+#[derive(Debug, Clone, Default)]
+pub struct Outbox {
+    pushes: Arc<Mutex<Vec<QueuedPush>>>,
+    woken: Arc<Notify>,
+}
+
+impl Outbox {
+    /// Creates an empty outbox
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a push to a device that could not be reached
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the share is sent to
+    /// * `peer` - Endpoint ID of the device
+    /// * `share_id` - The share being sent
+    /// * `ticket` - Ticket of the share
+    /// * `error` - Why the device could not be reached
+    ///
+    /// # Returns
+    ///
+    /// The ID of the queued push
+    pub fn queue(
+        &self,
+        device: PairedDevice,
+        peer: String,
+        share_id: ShareId,
+        ticket: String,
+        error: String,
+    ) -> String {
+        let id = Uuid::new_v4().to_string();
+        if let Ok(mut pushes) = self.pushes.lock() {
+            pushes.push(QueuedPush {
+                id: id.clone(),
+                device,
+                peer,
+                share_id,
+                ticket,
+                queued_at: current_unix_time(),
+                attempts: 1,
+                last_error: error,
+            });
+        }
+        id
+    }
+
+    /// Returns every queued push, oldest first
+    pub fn list(&self) -> Vec<QueuedPush> {
+        self.pushes
+            .lock()
+            .map(|pushes| pushes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Removes a push from the outbox
+    ///
+    /// Returns false if the push is not queued.
+    pub fn remove(&self, push_id: &str) -> bool {
+        let Ok(mut pushes) = self.pushes.lock() else {
+            return false;
+        };
+        let before = pushes.len();
+        pushes.retain(|push| push.id != push_id);
+        pushes.len() < before
+    }
+
+    /// Retries the pushes queued for a device right away, e.g. because it
+    /// just connected to this node
+    pub fn wake(&self, peer: &str) {
+        let queued = self
+            .pushes
+            .lock()
+            .is_ok_and(|pushes| pushes.iter().any(|push| push.peer == peer));
+        if queued {
+            self.woken.notify_one();
+        }
+    }
+
+    /// Records that a device could still not be reached
+    fn record_failure(&self, push_id: &str, error: String) {
+        if let Ok(mut pushes) = self.pushes.lock() {
+            if let Some(push) = pushes.iter_mut().find(|push| push.id == push_id) {
+                push.attempts += 1;
+                push.last_error = error;
+            }
+        }
+    }
+
+    /// Retries every queued push once.
+    ///
+    /// Pushes the device answered are removed, as are pushes of shares that
+    /// have closed in the meantime. Deliveries are added to the receipts.
+    ///
+    /// # Returns
+    ///
+    /// The pushes the devices answered, with their answers
+    pub async fn retry(
+        &self,
+        endpoint: &Endpoint,
+        shares: &ShareRegistry,
+        receipts: &ReceiptLog,
+    ) -> Vec<(QueuedPush, DropResponse)> {
+        let mut answered = Vec::new();
+        for push in self.list() {
+            if !shares.is_open(&push.share_id).await {
+                self.remove(&push.id);
+                continue;
+            }
+
+            let result = match parse_drop_ticket(&push.device.drop_ticket) {
+                Ok(drop_ticket) => push_ticket(endpoint, &drop_ticket, push.ticket.clone()).await,
+                Err(error) => Err(error),
+            };
+            match result {
+                Ok(response) => {
+                    self.remove(&push.id);
+                    if let Some(receipt) = Receipt::for_push(
+                        &push.share_id,
+                        push.peer.clone(),
+                        Some(push.device.name.clone()),
+                        &response,
+                    ) {
+                        receipts.record(receipt).await;
+                    }
+                    answered.push((push, response));
+                }
+                Err(error) => self.record_failure(&push.id, error.to_string()),
+            }
+        }
+        answered
+    }
+
+    /// Spawns a task retrying queued pushes until the endpoint is closed or
+    /// the outbox is dropped.
+    ///
+    /// Pushes are retried every [`OUTBOX_RETRY_INTERVAL`], and right away
+    /// when [`Outbox::wake`] is called for a device with queued pushes.
+    pub fn spawn_retries(&self, endpoint: Endpoint, shares: ShareRegistry, receipts: ReceiptLog) {
+        let pushes = Arc::downgrade(&self.pushes);
+        let woken = self.woken.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(OUTBOX_RETRY_INTERVAL) => {}
+                    _ = woken.notified() => {}
+                }
+                let Some(pushes) = pushes.upgrade() else {
+                    return;
+                };
+                if endpoint.is_closed() {
+                    return;
+                }
+                let outbox = Outbox {
+                    pushes,
+                    woken: woken.clone(),
+                };
+                outbox.retry(&endpoint, &shares, &receipts).await;
+            }
+        });
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> PairedDevice {
+        PairedDevice {
+            name: "phone".to_string(),
+            drop_ticket: "dropticket".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_outbox_queues_and_removes_pushes() {
+        let outbox = Outbox::new();
+        let first = outbox.queue(
+            device(),
+            "peer".to_string(),
+            "share-1".to_string(),
+            "ticket-1".to_string(),
+            "offline".to_string(),
+        );
+        let second = outbox.queue(
+            device(),
+            "peer".to_string(),
+            "share-2".to_string(),
+            "ticket-2".to_string(),
+            "offline".to_string(),
+        );
+
+        outbox.record_failure(&first, "timed out".to_string());
+        let pushes = outbox.list();
+        assert_eq!(pushes.len(), 2);
+        assert_eq!(pushes[0].attempts, 2);
+        assert_eq!(pushes[0].last_error, "timed out");
+        assert_eq!(pushes[1].share_id, "share-2");
+
+        assert!(outbox.remove(&second));
+        assert!(!outbox.remove(&second));
+        assert_eq!(outbox.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wake_only_for_devices_with_queued_pushes() {
+        let outbox = Outbox::new();
+        outbox.wake("peer");
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), outbox.woken.notified())
+                .await
+                .is_err()
+        );
+
+        outbox.queue(
+            device(),
+            "peer".to_string(),
+            "share".to_string(),
+            "ticket".to_string(),
+            "offline".to_string(),
+        );
+        outbox.wake("other");
+        outbox.wake("peer");
+        outbox.woken.notified().await;
+    }
+
+    #[test]
+    fn test_group_members_are_looked_up_by_name() {
+        let paired = vec![device()];
+        let group = DeviceGroup {
+            name: "Family".to_string(),
+            devices: vec!["phone".to_string(), "tablet".to_string()],
+        };
+
+        assert_eq!(
+            group.members(&paired),
+            vec![("phone", Some(&paired[0])), ("tablet", None)]
+        );
+    }
+
+    #[test]
+    fn test_paired_device_needs_drop_ticket() {
+        assert!(device().peer().is_err());
+    }
+}
@@ -0,0 +1,452 @@
+//! Writing received files to disk
+//!
+//! Downloaded files are streamed from the blob store in chunks through a
+//! buffer as they are read, so only a chunk of each file is held in memory at
+//! a time, reporting progress after every chunk, so a slow disk shows up as a
+//! slow write instead of a transfer that seems stuck. Callers hold a slot of
+//! the disk budget (see [`crate::concurrency`]) while writing, separate from
+//! the network lanes: a disk that is already busy only gets slower when more
+//! files are written to it at once, while downloads into the store can keep
+//! going in the meantime.
+//!
+//! Files are written under a temporary `.ginseng-partial` name next to their
+//! target and renamed once complete, so a crash, cancellation or failed write
+//! never leaves a truncated file that looks complete. The content is synced
+//! to disk before the rename, and the rename itself after it, so a power loss
+//! cannot leave the final name pointing at an empty or truncated file either.
+//! Before the rename the partial file is read back and hashed, so content the
+//! disk corrupted is caught while an existing file of that name is still
+//! intact. Partial files left behind by a crash are removed the next time the app
+//! starts.
+
+use anyhow::Result;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufWriter};
+use walkdir::WalkDir;
+
+/// Extension added to files while they are being written
+pub const PARTIAL_EXTENSION: &str = "ginseng-partial";
+
+/// Time since their last change after which partial files are considered
+/// abandoned; partial files being written change with every chunk
+const STALE_PARTIAL_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Bytes buffered in memory before they are handed to the file system
+const WRITE_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Bytes written between progress reports
+const WRITE_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Result of writing a file to disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WriteStats {
+    /// Bytes written
+    pub bytes: u64,
+    /// Time spent writing
+    pub elapsed: Duration,
+}
+
+impl WriteStats {
+    /// Returns the write throughput in bytes per second
+    pub fn rate(&self) -> Option<u64> {
+        write_rate(self.bytes, self.elapsed)
+    }
+}
+
+/// Returns the throughput of writing the given bytes in the given time.
+///
+/// Returns None until any time has been measured.
+pub fn write_rate(bytes: u64, elapsed: Duration) -> Option<u64> {
+    let micros = elapsed.as_micros();
+    (micros > 0).then(|| (u128::from(bytes) * 1_000_000 / micros) as u64)
+}
+
+/// Returns the temporary path a file is written to before it is complete.
+///
+/// # Examples
+///
+/// ```
+/// use ginseng_lib::disk::partial_path;
+/// use std::path::Path;
+/// assert_eq!(
+///     partial_path(Path::new("photos/beach.jpg")),
+///     Path::new("photos/beach.jpg.ginseng-partial")
+/// );
+/// ```
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".");
+    file_name.push(PARTIAL_EXTENSION);
+    path.with_file_name(file_name)
+}
+
+/// Removes a partial file when dropped, unless it was renamed into place.
+///
+/// This covers failed writes as well as writes whose future is dropped
+/// mid-way, e.g. because the transfer was cancelled.
+struct PartialFile {
+    path: PathBuf,
+    completed: bool,
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Writes a file to disk in buffered chunks.
+///
+/// `on_progress` is called with zero bytes when writing starts, and then with
+/// the bytes written and the time spent so far after every chunk. The content
+/// is written to the file's [`partial_path`], checked against `expected_hash`
+/// if given, read back to check it matches, and renamed to `path`, replacing
+/// an existing file at `path`. If writing or a check fails, the partial file
+/// is removed and an existing file is left untouched.
+///
+/// # Arguments
+///
+/// * `path` - Where to write the file; its parent folder must exist
+/// * `content` - Reader of the content of the file
+/// * `expected_hash` - The BLAKE3 hash the content must have, if known
+/// * `on_progress` - Called with the bytes written and the time spent writing
+///
+/// # Errors
+///
+/// Returns an error if the content cannot be read, the file cannot be created
+/// or written, the content does not have the expected hash, or the content
+/// read back from disk differs from the content written.
+pub async fn write_file(
+    path: &Path,
+    content: impl AsyncRead,
+    expected_hash: Option<blake3::Hash>,
+    mut on_progress: impl FnMut(u64, Duration),
+) -> Result<WriteStats> {
+    let started = Instant::now();
+    on_progress(0, Duration::ZERO);
+
+    let mut partial = PartialFile {
+        path: partial_path(path),
+        completed: false,
+    };
+    let file = File::create(&partial.path).await?;
+    let mut writer = BufWriter::with_capacity(WRITE_BUFFER_SIZE, file);
+    let mut hasher = blake3::Hasher::new();
+    let mut content = std::pin::pin!(content);
+    let mut buffer = vec![0; WRITE_CHUNK_SIZE];
+    let mut written = 0;
+    loop {
+        let read = read_chunk(&mut content, &mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &buffer[..read];
+
+        #[cfg(feature = "fault-injection")]
+        crate::faults::bytes_written(chunk.len() as u64).await;
+
+        hasher.update(chunk);
+        writer.write_all(chunk).await?;
+        written += chunk.len() as u64;
+        on_progress(written, started.elapsed());
+    }
+    writer.flush().await?;
+    writer.into_inner().sync_all().await?;
+
+    let expected = hasher.finalize();
+    if let Some(expected_hash) = expected_hash {
+        anyhow::ensure!(
+            expected == expected_hash,
+            "content does not match its hash (expected {}, found {})",
+            expected_hash,
+            expected
+        );
+    }
+    let on_disk = hash_file(&partial.path).await?;
+    anyhow::ensure!(
+        on_disk == expected,
+        "content on disk does not match (expected {}, found {})",
+        expected,
+        on_disk
+    );
+
+    fs::rename(&partial.path, path).await?;
+    partial.completed = true;
+    // The file is complete either way, so only durability is lost on failure
+    if let Err(error) = sync_parent_directory(path).await {
+        tracing::debug!(path = %path.display(), %error, "rename not synced to disk");
+    }
+
+    Ok(WriteStats {
+        bytes: written,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Reads content until the buffer is full or the content ends.
+///
+/// # Returns
+///
+/// The number of bytes read, 0 once the content has ended
+async fn read_chunk(content: &mut (impl AsyncRead + Unpin), buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match content.read(&mut buffer[filled..]).await? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Hashes a file in chunks, without reading it into memory at once.
+async fn hash_file(path: &Path) -> Result<blake3::Hash> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0; WRITE_CHUNK_SIZE];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => return Ok(hasher.finalize()),
+                read => {
+                    hasher.update(&buffer[..read]);
+                }
+            }
+        }
+    })
+    .await?
+}
+
+/// Syncs the folder a file was renamed in, so the rename survives a crash.
+#[cfg(unix)]
+async fn sync_parent_directory(path: &Path) -> std::io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent).await?.sync_all().await
+}
+
+/// Syncs the folder a file was renamed in, so the rename survives a crash.
+///
+/// Folders cannot be opened for syncing on this platform; renames are made
+/// durable by the file system itself.
+#[cfg(not(unix))]
+async fn sync_parent_directory(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Checks that downloaded files can be saved to a directory.
+///
+/// Writes and removes a small probe file, since permissions alone do not tell
+/// whether e.g. a read-only mount accepts new files.
+///
+/// # Arguments
+///
+/// * `directory` - The directory downloads would be saved to
+///
+/// # Errors
+///
+/// Returns an error if the directory does not exist, is not a directory, or
+/// is not writable.
+pub async fn validate_download_directory(directory: &Path) -> Result<()> {
+    let metadata = fs::metadata(directory).await.map_err(|error| {
+        let message = format!(
+            "Invalid download directory '{}': {}",
+            directory.display(),
+            error
+        );
+        anyhow::Error::new(error).context(message)
+    })?;
+    if !metadata.is_dir() {
+        anyhow::bail!("'{}' is not a directory", directory.display());
+    }
+
+    let probe = directory.join(format!(".ginseng-write-test-{}", std::process::id()));
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .await
+        .map_err(|error| {
+            let message = format!(
+                "Download directory '{}' is not writable: {}",
+                directory.display(),
+                error
+            );
+            anyhow::Error::new(error).context(message)
+        })?;
+    fs::remove_file(&probe).await.ok();
+    Ok(())
+}
+
+/// Removes partial files left behind in a folder and its subfolders.
+///
+/// Only partial files that have not changed for a while are removed, so
+/// files still being written by another process (e.g., the CLI) are kept.
+/// Cleanup is best effort: entries that cannot be read or removed are skipped.
+///
+/// # Returns
+///
+/// The number of partial files removed
+pub async fn remove_stale_partials(root: PathBuf) -> usize {
+    tokio::task::spawn_blocking(move || {
+        let now = SystemTime::now();
+        WalkDir::new(root)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension() == Some(PARTIAL_EXTENSION.as_ref())
+            })
+            .filter(|entry| {
+                entry
+                    .metadata()
+                    .ok()
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(|modified| now.duration_since(modified).ok())
+                    .is_some_and(|age| age >= STALE_PARTIAL_AGE)
+            })
+            .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+            .count()
+    })
+    .await
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_write_file_reports_every_chunk() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("file.bin");
+        std::fs::write(&path, b"old content that is longer").unwrap();
+        let content = vec![7u8; WRITE_CHUNK_SIZE * 2 + 10];
+
+        let mut reports = Vec::new();
+        let stats = write_file(
+            &path,
+            content.as_slice(),
+            Some(blake3::hash(&content)),
+            |bytes, _| reports.push(bytes),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert!(!partial_path(&path).exists());
+        assert_eq!(stats.bytes, content.len() as u64);
+        assert_eq!(
+            reports,
+            vec![
+                0,
+                WRITE_CHUNK_SIZE as u64,
+                WRITE_CHUNK_SIZE as u64 * 2,
+                content.len() as u64
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_leaves_no_partial_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("missing").join("file.bin");
+
+        assert!(write_file(&path, &b"content"[..], None, |_, _| {})
+            .await
+            .is_err());
+        assert!(!path.exists());
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_content_not_matching_hash_is_refused() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("file.bin");
+        std::fs::write(&path, b"old content").unwrap();
+
+        let result = write_file(
+            &path,
+            &b"tampered content"[..],
+            Some(blake3::hash(b"shared content")),
+            |_, _| {},
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old content");
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_hash_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("report.pdf");
+        let content = vec![3u8; WRITE_CHUNK_SIZE + 10];
+        std::fs::write(&path, &content).unwrap();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&content);
+        assert_eq!(hash_file(&path).await.unwrap(), hasher.finalize());
+        assert!(hash_file(&temp_dir.path().join("missing.pdf"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_partials() {
+        let temp_dir = tempdir().unwrap();
+        let stale = partial_path(&temp_dir.path().join("nested").join("old.bin"));
+        let fresh = partial_path(&temp_dir.path().join("new.bin"));
+        std::fs::create_dir_all(stale.parent().unwrap()).unwrap();
+        std::fs::write(&stale, b"old").unwrap();
+        std::fs::write(&fresh, b"new").unwrap();
+        std::fs::write(temp_dir.path().join("done.bin"), b"done").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_PARTIAL_AGE * 2)
+            .unwrap();
+
+        assert_eq!(
+            remove_stale_partials(temp_dir.path().to_path_buf()).await,
+            1
+        );
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        assert!(temp_dir.path().join("done.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_validate_download_directory() {
+        let temp_dir = tempdir().unwrap();
+        assert!(validate_download_directory(temp_dir.path()).await.is_ok());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        let file_path = temp_dir.path().join("file.txt");
+        std::fs::write(&file_path, b"content").unwrap();
+        assert!(validate_download_directory(&file_path).await.is_err());
+        assert!(
+            validate_download_directory(&temp_dir.path().join("missing"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_write_rate() {
+        assert_eq!(write_rate(1000, Duration::ZERO), None);
+        assert_eq!(write_rate(1000, Duration::from_millis(500)), Some(2000));
+    }
+}
@@ -0,0 +1,150 @@
+//! Publishing and resolving share tickets through DNS TXT records
+//!
+//! Self-hosters can publish a ticket under `_ginseng.<domain>` so recipients
+//! only need to type the domain name. Tickets are longer than the 255 byte
+//! limit of a single TXT character-string, so they are split across several
+//! strings of one record and joined again when resolving.
+
+use anyhow::Result;
+use hickory_resolver::TokioResolver;
+
+/// Label prepended to the domain to form the TXT record name
+const RECORD_LABEL: &str = "_ginseng";
+
+/// Prefix identifying a TXT record value as a Ginseng ticket
+const RECORD_PREFIX: &str = "ginseng=";
+
+/// Maximum length of a single TXT character-string
+const MAX_TXT_STRING_LENGTH: usize = 255;
+
+/// Returns the DNS name a ticket for the given domain is published under.
+///
+/// # Examples
+///
+/// ```
+/// use ginseng_lib::dns::record_name;
+/// assert_eq!(record_name("files.example.com"), "_ginseng.files.example.com");
+/// ```
+pub fn record_name(domain: &str) -> String {
+    format!("{}.{}", RECORD_LABEL, domain.trim_end_matches('.'))
+}
+
+/// Formats a ticket as a zone file TXT record for the given domain.
+///
+/// The ticket is prefixed with `ginseng=` and split into quoted strings of at
+/// most 255 bytes each, ready to paste into a zone file or DNS provider UI.
+pub fn format_txt_record(domain: &str, ticket: &str) -> String {
+    let value = format!("{}{}", RECORD_PREFIX, ticket.trim());
+    let strings = value
+        .as_bytes()
+        .chunks(MAX_TXT_STRING_LENGTH)
+        .map(|chunk| format!("\"{}\"", String::from_utf8_lossy(chunk)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!("{}. IN TXT {}", record_name(domain), strings)
+}
+
+/// Checks whether user input looks like a domain name rather than a ticket.
+///
+/// Tickets never contain dots, so any dotted input made of valid hostname
+/// characters is treated as a domain.
+pub fn looks_like_domain(input: &str) -> bool {
+    let input = input.trim().trim_end_matches('.');
+    input.contains('.') && input.len() <= 253 && input.split('.').all(is_valid_label)
+}
+
+/// Looks up the ticket published for a domain.
+///
+/// # Errors
+///
+/// Returns an error if the lookup fails or no Ginseng TXT record exists.
+pub async fn resolve_ticket(domain: &str) -> Result<String> {
+    let name = record_name(domain.trim());
+    let resolver = TokioResolver::builder_tokio()
+        .map_err(|error| anyhow::anyhow!("Failed to create DNS resolver: {}", error))?
+        .build();
+
+    let lookup = resolver
+        .txt_lookup(name.as_str())
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to look up '{}': {}", name, error))?;
+
+    lookup
+        .iter()
+        .map(|record| join_txt_strings(record.txt_data()))
+        .find_map(|value| parse_record_value(&value))
+        .ok_or_else(|| anyhow::anyhow!("No Ginseng ticket is published at '{}'", name))
+}
+
+/// Joins the character-strings of a TXT record into a single value.
+fn join_txt_strings(strings: &[Box<[u8]>]) -> String {
+    strings
+        .iter()
+        .map(|string| String::from_utf8_lossy(string))
+        .collect()
+}
+
+/// Extracts the ticket from a TXT record value, if it is a Ginseng record.
+fn parse_record_value(value: &str) -> Option<String> {
+    value
+        .strip_prefix(RECORD_PREFIX)
+        .map(|ticket| ticket.trim().to_string())
+        .filter(|ticket| !ticket.is_empty())
+}
+
+/// Checks whether a string is a valid hostname label.
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_txt_record_short_ticket() {
+        assert_eq!(
+            format_txt_record("files.example.com", "blobabc"),
+            "_ginseng.files.example.com. IN TXT \"ginseng=blobabc\""
+        );
+    }
+
+    #[test]
+    fn test_format_txt_record_splits_long_ticket() {
+        let ticket = "a".repeat(600);
+        let record = format_txt_record("example.com", &ticket);
+        let strings: Vec<&str> = record.split_once("IN TXT ").unwrap().1.split(' ').collect();
+
+        assert_eq!(strings.len(), 3);
+        assert!(strings
+            .iter()
+            .all(|s| s.trim_matches('"').len() <= MAX_TXT_STRING_LENGTH));
+    }
+
+    #[test]
+    fn test_join_and_parse_record_value() {
+        let strings: Vec<Box<[u8]>> = vec![
+            b"ginseng=blob".to_vec().into_boxed_slice(),
+            b"abc".to_vec().into_boxed_slice(),
+        ];
+        let value = join_txt_strings(&strings);
+        assert_eq!(parse_record_value(&value), Some("blobabc".to_string()));
+        assert_eq!(parse_record_value("v=spf1 -all"), None);
+    }
+
+    #[test]
+    fn test_looks_like_domain() {
+        assert!(looks_like_domain("files.example.com"));
+        assert!(looks_like_domain("files.example.com."));
+        assert!(!looks_like_domain("blobafkfrvhakfhakfhakf"));
+        assert!(!looks_like_domain("not a domain.com"));
+        assert!(!looks_like_domain("-bad.example.com"));
+    }
+}
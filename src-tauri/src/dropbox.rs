@@ -0,0 +1,654 @@
+//! Drop box: a long-lived "send me files" address
+//!
+//! The inverse of a regular share. The receiver opens a drop box and hands out
+//! its drop ticket; senders then push shares to it over a dedicated protocol.
+//! A push is a small request carrying a regular share ticket, which the drop
+//! box evaluates against its accept rules before pulling the files from the
+//! sender with the normal download path.
+
+use crate::bandwidth::BandwidthLimiter;
+use crate::core::{
+    determine_target_directory, download_all_files, download_and_parse_bundle, parse_ticket,
+    target_directory_in, ShareMetadata,
+};
+use crate::folder_names::DEFAULT_TEMPLATE;
+use anyhow::Result;
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::Endpoint;
+use iroh_blobs::{store::mem::MemStore, ticket::BlobTicket, BlobsProtocol};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use tracing::Instrument;
+
+/// ALPN identifying the drop box protocol
+pub const DROPBOX_ALPN: &[u8] = b"ginseng/dropbox/0";
+
+/// Prefix distinguishing drop tickets from regular share tickets
+const DROP_TICKET_PREFIX: &str = "drop";
+
+/// Maximum size of a push request or response message in bytes
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Number of drop box events buffered for slow subscribers
+const EVENT_CAPACITY: usize = 64;
+
+/// Rules deciding which pushes a drop box accepts.
+///
+/// Rules can be written as a JSON file so a headless drop box (e.g., on a NAS)
+/// can receive from known devices unattended:
+///
+/// ```json
+/// {
+///   "maxShareSize": 1073741824,
+///   "targetDirectory": "/srv/inbox",
+///   "folderNameTemplate": "Drop {date} {time}",
+///   "peers": [
+///     { "peer": "<endpoint id>", "name": "phone", "targetDirectory": "/srv/photos" }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptRules {
+    /// Endpoint IDs allowed to push files (empty means anyone with the drop ticket)
+    #[serde(default)]
+    pub allowed_peers: Vec<String>,
+    /// Maximum total size of a single pushed share in bytes
+    #[serde(default)]
+    pub max_share_size: Option<u64>,
+    /// Directory pushed shares are saved to (defaults to the Downloads folder)
+    #[serde(default)]
+    pub target_directory: Option<PathBuf>,
+    /// Template naming the folders shares of several files are saved in
+    /// (defaults to [`DEFAULT_TEMPLATE`])
+    #[serde(default)]
+    pub folder_name_template: Option<String>,
+    /// Rules for individual peers, which are also allowed to push
+    #[serde(default)]
+    pub peers: Vec<PeerRule>,
+}
+
+/// Accept rule for pushes from a single peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerRule {
+    /// Endpoint ID of the peer
+    pub peer: String,
+    /// Friendly name of the peer, for the rules file only
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Maximum total size of a share from this peer, overriding the drop box limit
+    #[serde(default)]
+    pub max_share_size: Option<u64>,
+    /// Directory shares from this peer are saved to, overriding the drop box directory
+    #[serde(default)]
+    pub target_directory: Option<PathBuf>,
+}
+
+impl AcceptRules {
+    /// Loads accept rules from a JSON file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not valid JSON
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await.map_err(|error| {
+            anyhow::anyhow!("Failed to read rules file '{}': {}", path.display(), error)
+        })?;
+        serde_json::from_str(&content)
+            .map_err(|error| anyhow::anyhow!("Invalid rules file '{}': {}", path.display(), error))
+    }
+
+    /// Returns the rule for a specific peer, if any
+    fn peer_rule(&self, peer: &str) -> Option<&PeerRule> {
+        self.peers.iter().find(|rule| rule.peer == peer)
+    }
+
+    /// Checks whether a peer is allowed to push to the drop box
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection reason if the peer is not allowed
+    pub fn check_peer(&self, peer: &str) -> Result<(), String> {
+        let anyone = self.allowed_peers.is_empty() && self.peers.is_empty();
+        if anyone || self.allowed_peers.iter().any(|p| p == peer) || self.peer_rule(peer).is_some()
+        {
+            Ok(())
+        } else {
+            Err("Sender is not allowed to push to this drop box".to_string())
+        }
+    }
+
+    /// Checks whether a share pushed by a peer fits within the drop box limits
+    ///
+    /// The size is taken from the file list, and a share whose declared total
+    /// size does not match it is refused. Files larger than listed stop the
+    /// download once their data goes past the listed size (see
+    /// [`download_all_files`]), so the limit holds for the data received too.
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection reason if the share is too large or its sizes do
+    /// not add up
+    pub fn check_metadata(&self, peer: &str, metadata: &ShareMetadata) -> Result<(), String> {
+        let listed_size = metadata
+            .files
+            .iter()
+            .fold(0u64, |total, file| total.saturating_add(file.size));
+        if listed_size != metadata.total_size {
+            return Err(format!(
+                "Share declares {} bytes, but its files add up to {} bytes",
+                metadata.total_size, listed_size
+            ));
+        }
+
+        let max_share_size = self
+            .peer_rule(peer)
+            .and_then(|rule| rule.max_share_size)
+            .or(self.max_share_size);
+
+        match max_share_size {
+            Some(max) if listed_size > max => Err(format!(
+                "Share is {} bytes, but this drop box accepts at most {} bytes",
+                listed_size, max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the directory a share pushed by a peer is saved to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no directory is configured and the Downloads
+    /// folder cannot be determined
+    pub fn target_directory(&self, peer: &str, metadata: &ShareMetadata) -> Result<PathBuf> {
+        let base = self
+            .peer_rule(peer)
+            .and_then(|rule| rule.target_directory.as_deref())
+            .or(self.target_directory.as_deref());
+        let folder_name_template = self
+            .folder_name_template
+            .as_deref()
+            .unwrap_or(DEFAULT_TEMPLATE);
+
+        match base {
+            Some(base) => Ok(target_directory_in(base, metadata, folder_name_template)),
+            None => determine_target_directory(metadata, None, folder_name_template),
+        }
+    }
+}
+
+/// Public description of a drop box, stored as the blob its drop ticket points at.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DropBoxInfo {
+    /// Human-readable name of the drop box (e.g., "Class assignments")
+    pub name: String,
+    /// Maximum total size of a single pushed share in bytes
+    pub max_share_size: Option<u64>,
+}
+
+/// Request sent by a peer pushing a share to a drop box
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropRequest {
+    /// Ticket of the share the sender wants to deliver
+    pub ticket: String,
+}
+
+/// Outcome of a push, sent back to the pushing peer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "status"
+)]
+pub enum DropResponse {
+    /// The drop box downloaded and verified the share; the sender keeps this
+    /// as a receipt. `delivered_at` is a Unix timestamp, 0 from drop boxes
+    /// that predate receipts.
+    Delivered {
+        file_count: usize,
+        total_size: u64,
+        #[serde(default)]
+        delivered_at: u64,
+    },
+    /// The drop box refused the share
+    Rejected { reason: String },
+    /// The drop box accepted the share but failed to download it
+    Failed { error: String },
+}
+
+/// Events emitted by the drop box as pushes arrive
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "event",
+    content = "data"
+)]
+pub enum DropEvent {
+    /// A peer started pushing a share
+    Incoming { peer: String },
+    /// A pushed share was downloaded
+    Received {
+        peer: String,
+        metadata: ShareMetadata,
+        download_path: String,
+    },
+    /// A pushed share was refused by the accept rules
+    Rejected { peer: String, reason: String },
+    /// A pushed share could not be downloaded
+    Failed { peer: String, error: String },
+}
+
+/// Configuration of an open drop box
+#[derive(Debug, Clone)]
+struct DropBoxConfig {
+    rules: AcceptRules,
+    ticket: String,
+}
+
+/// Protocol handler receiving pushed shares.
+///
+/// Registered on the router for every core, but rejects all pushes until
+/// [`DropBox::open`] is called.
+#[derive(Clone)]
+pub struct DropBox {
+    endpoint: Endpoint,
+    blobs: BlobsProtocol,
+    store: MemStore,
+    download_limit: BandwidthLimiter,
+    config: Arc<RwLock<Option<DropBoxConfig>>>,
+    events: broadcast::Sender<DropEvent>,
+}
+
+impl std::fmt::Debug for DropBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DropBox").finish_non_exhaustive()
+    }
+}
+
+impl DropBox {
+    /// Creates a closed drop box backed by the given endpoint and store
+    ///
+    /// Pushed files are downloaded at the rate allowed by `download_limit`.
+    pub fn new(
+        endpoint: Endpoint,
+        blobs: BlobsProtocol,
+        store: MemStore,
+        download_limit: BandwidthLimiter,
+    ) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            endpoint,
+            blobs,
+            store,
+            download_limit,
+            config: Arc::new(RwLock::new(None)),
+            events,
+        }
+    }
+
+    /// Opens the drop box and returns its drop ticket.
+    ///
+    /// Opening an already open drop box replaces its rules and issues a new ticket.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Human-readable name shown to senders
+    /// * `rules` - Rules deciding which pushes are accepted
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the drop box description cannot be stored.
+    pub async fn open(&self, name: String, rules: AcceptRules) -> Result<String> {
+        let info = DropBoxInfo {
+            name,
+            max_share_size: rules.max_share_size,
+        };
+        let tag = self
+            .blobs
+            .store()
+            .add_bytes(serde_json::to_vec(&info)?)
+            .await
+            .map_err(|error| anyhow::anyhow!("Failed to store drop box info: {}", error))?;
+
+        let blob_ticket = BlobTicket::new(self.endpoint.addr(), tag.hash, tag.format);
+        let ticket = format!("{}{}", DROP_TICKET_PREFIX, blob_ticket);
+
+        *self.config.write().await = Some(DropBoxConfig {
+            rules,
+            ticket: ticket.clone(),
+        });
+
+        Ok(ticket)
+    }
+
+    /// Closes the drop box so further pushes are rejected
+    pub async fn close(&self) {
+        *self.config.write().await = None;
+    }
+
+    /// Returns the drop ticket if the drop box is open
+    pub async fn ticket(&self) -> Option<String> {
+        self.config
+            .read()
+            .await
+            .as_ref()
+            .map(|config| config.ticket.clone())
+    }
+
+    /// Subscribes to events about incoming pushes
+    pub fn subscribe(&self) -> broadcast::Receiver<DropEvent> {
+        self.events.subscribe()
+    }
+
+    /// Serves a single push over an accepted connection.
+    async fn handle_connection(&self, connection: Connection) -> Result<()> {
+        let peer = connection
+            .remote_id()
+            .map_err(|error| anyhow::anyhow!("Failed to identify sender: {}", error))?
+            .to_string();
+
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let request_bytes = recv.read_to_end(MAX_MESSAGE_SIZE).await?;
+        let request: DropRequest = serde_json::from_slice(&request_bytes)?;
+
+        let span = tracing::info_span!("push", %peer, share_id = tracing::field::Empty);
+        let response = self.receive_push(&peer, &request).instrument(span).await;
+
+        send.write_all(&serde_json::to_vec(&response)?).await?;
+        send.finish()?;
+        connection.closed().await;
+
+        Ok(())
+    }
+
+    /// Evaluates a push against the accept rules and downloads the share.
+    async fn receive_push(&self, peer: &str, request: &DropRequest) -> DropResponse {
+        let rules = match self.config.read().await.as_ref() {
+            Some(config) => config.rules.clone(),
+            None => return self.reject(peer, "This drop box is closed".to_string()),
+        };
+
+        if let Err(reason) = rules.check_peer(peer) {
+            return self.reject(peer, reason);
+        }
+
+        self.emit(DropEvent::Incoming {
+            peer: peer.to_string(),
+        });
+
+        let ticket = match parse_ticket(&request.ticket) {
+            Ok(ticket) => ticket,
+            Err(error) => return self.reject(peer, error.to_string()),
+        };
+
+        let bundle = match download_and_parse_bundle(
+            &self.endpoint,
+            &self.blobs,
+            &self.store,
+            &ticket,
+        )
+        .await
+        {
+            Ok(bundle) => bundle,
+            Err(error) => return self.fail(peer, error.to_string()),
+        };
+        if let Some(share_id) = &bundle.metadata.share_id {
+            tracing::Span::current().record("share_id", share_id.as_str());
+        }
+        tracing::info!(files = bundle.metadata.files.len(), "push received");
+
+        if let Err(reason) = rules.check_metadata(peer, &bundle.metadata) {
+            return self.reject(peer, reason);
+        }
+
+        let target_directory = match rules.target_directory(peer, &bundle.metadata) {
+            Ok(target_directory) => target_directory,
+            Err(error) => return self.fail(peer, error.to_string()),
+        };
+
+        let result = download_all_files(
+            &self.endpoint,
+            &self.blobs,
+            &bundle.metadata,
+            &target_directory,
+            &ticket,
+            &self.download_limit,
+        )
+        .await;
+
+        match result {
+            Ok(()) => {
+                let response = DropResponse::Delivered {
+                    file_count: bundle.metadata.files.len(),
+                    total_size: bundle.metadata.total_size,
+                    delivered_at: current_unix_time(),
+                };
+                tracing::info!("push delivered");
+                self.emit(DropEvent::Received {
+                    peer: peer.to_string(),
+                    metadata: bundle.metadata,
+                    download_path: target_directory.to_string_lossy().to_string(),
+                });
+                response
+            }
+            Err(error) => self.fail(peer, error.to_string()),
+        }
+    }
+
+    /// Emits a rejection event and builds the matching response
+    fn reject(&self, peer: &str, reason: String) -> DropResponse {
+        tracing::info!(%reason, "push rejected");
+        self.emit(DropEvent::Rejected {
+            peer: peer.to_string(),
+            reason: reason.clone(),
+        });
+        DropResponse::Rejected { reason }
+    }
+
+    /// Emits a failure event and builds the matching response
+    fn fail(&self, peer: &str, error: String) -> DropResponse {
+        tracing::warn!(%error, "push failed");
+        self.emit(DropEvent::Failed {
+            peer: peer.to_string(),
+            error: error.clone(),
+        });
+        DropResponse::Failed { error }
+    }
+
+    /// Sends an event to all subscribers, ignoring the case where there are none
+    fn emit(&self, event: DropEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+impl ProtocolHandler for DropBox {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        self.handle_connection(connection)
+            .await
+            .map_err(|error| AcceptError::from_err(std::io::Error::other(error.to_string())))
+    }
+}
+
+/// Parses a drop ticket into the blob ticket describing the drop box.
+///
+/// # Errors
+///
+/// Returns an error if the string is not a drop ticket.
+pub fn parse_drop_ticket(drop_ticket: &str) -> Result<BlobTicket> {
+    drop_ticket
+        .trim()
+        .strip_prefix(DROP_TICKET_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Not a drop ticket"))?
+        .parse::<BlobTicket>()
+        .map_err(|error| anyhow::anyhow!("Failed to parse drop ticket: {}", error))
+}
+
+/// Pushes a share ticket to a drop box and waits for the outcome.
+///
+/// The sender must keep serving the share until this returns, since the drop
+/// box downloads the files before responding.
+///
+/// # Errors
+///
+/// Returns an error if the drop box cannot be reached or responds with an
+/// invalid message.
+pub async fn push_ticket(
+    endpoint: &Endpoint,
+    drop_ticket: &BlobTicket,
+    ticket: String,
+) -> Result<DropResponse> {
+    let connection = endpoint
+        .connect(drop_ticket.addr().clone(), DROPBOX_ALPN)
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to reach drop box: {}", error))?;
+
+    let (mut send, mut recv) = connection.open_bi().await?;
+    send.write_all(&serde_json::to_vec(&DropRequest { ticket })?)
+        .await?;
+    send.finish()?;
+
+    let response_bytes = recv.read_to_end(MAX_MESSAGE_SIZE).await?;
+    connection.close(0u32.into(), b"done");
+
+    serde_json::from_slice(&response_bytes)
+        .map_err(|error| anyhow::anyhow!("Invalid response from drop box: {}", error))
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::core::{FileInfo, ShareType};
+
+    fn metadata_with_size(total_size: u64) -> ShareMetadata {
+        ShareMetadata {
+            files: vec![FileInfo {
+                name: "file.bin".to_string(),
+                relative_path: "file.bin".to_string(),
+                size: total_size,
+                hash: "hash".to_string(),
+                compression: Compression::None,
+            }],
+            share_type: ShareType::MultipleFiles,
+            total_size,
+            skipped_files: vec![],
+            custom_fields: Default::default(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        }
+    }
+
+    #[test]
+    fn test_accept_rules_allow_anyone_by_default() {
+        let rules = AcceptRules::default();
+        assert!(rules.check_peer("any-peer").is_ok());
+        assert!(rules
+            .check_metadata("any-peer", &metadata_with_size(u64::MAX))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_accept_rules_allowed_peers() {
+        let rules = AcceptRules {
+            allowed_peers: vec!["laptop".to_string()],
+            ..Default::default()
+        };
+        assert!(rules.check_peer("laptop").is_ok());
+        assert!(rules.check_peer("stranger").is_err());
+    }
+
+    #[test]
+    fn test_accept_rules_max_share_size() {
+        let rules = AcceptRules {
+            max_share_size: Some(100),
+            ..Default::default()
+        };
+        assert!(rules
+            .check_metadata("peer", &metadata_with_size(100))
+            .is_ok());
+        assert!(rules
+            .check_metadata("peer", &metadata_with_size(101))
+            .is_err());
+    }
+
+    #[test]
+    fn test_accept_rules_refuse_understated_size() {
+        let rules = AcceptRules {
+            max_share_size: Some(100),
+            ..Default::default()
+        };
+        let understated = ShareMetadata {
+            total_size: 1,
+            ..metadata_with_size(1_000_000)
+        };
+        assert!(rules.check_metadata("peer", &understated).is_err());
+        assert!(AcceptRules::default()
+            .check_metadata("peer", &understated)
+            .is_err());
+    }
+
+    #[test]
+    fn test_accept_rules_peer_rules() {
+        let rules: AcceptRules = serde_json::from_str(
+            r#"{
+                "maxShareSize": 100,
+                "targetDirectory": "/srv/inbox",
+                "peers": [
+                    { "peer": "phone", "maxShareSize": 1000, "targetDirectory": "/srv/photos" },
+                    { "peer": "laptop" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(rules.check_peer("phone").is_ok());
+        assert!(rules.check_peer("laptop").is_ok());
+        assert!(rules.check_peer("stranger").is_err());
+
+        assert!(rules
+            .check_metadata("phone", &metadata_with_size(500))
+            .is_ok());
+        assert!(rules
+            .check_metadata("laptop", &metadata_with_size(500))
+            .is_err());
+
+        let metadata = ShareMetadata {
+            share_type: ShareType::Directory {
+                name: "album".to_string(),
+            },
+            ..metadata_with_size(10)
+        };
+        assert_eq!(
+            rules.target_directory("phone", &metadata).unwrap(),
+            PathBuf::from("/srv/photos/album")
+        );
+        assert_eq!(
+            rules.target_directory("laptop", &metadata).unwrap(),
+            PathBuf::from("/srv/inbox/album")
+        );
+    }
+
+    #[test]
+    fn test_parse_drop_ticket_rejects_share_ticket() {
+        assert!(parse_drop_ticket("blobafkfrvhakfhakf").is_err());
+        assert!(parse_drop_ticket("dropinvalid").is_err());
+    }
+}
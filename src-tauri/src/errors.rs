@@ -0,0 +1,139 @@
+//! Errors reported to the frontend
+//!
+//! Commands fail with a [`GinsengError`], which reaches the frontend as an
+//! object with a machine-readable `code` and a human-readable `message`, e.g.
+//! `{ "code": "peerUnreachable", "message": "Failed to establish connection: …" }`.
+//! The frontend branches on the code to show what the user can do about the
+//! error, and shows the message as is otherwise.
+//!
+//! Core functions keep returning [`anyhow::Error`]; the kind of error is
+//! recovered from the typed errors core already uses (e.g.
+//! [`DownloadError`]) and from I/O errors found in the error chain.
+
+use crate::core::{DownloadError, NotAGinsengShare, ShareTooLarge, TransferCancelled};
+use serde::Serialize;
+use std::io::ErrorKind;
+
+/// An error a command failed with, by kind
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "code", content = "message")]
+pub enum GinsengError {
+    /// The ticket could not be parsed
+    InvalidTicket(String),
+    /// The peer sharing the files could not be reached
+    PeerUnreachable(String),
+    /// The share expired and is no longer served
+    ShareExpired(String),
+    /// The ticket points at a raw blob instead of a Ginseng share
+    NotAGinsengShare(String),
+    /// The files to share exceed the size limit
+    ShareTooLarge(String),
+    /// The transfer was cancelled by the user
+    Cancelled(String),
+    /// There is no space left on the disk
+    DiskFull(String),
+    /// A file or folder does not exist
+    PathNotFound(String),
+    /// A file or folder cannot be accessed
+    PermissionDenied(String),
+    /// The core is still starting or failed to start
+    CoreUnavailable(String),
+    /// Any other error
+    Other(String),
+}
+
+impl GinsengError {
+    /// Returns the human-readable message of the error
+    pub fn message(&self) -> &str {
+        match self {
+            GinsengError::InvalidTicket(message)
+            | GinsengError::PeerUnreachable(message)
+            | GinsengError::ShareExpired(message)
+            | GinsengError::NotAGinsengShare(message)
+            | GinsengError::ShareTooLarge(message)
+            | GinsengError::Cancelled(message)
+            | GinsengError::DiskFull(message)
+            | GinsengError::PathNotFound(message)
+            | GinsengError::PermissionDenied(message)
+            | GinsengError::CoreUnavailable(message)
+            | GinsengError::Other(message) => message,
+        }
+    }
+}
+
+impl std::fmt::Display for GinsengError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for GinsengError {}
+
+impl From<anyhow::Error> for GinsengError {
+    fn from(error: anyhow::Error) -> Self {
+        let message = error.to_string();
+
+        if let Some(download_error) = error.downcast_ref::<DownloadError>() {
+            match download_error {
+                DownloadError::InvalidTicket(_) => return GinsengError::InvalidTicket(message),
+                DownloadError::PeerOffline(_) => return GinsengError::PeerUnreachable(message),
+                DownloadError::ShareExpired => return GinsengError::ShareExpired(message),
+                DownloadError::PartialFailure { .. } => {}
+            }
+        }
+        if error.is::<NotAGinsengShare>() {
+            return GinsengError::NotAGinsengShare(message);
+        }
+        if error.is::<ShareTooLarge>() {
+            return GinsengError::ShareTooLarge(message);
+        }
+        if error.is::<TransferCancelled>() {
+            return GinsengError::Cancelled(message);
+        }
+
+        let io_error = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<std::io::Error>());
+        match io_error.map(std::io::Error::kind) {
+            Some(ErrorKind::StorageFull) => GinsengError::DiskFull(message),
+            Some(ErrorKind::NotFound) => GinsengError::PathNotFound(message),
+            Some(ErrorKind::PermissionDenied) => GinsengError::PermissionDenied(message),
+            _ => GinsengError::Other(message),
+        }
+    }
+}
+
+impl From<String> for GinsengError {
+    fn from(message: String) -> Self {
+        GinsengError::Other(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_are_classified_by_kind() {
+        let error: GinsengError = anyhow::Error::from(DownloadError::ShareExpired).into();
+        assert_eq!(
+            error,
+            GinsengError::ShareExpired("This share has expired".to_string())
+        );
+
+        let disk_full = std::io::Error::from(ErrorKind::StorageFull);
+        let error: GinsengError = anyhow::Error::new(disk_full)
+            .context("Failed to export 'a.txt'")
+            .into();
+        assert_eq!(
+            error,
+            GinsengError::DiskFull("Failed to export 'a.txt'".to_string())
+        );
+
+        let error: GinsengError = anyhow::anyhow!("Something else").into();
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "code": "other", "message": "Something else" })
+        );
+    }
+}
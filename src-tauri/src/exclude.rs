@@ -0,0 +1,214 @@
+//! Leaving files out of shared directories
+//!
+//! Sharing a project folder would otherwise include dependencies and build
+//! artifacts such as `node_modules` or `target`. Exclude patterns use the
+//! `.gitignore` syntax and are matched relative to each shared directory, so
+//! `node_modules` leaves out every folder of that name, `*.log` every log
+//! file, and `/build` only the top-level build folder. Optionally, the
+//! `.gitignore` and `.ginsengignore` files found in shared directories are
+//! followed as well, each applying to the folder it is in.
+//!
+//! Files that are shared individually are never excluded, since they were
+//! picked on purpose.
+
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Names of the ignore files followed when enabled, in order of precedence
+pub const IGNORE_FILE_NAMES: [&str; 2] = [".ginsengignore", ".gitignore"];
+
+/// Which files of shared directories are left out
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExcludeRules {
+    /// Patterns in `.gitignore` syntax (e.g., "node_modules", "*.log")
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Whether `.gitignore` and `.ginsengignore` files are followed too
+    #[serde(default)]
+    pub ignore_files: bool,
+}
+
+/// Decides which entries of a shared directory are left out
+struct ExcludeFilter {
+    /// The shared directory
+    root: PathBuf,
+    /// The exclude patterns, relative to the shared directory
+    patterns: Gitignore,
+    /// Whether ignore files are followed
+    ignore_files: bool,
+    /// Ignore files read so far, by the directory they are in
+    directories: HashMap<PathBuf, Gitignore>,
+}
+
+impl ExcludeFilter {
+    /// Creates a filter for a shared directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern is invalid.
+    fn new(root: &Path, rules: &ExcludeRules) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &rules.patterns {
+            builder.add_line(None, pattern).map_err(|error| {
+                anyhow::anyhow!("Invalid exclude pattern '{}': {}", pattern, error)
+            })?;
+        }
+        let patterns = builder
+            .build()
+            .map_err(|error| anyhow::anyhow!("Invalid exclude patterns: {}", error))?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            patterns,
+            ignore_files: rules.ignore_files,
+            directories: HashMap::new(),
+        })
+    }
+
+    /// Checks whether an entry of the shared directory is left out.
+    ///
+    /// Entries are checked top-down, so the folders containing an entry have
+    /// already been checked and are not excluded.
+    fn is_excluded(&mut self, path: &Path, is_dir: bool) -> bool {
+        if path == self.root {
+            return false;
+        }
+        if self.patterns.matched(path, is_dir).is_ignore() {
+            return true;
+        }
+        if !self.ignore_files {
+            return false;
+        }
+
+        // The ignore file closest to the entry decides
+        for directory in path.ancestors().skip(1) {
+            match self.ignore_file(directory).matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+            if directory == self.root {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Returns the ignore rules of a directory, reading them on first use.
+    ///
+    /// Ignore files that cannot be read are treated as empty.
+    fn ignore_file(&mut self, directory: &Path) -> &Gitignore {
+        self.directories
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| {
+                let mut builder = GitignoreBuilder::new(directory);
+                // Added last so its rules take precedence over .gitignore
+                for name in IGNORE_FILE_NAMES.iter().rev() {
+                    let path = directory.join(name);
+                    if path.is_file() {
+                        builder.add(path);
+                    }
+                }
+                builder.build().unwrap_or_else(|_| Gitignore::empty())
+            })
+    }
+}
+
+/// Walks a shared directory, leaving out excluded files and folders.
+///
+/// Excluded folders are not descended into.
+///
+/// # Errors
+///
+/// Returns an error if an exclude pattern is invalid.
+pub fn walk_directory(
+    root: &Path,
+    rules: &ExcludeRules,
+) -> Result<impl Iterator<Item = walkdir::Result<DirEntry>>> {
+    let mut filter = ExcludeFilter::new(root, rules)?;
+    Ok(WalkDir::new(root)
+        .into_iter()
+        .filter_entry(move |entry| !filter.is_excluded(entry.path(), entry.file_type().is_dir())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn walked_files(root: &Path, rules: &ExcludeRules) -> Vec<String> {
+        let mut files: Vec<String> = walk_directory(root, rules)
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_patterns_and_ignore_files() {
+        let root = TempDir::new().unwrap();
+        for file in [
+            "index.js",
+            "debug.log",
+            "node_modules/left-pad/index.js",
+            "build/out.js",
+            "src/build/keep.js",
+            "src/secret.env",
+            "src/notes.txt",
+        ] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "content").unwrap();
+        }
+        std::fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(root.path().join("src/.ginsengignore"), "*.env\n").unwrap();
+
+        let mut rules = ExcludeRules {
+            patterns: vec!["node_modules".to_string(), "/build".to_string()],
+            ignore_files: false,
+        };
+        assert_eq!(
+            walked_files(root.path(), &rules),
+            vec![
+                ".gitignore",
+                "debug.log",
+                "index.js",
+                "src/.ginsengignore",
+                "src/build/keep.js",
+                "src/notes.txt",
+                "src/secret.env",
+            ]
+        );
+
+        rules.ignore_files = true;
+        assert_eq!(
+            walked_files(root.path(), &rules),
+            vec![
+                ".gitignore",
+                "index.js",
+                "src/.ginsengignore",
+                "src/build/keep.js",
+                "src/notes.txt",
+            ]
+        );
+
+        rules.patterns = vec!["{src".to_string()];
+        assert!(walk_directory(root.path(), &rules).is_err());
+    }
+}
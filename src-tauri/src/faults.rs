@@ -0,0 +1,174 @@
+//! Fault injection for exercising transfers against bad networks and disks
+//!
+//! Only compiled with the `fault-injection` feature. Faults are configured
+//! through environment variables read on first use:
+//!
+//! - `GINSENG_FAULT_LATENCY_MS` - delay before each blob download starts
+//! - `GINSENG_FAULT_BANDWIDTH` - cap in bytes per second applied to both
+//!   directions, on top of the configured bandwidth limits
+//! - `GINSENG_FAULT_ERROR_RATE` - chance between 0 and 1 that a chunk of data
+//!   fails, which aborts the stream it belongs to
+//! - `GINSENG_FAULT_DISK_BANDWIDTH` - cap in bytes per second on writing
+//!   received files to disk, simulating a slow HDD or SD card
+//!
+//! Downloads see all three faults. Uploads are capped and fail at random, but
+//! are not delayed, since the provider reports requests one at a time.
+
+use crate::bandwidth::BandwidthLimiter;
+use anyhow::Result;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Faults to inject, as read from the environment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultConfig {
+    /// Delay before each blob download starts
+    pub latency: Duration,
+    /// Cap in bytes per second for each direction, unlimited if None
+    pub bandwidth: Option<u64>,
+    /// Chance that a chunk of data fails, between 0 and 1
+    pub error_rate: f64,
+    /// Cap in bytes per second on writing files to disk, unlimited if None
+    pub disk_bandwidth: Option<u64>,
+}
+
+impl FaultConfig {
+    /// Reads the configuration from the environment
+    pub fn from_env() -> Self {
+        Self::parse(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the configuration using the given variable lookup
+    ///
+    /// Variables that are missing or invalid leave their fault disabled.
+    fn parse(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let number = |name: &str| lookup(name).and_then(|value| value.trim().parse::<f64>().ok());
+        Self {
+            latency: number("GINSENG_FAULT_LATENCY_MS")
+                .filter(|millis| *millis > 0.0)
+                .map_or(Duration::ZERO, |millis| {
+                    Duration::from_secs_f64(millis / 1000.0)
+                }),
+            bandwidth: number("GINSENG_FAULT_BANDWIDTH")
+                .filter(|rate| *rate >= 1.0)
+                .map(|rate| rate as u64),
+            error_rate: number("GINSENG_FAULT_ERROR_RATE").map_or(0.0, |rate| rate.clamp(0.0, 1.0)),
+            disk_bandwidth: number("GINSENG_FAULT_DISK_BANDWIDTH")
+                .filter(|rate| *rate >= 1.0)
+                .map(|rate| rate as u64),
+        }
+    }
+}
+
+/// Injected faults with the state they need
+struct Faults {
+    config: FaultConfig,
+    download: BandwidthLimiter,
+    upload: BandwidthLimiter,
+    disk: BandwidthLimiter,
+}
+
+/// Returns the faults configured for this process.
+fn faults() -> &'static Faults {
+    static FAULTS: OnceLock<Faults> = OnceLock::new();
+    FAULTS.get_or_init(|| {
+        let config = FaultConfig::from_env();
+        Faults {
+            download: BandwidthLimiter::new(config.bandwidth),
+            upload: BandwidthLimiter::new(config.bandwidth),
+            disk: BandwidthLimiter::new(config.disk_bandwidth),
+            config,
+        }
+    })
+}
+
+/// Delays the start of a blob download by the configured latency
+pub async fn download_started() {
+    let latency = faults().config.latency;
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+}
+
+/// Paces received bytes by the fault cap and fails them at random
+///
+/// # Errors
+///
+/// Returns an error if the chunk was picked to fail.
+pub async fn bytes_received(bytes: u64) -> Result<()> {
+    let faults = faults();
+    faults.download.acquire(bytes).await;
+    fail_at_random(faults.config.error_rate)
+}
+
+/// Paces sent bytes by the fault cap and fails them at random
+///
+/// # Errors
+///
+/// Returns an error if the chunk was picked to fail.
+pub async fn bytes_sent(bytes: u64) -> Result<()> {
+    let faults = faults();
+    faults.upload.acquire(bytes).await;
+    fail_at_random(faults.config.error_rate)
+}
+
+/// Paces bytes written to disk by the disk fault cap
+pub async fn bytes_written(bytes: u64) {
+    faults().disk.acquire(bytes).await;
+}
+
+/// Fails with the given probability.
+fn fail_at_random(error_rate: f64) -> Result<()> {
+    if error_rate > 0.0 && random_fraction() < error_rate {
+        anyhow::bail!("Injected fault");
+    }
+    Ok(())
+}
+
+/// Returns a random number in `[0, 1)`.
+///
+/// Every `RandomState` is seeded randomly, which is plenty for picking faults
+/// without pulling in a random number crate.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = FaultConfig::parse(|name| match name {
+            "GINSENG_FAULT_LATENCY_MS" => Some("250".to_string()),
+            "GINSENG_FAULT_BANDWIDTH" => Some("65536".to_string()),
+            "GINSENG_FAULT_ERROR_RATE" => Some("1.5".to_string()),
+            "GINSENG_FAULT_DISK_BANDWIDTH" => Some("1048576".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            config,
+            FaultConfig {
+                latency: Duration::from_millis(250),
+                bandwidth: Some(65536),
+                error_rate: 1.0,
+                disk_bandwidth: Some(1048576),
+            }
+        );
+
+        let config = FaultConfig::parse(|name| {
+            (name == "GINSENG_FAULT_LATENCY_MS").then(|| "soon".to_string())
+        });
+        assert_eq!(config, FaultConfig::default());
+    }
+
+    #[test]
+    fn test_fail_at_random_extremes() {
+        assert!(fail_at_random(0.0).is_ok());
+        assert!(fail_at_random(1.0).is_err());
+        assert!((0.0..1.0).contains(&random_fraction()));
+    }
+}
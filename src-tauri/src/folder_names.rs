@@ -0,0 +1,97 @@
+//! Names of the folders received files are collected in
+//!
+//! A share of several loose files has no folder name of its own, so it is
+//! saved in a folder named after when it was received, e.g.
+//! `Ginseng 2024-06-01 14-32`. The name follows a template kept in the
+//! settings, in which these placeholders are replaced:
+//!
+//! - `{date}` - the local date, e.g. `2024-06-01`
+//! - `{time}` - the local time of day, e.g. `14-32`
+//! - `{timestamp}` - the Unix timestamp, e.g. `1717252320`
+//!
+//! Names are made safe to create on every platform with the rules of
+//! [`crate::paths`], and a folder that already exists gets a counter, e.g.
+//! `Ginseng 2024-06-01 14-32 (1)`.
+
+use crate::paths::{sanitize_component, unique_folder_path};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+
+/// Template used when the settings name none
+pub const DEFAULT_TEMPLATE: &str = "Ginseng {date} {time}";
+
+/// Returns the folder name a template gives at a point in time.
+///
+/// # Arguments
+///
+/// * `template` - The naming template
+/// * `now` - When the files are received
+///
+/// # Returns
+///
+/// The name, safe to create on every platform; never empty
+pub fn folder_name(template: &str, now: DateTime<Local>) -> String {
+    let name = template
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H-%M").to_string())
+        .replace("{timestamp}", &now.timestamp().to_string());
+    sanitize_component(name.trim())
+}
+
+/// Returns a folder in `base` that does not exist yet.
+///
+/// The folder is named `name` if that is free, otherwise a counter is added,
+/// e.g. `Ginseng 2024-06-01 14-32 (1)`. Unlike file names, everything after
+/// a dot is kept as part of the name.
+pub fn unique_folder(base: &Path, name: &str) -> PathBuf {
+    unique_folder_path(&base.join(name), Path::exists)
+}
+
+/// Checks that a naming template can be used.
+///
+/// # Errors
+///
+/// Returns an error if the template is empty.
+pub fn validate_template(template: &str) -> Result<()> {
+    if template.trim().is_empty() {
+        anyhow::bail!("The folder name template cannot be empty");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_folder_name_and_collisions() {
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 14, 32, 5).unwrap();
+        assert_eq!(
+            folder_name(DEFAULT_TEMPLATE, now),
+            "Ginseng 2024-06-01 14-32"
+        );
+        assert_eq!(
+            folder_name("ginseng_files_{timestamp}", now),
+            format!("ginseng_files_{}", now.timestamp())
+        );
+        assert_eq!(folder_name("Inbox: {date}...", now), "Inbox_ 2024-06-01");
+        assert_eq!(folder_name("  ", now), "_");
+
+        let base = TempDir::new().unwrap();
+        let name = folder_name(DEFAULT_TEMPLATE, now);
+        assert_eq!(unique_folder(base.path(), &name), base.path().join(&name));
+
+        std::fs::create_dir(base.path().join(&name)).unwrap();
+        std::fs::create_dir(base.path().join(format!("{} (1)", name))).unwrap();
+        assert_eq!(
+            unique_folder(base.path(), &name),
+            base.path().join(format!("{} (2)", name))
+        );
+
+        assert!(validate_template(DEFAULT_TEMPLATE).is_ok());
+        assert!(validate_template(" ").is_err());
+    }
+}
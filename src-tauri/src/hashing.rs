@@ -0,0 +1,71 @@
+//! Worker threads for hashing and verifying blob data
+//!
+//! The blob store computes the BLAKE3 hash tree of every file it imports and
+//! verifies every chunk it receives from a peer. Both take a while for
+//! multi-GB files and would otherwise run on the async runtime, stalling
+//! progress events and other transfers until they finish. The store is
+//! therefore started on a runtime of its own, whose threads do nothing but
+//! this work; requests to the store are still made from any runtime.
+
+use iroh_blobs::store::mem::MemStore;
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+/// Name of the threads of the hashing runtime
+const THREAD_NAME: &str = "ginseng-hashing";
+
+/// Returns the runtime hashing and verifying blob data.
+///
+/// The runtime is started on first use with one thread per CPU core and runs
+/// until the process exits.
+///
+/// # Returns
+///
+/// The runtime, or None if its threads could not be started
+pub fn runtime() -> Option<&'static Runtime> {
+    static RUNTIME: OnceLock<Option<Runtime>> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            Builder::new_multi_thread()
+                .thread_name(THREAD_NAME)
+                .enable_all()
+                .build()
+                .ok()
+        })
+        .as_ref()
+}
+
+/// Creates an in-memory blob store that hashes and verifies on the hashing runtime.
+///
+/// Falls back to a store on the current runtime if the hashing runtime could
+/// not be started.
+pub fn new_store() -> MemStore {
+    match runtime() {
+        Some(runtime) => {
+            let _guard = runtime.enter();
+            MemStore::new()
+        }
+        None => MemStore::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_store_runs_on_hashing_threads() {
+        let thread_name = runtime()
+            .unwrap()
+            .spawn(async { std::thread::current().name().map(str::to_string) })
+            .await
+            .unwrap();
+        assert_eq!(thread_name.as_deref(), Some(THREAD_NAME));
+
+        let store = new_store();
+        let content = vec![7u8; 3 * 1024 * 1024];
+        let tag = store.add_bytes(content.clone()).await.unwrap();
+        assert_eq!(tag.hash, iroh_blobs::Hash::new(&content));
+        assert_eq!(store.get_bytes(tag.hash).await.unwrap(), content);
+    }
+}
@@ -0,0 +1,377 @@
+//! History of finished shares and downloads
+//!
+//! Every share and download this node finishes, whether it completed, failed
+//! or was cancelled, is recorded with its ticket, peer, files, sizes and
+//! timestamps in an SQLite database in the user's config directory. Unlike
+//! the transfer log, which only keeps failed transfers until they are retried,
+//! the history keeps every transfer until it is cleared.
+
+use crate::progress::{FileStatus, TransferProgress, TransferType};
+use crate::shares::ShareId;
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the database the history is stored in
+const HISTORY_FILE_NAME: &str = "history.db";
+
+/// Schema of the history database
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS transfers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    transfer_type TEXT NOT NULL,
+    share_id TEXT,
+    ticket TEXT,
+    peer TEXT,
+    files TEXT NOT NULL,
+    total_size INTEGER NOT NULL,
+    transferred_bytes INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    error TEXT
+)";
+
+/// How a transfer ended
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+    /// Every file was transferred
+    Completed,
+    /// The transfer finished, but some files failed
+    PartiallyFailed,
+    /// The transfer failed as a whole
+    Failed,
+    /// The transfer was cancelled
+    Cancelled,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Completed => "completed",
+            Outcome::PartiallyFailed => "partiallyFailed",
+            Outcome::Failed => "failed",
+            Outcome::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "completed" => Outcome::Completed,
+            "partiallyFailed" => Outcome::PartiallyFailed,
+            "cancelled" => Outcome::Cancelled,
+            _ => Outcome::Failed,
+        }
+    }
+}
+
+/// A file of a finished transfer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryFile {
+    /// Path of the file within the share
+    pub relative_path: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Why the file failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A finished share or download
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// Position of the entry in the history, assigned when it is recorded
+    pub id: i64,
+    /// Whether files were shared or downloaded
+    pub transfer_type: TransferType,
+    /// ID of the share, once known
+    pub share_id: Option<ShareId>,
+    /// The ticket created for the share or downloaded from
+    pub ticket: Option<String>,
+    /// Endpoint ID of the peer downloaded from, if known
+    pub peer: Option<String>,
+    /// The files of the transfer
+    pub files: Vec<HistoryFile>,
+    /// Total size of the files in bytes
+    pub total_size: u64,
+    /// Bytes actually transferred
+    pub transferred_bytes: u64,
+    /// Unix timestamp when the transfer started
+    pub started_at: u64,
+    /// Unix timestamp when the transfer ended
+    pub finished_at: u64,
+    /// How the transfer ended
+    pub outcome: Outcome,
+    /// Why the transfer failed, if it did
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Creates the entry of a transfer from its last progress snapshot
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - Progress of the transfer when it ended
+    /// * `outcome` - How the transfer ended
+    /// * `error` - Why the transfer failed, if it did
+    pub fn from_snapshot(
+        snapshot: &TransferProgress,
+        outcome: Outcome,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            id: 0,
+            transfer_type: snapshot.transfer_type.clone(),
+            share_id: snapshot.share_id.clone(),
+            ticket: None,
+            peer: None,
+            files: snapshot
+                .files
+                .iter()
+                .map(|file| HistoryFile {
+                    relative_path: file.relative_path.clone(),
+                    size: file.total_bytes,
+                    error: (file.status == FileStatus::Failed)
+                        .then(|| file.error.clone())
+                        .flatten(),
+                })
+                .collect(),
+            total_size: snapshot.total_bytes,
+            transferred_bytes: snapshot.transferred_bytes,
+            started_at: snapshot.start_time,
+            finished_at: current_unix_time(),
+            outcome,
+            error,
+        }
+    }
+
+    /// Reads an entry from a row of the transfers table
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let transfer_type: String = row.get("transfer_type")?;
+        let files: String = row.get("files")?;
+        let outcome: String = row.get("outcome")?;
+        Ok(Self {
+            id: row.get("id")?,
+            transfer_type: if transfer_type == "upload" {
+                TransferType::Upload
+            } else {
+                TransferType::Download
+            },
+            share_id: row.get("share_id")?,
+            ticket: row.get("ticket")?,
+            peer: row.get("peer")?,
+            files: serde_json::from_str(&files).unwrap_or_default(),
+            total_size: row.get::<_, i64>("total_size")? as u64,
+            transferred_bytes: row.get::<_, i64>("transferred_bytes")? as u64,
+            started_at: row.get::<_, i64>("started_at")? as u64,
+            finished_at: row.get::<_, i64>("finished_at")? as u64,
+            outcome: Outcome::parse(&outcome),
+            error: row.get("error")?,
+        })
+    }
+}
+
+/// Thread-safe history of finished transfers, stored in an SQLite database
+#[derive(Debug, Clone)]
+pub struct TransferHistory {
+    connection: Option<Arc<Mutex<Connection>>>,
+}
+
+impl TransferHistory {
+    /// Creates a history that keeps transfers for this session only
+    pub fn in_memory() -> Self {
+        let connection = Connection::open_in_memory()
+            .and_then(|connection| connection.execute(SCHEMA, []).map(|_| connection))
+            .ok();
+        Self {
+            connection: connection.map(|connection| Arc::new(Mutex::new(connection))),
+        }
+    }
+
+    /// Opens the history database at a path, creating it if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be created or opened
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).map_err(|error| {
+            anyhow::anyhow!("Invalid history database '{}': {}", path.display(), error)
+        })?;
+        connection.execute(SCHEMA, [])?;
+
+        Ok(Self {
+            connection: Some(Arc::new(Mutex::new(connection))),
+        })
+    }
+
+    /// Returns where the history database is kept, if the config directory is known
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|config_dir| config_dir.join("ginseng").join(HISTORY_FILE_NAME))
+    }
+
+    /// Opens the history database in the user's config directory
+    ///
+    /// Falls back to a history for this session only if the config directory
+    /// cannot be determined or the database cannot be opened.
+    pub fn open_default() -> Self {
+        Self::default_path()
+            .and_then(|path| Self::open(&path).ok())
+            .unwrap_or_else(Self::in_memory)
+    }
+
+    /// Records a finished transfer
+    ///
+    /// Recording is best effort: the transfer itself has already ended.
+    pub async fn record(&self, entry: HistoryEntry) {
+        let recorded = self
+            .with_connection(move |connection| {
+                let files = serde_json::to_string(&entry.files).unwrap_or_default();
+                connection.execute(
+                    "INSERT INTO transfers (transfer_type, share_id, ticket, peer, files, \
+                     total_size, transferred_bytes, started_at, finished_at, outcome, error) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        match entry.transfer_type {
+                            TransferType::Upload => "upload",
+                            TransferType::Download => "download",
+                        },
+                        entry.share_id,
+                        entry.ticket,
+                        entry.peer,
+                        files,
+                        entry.total_size as i64,
+                        entry.transferred_bytes as i64,
+                        entry.started_at as i64,
+                        entry.finished_at as i64,
+                        entry.outcome.as_str(),
+                        entry.error,
+                    ],
+                )
+            })
+            .await;
+        if let Err(error) = recorded {
+            tracing::warn!(%error, "transfer could not be added to the history");
+        }
+    }
+
+    /// Returns recorded transfers, newest first
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Largest number of transfers returned, all if None
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read
+    pub async fn list(&self, limit: Option<usize>) -> Result<Vec<HistoryEntry>> {
+        let limit = limit.map_or(-1, |limit| limit as i64);
+        self.with_connection(move |connection| {
+            let mut statement =
+                connection.prepare("SELECT * FROM transfers ORDER BY id DESC LIMIT ?1")?;
+            let entries = statement.query_map([limit], HistoryEntry::from_row)?;
+            entries.collect()
+        })
+        .await
+    }
+
+    /// Removes every recorded transfer
+    ///
+    /// # Returns
+    ///
+    /// The number of transfers removed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be written
+    pub async fn clear(&self) -> Result<usize> {
+        self.with_connection(|connection| connection.execute("DELETE FROM transfers", []))
+            .await
+    }
+
+    /// Runs a query on the database without blocking the async runtime
+    async fn with_connection<T, F>(&self, query: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = self
+            .connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Transfer history is unavailable"))?;
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Transfer history is unavailable"))?;
+            query(&connection).map_err(anyhow::Error::from)
+        })
+        .await?
+    }
+}
+
+/// Returns the current Unix timestamp in seconds
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::FileProgress;
+    use tempfile::tempdir;
+
+    fn snapshot(transfer_type: TransferType) -> TransferProgress {
+        let mut snapshot = TransferProgress::new("transfer".to_string(), transfer_type);
+        let mut failed = FileProgress::new("b.txt".to_string(), "b.txt".to_string(), 20);
+        failed.status = FileStatus::Failed;
+        failed.error = Some("unreadable".to_string());
+        snapshot.files = vec![
+            FileProgress::new("a.txt".to_string(), "a.txt".to_string(), 10),
+            failed,
+        ];
+        snapshot.total_bytes = 30;
+        snapshot
+    }
+
+    #[tokio::test]
+    async fn test_record_list_and_clear_history() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join(HISTORY_FILE_NAME);
+        let history = TransferHistory::open(&path).unwrap();
+
+        let mut share = HistoryEntry::from_snapshot(
+            &snapshot(TransferType::Upload),
+            Outcome::PartiallyFailed,
+            None,
+        );
+        share.ticket = Some("ticket".to_string());
+        history.record(share.clone()).await;
+        let download = HistoryEntry::from_snapshot(
+            &snapshot(TransferType::Download),
+            Outcome::Failed,
+            Some("peer offline".to_string()),
+        );
+        history.record(download.clone()).await;
+
+        let reopened = TransferHistory::open(&path).unwrap();
+        let entries = reopened.list(None).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], HistoryEntry { id: 2, ..download });
+        assert_eq!(entries[1], HistoryEntry { id: 1, ..share });
+        assert_eq!(entries[1].files[1].error.as_deref(), Some("unreadable"));
+        assert_eq!(reopened.list(Some(1)).await.unwrap().len(), 1);
+
+        assert_eq!(reopened.clear().await.unwrap(), 2);
+        assert!(reopened.list(None).await.unwrap().is_empty());
+    }
+}
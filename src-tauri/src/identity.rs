@@ -0,0 +1,81 @@
+//! Identity of this node across restarts
+//!
+//! An endpoint's ID is derived from its secret key, and every ticket names the
+//! endpoint serving the share by that ID. The app keeps its secret key in the
+//! user's config directory, so tickets it handed out before a restart still
+//! point at it afterwards. The CLI gets a new identity every time it runs, so
+//! it can run next to the app without two endpoints sharing one ID.
+
+use anyhow::Result;
+use iroh::SecretKey;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Name of the file the secret key is stored in
+const SECRET_KEY_FILE_NAME: &str = "secret_key";
+
+/// Returns where the app keeps its secret key, if the config directory is known
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("ginseng").join(SECRET_KEY_FILE_NAME))
+}
+
+/// Loads a secret key saved with [`save_secret_key`].
+///
+/// # Returns
+///
+/// The secret key, or None if none was saved yet
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read or holds no key.
+pub async fn load_secret_key(path: &Path) -> Result<Option<SecretKey>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(path).await?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid secret key file '{}'", path.display()))?;
+    Ok(Some(SecretKey::from_bytes(&bytes)))
+}
+
+/// Saves a secret key, readable by the current user only.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub async fn save_secret_key(path: &Path, secret_key: &SecretKey) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, secret_key.to_bytes()).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_secret_key_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join(SECRET_KEY_FILE_NAME);
+        assert!(load_secret_key(&path).await.unwrap().is_none());
+
+        let secret_key = SecretKey::from_bytes(&[7u8; 32]);
+        save_secret_key(&path, &secret_key).await.unwrap();
+        let loaded = load_secret_key(&path).await.unwrap().unwrap();
+        assert_eq!(loaded.public(), secret_key.public());
+
+        std::fs::write(&path, b"too short").unwrap();
+        assert!(load_secret_key(&path).await.is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Raw access to the blob store for debugging
+//!
+//! Shares, the library and transfers all keep their data in one blob store,
+//! with tags protecting the blobs that are still needed. This module lists
+//! what the store holds and which tags reference it, reads blobs for preview,
+//! and deletes single blobs. It backs the storage inspector, a developer tool
+//! for finding out why the store holds what it does.
+
+use anyhow::Result;
+use futures::StreamExt;
+use iroh_blobs::{store::mem::MemStore, Hash};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Number of bytes returned by a preview unless the caller asks for more
+pub const DEFAULT_PREVIEW_BYTES: usize = 64 * 1024;
+
+/// A blob held in the store
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobEntry {
+    /// Hash of the blob
+    pub hash: String,
+    /// Bytes of the blob present in the store
+    pub size: u64,
+    /// Whether the whole blob is present, not just some ranges of it
+    pub complete: bool,
+    /// Names of the tags protecting the blob
+    pub tags: Vec<String>,
+    /// Number of tags protecting the blob; unreferenced blobs may be collected
+    pub reference_count: usize,
+}
+
+/// A tag protecting a blob from being collected
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagEntry {
+    /// Name of the tag, e.g. `library/<share>/<file>`
+    pub name: String,
+    /// Hash of the blob the tag protects
+    pub hash: String,
+    /// Format of the tagged blob, `Raw` or `HashSeq`
+    pub format: String,
+}
+
+/// The start of a blob's content
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobPreview {
+    /// Hash of the blob
+    pub hash: String,
+    /// Size of the whole blob in bytes
+    pub size: u64,
+    /// The first bytes of the blob
+    pub bytes: Vec<u8>,
+    /// Whether the blob is longer than the returned bytes
+    pub truncated: bool,
+}
+
+/// Lists every tag in the store, sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if the tags cannot be listed.
+pub async fn list_tags(store: &MemStore) -> Result<Vec<TagEntry>> {
+    let mut tags = store
+        .tags()
+        .list()
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to list tags: {}", error))?;
+
+    let mut entries = Vec::new();
+    while let Some(tag) = tags.next().await {
+        let Ok(tag) = tag else {
+            continue;
+        };
+        entries.push(TagEntry {
+            name: tag.name.to_string(),
+            hash: tag.hash.to_string(),
+            format: format!("{:?}", tag.format),
+        });
+    }
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Lists every blob in the store with its size and the tags referencing it.
+///
+/// Blobs are sorted by hash.
+///
+/// # Errors
+///
+/// Returns an error if the blobs or tags cannot be listed.
+pub async fn list_blobs(store: &MemStore) -> Result<Vec<BlobEntry>> {
+    let hashes = store
+        .blobs()
+        .list()
+        .hashes()
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to list blobs: {}", error))?;
+
+    let mut tags_by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for tag in list_tags(store).await? {
+        tags_by_hash.entry(tag.hash).or_default().push(tag.name);
+    }
+
+    let mut entries = Vec::with_capacity(hashes.len());
+    for hash in hashes {
+        let (size, complete) = match store.remote().local(hash).await {
+            Ok(local) => (local.local_bytes(), local.is_complete()),
+            Err(_) => (0, false),
+        };
+        let hash = hash.to_string();
+        let tags = tags_by_hash.remove(&hash).unwrap_or_default();
+        entries.push(BlobEntry {
+            hash,
+            size,
+            complete,
+            reference_count: tags.len(),
+            tags,
+        });
+    }
+
+    entries.sort_by(|a, b| a.hash.cmp(&b.hash));
+    Ok(entries)
+}
+
+/// Reads the start of a blob for preview.
+///
+/// # Arguments
+///
+/// * `store` - The store holding the blob
+/// * `hash` - Hash of the blob to read
+/// * `max_bytes` - Maximum number of bytes to return
+///
+/// # Errors
+///
+/// Returns an error if the hash is invalid or the blob is not complete in the
+/// store.
+pub async fn read_blob(store: &MemStore, hash: &str, max_bytes: usize) -> Result<BlobPreview> {
+    let parsed = parse_hash(hash)?;
+    let bytes = store
+        .get_bytes(parsed)
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to read blob '{}': {}", hash, error))?;
+
+    let size = bytes.len() as u64;
+    let truncated = bytes.len() > max_bytes;
+    Ok(BlobPreview {
+        hash: hash.to_string(),
+        size,
+        bytes: bytes[..bytes.len().min(max_bytes)].to_vec(),
+        truncated,
+    })
+}
+
+/// Deletes a blob from the store along with every tag protecting it.
+///
+/// Shares and library entries using the blob can no longer be served or
+/// exported afterwards, so this is meant for debugging only.
+///
+/// # Returns
+///
+/// The number of tags that were removed.
+///
+/// # Errors
+///
+/// Returns an error if the hash is invalid, the tags cannot be listed, or the
+/// blob cannot be deleted.
+pub async fn delete_blob(store: &MemStore, hash: &str) -> Result<usize> {
+    let parsed = parse_hash(hash)?;
+
+    let mut dropped_tags = 0;
+    for tag in list_tags(store).await? {
+        if tag.hash == hash && store.tags().delete(tag.name).await.is_ok() {
+            dropped_tags += 1;
+        }
+    }
+
+    store
+        .blobs()
+        .delete([parsed])
+        .await
+        .map_err(|error| anyhow::anyhow!("Failed to delete blob '{}': {}", hash, error))?;
+
+    Ok(dropped_tags)
+}
+
+/// Parses a blob hash given by the frontend.
+fn parse_hash(hash: &str) -> Result<Hash> {
+    hash.parse()
+        .map_err(|error| anyhow::anyhow!("Invalid hash '{}': {}", hash, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inspect_and_delete_blob() {
+        let store = MemStore::new();
+        let tag = store.add_bytes(b"hello inspector".to_vec()).await.unwrap();
+        let hash = tag.hash.to_string();
+
+        let blobs = list_blobs(&store).await.unwrap();
+        let entry = blobs.iter().find(|entry| entry.hash == hash).unwrap();
+        assert_eq!(entry.size, 15);
+        assert!(entry.complete);
+        assert_eq!(entry.reference_count, 1);
+
+        let preview = read_blob(&store, &hash, 5).await.unwrap();
+        assert_eq!(preview.bytes, b"hello");
+        assert_eq!(preview.size, 15);
+        assert!(preview.truncated);
+
+        assert_eq!(delete_blob(&store, &hash).await.unwrap(), 1);
+        assert!(list_tags(&store)
+            .await
+            .unwrap()
+            .iter()
+            .all(|tag| tag.hash != hash));
+        assert!(read_blob(&store, "not a hash", 5).await.is_err());
+    }
+}
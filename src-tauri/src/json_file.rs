@@ -0,0 +1,98 @@
+//! JSON documents kept in the user's config directory
+//!
+//! Peer statistics, receipts and saved shares are each stored as a single JSON
+//! document that is read whole when the app starts and rewritten whole when it
+//! changes. A document is written to a temporary file next to it first and
+//! then renamed over the old one, so a crash while writing leaves the previous
+//! version in place instead of a truncated file.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Returns the path of a file in the app's config directory, if the config
+/// directory can be determined
+pub fn config_path(file_name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("ginseng").join(file_name))
+}
+
+/// Reads a JSON document from a file.
+///
+/// # Arguments
+///
+/// * `path` - The file to read
+/// * `kind` - What the file holds, used in the error message
+///
+/// # Returns
+///
+/// The parsed document, or None if the file does not exist
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read or parsed.
+pub async fn read<T: DeserializeOwned>(path: &Path, kind: &str) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).await?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|error| anyhow::anyhow!("Invalid {} file '{}': {}", kind, path.display(), error))
+}
+
+/// Writes a value as a JSON document, replacing the file atomically.
+///
+/// The parent directory is created if needed.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be serialized or the file cannot be
+/// written or renamed.
+pub async fn write<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let json = serde_json::to_string_pretty(value)?;
+    let temp_path = temp_path(path);
+    if let Err(error) = fs::write(&temp_path, json).await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(error.into());
+    }
+    fs::rename(&temp_path, path).await?;
+    Ok(())
+}
+
+/// Returns the path a document is written to before it replaces `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_write_and_read() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join("values.json");
+
+        assert_eq!(read::<Vec<u32>>(&path, "values").await.unwrap(), None);
+
+        write(&path, &[1, 2]).await.unwrap();
+        write(&path, &[3]).await.unwrap();
+        assert_eq!(
+            read::<Vec<u32>>(&path, "values").await.unwrap(),
+            Some(vec![3])
+        );
+        assert!(!temp_path(&path).exists());
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(read::<Vec<u32>>(&path, "values").await.is_err());
+    }
+}
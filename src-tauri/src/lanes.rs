@@ -0,0 +1,149 @@
+//! Priority lanes for blob downloads
+//!
+//! Small control blobs (share bundles and manifest pages) are fetched on a
+//! reserved lane with its own downloader, so they never queue behind the
+//! connections carrying bulk file data. Bulk downloads run on a bounded lane:
+//! only as many files as the network budget allows download at once across all
+//! transfers, and a file waits to start while control blobs are being fetched.
+//! A receiver opening a new share therefore sees its file list quickly even
+//! while large downloads are running.
+
+use crate::concurrency::{Budget, BudgetPermit};
+use iroh::Endpoint;
+use iroh_blobs::{api::downloader::Downloader, store::mem::MemStore};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Downloaders for the control and bulk lanes
+#[derive(Clone)]
+pub struct DownloadLanes {
+    gate: PriorityGate,
+    control: Downloader,
+    bulk: Downloader,
+}
+
+impl std::fmt::Debug for DownloadLanes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadLanes").finish_non_exhaustive()
+    }
+}
+
+impl DownloadLanes {
+    /// Creates the lanes, each with its own downloader and connections
+    ///
+    /// The bulk lane takes a slot of the `network` budget for every file.
+    pub fn new(store: &MemStore, endpoint: &Endpoint, network: Budget) -> Self {
+        Self {
+            gate: PriorityGate::new(network),
+            control: store.downloader(endpoint),
+            bulk: store.downloader(endpoint),
+        }
+    }
+
+    /// Enters the control lane for fetching a control blob
+    ///
+    /// Bulk downloads do not start new files until the returned lane is dropped.
+    pub fn control(&self) -> Lane<'_, ControlPermit> {
+        Lane {
+            downloader: &self.control,
+            _permit: self.gate.control(),
+        }
+    }
+
+    /// Enters the bulk lane for downloading file data
+    ///
+    /// Waits until no control blobs are being fetched and a bulk slot is free.
+    pub async fn bulk(&self) -> Lane<'_, BudgetPermit> {
+        Lane {
+            downloader: &self.bulk,
+            _permit: self.gate.bulk().await,
+        }
+    }
+}
+
+/// A downloader held on one lane until dropped
+pub struct Lane<'a, P> {
+    downloader: &'a Downloader,
+    _permit: P,
+}
+
+impl<P> Lane<'_, P> {
+    /// Returns the downloader of the lane
+    pub fn downloader(&self) -> &Downloader {
+        self.downloader
+    }
+}
+
+/// Orders control fetches ahead of bulk downloads
+#[derive(Debug, Clone)]
+struct PriorityGate {
+    control: Arc<watch::Sender<usize>>,
+    bulk: Budget,
+}
+
+impl PriorityGate {
+    fn new(bulk: Budget) -> Self {
+        Self {
+            control: Arc::new(watch::Sender::new(0)),
+            bulk,
+        }
+    }
+
+    /// Registers a control fetch until the permit is dropped
+    fn control(&self) -> ControlPermit {
+        self.control.send_modify(|in_flight| *in_flight += 1);
+        ControlPermit {
+            control: self.control.clone(),
+        }
+    }
+
+    /// Waits for control fetches to finish, then takes a bulk slot
+    async fn bulk(&self) -> BudgetPermit {
+        let mut control = self.control.subscribe();
+        // The sender lives as long as the gate, so waiting cannot fail
+        let _ = control.wait_for(|in_flight| *in_flight == 0).await;
+
+        self.bulk.acquire().await
+    }
+}
+
+/// Marks a control fetch as in flight until dropped
+#[derive(Debug)]
+pub struct ControlPermit {
+    control: Arc<watch::Sender<usize>>,
+}
+
+impl Drop for ControlPermit {
+    fn drop(&mut self) {
+        self.control.send_modify(|in_flight| *in_flight -= 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_bulk_waits_for_control_fetches() {
+        let gate = PriorityGate::new(Budget::new(1));
+
+        let control = gate.control();
+        assert!(timeout(Duration::from_millis(50), gate.bulk())
+            .await
+            .is_err());
+
+        drop(control);
+        let bulk = timeout(Duration::from_millis(50), gate.bulk())
+            .await
+            .unwrap();
+
+        // Control fetches never wait for bulk downloads
+        let _control = gate.control();
+        assert!(timeout(Duration::from_millis(50), gate.bulk())
+            .await
+            .is_err());
+        drop(bulk);
+    }
+}
@@ -1,21 +1,90 @@
+pub mod archive;
+pub mod bandwidth;
+pub mod bookmarks;
+pub mod capabilities;
+pub mod cleanup;
+pub mod collisions;
 mod commands;
+pub mod companion;
+pub mod compression;
+pub mod concurrency;
+pub mod context_menu;
 pub mod core;
+pub mod delta;
+pub mod devices;
+pub mod disk;
+pub mod dns;
+pub mod dropbox;
+pub mod errors;
+pub mod exclude;
+#[cfg(feature = "fault-injection")]
+mod faults;
+pub mod folder_names;
+pub mod hashing;
+pub mod history;
+pub mod identity;
+pub mod inspector;
+mod json_file;
+pub mod lanes;
+pub mod library;
+pub mod logging;
+pub mod netstats;
+pub mod organize;
+pub mod paths;
+pub mod peers;
+pub mod portmap;
 pub mod progress;
+mod provider;
+pub mod queue;
+pub mod receipts;
+pub mod relays;
+pub mod running;
+pub mod saved_shares;
+pub mod settings;
+pub mod share_card;
+pub mod share_diff;
+pub mod shares;
+pub mod sinks;
+pub mod sources;
 mod state;
+pub mod templates;
+pub mod tickets;
+pub mod transfers;
 mod utils;
-use tauri::Manager;
+pub mod warmup;
+pub mod watcher;
 
 pub use core::{GinsengCore, ShareType};
+#[cfg(desktop)]
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    logging::init(false);
+    let builder = tauri::Builder::default();
+    // Registered first, so a second launch (e.g. from the file manager context
+    // menu) hands its arguments over before starting anything else
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+        let cwd = std::path::PathBuf::from(cwd);
+        if let Some(paths) = context_menu::share_paths_from_args(args, &cwd) {
+            state::queue_context_menu_share(app, paths);
+        }
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }));
+    builder
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .manage(state::AppState::default())
         .setup(|app| {
-            let state = app.state::<state::AppState>();
-            tauri::async_runtime::block_on(state::setup_ginseng(state))?;
+            let cwd = std::env::current_dir().unwrap_or_default();
+            if let Some(paths) = context_menu::share_paths_from_args(std::env::args_os(), &cwd) {
+                state::queue_context_menu_share(app.handle(), paths);
+            }
+            state::spawn_setup(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -24,8 +93,86 @@ pub fn run() {
             commands::download_file,
             commands::download_files,
             commands::share_files_parallel,
+            commands::scan_directory,
             commands::download_files_parallel,
-            commands::node_info
+            commands::preview_share,
+            commands::diff_tickets,
+            commands::download_selected_files,
+            commands::split_pasted_tickets,
+            commands::download_raw_blob,
+            commands::cancel_transfer,
+            commands::list_queue,
+            commands::move_in_queue,
+            commands::create_followup,
+            commands::retry_failed_files,
+            commands::node_info,
+            commands::network_stats,
+            commands::start_ticket_watcher,
+            commands::start_companion_endpoint,
+            commands::stop_companion_endpoint,
+            commands::reset_companion_token,
+            commands::install_context_menu,
+            commands::uninstall_context_menu,
+            commands::context_menu_installed,
+            commands::take_context_menu_paths,
+            commands::log_directory,
+            commands::stop_ticket_watcher,
+            commands::export_ticket,
+            commands::load_ticket,
+            commands::format_dns_record,
+            commands::resolve_dns_ticket,
+            commands::open_drop_box,
+            commands::close_drop_box,
+            commands::send_to_drop_box,
+            commands::pair_device,
+            commands::unpair_device,
+            commands::send_to_device,
+            commands::save_device_group,
+            commands::delete_device_group,
+            commands::share_to_group,
+            commands::list_outbox,
+            commands::cancel_queued_push,
+            commands::list_receipts,
+            commands::list_history,
+            commands::clear_history,
+            commands::list_shares,
+            commands::share_activity,
+            commands::stop_share,
+            commands::unlock_share,
+            commands::export_share_card,
+            commands::library_shares,
+            commands::export_from_library,
+            commands::remove_from_library,
+            commands::list_store_blobs,
+            commands::list_store_tags,
+            commands::read_store_blob,
+            commands::delete_store_blob,
+            commands::peer_stats,
+            commands::list_peer_stats,
+            commands::get_settings,
+            commands::set_peer_pinned,
+            commands::set_share_pinned,
+            commands::get_bandwidth_limits,
+            commands::set_bandwidth_limits,
+            commands::get_bandwidth_schedule,
+            commands::set_bandwidth_schedule,
+            commands::get_concurrency_limits,
+            commands::set_concurrency_limits,
+            commands::set_max_share_size,
+            commands::set_download_directory,
+            commands::set_device_folder,
+            commands::set_organize_rules,
+            commands::set_folder_name_template,
+            commands::set_relay_preference,
+            commands::set_warm_connections,
+            commands::set_port_mapping,
+            commands::set_release_downloads,
+            commands::list_share_templates,
+            commands::save_share_template,
+            commands::delete_share_template,
+            commands::run_share_template,
+            commands::core_status,
+            commands::retry_initialization
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
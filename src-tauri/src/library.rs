@@ -0,0 +1,206 @@
+//! Library of shares received by this node
+//!
+//! Every successfully downloaded share is recorded here together with its
+//! metadata. The files stay in the blob store, protected by tags, so they can
+//! be exported to another folder later without downloading them again. Shares
+//! are identified by the hash of their bundle, so receiving the same share
+//! twice results in a single library entry.
+
+use crate::core::ShareMetadata;
+use crate::shares::ShareId;
+use anyhow::Result;
+use iroh_blobs::{store::mem::MemStore, Hash};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// A share kept in the library
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LibraryEntry {
+    /// Hash of the share bundle, identifying the share
+    pub share_id: ShareId,
+    /// The ticket the share was received from
+    pub ticket: String,
+    /// Metadata listing the files in the share
+    pub metadata: ShareMetadata,
+    /// Unix timestamp when the share was last received
+    pub received_at: u64,
+}
+
+/// Thread-safe library of received shares
+#[derive(Clone)]
+pub struct Library {
+    store: MemStore,
+    entries: Arc<RwLock<HashMap<ShareId, LibraryEntry>>>,
+}
+
+impl std::fmt::Debug for Library {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Library").finish_non_exhaustive()
+    }
+}
+
+impl Library {
+    /// Creates an empty library backed by the given store
+    pub fn new(store: MemStore) -> Self {
+        Self {
+            store,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Records a received share and tags its files so they are kept in the store
+    ///
+    /// # Arguments
+    ///
+    /// * `bundle_hash` - Hash of the share bundle, used as the share ID
+    /// * `ticket` - The ticket the share was received from
+    /// * `metadata` - Metadata listing the files in the share
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files cannot be tagged in the store.
+    pub async fn add(
+        &self,
+        bundle_hash: String,
+        ticket: String,
+        metadata: ShareMetadata,
+    ) -> Result<ShareId> {
+        for file in &metadata.files {
+            let hash: Hash = file
+                .hash
+                .parse()
+                .map_err(|error| anyhow::anyhow!("Invalid hash: {}", error))?;
+            self.store
+                .tags()
+                .set(tag_name(&bundle_hash, &file.hash), hash)
+                .await
+                .map_err(|error| anyhow::anyhow!("Failed to tag '{}': {}", file.name, error))?;
+        }
+
+        let entry = LibraryEntry {
+            share_id: bundle_hash.clone(),
+            ticket,
+            metadata,
+            received_at: current_unix_time(),
+        };
+        self.entries
+            .write()
+            .await
+            .insert(bundle_hash.clone(), entry);
+
+        Ok(bundle_hash)
+    }
+
+    /// Returns every share in the library, most recently received first
+    pub async fn list(&self) -> Vec<LibraryEntry> {
+        let mut entries: Vec<LibraryEntry> = self.entries.read().await.values().cloned().collect();
+        entries.sort_by_key(|entry| Reverse(entry.received_at));
+        entries
+    }
+
+    /// Returns a share in the library
+    pub async fn get(&self, share_id: &str) -> Option<LibraryEntry> {
+        self.entries.read().await.get(share_id).cloned()
+    }
+
+    /// Returns whether a share in the library keeps a blob in the store
+    pub async fn contains_blob(&self, hash: &str) -> bool {
+        self.entries
+            .read()
+            .await
+            .values()
+            .any(|entry| entry.metadata.files.iter().any(|file| file.hash == hash))
+    }
+
+    /// Removes a share from the library and untags its files
+    ///
+    /// The files are kept in the store if another share still references them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the share is not in the library.
+    pub async fn remove(&self, share_id: &str) -> Result<()> {
+        let entry = self
+            .entries
+            .write()
+            .await
+            .remove(share_id)
+            .ok_or_else(|| anyhow::anyhow!("Share '{}' is not in the library", share_id))?;
+
+        for file in &entry.metadata.files {
+            let _ = self
+                .store
+                .tags()
+                .delete(tag_name(share_id, &file.hash))
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the name of the tag protecting a file of a library share.
+fn tag_name(share_id: &str, file_hash: &str) -> String {
+    format!("library/{}/{}", share_id, file_hash)
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::core::{FileInfo, ShareType};
+
+    fn metadata(content: &[u8]) -> ShareMetadata {
+        ShareMetadata {
+            files: vec![FileInfo {
+                name: "a.txt".to_string(),
+                relative_path: "a.txt".to_string(),
+                size: content.len() as u64,
+                hash: Hash::new(content).to_string(),
+                compression: Compression::None,
+            }],
+            share_type: ShareType::SingleFile,
+            total_size: content.len() as u64,
+            skipped_files: vec![],
+            custom_fields: Default::default(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_list_and_remove() {
+        let library = Library::new(MemStore::new());
+
+        let share_id = library
+            .add("bundle".to_string(), "ticket".to_string(), metadata(b"a"))
+            .await
+            .unwrap();
+        library
+            .add("bundle".to_string(), "ticket".to_string(), metadata(b"a"))
+            .await
+            .unwrap();
+
+        assert_eq!(library.list().await.len(), 1);
+        assert_eq!(library.get(&share_id).await.unwrap().ticket, "ticket");
+        assert!(library.contains_blob(&Hash::new(b"a").to_string()).await);
+
+        library.remove(&share_id).await.unwrap();
+        assert!(!library.contains_blob(&Hash::new(b"a").to_string()).await);
+        assert!(library.get(&share_id).await.is_none());
+        assert!(library.remove(&share_id).await.is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Logging of transfers on both peers
+//!
+//! Every transfer runs in a `transfer` span carrying its transfer ID, and the
+//! ID of the share it sends or receives. The share ID is assigned by the
+//! sender and travels to the receiver in the share metadata, so the logs of a
+//! failed transfer can be matched up across both machines by searching for
+//! the share ID. The sender's provider logs every blob request with the share
+//! it belongs to as well.
+//!
+//! Logs are written to stderr, and to a log file in `ginseng/logs` in the
+//! data directory (e.g., `~/.local/share/ginseng/logs` on Linux) that users
+//! can attach when reporting a failed transfer. A new file is started every
+//! day and only the most recent ones are kept. The `RUST_LOG` environment
+//! variable selects what is logged (e.g., `RUST_LOG=ginseng_lib=trace`).
+
+use std::path::PathBuf;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Filter used when `RUST_LOG` is not set
+const DEFAULT_FILTER: &str = "ginseng_lib=info";
+
+/// Filter used for verbose output when `RUST_LOG` is not set
+const VERBOSE_FILTER: &str = "ginseng_lib=debug";
+
+/// Name log files start with, followed by the day they were started
+const LOG_FILE_PREFIX: &str = "ginseng";
+
+/// Number of daily log files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+/// Returns the directory log files are written to.
+pub fn log_directory() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join("ginseng").join("logs"))
+}
+
+/// Installs the logger for this process.
+///
+/// Does nothing if a logger is already installed. If the log directory cannot
+/// be created, logs are only written to stderr.
+///
+/// # Arguments
+///
+/// * `verbose` - Whether to log debug messages, unless `RUST_LOG` says otherwise
+pub fn init(verbose: bool) {
+    let default = if verbose {
+        VERBOSE_FILTER
+    } else {
+        DEFAULT_FILTER
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default));
+    let file = log_directory().and_then(|directory| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log")
+            .max_log_files(MAX_LOG_FILES)
+            .build(directory)
+            .ok()
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file.map(|file| fmt::layer().with_ansi(false).with_writer(file)))
+        .try_init()
+        .ok();
+}
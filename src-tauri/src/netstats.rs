@@ -0,0 +1,51 @@
+//! Traffic of the endpoint as a whole
+//!
+//! Counts what the endpoint sent and received since it started, over every
+//! connection and protocol, split into traffic that went to peers directly and
+//! traffic that went through a relay. The counters only grow; a client polls
+//! them and derives the current rate from the difference between two polls.
+
+use iroh::Endpoint;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bytes moved by the endpoint since it started, and its open connections
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStats {
+    /// Bytes sent to peers, directly and through relays
+    pub bytes_sent: u64,
+    /// Bytes received from peers, directly and through relays
+    pub bytes_received: u64,
+    /// Part of the bytes sent that went through relays
+    pub relay_bytes_sent: u64,
+    /// Part of the bytes received that came through relays
+    pub relay_bytes_received: u64,
+    /// Connections to peers open right now
+    pub active_connections: u64,
+    /// When the stats were taken, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Reads the traffic counters of an endpoint.
+pub fn network_stats(endpoint: &Endpoint) -> NetworkStats {
+    let metrics = &endpoint.metrics().magicsock;
+    let relay_bytes_sent = metrics.send_relay.get();
+    let relay_bytes_received = metrics.recv_data_relay.get();
+    NetworkStats {
+        bytes_sent: metrics.send_ipv4.get() + metrics.send_ipv6.get() + relay_bytes_sent,
+        bytes_received: metrics.recv_data_ipv4.get()
+            + metrics.recv_data_ipv6.get()
+            + relay_bytes_received,
+        relay_bytes_sent,
+        relay_bytes_received,
+        active_connections: metrics
+            .num_conns_opened
+            .get()
+            .saturating_sub(metrics.num_conns_closed.get()),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    }
+}
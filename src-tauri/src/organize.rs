@@ -0,0 +1,164 @@
+//! Automatic organization of downloads into subfolders
+//!
+//! Organization rules add subfolders between the download folder and a
+//! received share, e.g. `Downloads/2025-03/Images/holiday.jpg`. Rules are
+//! applied in the order given, each adding one level. The default rules are
+//! kept in the settings and can be replaced for a single download.
+
+use crate::core::ShareMetadata;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Number of endpoint ID characters used to name a sender's folder
+const SENDER_FOLDER_LENGTH: usize = 10;
+
+/// A subfolder level added to the download folder
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum OrganizeRule {
+    /// Month the share was received in, e.g. `2025-03`
+    Month,
+    /// The sending device, named by the start of its endpoint ID
+    Sender,
+    /// Kind of the shared files, e.g. `Images` or `Documents`
+    FileType,
+}
+
+/// Returns the folder a share is saved under after applying the rules.
+///
+/// # Arguments
+///
+/// * `base` - The download folder the rules add subfolders to
+/// * `rules` - The rules to apply, outermost first
+/// * `metadata` - Metadata of the received share
+/// * `sender` - Endpoint ID of the sending device
+/// * `received_at` - When the share was received
+pub fn organized_directory(
+    base: &Path,
+    rules: &[OrganizeRule],
+    metadata: &ShareMetadata,
+    sender: &str,
+    received_at: DateTime<Local>,
+) -> PathBuf {
+    let mut directory = base.to_path_buf();
+    for rule in rules {
+        match rule {
+            OrganizeRule::Month => {
+                directory.push(received_at.format("%Y-%m").to_string());
+            }
+            OrganizeRule::Sender => {
+                directory.push(
+                    sender
+                        .chars()
+                        .take(SENDER_FOLDER_LENGTH)
+                        .collect::<String>(),
+                );
+            }
+            OrganizeRule::FileType => directory.push(share_file_type(metadata)),
+        }
+    }
+    directory
+}
+
+/// Returns the folder name for the kind of files in a share.
+///
+/// Shares mixing several kinds of files go to a `Mixed` folder.
+fn share_file_type(metadata: &ShareMetadata) -> &'static str {
+    let mut kinds = metadata
+        .files
+        .iter()
+        .map(|file| file_type(&file.relative_path));
+    let Some(first) = kinds.next() else {
+        return "Other";
+    };
+    if kinds.all(|kind| kind == first) {
+        first
+    } else {
+        "Mixed"
+    }
+}
+
+/// Returns the folder name for the kind of a file, based on its extension.
+fn file_type(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "heic" | "bmp" | "svg" | "tiff" => "Images",
+        "mp4" | "mov" | "mkv" | "avi" | "webm" => "Videos",
+        "mp3" | "wav" | "flac" | "aac" | "ogg" | "m4a" => "Audio",
+        "pdf" | "doc" | "docx" | "odt" | "rtf" | "txt" | "md" | "csv" | "xls" | "xlsx" | "ppt"
+        | "pptx" => "Documents",
+        "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" => "Archives",
+        _ => "Other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::core::{FileInfo, ShareType};
+    use chrono::TimeZone;
+    use std::collections::BTreeMap;
+
+    fn metadata(paths: &[&str]) -> ShareMetadata {
+        ShareMetadata {
+            files: paths
+                .iter()
+                .map(|path| FileInfo {
+                    name: path.to_string(),
+                    relative_path: path.to_string(),
+                    size: 0,
+                    hash: String::new(),
+                    compression: Compression::None,
+                })
+                .collect(),
+            share_type: ShareType::MultipleFiles,
+            total_size: 0,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        }
+    }
+
+    #[test]
+    fn test_organized_directory_applies_rules_in_order() {
+        let received_at = Local.with_ymd_and_hms(2025, 3, 14, 12, 0, 0).unwrap();
+        let base = PathBuf::from("/downloads");
+
+        let directory = organized_directory(
+            &base,
+            &[
+                OrganizeRule::Month,
+                OrganizeRule::Sender,
+                OrganizeRule::FileType,
+            ],
+            &metadata(&["a.JPG", "b.png"]),
+            "0123456789abcdef",
+            received_at,
+        );
+
+        assert_eq!(
+            directory,
+            base.join("2025-03").join("0123456789").join("Images")
+        );
+        assert_eq!(
+            organized_directory(&base, &[], &metadata(&["a.jpg"]), "peer", received_at),
+            base
+        );
+    }
+
+    #[test]
+    fn test_share_file_type() {
+        assert_eq!(share_file_type(&metadata(&["report.pdf"])), "Documents");
+        assert_eq!(share_file_type(&metadata(&["a.pdf", "b.mp3"])), "Mixed");
+        assert_eq!(share_file_type(&metadata(&["notes"])), "Other");
+        assert_eq!(share_file_type(&metadata(&[])), "Other");
+    }
+}
@@ -0,0 +1,531 @@
+//! Cross-platform rules for the paths of shared files
+//!
+//! Shares describe their files by relative paths using `/` as separator, so a
+//! share created on one platform can be received on any other. Those paths
+//! come from the sender and cannot be trusted: a receiver turns them into local
+//! paths with [`local_path`], which keeps every file inside the target folder
+//! and applies the Windows naming rules on all platforms. Applying the
+//! strictest rules everywhere means a share is saved with the same names no
+//! matter which platform receives it. Downloads go further and refuse files
+//! whose paths try to leave the target folder at all, see
+//! [`validate_relative_path`].
+//!
+//! The rules for each path component are:
+//!
+//! - `..`, `.`, empty components, roots and drive prefixes are dropped
+//! - control characters and `< > : " / \ | ? *` are replaced with `_`
+//! - trailing dots and spaces are removed
+//! - reserved device names (`CON`, `PRN`, `AUX`, `NUL`, `COM1`-`COM9`,
+//!   `LPT1`-`LPT9`), with or without an extension, get a `_` prefix
+//! - components longer than 255 bytes are shortened, keeping the extension
+//! - components left empty become `_`
+
+use crate::utils::extract_file_name;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// Separator used in the relative paths of shared files
+pub const SEPARATOR: char = '/';
+
+/// Longest file name most file systems accept, in bytes
+pub const MAX_COMPONENT_LENGTH: usize = 255;
+
+/// Characters Windows does not allow in file names
+const INVALID_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Calculates the relative path of a shared file from the path it was shared from.
+///
+/// If the file path equals the base path, returns just the file name.
+/// Otherwise, strips the base path prefix and joins the remaining components
+/// with `/`, so the result is the same on every platform.
+///
+/// # Arguments
+/// * `file_path` - The target file path
+/// * `base_path` - The base path to calculate relative to
+///
+/// # Returns
+/// The relative path as a string
+///
+/// # Errors
+/// Returns an error if the file path is not within the base path
+pub fn calculate_relative_path(file_path: &Path, base_path: &Path) -> Result<String> {
+    if file_path == base_path {
+        return Ok(extract_file_name(file_path));
+    }
+
+    let relative = file_path
+        .strip_prefix(base_path)
+        .map_err(|error| anyhow::anyhow!("Failed to calculate relative path: {}", error))?;
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .unwrap_or("unknown")
+                .to_string()
+        })
+        .collect();
+    Ok(components.join(&SEPARATOR.to_string()))
+}
+
+/// Gives every file shared together a relative path of its own.
+///
+/// Files picked from different folders can have the same name, and two
+/// shared folders can hold files at the same path; received as they are, one
+/// would overwrite the other. Each path shared by several files is prefixed
+/// with the name of the folder the file was picked from, or of the shared
+/// folder it is in. If that path is taken as well, a counter is added to the
+/// file name, e.g. `report (1).pdf`. Paths are compared ignoring case, since
+/// receivers may save them to a file system that does too.
+///
+/// # Arguments
+/// * `files` - Each file with the path it was shared from, as with
+///   [`calculate_relative_path`]
+///
+/// # Returns
+/// The relative path of each file, in the same order
+///
+/// # Errors
+/// Returns an error if a file is not within the path it was shared from
+pub fn unique_relative_paths(files: &[(PathBuf, PathBuf)]) -> Result<Vec<String>> {
+    let relative_paths = files
+        .iter()
+        .map(|(file_path, base_path)| calculate_relative_path(file_path, base_path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for relative_path in &relative_paths {
+        *counts.entry(relative_path.to_lowercase()).or_default() += 1;
+    }
+    let collides = |relative_path: &str| counts[&relative_path.to_lowercase()] > 1;
+    // Paths only one file has are kept, so disambiguated paths go around them
+    let mut taken: HashSet<String> = relative_paths
+        .iter()
+        .filter(|relative_path| !collides(relative_path))
+        .map(|relative_path| relative_path.to_lowercase())
+        .collect();
+
+    let mut unique = Vec::with_capacity(relative_paths.len());
+    for ((file_path, base_path), relative_path) in files.iter().zip(&relative_paths) {
+        if !collides(relative_path) {
+            unique.push(relative_path.clone());
+            continue;
+        }
+
+        let folder = if file_path == base_path {
+            base_path.parent()
+        } else {
+            Some(base_path.as_path())
+        };
+        let prefixed = match folder.and_then(Path::file_name) {
+            Some(folder) => format!("{}{}{}", folder.to_string_lossy(), SEPARATOR, relative_path),
+            None => relative_path.clone(),
+        };
+        let (directory, file_name) = match prefixed.rsplit_once(SEPARATOR) {
+            Some((directory, file_name)) => (format!("{}{}", directory, SEPARATOR), file_name),
+            None => (String::new(), prefixed.as_str()),
+        };
+        let file_name = unique_path(Path::new(file_name), |candidate| {
+            taken.contains(&format!("{}{}", directory, candidate.to_string_lossy()).to_lowercase())
+        });
+        let path = format!("{}{}", directory, file_name.to_string_lossy());
+        taken.insert(path.to_lowercase());
+        unique.push(path);
+    }
+
+    Ok(unique)
+}
+
+/// Makes a single file or folder name safe to create on every platform.
+///
+/// # Arguments
+/// * `name` - A file or folder name received from another peer
+///
+/// # Returns
+/// The name with the rules of this module applied; never empty
+pub fn sanitize_component(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_CHARACTERS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let mut sanitized = truncate_component(replaced.trim_end_matches(['.', ' ']));
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        return "_".to_string();
+    }
+    if is_reserved_name(&sanitized) {
+        sanitized.insert(0, '_');
+        sanitized = truncate_component(&sanitized);
+    }
+    sanitized
+}
+
+/// Turns a relative path received from another peer into a safe relative path.
+///
+/// Both `/` and `\` are accepted as separators. Components that would leave
+/// the folder the path is relative to are dropped rather than rejected, so a
+/// malicious path still ends up somewhere inside it.
+///
+/// # Arguments
+/// * `relative_path` - The relative path of a shared file
+///
+/// # Returns
+/// A relative path made only of sanitized normal components; never empty
+pub fn sanitize_relative_path(relative_path: &str) -> PathBuf {
+    let sanitized: PathBuf = relative_path
+        .split(['/', '\\'])
+        .filter(|component| !matches!(*component, "" | "." | ".."))
+        .filter(|component| !is_drive_prefix(component))
+        .map(sanitize_component)
+        .collect();
+
+    if sanitized.as_os_str().is_empty() {
+        PathBuf::from("_")
+    } else {
+        sanitized
+    }
+}
+
+/// Checks that a relative path received from another peer stays inside the
+/// folder it is relative to.
+///
+/// Unlike [`sanitize_relative_path`], which drops such components, this
+/// rejects paths that are absolute or contain `..` components or drive
+/// prefixes, as no honest share contains them.
+///
+/// # Arguments
+/// * `relative_path` - The relative path of a shared file
+///
+/// # Errors
+/// Returns an error describing the first unsafe part of the path
+pub fn validate_relative_path(relative_path: &str) -> Result<()> {
+    if relative_path.starts_with(['/', '\\']) {
+        anyhow::bail!("Refused absolute path '{}'", relative_path);
+    }
+    for component in relative_path.split(['/', '\\']) {
+        if component == ".." {
+            anyhow::bail!("Refused path '{}' leaving the target folder", relative_path);
+        }
+        if is_drive_prefix(component.get(..2).unwrap_or(component)) {
+            anyhow::bail!("Refused path '{}' with a drive prefix", relative_path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns where a shared file is saved within a folder.
+///
+/// # Arguments
+/// * `base` - The folder the share is saved to
+/// * `relative_path` - The relative path of the file in the share
+///
+/// # Returns
+/// A path inside `base`, with the rules of this module applied
+pub fn local_path(base: &Path, relative_path: &str) -> PathBuf {
+    base.join(sanitize_relative_path(relative_path))
+}
+
+/// Returns a path that does not collide with an existing file or folder.
+///
+/// The path is returned unchanged if nothing exists there yet. Otherwise a
+/// counter is added before the extension, e.g. `photo (1).jpg`, `photo (2).jpg`.
+///
+/// # Arguments
+/// * `path` - The preferred path
+/// * `exists` - Checks whether something already exists at a path, usually
+///   [`Path::exists`]
+pub fn unique_path(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    numbered_path(path, &stem, &extension, exists)
+}
+
+/// Returns a folder path that does not collide with an existing file or folder.
+///
+/// Like [`unique_path`], but everything after a dot is kept as part of the
+/// name, e.g. `Inbox 1.5 (1)`.
+///
+/// # Arguments
+/// * `path` - The preferred path
+/// * `exists` - Checks whether something already exists at a path, usually
+///   [`Path::exists`]
+pub fn unique_folder_path(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    numbered_path(path, &name, "", exists)
+}
+
+/// Adds the first free counter between `stem` and `extension`, unless the
+/// path itself is free.
+fn numbered_path(
+    path: &Path,
+    stem: &str,
+    extension: &str,
+    exists: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    if !exists(path) {
+        return path.to_path_buf();
+    }
+
+    (1u64..)
+        .map(|counter| path.with_file_name(format!("{} ({}){}", stem, counter, extension)))
+        .find(|candidate| !exists(candidate))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Checks whether a path stays inside the folder it is relative to.
+///
+/// # Arguments
+/// * `relative_path` - A path relative to some folder
+pub fn is_contained(relative_path: &Path) -> bool {
+    relative_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Checks whether a name is reserved for a device on Windows.
+fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Checks whether a component is a Windows drive prefix such as `C:`.
+fn is_drive_prefix(component: &str) -> bool {
+    let mut chars = component.chars();
+    matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(letter), Some(':'), None) if letter.is_ascii_alphabetic()
+    )
+}
+
+/// Shortens a name to the maximum length, keeping a short extension intact.
+fn truncate_component(name: &str) -> String {
+    if name.len() <= MAX_COMPONENT_LENGTH {
+        return name.to_string();
+    }
+
+    let extension = name
+        .rfind('.')
+        .map(|index| &name[index..])
+        .filter(|extension| extension.len() <= 16)
+        .unwrap_or("");
+    let mut end = MAX_COMPONENT_LENGTH - extension.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &name[..end], extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_calculate_relative_path() {
+        let path = Path::new("/home/user/file.txt");
+        assert_eq!(calculate_relative_path(path, path).unwrap(), "file.txt");
+
+        let base = Path::new("/home/user");
+        let file = Path::new("/home/user/docs/file.txt");
+        assert_eq!(
+            calculate_relative_path(file, base).unwrap(),
+            "docs/file.txt"
+        );
+        assert!(calculate_relative_path(file, Path::new("/other")).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(sanitize_component("report.pdf"), "report.pdf");
+        assert_eq!(sanitize_component("a<b>c:d|e?f*g\"h"), "a_b_c_d_e_f_g_h");
+        assert_eq!(sanitize_component("tab\there"), "tab_here");
+        assert_eq!(sanitize_component("notes. . "), "notes");
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("nul.txt"), "_nul.txt");
+        assert_eq!(sanitize_component("COM10"), "COM10");
+        assert_eq!(sanitize_component("console"), "console");
+        assert_eq!(sanitize_component(""), "_");
+        assert_eq!(sanitize_component(".."), "_");
+        assert_eq!(sanitize_component("..."), "_");
+        assert_eq!(sanitize_component(".hidden"), ".hidden");
+    }
+
+    #[test]
+    fn test_sanitize_component_truncates_long_names() {
+        let name = format!("{}.jpg", "é".repeat(200));
+        let sanitized = sanitize_component(&name);
+        assert!(sanitized.len() <= MAX_COMPONENT_LENGTH);
+        assert!(sanitized.ends_with(".jpg"));
+    }
+
+    #[test]
+    fn test_sanitize_relative_path() {
+        assert_eq!(
+            sanitize_relative_path("docs/2024/report.pdf"),
+            PathBuf::from("docs").join("2024").join("report.pdf")
+        );
+        assert_eq!(
+            sanitize_relative_path("docs\\report.pdf"),
+            PathBuf::from("docs").join("report.pdf")
+        );
+        assert_eq!(
+            sanitize_relative_path("../../etc/passwd"),
+            PathBuf::from("etc").join("passwd")
+        );
+        assert_eq!(
+            sanitize_relative_path("/etc/passwd"),
+            PathBuf::from("etc").join("passwd")
+        );
+        assert_eq!(
+            sanitize_relative_path("C:\\Windows\\system.ini"),
+            PathBuf::from("Windows").join("system.ini")
+        );
+        assert_eq!(
+            sanitize_relative_path("./a//b/."),
+            PathBuf::from("a").join("b")
+        );
+        assert_eq!(sanitize_relative_path(".."), PathBuf::from("_"));
+    }
+
+    #[test]
+    fn test_validate_relative_path() {
+        assert!(validate_relative_path("docs/2024/report.pdf").is_ok());
+        assert!(validate_relative_path("docs\\report.pdf").is_ok());
+        assert!(validate_relative_path("./notes..txt").is_ok());
+        assert!(validate_relative_path("../../.bashrc").is_err());
+        assert!(validate_relative_path("docs/../../.bashrc").is_err());
+        assert!(validate_relative_path("docs\\..\\.bashrc").is_err());
+        assert!(validate_relative_path("/etc/passwd").is_err());
+        assert!(validate_relative_path("\\\\server\\share").is_err());
+        assert!(validate_relative_path("C:\\Windows\\system.ini").is_err());
+        assert!(validate_relative_path("docs/C:/report.pdf").is_err());
+        assert!(validate_relative_path("C:report.pdf").is_err());
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let taken: HashSet<PathBuf> = ["photo.jpg", "photo (1).jpg", "album"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let exists = |path: &Path| taken.contains(path);
+
+        assert_eq!(
+            unique_path(Path::new("new.jpg"), exists),
+            PathBuf::from("new.jpg")
+        );
+        assert_eq!(
+            unique_path(Path::new("photo.jpg"), exists),
+            PathBuf::from("photo (2).jpg")
+        );
+        assert_eq!(
+            unique_path(Path::new("album"), exists),
+            PathBuf::from("album (1)")
+        );
+        assert_eq!(
+            unique_folder_path(Path::new("photo.jpg"), exists),
+            PathBuf::from("photo.jpg (1)")
+        );
+    }
+
+    #[test]
+    fn test_unique_relative_paths() {
+        let file = |path: &str| (PathBuf::from(path), PathBuf::from(path));
+        let files = vec![
+            file("/home/user/docs/report.pdf"),
+            file("/home/user/old/report.pdf"),
+            file("/home/user/notes.txt"),
+            file("/mnt/a/docs/Report.pdf"),
+            (
+                PathBuf::from("/home/user/photos/notes.txt"),
+                PathBuf::from("/home/user/photos"),
+            ),
+            (
+                PathBuf::from("/home/user/music/notes.txt"),
+                PathBuf::from("/home/user/music"),
+            ),
+        ];
+
+        assert_eq!(
+            unique_relative_paths(&files).unwrap(),
+            vec![
+                "docs/report.pdf",
+                "old/report.pdf",
+                "user/notes.txt",
+                "docs/Report (1).pdf",
+                "photos/notes.txt",
+                "music/notes.txt",
+            ]
+        );
+
+        let distinct = vec![file("/a/one.txt"), file("/b/two.txt")];
+        assert_eq!(
+            unique_relative_paths(&distinct).unwrap(),
+            vec!["one.txt", "two.txt"]
+        );
+    }
+
+    quickcheck! {
+        fn prop_local_paths_stay_inside_base(relative_path: String) -> bool {
+            let base = Path::new("base");
+            let path = local_path(base, &relative_path);
+            path.strip_prefix(base)
+                .is_ok_and(|relative| relative.components().count() > 0 && is_contained(relative))
+        }
+
+        fn prop_sanitized_components_are_portable(name: String) -> bool {
+            let sanitized = sanitize_component(&name);
+            !sanitized.is_empty()
+                && sanitized.len() <= MAX_COMPONENT_LENGTH
+                && !sanitized.ends_with(['.', ' '])
+                && !sanitized.chars().any(|c| c.is_control() || INVALID_CHARACTERS.contains(&c))
+                && !is_reserved_name(&sanitized)
+        }
+
+        fn prop_sanitize_component_is_idempotent(name: String) -> bool {
+            let sanitized = sanitize_component(&name);
+            sanitize_component(&sanitized) == sanitized
+        }
+
+        fn prop_sanitized_paths_round_trip(relative_path: String) -> bool {
+            let sanitized = sanitize_relative_path(&relative_path);
+            let shared = calculate_relative_path(&Path::new("base").join(&sanitized), Path::new("base"));
+            shared.is_ok_and(|shared| sanitize_relative_path(&shared) == sanitized)
+        }
+
+        fn prop_unique_paths_are_free(names: Vec<u8>) -> bool {
+            let taken: HashSet<PathBuf> = names
+                .iter()
+                .map(|counter| PathBuf::from(format!("file ({}).txt", counter)))
+                .chain(std::iter::once(PathBuf::from("file.txt")))
+                .collect();
+            !taken.contains(&unique_path(Path::new("file.txt"), |path| taken.contains(path)))
+        }
+    }
+}
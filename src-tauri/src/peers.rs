@@ -0,0 +1,259 @@
+//! Long-term transfer statistics for known peers
+//!
+//! Every finished transfer with a peer adds to that peer's totals: bytes
+//! exchanged in each direction, time spent transferring, and whether the
+//! transfer succeeded. The totals are kept in a JSON file in the user's config
+//! directory, so a devices page can show meaningful history across sessions.
+
+use crate::json_file;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// Name of the file peer statistics are stored in
+const PEER_STATS_FILE_NAME: &str = "peers.json";
+
+/// Direction of a transfer as seen from this node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Files were sent to the peer
+    Sent,
+    /// Files were received from the peer
+    Received,
+}
+
+/// A finished transfer with a peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferRecord {
+    /// Whether files were sent or received
+    pub direction: Direction,
+    /// Bytes of file content exchanged
+    pub bytes: u64,
+    /// How long the transfer took
+    pub duration: Duration,
+    /// Whether every file was transferred
+    pub succeeded: bool,
+}
+
+/// Totals stored for a peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct PeerRecord {
+    bytes_sent: u64,
+    bytes_received: u64,
+    transfers: u64,
+    successful_transfers: u64,
+    transfer_millis: u64,
+    first_seen: u64,
+    last_seen: u64,
+}
+
+/// Transfer statistics of a peer
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStats {
+    /// Endpoint ID of the peer
+    pub peer: String,
+    /// Bytes of file content sent to the peer
+    pub bytes_sent: u64,
+    /// Bytes of file content received from the peer
+    pub bytes_received: u64,
+    /// Number of finished transfers with the peer
+    pub transfers: u64,
+    /// Average speed across all transfers in bytes per second
+    pub average_speed: Option<u64>,
+    /// Share of transfers that succeeded, between 0 and 1
+    pub success_rate: Option<f64>,
+    /// Unix timestamp when the peer was first seen
+    pub first_seen: u64,
+    /// Unix timestamp when the peer was last seen
+    pub last_seen: u64,
+}
+
+impl PeerStats {
+    fn new(peer: String, record: &PeerRecord) -> Self {
+        let total_bytes = record.bytes_sent + record.bytes_received;
+        Self {
+            peer,
+            bytes_sent: record.bytes_sent,
+            bytes_received: record.bytes_received,
+            transfers: record.transfers,
+            average_speed: (record.transfer_millis > 0)
+                .then(|| total_bytes.saturating_mul(1000) / record.transfer_millis),
+            success_rate: (record.transfers > 0)
+                .then(|| record.successful_transfers as f64 / record.transfers as f64),
+            first_seen: record.first_seen,
+            last_seen: record.last_seen,
+        }
+    }
+}
+
+/// Thread-safe store of peer statistics, persisted to a JSON file
+#[derive(Debug, Clone)]
+pub struct PeerStatsStore {
+    path: Option<PathBuf>,
+    records: Arc<RwLock<HashMap<String, PeerRecord>>>,
+}
+
+impl PeerStatsStore {
+    /// Creates a store that keeps statistics for this session only
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            records: Arc::default(),
+        }
+    }
+
+    /// Loads statistics from a file, starting empty if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        let records = json_file::read(&path, "peer statistics")
+            .await?
+            .unwrap_or_default();
+
+        Ok(Self {
+            path: Some(path),
+            records: Arc::new(RwLock::new(records)),
+        })
+    }
+
+    /// Loads statistics from the user's config directory
+    ///
+    /// Falls back to statistics for this session only if the config directory
+    /// cannot be determined or the file is unreadable.
+    pub async fn open_default() -> Self {
+        let Some(path) = json_file::config_path(PEER_STATS_FILE_NAME) else {
+            return Self::in_memory();
+        };
+        Self::load(path).await.unwrap_or_else(|_| Self::in_memory())
+    }
+
+    /// Returns the statistics of a peer, if it has been seen
+    pub async fn get(&self, peer: &str) -> Option<PeerStats> {
+        let records = self.records.read().await;
+        records
+            .get(peer)
+            .map(|record| PeerStats::new(peer.to_string(), record))
+    }
+
+    /// Returns the statistics of every known peer, most recently seen first
+    pub async fn list(&self) -> Vec<PeerStats> {
+        let records = self.records.read().await;
+        let mut stats: Vec<PeerStats> = records
+            .iter()
+            .map(|(peer, record)| PeerStats::new(peer.clone(), record))
+            .collect();
+        stats.sort_by_key(|stats| Reverse(stats.last_seen));
+        stats
+    }
+
+    /// Records that a peer connected
+    pub async fn seen(&self, peer: &str) {
+        self.update(peer, |_| {}).await;
+    }
+
+    /// Adds a finished transfer to a peer's totals
+    pub async fn record(&self, peer: &str, transfer: TransferRecord) {
+        self.update(peer, |record| {
+            match transfer.direction {
+                Direction::Sent => record.bytes_sent += transfer.bytes,
+                Direction::Received => record.bytes_received += transfer.bytes,
+            }
+            record.transfers += 1;
+            if transfer.succeeded {
+                record.successful_transfers += 1;
+            }
+            record.transfer_millis += transfer.duration.as_millis() as u64;
+        })
+        .await;
+    }
+
+    /// Updates a peer's record and its last seen time, then persists the store.
+    ///
+    /// Persisting is best effort: statistics are never worth failing a transfer.
+    async fn update(&self, peer: &str, updater: impl FnOnce(&mut PeerRecord)) {
+        let now = current_unix_time();
+        let mut records = self.records.write().await;
+
+        let record = records
+            .entry(peer.to_string())
+            .or_insert_with(|| PeerRecord {
+                first_seen: now,
+                ..Default::default()
+            });
+        updater(record);
+        record.last_seen = now;
+
+        if let Some(path) = &self.path {
+            let _ = json_file::write(path, &*records).await;
+        }
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_record_transfers_and_reload() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join(PEER_STATS_FILE_NAME);
+        let store = PeerStatsStore::load(path.clone()).await.unwrap();
+
+        store
+            .record(
+                "peer",
+                TransferRecord {
+                    direction: Direction::Received,
+                    bytes: 3000,
+                    duration: Duration::from_secs(2),
+                    succeeded: true,
+                },
+            )
+            .await;
+        store
+            .record(
+                "peer",
+                TransferRecord {
+                    direction: Direction::Sent,
+                    bytes: 1000,
+                    duration: Duration::from_secs(2),
+                    succeeded: false,
+                },
+            )
+            .await;
+        store.seen("other").await;
+
+        let reloaded = PeerStatsStore::load(path).await.unwrap();
+        let stats = reloaded.get("peer").await.unwrap();
+        assert_eq!(stats.bytes_received, 3000);
+        assert_eq!(stats.bytes_sent, 1000);
+        assert_eq!(stats.transfers, 2);
+        assert_eq!(stats.average_speed, Some(1000));
+        assert_eq!(stats.success_rate, Some(0.5));
+
+        let other = reloaded.get("other").await.unwrap();
+        assert_eq!(other.transfers, 0);
+        assert_eq!(other.average_speed, None);
+        assert_eq!(other.success_rate, None);
+        assert_eq!(reloaded.list().await.len(), 2);
+        assert!(reloaded.get("unknown").await.is_none());
+    }
+}
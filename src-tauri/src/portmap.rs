@@ -0,0 +1,188 @@
+//! Port mapping on home routers
+//!
+//! Most home routers drop packets from peers this node has not contacted
+//! first, so peers behind them often only reach each other through a relay.
+//! When enabled, the router is asked over UPnP, NAT-PMP or PCP to forward the
+//! endpoint's UDP port, which lets peers connect directly. Routers that support
+//! none of these protocols, or have them turned off, leave the mapping failed
+//! and connections go through relays as before.
+//!
+//! The mapping is renewed for as long as it is enabled; the router removes it
+//! once it is no longer renewed.
+
+use iroh::Endpoint;
+use serde::Serialize;
+use std::fmt;
+use std::net::{SocketAddr, SocketAddrV4};
+use std::num::NonZeroU16;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long the router may take to map the port before it counts as failed
+const MAPPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why a port is not mapped while mapping is enabled
+const NOT_MAPPED: &str = "No router mapped the port over UPnP, NAT-PMP or PCP";
+
+/// Whether the endpoint's port is mapped on the router
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum PortMapping {
+    /// Port mapping is turned off
+    #[default]
+    Disabled,
+    /// Waiting for the router to map the port
+    Pending,
+    /// The router forwards this address to the endpoint
+    #[serde(rename_all = "camelCase")]
+    Mapped { external_address: String },
+    /// The port could not be mapped
+    Failed { error: String },
+}
+
+impl fmt::Display for PortMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortMapping::Disabled => write!(f, "disabled"),
+            PortMapping::Pending => write!(f, "pending"),
+            PortMapping::Mapped { external_address } => write!(f, "mapped to {}", external_address),
+            PortMapping::Failed { error } => write!(f, "failed ({})", error),
+        }
+    }
+}
+
+/// The mapping client while enabled, and the last known state of the mapping
+#[derive(Default)]
+struct MapperState {
+    /// Client procuring and renewing the mapping; dropping it stops renewals
+    client: Option<portmapper::Client>,
+    status: PortMapping,
+}
+
+/// Maps the endpoint's port on the router while enabled
+#[derive(Clone, Default)]
+pub struct PortMapper {
+    state: Arc<Mutex<MapperState>>,
+}
+
+impl PortMapper {
+    /// Creates a mapper that maps nothing until enabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the port is mapped right now
+    pub fn status(&self) -> PortMapping {
+        self.state
+            .lock()
+            .map(|state| state.status.clone())
+            .unwrap_or_default()
+    }
+
+    /// Turns port mapping on or off
+    ///
+    /// Turning it on asks the router to map the endpoint's port; the status
+    /// stays pending until the router answers or [`MAPPING_TIMEOUT`] passes.
+    pub fn set_enabled(&self, endpoint: &Endpoint, enabled: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if !enabled {
+            state.client = None;
+            state.status = PortMapping::Disabled;
+            return;
+        }
+        if state.client.is_some() {
+            return;
+        }
+
+        let Some(port) = local_port(endpoint) else {
+            state.status = PortMapping::Failed {
+                error: "The endpoint has no IPv4 socket".to_string(),
+            };
+            return;
+        };
+        let client = portmapper::Client::default();
+        client.update_local_port(port);
+        client.procure_mapping();
+        let external_address = client.watch_external_address();
+        state.client = Some(client);
+        state.status = PortMapping::Pending;
+        drop(state);
+
+        watch_mapping(Arc::downgrade(&self.state), external_address);
+    }
+}
+
+/// Returns the port of the endpoint's IPv4 socket.
+fn local_port(endpoint: &Endpoint) -> Option<NonZeroU16> {
+    endpoint
+        .bound_sockets()
+        .into_iter()
+        .filter(SocketAddr::is_ipv4)
+        .find_map(|addr| NonZeroU16::new(addr.port()))
+}
+
+/// Spawns a task keeping the status up to date with the mapping, until port
+/// mapping is turned off or the mapper is dropped.
+fn watch_mapping(
+    state: Weak<Mutex<MapperState>>,
+    mut external_address: watch::Receiver<Option<SocketAddrV4>>,
+) {
+    tokio::spawn(async move {
+        // Routers usually answer within a second; until then the mapping is pending
+        tokio::time::timeout(MAPPING_TIMEOUT, external_address.wait_for(Option::is_some))
+            .await
+            .ok();
+
+        loop {
+            // The client drops its end once port mapping is turned off
+            if external_address.has_changed().is_err() {
+                return;
+            }
+            let status = match *external_address.borrow_and_update() {
+                Some(address) => PortMapping::Mapped {
+                    external_address: address.to_string(),
+                },
+                None => PortMapping::Failed {
+                    error: NOT_MAPPED.to_string(),
+                },
+            };
+            if !update_status(&state, status) {
+                return;
+            }
+            if external_address.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Records a new status of the mapping.
+///
+/// # Returns
+///
+/// Whether port mapping is still enabled
+fn update_status(state: &Weak<Mutex<MapperState>>, status: PortMapping) -> bool {
+    let Some(state) = state.upgrade() else {
+        return false;
+    };
+    let Ok(mut state) = state.lock() else {
+        return false;
+    };
+    if state.client.is_none() {
+        return false;
+    }
+
+    if state.status != status {
+        match &status {
+            PortMapping::Mapped { external_address } => {
+                tracing::info!(%external_address, "port mapped on router")
+            }
+            _ => tracing::info!(%status, "port not mapped on router"),
+        }
+    }
+    state.status = status;
+    true
+}
@@ -2,16 +2,30 @@
 //!
 //! This module provides a tokio-based concurrent progress system that tracks
 //! multiple file transfers in parallel with real-time updates.
+//!
+//! Files read from a stream (e.g., stdin) have no known size until the stream
+//! ends. Their progress is indeterminate: bytes so far are reported as usual,
+//! while the transfer's total and ETA are left out until every size is known.
 
+use crate::capabilities::Capabilities;
+use crate::cleanup::CleanupReport;
+use crate::disk::write_rate;
+use iroh_blobs::Hash;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
 /// Unique identifier for a transfer session
 pub type TransferId = String;
 
+/// Generates a new random transfer ID
+pub fn new_transfer_id() -> TransferId {
+    Uuid::new_v4().to_string()
+}
+
 /// Unique identifier for a file within a transfer
 pub type FileId = String;
 
@@ -29,6 +43,8 @@ pub enum TransferType {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum TransferStage {
+    /// Waiting in the queue for other transfers to finish
+    Queued,
     /// Preparing the transfer (collecting files, creating metadata)
     Initializing,
     /// Establishing connection with the peer
@@ -53,6 +69,8 @@ pub enum FileStatus {
     Pending,
     /// File is currently being transferred
     Transferring,
+    /// File has been received and is being written to disk
+    Writing,
     /// File transfer completed successfully
     Completed,
     /// File transfer failed
@@ -61,6 +79,14 @@ pub enum FileStatus {
     Skipped,
 }
 
+/// Derives a stable file ID from a file's content hash and relative path
+///
+/// The same content at the same path always yields the same ID, which lets
+/// persisted state, resumed transfers, and history refer to files reliably.
+pub fn derive_file_id(hash: &str, relative_path: &str) -> FileId {
+    Hash::new(format!("{}\0{}", hash, relative_path)).to_string()
+}
+
 /// Progress tracking information for a single file within a transfer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -71,20 +97,33 @@ pub struct FileProgress {
     pub name: String,
     /// Relative path from the transfer root (e.g., "folder/document.pdf")
     pub relative_path: String,
-    /// Total size of the file in bytes
+    /// Total size of the file in bytes, 0 while the size is unknown
     pub total_bytes: u64,
+    /// Whether the size of the file is unknown because it is read from a stream
+    #[serde(default)]
+    pub indeterminate: bool,
     /// Number of bytes transferred so far
     pub transferred_bytes: u64,
     /// Current status of this file's transfer
     pub status: FileStatus,
     /// Transfer rate in bytes per second (None if not yet calculated)
     pub transfer_rate: Option<u64>,
+    /// Number of bytes written to disk so far
+    #[serde(default)]
+    pub written_bytes: u64,
+    /// Rate the file is written to disk in bytes per second (None until writing starts)
+    #[serde(default)]
+    pub write_rate: Option<u64>,
     /// Error message if the file transfer failed
     pub error: Option<String>,
 }
 
 impl FileProgress {
-    /// Creates a new file progress tracker
+    /// Creates a new file progress tracker for a file whose content hash is not known yet
+    ///
+    /// The file ID is derived from the relative path and size, which is stable
+    /// for the same source file across sessions. Use [`FileProgress::for_content`]
+    /// when the content hash is available.
     ///
     /// # Arguments
     ///
@@ -92,14 +131,55 @@ impl FileProgress {
     /// * `relative_path` - The relative path from the transfer root
     /// * `total_bytes` - Total size of the file in bytes
     pub fn new(name: String, relative_path: String, total_bytes: u64) -> Self {
+        let file_id = derive_file_id(&format!("size:{}", total_bytes), &relative_path);
+        Self::with_id(file_id, name, relative_path, total_bytes)
+    }
+
+    /// Creates a new file progress tracker for a file with a known content hash
+    ///
+    /// The file ID is derived from the hash and relative path, so the same file
+    /// gets the same ID in resumed or retried transfers.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The content hash of the file
+    /// * `name` - The file name
+    /// * `relative_path` - The relative path from the transfer root
+    /// * `total_bytes` - Total size of the file in bytes
+    pub fn for_content(hash: &str, name: String, relative_path: String, total_bytes: u64) -> Self {
+        let file_id = derive_file_id(hash, &relative_path);
+        Self::with_id(file_id, name, relative_path, total_bytes)
+    }
+
+    /// Creates a new file progress tracker for a file read from a stream
+    ///
+    /// The size of the file is unknown until the stream ends, see
+    /// [`ProgressTracker::set_file_size`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The file name
+    /// * `relative_path` - The relative path from the transfer root
+    pub fn streamed(name: String, relative_path: String) -> Self {
+        let file_id = derive_file_id("stream", &relative_path);
+        Self {
+            indeterminate: true,
+            ..Self::with_id(file_id, name, relative_path, 0)
+        }
+    }
+
+    fn with_id(file_id: FileId, name: String, relative_path: String, total_bytes: u64) -> Self {
         Self {
-            file_id: Uuid::new_v4().to_string(),
+            file_id,
             name,
             relative_path,
             total_bytes,
+            indeterminate: false,
             transferred_bytes: 0,
             status: FileStatus::Pending,
             transfer_rate: None,
+            written_bytes: 0,
+            write_rate: None,
             error: None,
         }
     }
@@ -129,10 +209,21 @@ pub struct TransferProgress {
     pub completed_files: u64,
     /// Number of files that failed to transfer
     pub failed_files: u64,
-    /// Total size of all files in bytes
+    /// Total size of all files in bytes, only counting files of known size
     pub total_bytes: u64,
+    /// Whether the size of any file is still unknown, in which case the total
+    /// size is incomplete and no ETA is given
+    #[serde(default)]
+    pub indeterminate: bool,
     /// Total bytes transferred across all files
     pub transferred_bytes: u64,
+    /// Files found so far while preparing a share, before their total is known
+    #[serde(default)]
+    pub scanned_files: u64,
+    /// Bytes of files read and hashed into the store so far while preparing
+    /// a share
+    #[serde(default)]
+    pub hashed_bytes: u64,
     /// Overall transfer rate in bytes per second (None if not yet calculated)
     pub transfer_rate: Option<u64>,
     /// Unix timestamp when the transfer started
@@ -143,6 +234,16 @@ pub struct TransferProgress {
     pub files: Vec<FileProgress>,
     /// Error message if the transfer failed
     pub error: Option<String>,
+    /// ID the sender assigned to the share, once known, for matching up the
+    /// logs of both peers
+    pub share_id: Option<String>,
+    /// Protocol version and features negotiated with the peer, once known
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+    /// Rate expected from earlier transfers with the same peer, which the
+    /// ETA leans on until enough of this transfer has been measured
+    #[serde(skip)]
+    pub expected_rate: Option<u64>,
 }
 
 impl TransferProgress {
@@ -161,7 +262,10 @@ impl TransferProgress {
             completed_files: 0,
             failed_files: 0,
             total_bytes: 0,
+            indeterminate: false,
             transferred_bytes: 0,
+            scanned_files: 0,
+            hashed_bytes: 0,
             transfer_rate: None,
             start_time: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -170,6 +274,9 @@ impl TransferProgress {
             eta_seconds: None,
             files: Vec::new(),
             error: None,
+            share_id: None,
+            capabilities: None,
+            expected_rate: None,
         }
     }
 
@@ -177,6 +284,9 @@ impl TransferProgress {
     ///
     /// Calculates the overall transfer rate by dividing total transferred bytes
     /// by elapsed time, then estimates the remaining time based on this rate.
+    /// If an expected rate is known, the estimate starts from it and shifts to
+    /// the measured rate over the first seconds of the transfer. No ETA is
+    /// given while the size of any file is unknown.
     pub fn update_rates(&mut self) {
         let elapsed = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -186,13 +296,16 @@ impl TransferProgress {
 
         if elapsed > 0 && self.transferred_bytes > 0 {
             self.transfer_rate = Some(self.transferred_bytes / elapsed);
+        }
 
-            if let Some(rate) = self.transfer_rate {
-                if rate > 0 {
-                    let remaining = self.total_bytes.saturating_sub(self.transferred_bytes);
-                    self.eta_seconds = Some(remaining / rate);
-                }
-            }
+        if self.indeterminate {
+            self.eta_seconds = None;
+            return;
+        }
+        let rate = estimated_rate(self.transfer_rate, self.expected_rate, elapsed);
+        let remaining = self.total_bytes.saturating_sub(self.transferred_bytes);
+        if let Some(eta) = rate.and_then(|rate| remaining.checked_div(rate)) {
+            self.eta_seconds = Some(eta);
         }
     }
 
@@ -214,6 +327,28 @@ impl TransferProgress {
     }
 }
 
+/// Seconds over which ETA estimates shift from the expected to the measured rate
+const ETA_CALIBRATION_SECS: u64 = 10;
+
+/// Returns the rate to estimate the remaining time with.
+///
+/// Rates measured in the first seconds swing widely, e.g. while the
+/// connection is set up, so they are blended with the expected rate, weighted
+/// by how much of the calibration period has passed.
+fn estimated_rate(measured: Option<u64>, expected: Option<u64>, elapsed: u64) -> Option<u64> {
+    match (measured, expected) {
+        (Some(measured), Some(expected)) if elapsed < ETA_CALIBRATION_SECS => {
+            let weight = u128::from(elapsed);
+            let blended = (u128::from(measured) * weight
+                + u128::from(expected) * (u128::from(ETA_CALIBRATION_SECS) - weight))
+                / u128::from(ETA_CALIBRATION_SECS);
+            Some(blended as u64)
+        }
+        (Some(measured), _) => Some(measured),
+        (None, expected) => expected,
+    }
+}
+
 /// Events emitted during a transfer for real-time progress updates
 ///
 /// These events are sent through Tauri channels to the frontend for UI updates.
@@ -235,22 +370,172 @@ pub enum ProgressEvent {
         stage: TransferStage,
         message: Option<String>,
     },
+    /// Work within a stage that has no files yet has progressed
+    ///
+    /// Used while the share manifest is fetched, which can take a while for
+    /// shares with many files.
+    StageProgress {
+        transfer_id: TransferId,
+        stage: TransferStage,
+        message: String,
+        processed_bytes: u64,
+    },
     /// Transfer has completed successfully
     TransferCompleted { transfer: TransferProgress },
+    /// Transfer was cancelled and its partial state is being removed
+    TransferCancelled { transfer: TransferProgress },
     /// Transfer has failed
     TransferFailed {
         transfer: TransferProgress,
         error: String,
     },
+    /// A shared file changed on disk after the share started
+    ///
+    /// Receivers keep getting the content that was read when the file was
+    /// shared, so the file needs to be shared again to send the new version.
+    SourceChanged {
+        transfer_id: TransferId,
+        path: String,
+    },
+    /// Partial state of a failed or cancelled transfer has been removed
+    CleanupCompleted {
+        transfer_id: TransferId,
+        report: CleanupReport,
+    },
+}
+
+/// Lock-free byte counter for a single file in a transfer
+///
+/// Returned by [`ProgressTracker::add_file`] so transfer tasks can report
+/// progress on the hot path without taking the tracker's lock. Counters are
+/// aggregated into the transfer totals whenever a snapshot is taken.
+#[derive(Clone)]
+pub struct FileCounter {
+    bytes: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+    written: Arc<WriteCounter>,
+}
+
+/// Progress of writing a file to disk
+#[derive(Default)]
+struct WriteCounter {
+    started: AtomicBool,
+    bytes: AtomicU64,
+    micros: AtomicU64,
+}
+
+impl FileCounter {
+    /// Records additional bytes transferred for this file
+    pub fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Sets the number of bytes transferred for this file
+    pub fn set(&self, bytes: u64) {
+        let previous = self.bytes.swap(bytes, Ordering::Relaxed);
+        if bytes >= previous {
+            self.total.fetch_add(bytes - previous, Ordering::Relaxed);
+        } else {
+            self.total.fetch_sub(previous - bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of bytes transferred for this file
+    pub fn get(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Moves the count by the difference between two values
+    ///
+    /// Unlike [`FileCounter::set`], bytes added concurrently are kept.
+    fn shift(&self, from: u64, to: u64) {
+        if to >= from {
+            self.add(to - from);
+        } else {
+            self.bytes.fetch_sub(from - to, Ordering::Relaxed);
+            self.total.fetch_sub(from - to, Ordering::Relaxed);
+        }
+    }
+
+    /// Records the bytes of this file written to disk and the time spent writing
+    ///
+    /// A transferring file is shown as writing from the first report on.
+    pub fn set_written(&self, bytes: u64, elapsed: Duration) {
+        self.written.bytes.store(bytes, Ordering::Relaxed);
+        self.written
+            .micros
+            .store(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.written.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Forgets an earlier write of this file, e.g. before it is retried
+    pub fn reset_written(&self) {
+        self.written.started.store(false, Ordering::Relaxed);
+        self.written.bytes.store(0, Ordering::Relaxed);
+        self.written.micros.store(0, Ordering::Relaxed);
+    }
+
+    /// Copies the write progress into a file's progress.
+    fn apply_written(&self, file: &mut FileProgress) {
+        if !self.written.started.load(Ordering::Relaxed) {
+            return;
+        }
+        if file.status == FileStatus::Transferring {
+            file.status = FileStatus::Writing;
+        }
+        file.written_bytes = self.written.bytes.load(Ordering::Relaxed);
+        file.write_rate = write_rate(
+            file.written_bytes,
+            Duration::from_micros(self.written.micros.load(Ordering::Relaxed)),
+        );
+    }
+}
+
+/// Lock-free counters of the work done preparing a share
+#[derive(Default)]
+struct PreparationCounter {
+    scanned_files: AtomicU64,
+    hashed_bytes: AtomicU64,
+}
+
+/// Progress state guarded by the tracker's lock
+struct TrackerState {
+    progress: TransferProgress,
+    counters: Vec<FileCounter>,
+}
+
+impl TrackerState {
+    /// Returns the progress with the byte counters copied in and rates refreshed
+    fn aggregate(
+        &self,
+        transferred: &AtomicU64,
+        preparation: &PreparationCounter,
+    ) -> TransferProgress {
+        let mut progress = self.progress.clone();
+        for (file, counter) in progress.files.iter_mut().zip(&self.counters) {
+            file.transferred_bytes = counter.get();
+            counter.apply_written(file);
+        }
+        progress.transferred_bytes = transferred.load(Ordering::Relaxed);
+        progress.scanned_files = preparation.scanned_files.load(Ordering::Relaxed);
+        progress.hashed_bytes = preparation.hashed_bytes.load(Ordering::Relaxed);
+        progress.update_rates();
+        progress
+    }
 }
 
 /// Thread-safe progress tracker that can be shared across parallel tasks
 ///
-/// Uses RwLock internally to allow concurrent reads and exclusive writes,
-/// enabling multiple tokio tasks to safely update progress in parallel.
+/// Byte counts live in atomic [`FileCounter`]s so parallel transfers can
+/// report progress without contending for a lock. File statuses and other
+/// rarely changing state sit behind a RwLock, and the two are combined when a
+/// snapshot is taken.
 #[derive(Clone)]
 pub struct ProgressTracker {
-    inner: Arc<RwLock<TransferProgress>>,
+    inner: Arc<RwLock<TrackerState>>,
+    transferred: Arc<AtomicU64>,
+    preparation: Arc<PreparationCounter>,
 }
 
 impl ProgressTracker {
@@ -262,42 +547,116 @@ impl ProgressTracker {
     /// * `transfer_type` - Type of transfer (upload or download)
     pub fn new(transfer_id: String, transfer_type: TransferType) -> Self {
         Self {
-            inner: Arc::new(RwLock::new(TransferProgress::new(
-                transfer_id,
-                transfer_type,
-            ))),
+            inner: Arc::new(RwLock::new(TrackerState {
+                progress: TransferProgress::new(transfer_id, transfer_type),
+                counters: Vec::new(),
+            })),
+            transferred: Arc::new(AtomicU64::new(0)),
+            preparation: Arc::default(),
         }
     }
 
     /// Gets a snapshot of the current progress state
     ///
-    /// Returns a clone of the current progress, allowing the caller to
-    /// inspect progress without holding a lock.
+    /// Aggregates the byte counters into the returned progress, allowing the
+    /// caller to inspect progress without holding a lock. Only a read lock is
+    /// taken, so polling snapshots does not hold up other updates.
     pub async fn get_snapshot(&self) -> TransferProgress {
-        self.inner.read().await.clone()
+        self.inner
+            .read()
+            .await
+            .aggregate(&self.transferred, &self.preparation)
+    }
+
+    /// Sets the rate expected from earlier transfers, used to seed the ETA
+    pub async fn set_expected_rate(&self, rate: Option<u64>) {
+        let mut state = self.inner.write().await;
+        state.progress.expected_rate = rate;
+    }
+
+    /// Sets the ID of the share being transferred
+    pub async fn set_share_id(&self, share_id: String) {
+        let mut state = self.inner.write().await;
+        state.progress.share_id = Some(share_id);
+    }
+
+    /// Sets the capabilities negotiated with the peer
+    pub async fn set_capabilities(&self, capabilities: Capabilities) {
+        let mut state = self.inner.write().await;
+        state.progress.capabilities = Some(capabilities);
     }
 
     /// Updates the current transfer stage
     pub async fn set_stage(&self, stage: TransferStage) {
-        let mut inner = self.inner.write().await;
-        inner.stage = stage;
+        let mut state = self.inner.write().await;
+        state.progress.stage = stage;
+    }
+
+    /// Restarts the clock the transfer rate is measured from
+    ///
+    /// Used when a transfer starts after waiting in the queue, so the wait
+    /// does not count towards its rate.
+    pub async fn restart_clock(&self) {
+        let mut state = self.inner.write().await;
+        state.progress.start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+    }
+
+    /// Records a file found while preparing a share
+    ///
+    /// Lock-free, so it can be called for every file of a large share.
+    pub fn file_scanned(&self) {
+        self.preparation
+            .scanned_files
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records bytes of a file read and hashed into the store while preparing a share
+    pub fn add_hashed_bytes(&self, bytes: u64) {
+        self.preparation
+            .hashed_bytes
+            .fetch_add(bytes, Ordering::Relaxed);
     }
 
     /// Adds a new file to the transfer
     ///
-    /// Updates total file count and total bytes accordingly.
-    pub async fn add_file(&self, file: FileProgress) {
-        let mut inner = self.inner.write().await;
-        inner.total_files += 1;
-        inner.total_bytes += file.total_bytes;
-        inner.files.push(file);
+    /// Updates total file count and total bytes accordingly, and returns the
+    /// counter used to report bytes transferred for the file.
+    pub async fn add_file(&self, file: FileProgress) -> FileCounter {
+        let counter = FileCounter {
+            bytes: Arc::new(AtomicU64::new(0)),
+            total: self.transferred.clone(),
+            written: Arc::default(),
+        };
+        counter.set(file.transferred_bytes);
+
+        let mut state = self.inner.write().await;
+        state.progress.total_files += 1;
+        state.progress.total_bytes += file.total_bytes;
+        state.progress.indeterminate |= file.indeterminate;
+        state.progress.files.push(file);
+        state.counters.push(counter.clone());
+        counter
+    }
+
+    /// Returns the byte counter of a file, if the file is part of the transfer
+    pub async fn file_counter(&self, file_id: &str) -> Option<FileCounter> {
+        let state = self.inner.read().await;
+        let index = state
+            .progress
+            .files
+            .iter()
+            .position(|f| f.file_id == file_id)?;
+        Some(state.counters[index].clone())
     }
 
     /// Updates a specific file's progress using a closure
     ///
-    /// Finds the file by ID, applies the update function, and recalculates
-    /// transfer totals and rates. This is the primary way to update file progress
-    /// during parallel transfers.
+    /// Finds the file by ID, applies the update function, and keeps the
+    /// completed and failed counts in sync. Intended for status changes; byte
+    /// progress should be reported through the file's [`FileCounter`].
     ///
     /// # Arguments
     ///
@@ -307,75 +666,169 @@ impl ProgressTracker {
     where
         F: FnOnce(&mut FileProgress),
     {
-        let mut inner = self.inner.write().await;
-        if let Some(file) = inner.files.iter_mut().find(|f| f.file_id == file_id) {
-            updater(file);
-            inner.recalculate_totals();
-            inner.update_rates();
+        let mut state = self.inner.write().await;
+        let TrackerState { progress, counters } = &mut *state;
+
+        let Some(index) = progress.files.iter().position(|f| f.file_id == file_id) else {
+            return;
+        };
+
+        let file = &mut progress.files[index];
+        let previous_status = file.status.clone();
+        let counted = counters[index].get();
+        file.transferred_bytes = counted;
+        updater(file);
+        // Applied as a change, so bytes reported meanwhile are not lost
+        counters[index].shift(counted, file.transferred_bytes);
+
+        let status = file.status.clone();
+        if status != previous_status {
+            progress.completed_files = progress
+                .files
+                .iter()
+                .filter(|f| f.status == FileStatus::Completed)
+                .count() as u64;
+            progress.failed_files = progress
+                .files
+                .iter()
+                .filter(|f| f.status == FileStatus::Failed)
+                .count() as u64;
         }
     }
 
+    /// Sets the size of a file read from a stream once the stream has ended
+    ///
+    /// The file is added to the transfer's total size, and the transfer gets
+    /// an ETA again once no file of unknown size is left.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The ID of the file
+    /// * `total_bytes` - Size of the file in bytes
+    pub async fn set_file_size(&self, file_id: &str, total_bytes: u64) {
+        let mut state = self.inner.write().await;
+        let progress = &mut state.progress;
+
+        let Some(file) = progress.files.iter_mut().find(|f| f.file_id == file_id) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut file.total_bytes, total_bytes);
+        file.indeterminate = false;
+
+        progress.total_bytes = progress.total_bytes - previous + total_bytes;
+        progress.indeterminate = progress.files.iter().any(|f| f.indeterminate);
+    }
+
     /// Marks the transfer as failed with an error message
     pub async fn set_error(&self, error: String) {
-        let mut inner = self.inner.write().await;
-        inner.error = Some(error);
-        inner.stage = TransferStage::Failed;
+        let mut state = self.inner.write().await;
+        state.progress.error = Some(error);
+        state.progress.stage = TransferStage::Failed;
+    }
+
+    /// Marks the transfer as cancelled and updates final rates
+    pub async fn cancel(&self) {
+        let mut state = self.inner.write().await;
+        state.progress.stage = TransferStage::Cancelled;
+        state.progress = state.aggregate(&self.transferred, &self.preparation);
     }
 
     /// Marks the transfer as completed and updates final rates
     pub async fn complete(&self) {
-        let mut inner = self.inner.write().await;
-        inner.stage = TransferStage::Completed;
-        inner.update_rates();
+        let mut state = self.inner.write().await;
+        state.progress.stage = TransferStage::Completed;
+        state.progress = state.aggregate(&self.transferred, &self.preparation);
+    }
+}
+
+/// Number of back-to-back progress events allowed
+const PROGRESS_BURST: u32 = 3;
+
+/// Lock-free token bucket
+///
+/// Implemented as a generic cell rate algorithm: instead of storing a token
+/// count and a refill timestamp, it stores the theoretical arrival time of the
+/// next event, which fits in a single atomic and needs no lock.
+struct TokenBucket {
+    /// Nanoseconds (relative to the limiter's origin) at which the bucket is full again
+    theoretical_arrival: AtomicU64,
+    /// Nanoseconds it takes to earn one token
+    interval: u64,
+    /// How far ahead of now the arrival time may run, allowing bursts
+    tolerance: u64,
+}
+
+impl TokenBucket {
+    /// Creates a bucket allowing `burst` events at once, refilled every `interval`
+    fn new(interval: Duration, burst: u32) -> Self {
+        let interval = interval.as_nanos().min(u64::MAX as u128) as u64;
+        Self {
+            theoretical_arrival: AtomicU64::new(0),
+            interval,
+            tolerance: interval.saturating_mul(u64::from(burst.max(1)) - 1),
+        }
+    }
+
+    /// Takes a token if one is available at `now`
+    fn try_acquire(&self, now: u64) -> bool {
+        self.theoretical_arrival
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |arrival| {
+                let arrival = arrival.max(now);
+                (arrival - now <= self.tolerance).then(|| arrival.saturating_add(self.interval))
+            })
+            .is_ok()
+    }
+
+    /// Refills the bucket to its full burst allowance
+    fn refill(&self) {
+        self.theoretical_arrival.store(0, Ordering::Release);
     }
 }
 
 /// Rate limiter for progress updates to prevent flooding the UI with events
 ///
-/// Ensures that progress events are only emitted at a reasonable frequency,
-/// typically used to avoid overwhelming the frontend with updates during
-/// high-speed transfers.
+/// Uses a token bucket, so bursts of updates (e.g., several small files
+/// finishing at once) get through while sustained high-speed transfers are
+/// throttled. Stage changes are rare and always sent, so only progress events
+/// go through the limiter. The limiter is lock-free and cheap to clone, so a
+/// single instance can be shared by all parallel tasks of a transfer.
 #[derive(Clone)]
 pub struct RateLimiter {
-    last_emission: Arc<RwLock<SystemTime>>,
-    min_interval: Duration,
+    origin: Instant,
+    bucket: Arc<TokenBucket>,
 }
 
 impl RateLimiter {
     /// Creates a new rate limiter
     ///
+    /// Progress events are allowed once per `min_interval` on average, with a
+    /// small burst allowance.
+    ///
     /// # Arguments
     ///
-    /// * `min_interval` - Minimum time between emissions
+    /// * `min_interval` - Average time between progress emissions
     pub fn new(min_interval: Duration) -> Self {
         Self {
-            last_emission: Arc::new(RwLock::new(SystemTime::now())),
-            min_interval,
+            origin: Instant::now(),
+            bucket: Arc::new(TokenBucket::new(min_interval, PROGRESS_BURST)),
         }
     }
 
-    /// Checks if enough time has passed to emit a new event
+    /// Checks whether a progress event may be emitted now
     ///
-    /// If the minimum interval has elapsed, updates the last emission time
-    /// and returns true. Otherwise returns false.
-    pub async fn should_emit(&self) -> bool {
-        let now = SystemTime::now();
-        let last = *self.last_emission.read().await;
-
-        if now.duration_since(last).unwrap_or_default() >= self.min_interval {
-            *self.last_emission.write().await = now;
-            true
-        } else {
-            false
-        }
+    /// Consumes one token and returns true if a token was available.
+    /// Otherwise returns false and the event should be dropped.
+    pub fn should_emit(&self) -> bool {
+        let now = self.origin.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        self.bucket.try_acquire(now)
     }
 
-    /// Forces the next emission to be allowed
+    /// Restores the full burst allowance
     ///
-    /// Resets the last emission time to the epoch, ensuring the next
-    /// call to `should_emit` will return true.
-    pub async fn force_emit(&self) {
-        *self.last_emission.write().await = SystemTime::UNIX_EPOCH;
+    /// Useful after important milestones so the following updates are not
+    /// delayed by earlier ones.
+    pub fn force_emit(&self) {
+        self.bucket.refill();
     }
 }
 
@@ -415,3 +868,218 @@ pub fn format_bytes(bytes: u64) -> String {
 
     format!("{:.2} {}", size, UNITS[unit_index])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_ids_are_deterministic() {
+        let first = FileProgress::for_content("abc", "a".to_string(), "dir/a".to_string(), 1);
+        let second = FileProgress::for_content("abc", "a".to_string(), "dir/a".to_string(), 1);
+        let moved = FileProgress::for_content("abc", "a".to_string(), "other/a".to_string(), 1);
+
+        assert_eq!(first.file_id, second.file_id);
+        assert_ne!(first.file_id, moved.file_id);
+        assert_eq!(first.file_id, derive_file_id("abc", "dir/a"));
+    }
+
+    #[tokio::test]
+    async fn test_file_counters_aggregate_into_snapshot() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Download);
+        let first = tracker
+            .add_file(FileProgress::new("a".to_string(), "a".to_string(), 100))
+            .await;
+        let second = tracker
+            .add_file(FileProgress::new("b".to_string(), "b".to_string(), 50))
+            .await;
+
+        first.add(40);
+        first.add(20);
+        second.set(50);
+
+        let snapshot = tracker.get_snapshot().await;
+        assert_eq!(snapshot.transferred_bytes, 110);
+        assert_eq!(snapshot.files[0].transferred_bytes, 60);
+        assert_eq!(snapshot.files[1].transferred_bytes, 50);
+
+        second.set(10);
+        assert_eq!(tracker.get_snapshot().await.transferred_bytes, 70);
+    }
+
+    #[tokio::test]
+    async fn test_update_file_keeps_bytes_reported_meanwhile() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Download);
+        let file = FileProgress::new("a".to_string(), "a".to_string(), 100);
+        let file_id = file.file_id.clone();
+        let counter = tracker.add_file(file).await;
+        counter.add(10);
+
+        tracker
+            .update_file(&file_id, |f| {
+                // A transfer task reporting bytes while the status changes
+                counter.add(5);
+                f.status = FileStatus::Transferring;
+            })
+            .await;
+        assert_eq!(counter.get(), 15);
+
+        tracker
+            .update_file(&file_id, |f| f.transferred_bytes = 100)
+            .await;
+        assert_eq!(tracker.get_snapshot().await.transferred_bytes, 100);
+    }
+
+    #[tokio::test]
+    async fn test_preparation_counters_aggregate_into_snapshot() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Upload);
+        tracker.file_scanned();
+        tracker.file_scanned();
+        tracker.add_hashed_bytes(1024);
+        tracker.add_hashed_bytes(512);
+
+        let snapshot = tracker.get_snapshot().await;
+        assert_eq!(snapshot.scanned_files, 2);
+        assert_eq!(snapshot.hashed_bytes, 1536);
+        assert_eq!(snapshot.transferred_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_files_suppress_eta_until_their_size_is_known() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Upload);
+        tracker.set_expected_rate(Some(10)).await;
+        tracker
+            .add_file(FileProgress::new("a".to_string(), "a".to_string(), 100))
+            .await;
+        let streamed = FileProgress::streamed("stdin".to_string(), "stdin".to_string());
+        let file_id = streamed.file_id.clone();
+        let counter = tracker.add_file(streamed).await;
+        counter.add(300);
+
+        let snapshot = tracker.get_snapshot().await;
+        assert!(snapshot.indeterminate);
+        assert_eq!(snapshot.total_bytes, 100);
+        assert_eq!(snapshot.transferred_bytes, 300);
+        assert_eq!(snapshot.eta_seconds, None);
+
+        tracker.set_file_size(&file_id, 500).await;
+        let snapshot = tracker.get_snapshot().await;
+        assert!(!snapshot.indeterminate);
+        assert!(!snapshot.files[1].indeterminate);
+        assert_eq!(snapshot.total_bytes, 600);
+        assert_eq!(snapshot.eta_seconds, Some(30));
+    }
+
+    #[tokio::test]
+    async fn test_update_file_keeps_counters_and_counts_in_sync() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Upload);
+        let file = FileProgress::new("a".to_string(), "a".to_string(), 100);
+        let file_id = file.file_id.clone();
+        let counter = tracker.add_file(file).await;
+
+        counter.add(30);
+        tracker
+            .update_file(&file_id, |f| {
+                assert_eq!(f.transferred_bytes, 30);
+                f.status = FileStatus::Completed;
+                f.transferred_bytes = f.total_bytes;
+            })
+            .await;
+
+        assert_eq!(counter.get(), 100);
+        let snapshot = tracker.get_snapshot().await;
+        assert_eq!(snapshot.completed_files, 1);
+        assert_eq!(snapshot.transferred_bytes, 100);
+    }
+
+    #[tokio::test]
+    async fn test_written_bytes_show_file_as_writing() {
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Download);
+        let file = FileProgress::new("a".to_string(), "a".to_string(), 100);
+        let file_id = file.file_id.clone();
+        let counter = tracker.add_file(file).await;
+        tracker
+            .update_file(&file_id, |f| f.status = FileStatus::Transferring)
+            .await;
+
+        counter.set(100);
+        counter.set_written(50, Duration::from_millis(250));
+        let file = &tracker.get_snapshot().await.files[0];
+        assert_eq!(file.status, FileStatus::Writing);
+        assert_eq!(file.written_bytes, 50);
+        assert_eq!(file.write_rate, Some(200));
+
+        counter.reset_written();
+        tracker
+            .update_file(&file_id, |f| f.status = FileStatus::Transferring)
+            .await;
+        let file = &tracker.get_snapshot().await.files[0];
+        assert_eq!(file.status, FileStatus::Transferring);
+    }
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_estimated_rate_shifts_from_expected_to_measured() {
+        assert_eq!(estimated_rate(None, None, 0), None);
+        assert_eq!(estimated_rate(None, Some(1000), 0), Some(1000));
+        assert_eq!(estimated_rate(Some(200), None, 1), Some(200));
+        assert_eq!(estimated_rate(Some(200), Some(1000), 0), Some(1000));
+        assert_eq!(estimated_rate(Some(200), Some(1000), 5), Some(600));
+        assert_eq!(
+            estimated_rate(Some(200), Some(1000), ETA_CALIBRATION_SECS),
+            Some(200)
+        );
+    }
+
+    #[test]
+    fn test_token_bucket_allows_burst_then_throttles() {
+        let bucket = TokenBucket::new(Duration::from_secs(1), 3);
+
+        assert!(bucket.try_acquire(0));
+        assert!(bucket.try_acquire(0));
+        assert!(bucket.try_acquire(0));
+        assert!(!bucket.try_acquire(0));
+
+        assert!(bucket.try_acquire(SECOND));
+        assert!(!bucket.try_acquire(SECOND));
+    }
+
+    #[test]
+    fn test_token_bucket_refills_over_time() {
+        let bucket = TokenBucket::new(Duration::from_secs(1), 2);
+
+        assert!(bucket.try_acquire(0));
+        assert!(bucket.try_acquire(0));
+        assert!(!bucket.try_acquire(0));
+
+        assert!(bucket.try_acquire(10 * SECOND));
+        assert!(bucket.try_acquire(10 * SECOND));
+        assert!(!bucket.try_acquire(10 * SECOND));
+    }
+
+    #[test]
+    fn test_token_bucket_refill_restores_burst() {
+        let bucket = TokenBucket::new(Duration::from_secs(1), 2);
+
+        assert!(bucket.try_acquire(0));
+        assert!(bucket.try_acquire(0));
+        bucket.refill();
+        assert!(bucket.try_acquire(0));
+        assert!(bucket.try_acquire(0));
+        assert!(!bucket.try_acquire(0));
+    }
+
+    #[test]
+    fn test_rate_limiter_force_emit_restores_burst() {
+        let limiter = RateLimiter::new(Duration::from_secs(60));
+
+        for _ in 0..PROGRESS_BURST {
+            assert!(limiter.should_emit());
+        }
+        assert!(!limiter.should_emit());
+
+        limiter.force_emit();
+        assert!(limiter.should_emit());
+    }
+}
@@ -0,0 +1,164 @@
+//! Provider event handling for the blobs protocol
+//!
+//! The blobs provider reports connections and requests from peers fetching
+//! our shares. This module subscribes to those events and forwards them to the
+//! share registry so per-share activity can be reported, with the bytes each
+//! request actually sent, and adds finished fetches to the statistics of the
+//! peers that made them. A peer connecting
+//! also wakes the pushes queued for it in the outbox. The provider also
+//! asks before sending each chunk of data, which is where the upload rate
+//! limit is enforced, and before answering each request, which is where
+//! requests for expired and used up shares, and for the files of locked
+//! preview shares, are refused.
+
+use crate::bandwidth::BandwidthLimiter;
+use crate::devices::Outbox;
+use crate::peers::{Direction, PeerStatsStore, TransferRecord};
+use crate::shares::ShareRegistry;
+use iroh_blobs::provider::events::{
+    AbortReason, ConnectMode, EventMask, EventSender, ProviderMessage, RequestMode, RequestUpdate,
+    ThrottleMode,
+};
+use iroh_blobs::provider::TransferStats;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Capacity of the provider event channel
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Creates a provider event sender that records share activity.
+///
+/// Spawns a background task that consumes provider events for as long as the
+/// blobs protocol holds the returned sender.
+///
+/// # Arguments
+///
+/// * `shares` - The registry to record connections and requests in
+/// * `peers` - The statistics to add finished fetches to
+/// * `upload_limit` - The limiter data sent to peers is paced by
+/// * `outbox` - Pushes to retry when their device connects
+pub fn spawn_event_handler(
+    shares: ShareRegistry,
+    peers: PeerStatsStore,
+    upload_limit: BandwidthLimiter,
+    outbox: Outbox,
+) -> EventSender {
+    let mask = EventMask {
+        connected: ConnectMode::Notify,
+        get: RequestMode::InterceptLog,
+        throttle: ThrottleMode::Intercept,
+        ..EventMask::DEFAULT
+    };
+    let (sender, mut receiver) = EventSender::channel(EVENT_CHANNEL_CAPACITY, mask);
+
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            handle_message(&shares, &peers, &upload_limit, &outbox, message).await;
+        }
+    });
+
+    sender
+}
+
+/// Records a single provider event in the share registry and peer statistics.
+async fn handle_message(
+    shares: &ShareRegistry,
+    peers: &PeerStatsStore,
+    upload_limit: &BandwidthLimiter,
+    outbox: &Outbox,
+    message: ProviderMessage,
+) {
+    match message {
+        ProviderMessage::ClientConnectedNotify(message) => {
+            let peer = message.inner.endpoint_id.map(|id| id.to_string());
+            if let Some(peer) = &peer {
+                peers.seen(peer).await;
+                outbox.wake(peer);
+            }
+            shares
+                .connection_opened(message.inner.connection_id, peer)
+                .await;
+        }
+        ProviderMessage::ConnectionClosed(message) => {
+            let finished = shares.connection_closed(message.inner.connection_id).await;
+            for redemption in finished {
+                let Some(peer) = redemption.peer else {
+                    continue;
+                };
+                let elapsed = current_unix_time().saturating_sub(redemption.started_at);
+                peers
+                    .record(
+                        &peer,
+                        TransferRecord {
+                            direction: Direction::Sent,
+                            bytes: redemption.bytes_sent,
+                            duration: Duration::from_secs(elapsed),
+                            succeeded: redemption.completed,
+                        },
+                    )
+                    .await;
+            }
+        }
+        ProviderMessage::GetRequestReceived(message) => {
+            let connection_id = message.inner.connection_id;
+            let hash = message.inner.request.hash.to_string();
+            if shares.is_closed_blob(&hash).await {
+                tracing::info!(connection_id, %hash, "refused request for blob of closed share");
+                message.tx.send(Err(AbortReason::Permission)).await.ok();
+                return;
+            }
+            if shares.is_locked_blob(&hash).await {
+                tracing::info!(connection_id, %hash, "refused request for file of locked share");
+                message.tx.send(Err(AbortReason::Permission)).await.ok();
+                return;
+            }
+            message.tx.send(Ok(())).await.ok();
+            shares.request_received(connection_id, &hash).await;
+
+            let shares = shares.clone();
+            let mut updates = message.rx;
+            tokio::spawn(async move {
+                while let Ok(Some(update)) = updates.recv().await {
+                    match update {
+                        RequestUpdate::Completed(completed) => {
+                            let sent = wire_bytes(&completed.stats);
+                            shares.request_completed(connection_id, &hash, sent).await;
+                        }
+                        RequestUpdate::Aborted(aborted) => {
+                            let sent = wire_bytes(&aborted.stats);
+                            shares.request_aborted(connection_id, &hash, sent).await;
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+        ProviderMessage::Throttle(message) => {
+            // Waiting happens off the event loop so other events are not held up
+            let upload_limit = upload_limit.clone();
+            tokio::spawn(async move {
+                upload_limit.acquire(message.inner.size).await;
+                #[cfg(feature = "fault-injection")]
+                if crate::faults::bytes_sent(message.inner.size).await.is_err() {
+                    message.tx.send(Err(AbortReason::RateLimited)).await.ok();
+                    return;
+                }
+                message.tx.send(Ok(())).await.ok();
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Returns the bytes a request sent over the wire, content and verification
+/// data together.
+fn wire_bytes(stats: &TransferStats) -> u64 {
+    stats.payload_bytes_sent + stats.other_bytes_sent
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,320 @@
+//! Queue of transfers waiting for their turn
+//!
+//! Only a few transfers run at once, uploads and downloads alike. A transfer
+//! started while all slots are taken waits in the queue and starts when a
+//! running transfer finishes. Queued transfers start in queue order, which can
+//! be changed while they wait.
+
+use crate::progress::{TransferId, TransferType};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+/// Number of transfers running at once, across uploads and downloads
+pub const MAX_RUNNING_TRANSFERS: usize = 3;
+
+/// Whether a transfer in the queue is running or waiting
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueState {
+    /// Waiting for a running transfer to finish
+    Queued,
+    /// Holding one of the slots
+    Running,
+}
+
+/// A transfer in the queue, as listed for the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEntry {
+    /// The transfer
+    pub transfer_id: TransferId,
+    /// Whether the transfer is an upload or a download
+    pub transfer_type: TransferType,
+    /// Whether the transfer is running or waiting
+    pub state: QueueState,
+    /// Place among the waiting transfers, starting at 0; None while running
+    pub position: Option<usize>,
+    /// Unix timestamp when the transfer was added to the queue
+    pub enqueued_at: u64,
+}
+
+/// Thread-safe queue limiting how many transfers run at once
+#[derive(Debug, Clone)]
+pub struct TransferQueue {
+    entries: Arc<Mutex<Vec<QueueEntry>>>,
+    changed: Arc<Notify>,
+    max_running: usize,
+}
+
+impl Default for TransferQueue {
+    fn default() -> Self {
+        Self::new(MAX_RUNNING_TRANSFERS)
+    }
+}
+
+impl TransferQueue {
+    /// Creates an empty queue running at most `max_running` transfers at once
+    pub fn new(max_running: usize) -> Self {
+        Self {
+            entries: Arc::default(),
+            changed: Arc::default(),
+            max_running: max_running.max(1),
+        }
+    }
+
+    /// Adds a transfer to the end of the queue
+    ///
+    /// The transfer starts right away if a slot is free. It leaves the queue,
+    /// freeing its slot, when the returned handle is dropped.
+    pub fn enqueue(&self, transfer_id: TransferId, transfer_type: TransferType) -> QueueSlot {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.push(QueueEntry {
+                transfer_id: transfer_id.clone(),
+                transfer_type,
+                state: QueueState::Queued,
+                position: None,
+                enqueued_at: current_unix_time(),
+            });
+        }
+
+        let slot = QueueSlot {
+            queue: self.clone(),
+            transfer_id,
+        };
+        self.try_start(&slot.transfer_id);
+        slot
+    }
+
+    /// Returns every transfer in the queue, running transfers first
+    pub fn list(&self) -> Vec<QueueEntry> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+
+        let (mut running, queued): (Vec<QueueEntry>, Vec<QueueEntry>) = entries
+            .iter()
+            .cloned()
+            .partition(|entry| entry.state == QueueState::Running);
+        running.extend(
+            queued
+                .into_iter()
+                .enumerate()
+                .map(|(position, entry)| QueueEntry {
+                    position: Some(position),
+                    ..entry
+                }),
+        );
+        running
+    }
+
+    /// Moves a waiting transfer to a new place among the waiting transfers
+    ///
+    /// Positions past the end move the transfer to the end.
+    ///
+    /// # Returns
+    ///
+    /// False if the transfer is not waiting in the queue
+    pub fn move_to(&self, transfer_id: &str, position: usize) -> bool {
+        let Ok(mut entries) = self.entries.lock() else {
+            return false;
+        };
+        let Some(index) = entries.iter().position(|entry| {
+            entry.transfer_id == transfer_id && entry.state == QueueState::Queued
+        }) else {
+            return false;
+        };
+
+        let entry = entries.remove(index);
+        let queued_indices: Vec<usize> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.state == QueueState::Queued)
+            .map(|(index, _)| index)
+            .collect();
+        let target = queued_indices
+            .get(position)
+            .copied()
+            .unwrap_or(entries.len());
+        entries.insert(target, entry);
+        drop(entries);
+
+        self.changed.notify_waiters();
+        true
+    }
+
+    /// Starts a transfer if it is next in line and a slot is free.
+    ///
+    /// Returns whether the transfer is running.
+    fn try_start(&self, transfer_id: &str) -> bool {
+        let Ok(mut entries) = self.entries.lock() else {
+            return false;
+        };
+
+        let running = entries
+            .iter()
+            .filter(|entry| entry.state == QueueState::Running)
+            .count();
+        let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| entry.transfer_id == transfer_id)
+        else {
+            return false;
+        };
+        if entry.state == QueueState::Running {
+            return true;
+        }
+        if running >= self.max_running {
+            return false;
+        }
+
+        let next = entries
+            .iter_mut()
+            .find(|entry| entry.state == QueueState::Queued)
+            .filter(|entry| entry.transfer_id == transfer_id);
+        match next {
+            Some(entry) => {
+                entry.state = QueueState::Running;
+                // The next transfer in line may fit into another free slot
+                self.changed.notify_waiters();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes a transfer from the queue and lets the next one start.
+    fn remove(&self, transfer_id: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|entry| entry.transfer_id != transfer_id);
+        }
+        self.changed.notify_waiters();
+    }
+}
+
+/// Place of a transfer in the queue, given up when dropped
+pub struct QueueSlot {
+    queue: TransferQueue,
+    transfer_id: TransferId,
+}
+
+impl QueueSlot {
+    /// Checks whether the transfer holds a slot and may run
+    pub fn is_running(&self) -> bool {
+        self.queue.try_start(&self.transfer_id)
+    }
+
+    /// Waits until the transfer holds a slot and may run
+    pub async fn ready(&self) {
+        loop {
+            let notified = self.queue.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.is_running() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        self.queue.remove(&self.transfer_id);
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(queue: &TransferQueue) -> Vec<(String, QueueState, Option<usize>)> {
+        queue
+            .list()
+            .into_iter()
+            .map(|entry| (entry.transfer_id, entry.state, entry.position))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_queue_runs_transfers_in_order_within_budget() {
+        let queue = TransferQueue::new(1);
+        let first = queue.enqueue("a".to_string(), TransferType::Download);
+        let second = queue.enqueue("b".to_string(), TransferType::Upload);
+        let third = queue.enqueue("c".to_string(), TransferType::Download);
+
+        assert!(first.is_running());
+        assert!(!second.is_running());
+        assert_eq!(
+            ids(&queue),
+            vec![
+                ("a".to_string(), QueueState::Running, None),
+                ("b".to_string(), QueueState::Queued, Some(0)),
+                ("c".to_string(), QueueState::Queued, Some(1)),
+            ]
+        );
+
+        assert!(queue.move_to("c", 0));
+        assert!(!queue.move_to("a", 1));
+        assert!(!queue.move_to("unknown", 0));
+
+        let ready = third.ready();
+        tokio::pin!(ready);
+        assert!(futures::poll!(ready.as_mut()).is_pending());
+
+        drop(first);
+        ready.await;
+        assert!(!second.is_running());
+        assert_eq!(
+            ids(&queue),
+            vec![
+                ("c".to_string(), QueueState::Running, None),
+                ("b".to_string(), QueueState::Queued, Some(0)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_freed_slots_are_all_filled() {
+        let queue = TransferQueue::new(2);
+        let a = queue.enqueue("a".to_string(), TransferType::Download);
+        let b = queue.enqueue("b".to_string(), TransferType::Download);
+        let c = queue.enqueue("c".to_string(), TransferType::Download);
+        let d = queue.enqueue("d".to_string(), TransferType::Download);
+
+        let d_ready = d.ready();
+        tokio::pin!(d_ready);
+        assert!(futures::poll!(d_ready.as_mut()).is_pending());
+
+        drop(a);
+        drop(b);
+        tokio::join!(d_ready, c.ready());
+    }
+
+    #[test]
+    fn test_move_to_past_the_end() {
+        let queue = TransferQueue::new(1);
+        let _running = queue.enqueue("a".to_string(), TransferType::Upload);
+        let _b = queue.enqueue("b".to_string(), TransferType::Upload);
+        let _c = queue.enqueue("c".to_string(), TransferType::Upload);
+
+        assert!(queue.move_to("b", 10));
+        let queued: Vec<String> = queue
+            .list()
+            .into_iter()
+            .skip(1)
+            .map(|entry| entry.transfer_id)
+            .collect();
+        assert_eq!(queued, vec!["c".to_string(), "b".to_string()]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Receipts of shares delivered to drop boxes
+//!
+//! A drop box answers a push only after it has downloaded the share and every
+//! blob has been verified against its hash, so a delivered answer confirms
+//! that the receiver holds exactly the files that were sent. Each such answer
+//! is kept as a receipt recording who received which share and when, in a
+//! JSON file in the user's config directory, so deliveries can be looked up
+//! later.
+
+use crate::dropbox::DropResponse;
+use crate::json_file;
+use crate::shares::ShareId;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Name of the file receipts are stored in
+const RECEIPTS_FILE_NAME: &str = "receipts.json";
+
+/// Confirmation that a receiver downloaded and verified a share
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    /// The share that was delivered
+    pub share_id: ShareId,
+    /// Endpoint ID of the receiver, as authenticated by the connection
+    pub receiver: String,
+    /// Name of the receiver, e.g. a paired device, if known
+    #[serde(default)]
+    pub receiver_name: Option<String>,
+    /// Unix timestamp when the receiver finished verifying the share
+    pub delivered_at: u64,
+    /// Number of files the receiver saved
+    pub file_count: usize,
+    /// Total size of the files in bytes
+    pub total_size: u64,
+}
+
+impl Receipt {
+    /// Creates the receipt for a push, if the receiver confirmed delivery
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The share that was pushed
+    /// * `receiver` - Endpoint ID of the drop box that answered
+    /// * `receiver_name` - Name of the receiver, if known
+    /// * `response` - The answer of the drop box
+    pub fn for_push(
+        share_id: &str,
+        receiver: String,
+        receiver_name: Option<String>,
+        response: &DropResponse,
+    ) -> Option<Self> {
+        match response {
+            DropResponse::Delivered {
+                file_count,
+                total_size,
+                delivered_at,
+            } => Some(Self {
+                share_id: share_id.to_string(),
+                receiver,
+                receiver_name,
+                delivered_at: *delivered_at,
+                file_count: *file_count,
+                total_size: *total_size,
+            }),
+            DropResponse::Rejected { .. } | DropResponse::Failed { .. } => None,
+        }
+    }
+}
+
+/// Thread-safe log of receipts, persisted to a JSON file
+#[derive(Debug, Clone)]
+pub struct ReceiptLog {
+    path: Option<PathBuf>,
+    receipts: Arc<RwLock<Vec<Receipt>>>,
+}
+
+impl ReceiptLog {
+    /// Creates a log that keeps receipts for this session only
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            receipts: Arc::default(),
+        }
+    }
+
+    /// Loads receipts from a file, starting empty if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        let receipts = json_file::read(&path, "receipts")
+            .await?
+            .unwrap_or_default();
+
+        Ok(Self {
+            path: Some(path),
+            receipts: Arc::new(RwLock::new(receipts)),
+        })
+    }
+
+    /// Loads receipts from the user's config directory
+    ///
+    /// Falls back to receipts for this session only if the config directory
+    /// cannot be determined or the file is unreadable.
+    pub async fn open_default() -> Self {
+        let Some(path) = json_file::config_path(RECEIPTS_FILE_NAME) else {
+            return Self::in_memory();
+        };
+        Self::load(path).await.unwrap_or_else(|_| Self::in_memory())
+    }
+
+    /// Returns the receipts of a share, or of every share if None, oldest first
+    pub async fn list(&self, share_id: Option<&str>) -> Vec<Receipt> {
+        let receipts = self.receipts.read().await;
+        receipts
+            .iter()
+            .filter(|receipt| share_id.is_none_or(|share_id| receipt.share_id == share_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Adds a receipt and persists the log
+    ///
+    /// Persisting is best effort: the delivery itself has already succeeded.
+    pub async fn record(&self, receipt: Receipt) {
+        let mut receipts = self.receipts.write().await;
+        receipts.push(receipt);
+
+        if let Some(path) = &self.path {
+            let _ = json_file::write(path, &*receipts).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_record_receipts_and_reload() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join(RECEIPTS_FILE_NAME);
+        let log = ReceiptLog::load(path.clone()).await.unwrap();
+
+        let delivered = DropResponse::Delivered {
+            file_count: 2,
+            total_size: 300,
+            delivered_at: 1_700_000_000,
+        };
+        let receipt = Receipt::for_push(
+            "share-1",
+            "peer".to_string(),
+            Some("phone".to_string()),
+            &delivered,
+        )
+        .unwrap();
+        log.record(receipt.clone()).await;
+        log.record(Receipt {
+            share_id: "share-2".to_string(),
+            ..receipt.clone()
+        })
+        .await;
+
+        let rejected = DropResponse::Rejected {
+            reason: "too large".to_string(),
+        };
+        assert!(Receipt::for_push("share-1", "peer".to_string(), None, &rejected).is_none());
+
+        let reloaded = ReceiptLog::load(path).await.unwrap();
+        assert_eq!(reloaded.list(Some("share-1")).await, vec![receipt]);
+        assert_eq!(reloaded.list(None).await.len(), 2);
+        assert!(reloaded.list(Some("unknown")).await.is_empty());
+    }
+}
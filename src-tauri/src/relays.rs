@@ -0,0 +1,141 @@
+//! Which relay servers the endpoint connects through
+//!
+//! Peers that cannot reach each other directly exchange data through a relay
+//! server, and the first connection to a peer always starts there. By default
+//! the endpoint knows the public relays of every region and makes the one
+//! with the lowest latency its home relay. Users far from those relays can
+//! add relay servers of their own (e.g. self-hosted nearby), which then take
+//! part in the same latency-based selection, or pin the endpoint to a few
+//! regions or to their own relays only.
+//!
+//! The relays are chosen when the endpoint is created, so a changed
+//! preference takes effect on the next start.
+
+use anyhow::Result;
+use iroh::{RelayMap, RelayMode, RelayUrl};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Region of a public relay server
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RelayRegion {
+    /// North America (us-east)
+    NorthAmerica,
+    /// Europe (eu-central)
+    Europe,
+    /// Asia-Pacific (ap-southeast)
+    AsiaPacific,
+}
+
+impl RelayRegion {
+    /// Every region, in the order they are offered
+    pub const ALL: [RelayRegion; 3] = [
+        RelayRegion::NorthAmerica,
+        RelayRegion::Europe,
+        RelayRegion::AsiaPacific,
+    ];
+
+    /// Returns the URL of the public relay in this region
+    pub fn url(self) -> &'static str {
+        match self {
+            RelayRegion::NorthAmerica => "https://use1-1.relay.n0.iroh.iroh.link./",
+            RelayRegion::Europe => "https://euc1-1.relay.n0.iroh.iroh.link./",
+            RelayRegion::AsiaPacific => "https://aps1-1.relay.n0.iroh.iroh.link./",
+        }
+    }
+}
+
+impl FromStr for RelayRegion {
+    type Err = String;
+
+    /// Parses a region from its short name, e.g. "na", "eu" or "ap"
+    fn from_str(region: &str) -> Result<Self, Self::Err> {
+        match region.to_ascii_lowercase().as_str() {
+            "na" | "northamerica" => Ok(RelayRegion::NorthAmerica),
+            "eu" | "europe" => Ok(RelayRegion::Europe),
+            "ap" | "asiapacific" => Ok(RelayRegion::AsiaPacific),
+            _ => Err(format!(
+                "Unknown relay region '{}', expected na, eu or ap",
+                region
+            )),
+        }
+    }
+}
+
+/// Relay servers the endpoint chooses its home relay from
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPreference {
+    /// Regions of the public relays to use; every region if None, and no
+    /// public relay at all if empty
+    #[serde(default)]
+    pub regions: Option<Vec<RelayRegion>>,
+    /// Further relay servers, e.g. "https://relay.example.com"
+    #[serde(default)]
+    pub custom_urls: Vec<String>,
+}
+
+impl RelayPreference {
+    /// Returns the relay mode an endpoint is created with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a custom URL is invalid or no relay is left.
+    pub fn relay_mode(&self) -> Result<RelayMode> {
+        if self.regions.is_none() && self.custom_urls.is_empty() {
+            return Ok(RelayMode::Default);
+        }
+
+        let regions = self.regions.as_deref().unwrap_or(&RelayRegion::ALL);
+        let urls = regions
+            .iter()
+            .map(|region| region.url())
+            .chain(self.custom_urls.iter().map(String::as_str))
+            .map(|url| {
+                url.parse::<RelayUrl>()
+                    .map_err(|error| anyhow::anyhow!("Invalid relay URL '{}': {}", url, error))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if urls.is_empty() {
+            anyhow::bail!("At least one relay region or custom relay is needed");
+        }
+
+        Ok(RelayMode::Custom(RelayMap::from_iter(urls)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_mode() {
+        assert!(matches!(
+            RelayPreference::default().relay_mode().unwrap(),
+            RelayMode::Default
+        ));
+
+        let pinned = RelayPreference {
+            regions: Some(vec!["eu".parse().unwrap()]),
+            custom_urls: vec!["https://relay.example.com".to_string()],
+        };
+        let RelayMode::Custom(map) = pinned.relay_mode().unwrap() else {
+            panic!("expected custom relays");
+        };
+        assert_eq!(map.len(), 2);
+
+        let none = RelayPreference {
+            regions: Some(Vec::new()),
+            custom_urls: Vec::new(),
+        };
+        assert!(none.relay_mode().is_err());
+
+        let invalid = RelayPreference {
+            regions: None,
+            custom_urls: vec!["not a url".to_string()],
+        };
+        assert!(invalid.relay_mode().is_err());
+        assert!("mars".parse::<RelayRegion>().is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Shares served by running CLI processes
+//!
+//! Every `ginseng-cli send` runs its own node, so another invocation cannot
+//! reach its shares directly. Instead, each running share is recorded as a
+//! small JSON file in the config directory. `ginseng-cli stop` deletes the
+//! file, and the sending process stops serving the share once it notices.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Name of the folder holding the running shares in the config directory
+const RUNNING_SHARES_DIR_NAME: &str = "running";
+
+/// A share served by a running CLI process
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningShare {
+    /// ID of the share within the sending process
+    pub share_id: String,
+    /// The ticket handed out for the share
+    pub ticket: String,
+    /// Paths being shared
+    pub paths: Vec<PathBuf>,
+    /// Unix timestamp when sharing started
+    pub started_at: u64,
+}
+
+/// Folder of shares served by running CLI processes
+#[derive(Debug, Clone)]
+pub struct RunningShares {
+    directory: PathBuf,
+}
+
+impl RunningShares {
+    /// Creates a registry in the given folder
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// Creates a registry in the user's config directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config directory cannot be determined
+    pub fn open_default() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+        Ok(Self::new(
+            config_dir.join("ginseng").join(RUNNING_SHARES_DIR_NAME),
+        ))
+    }
+
+    /// Records a share as running
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record cannot be written
+    pub async fn add(&self, share: &RunningShare) -> Result<()> {
+        fs::create_dir_all(&self.directory).await?;
+        let json = serde_json::to_string_pretty(share)?;
+        let path = self.path(&share.share_id);
+        fs::write(&path, json).await.map_err(|error| {
+            anyhow::anyhow!("Failed to record share '{}': {}", path.display(), error)
+        })
+    }
+
+    /// Checks whether a share is still recorded as running
+    ///
+    /// Assumes it is if the folder cannot be read, so a share is only stopped
+    /// when asked to.
+    pub async fn contains(&self, share_id: &str) -> bool {
+        fs::try_exists(self.path(share_id)).await.unwrap_or(true)
+    }
+
+    /// Returns every running share, oldest first
+    ///
+    /// Records that cannot be read are skipped.
+    pub async fn list(&self) -> Vec<RunningShare> {
+        let Ok(mut entries) = fs::read_dir(&self.directory).await else {
+            return Vec::new();
+        };
+
+        let mut shares = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(content) = fs::read_to_string(entry.path()).await else {
+                continue;
+            };
+            if let Ok(share) = serde_json::from_str::<RunningShare>(&content) {
+                shares.push(share);
+            }
+        }
+
+        shares.sort_by_key(|share| share.started_at);
+        shares
+    }
+
+    /// Removes the record of a share, which tells its process to stop serving it
+    ///
+    /// # Arguments
+    ///
+    /// * `share` - The ID or the ticket of the share
+    ///
+    /// # Returns
+    ///
+    /// The removed share, or None if no running share matches
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record cannot be deleted
+    pub async fn remove(&self, share: &str) -> Result<Option<RunningShare>> {
+        let Some(running) = self
+            .list()
+            .await
+            .into_iter()
+            .find(|running| running.share_id == share || running.ticket == share)
+        else {
+            return Ok(None);
+        };
+
+        match fs::remove_file(self.path(&running.share_id)).await {
+            Ok(()) => Ok(Some(running)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(anyhow::anyhow!(
+                "Failed to stop share '{}': {}",
+                running.share_id,
+                error
+            )),
+        }
+    }
+
+    /// Returns the path of the record of a share.
+    fn path(&self, share_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", share_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn share(share_id: &str, started_at: u64) -> RunningShare {
+        RunningShare {
+            share_id: share_id.to_string(),
+            ticket: format!("ticket-{}", share_id),
+            paths: vec![PathBuf::from("/tmp/file.txt")],
+            started_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_list_and_remove() {
+        let temp_dir = tempdir().unwrap();
+        let running = RunningShares::new(temp_dir.path().join("running"));
+        assert!(running.list().await.is_empty());
+
+        running.add(&share("b", 2)).await.unwrap();
+        running.add(&share("a", 1)).await.unwrap();
+        assert_eq!(running.list().await, vec![share("a", 1), share("b", 2)]);
+        assert!(running.contains("a").await);
+
+        assert_eq!(running.remove("a").await.unwrap(), Some(share("a", 1)));
+        assert!(!running.contains("a").await);
+        assert_eq!(
+            running.remove("ticket-b").await.unwrap(),
+            Some(share("b", 2))
+        );
+        assert_eq!(running.remove("b").await.unwrap(), None);
+    }
+}
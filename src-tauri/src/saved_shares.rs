@@ -0,0 +1,182 @@
+//! Shares kept open across restarts
+//!
+//! Blobs live in memory only, so a share would stop working when the app
+//! closes. Each open share is saved instead, with its ticket, its metadata and
+//! the files its blobs were read from, in a JSON file in the user's config
+//! directory. When the app starts again the files are stored once more and the
+//! share is registered under its old ticket; since blobs are addressed by
+//! their content and the endpoint keeps its identity (see
+//! [`crate::identity`]), the ticket leads to the same share as before.
+//!
+//! Archive shares are not saved, since their blob is built from the files
+//! rather than read from one.
+
+use crate::bookmarks::Bookmarks;
+use crate::core::ShareMetadata;
+use crate::json_file;
+use crate::shares::ShareId;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Name of the file open shares are stored in
+const SAVED_SHARES_FILE_NAME: &str = "shares.json";
+
+/// An open share, with what is needed to serve it again
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedShare {
+    /// The ID the share was created with
+    pub share_id: ShareId,
+    /// The ticket handed out for the share
+    pub ticket: String,
+    /// Metadata of the share, as served to receivers
+    pub metadata: ShareMetadata,
+    /// Files and directories the share was created from
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// Security-scoped bookmarks of the paths, needed to read them again
+    /// under the macOS sandbox
+    #[serde(default, skip_serializing_if = "Bookmarks::is_empty")]
+    pub bookmarks: Bookmarks,
+    /// File each blob of the share was read from, by hash
+    pub sources: BTreeMap<String, PathBuf>,
+    /// Whether the share closes after its first complete download
+    pub single_use: bool,
+    /// Whether the files are still locked for a preview
+    #[serde(default)]
+    pub locked: bool,
+}
+
+/// Thread-safe list of open shares, persisted to a JSON file
+#[derive(Debug, Clone)]
+pub struct SavedShares {
+    path: Option<PathBuf>,
+    shares: Arc<RwLock<Vec<SavedShare>>>,
+}
+
+impl SavedShares {
+    /// Creates a list that keeps shares for this session only
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            shares: Arc::default(),
+        }
+    }
+
+    /// Loads shares from a file, starting empty if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        let shares = json_file::read(&path, "shares").await?.unwrap_or_default();
+
+        Ok(Self {
+            path: Some(path),
+            shares: Arc::new(RwLock::new(shares)),
+        })
+    }
+
+    /// Loads shares from the user's config directory
+    ///
+    /// Falls back to shares for this session only if the config directory
+    /// cannot be determined or the file is unreadable.
+    pub async fn open_default() -> Self {
+        let Some(path) = json_file::config_path(SAVED_SHARES_FILE_NAME) else {
+            return Self::in_memory();
+        };
+        Self::load(path).await.unwrap_or_else(|_| Self::in_memory())
+    }
+
+    /// Returns all saved shares, oldest first
+    pub async fn list(&self) -> Vec<SavedShare> {
+        self.shares.read().await.clone()
+    }
+
+    /// Saves a share, replacing an earlier one with the same ID
+    ///
+    /// Persisting is best effort: the share is open either way.
+    pub async fn save(&self, share: SavedShare) {
+        let mut shares = self.shares.write().await;
+        shares.retain(|saved| saved.share_id != share.share_id);
+        shares.push(share);
+        self.persist(&shares).await;
+    }
+
+    /// Forgets a share once it is closed
+    pub async fn remove(&self, share_id: &str) {
+        let mut shares = self.shares.write().await;
+        let count = shares.len();
+        shares.retain(|saved| saved.share_id != share_id);
+        if shares.len() != count {
+            self.persist(&shares).await;
+        }
+    }
+
+    /// Records whether the files of a share are locked for a preview
+    pub async fn set_locked(&self, share_id: &str, locked: bool) {
+        let mut shares = self.shares.write().await;
+        let Some(share) = shares.iter_mut().find(|saved| saved.share_id == share_id) else {
+            return;
+        };
+        share.locked = locked;
+        self.persist(&shares).await;
+    }
+
+    /// Writes the shares to the file, if this list has one
+    async fn persist(&self, shares: &[SavedShare]) {
+        if let Some(path) = &self.path {
+            let _ = json_file::write(path, shares).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ShareType;
+    use tempfile::tempdir;
+
+    fn saved_share(share_id: &str) -> SavedShare {
+        SavedShare {
+            share_id: share_id.to_string(),
+            ticket: format!("ticket-{}", share_id),
+            metadata: ShareMetadata {
+                files: Vec::new(),
+                share_type: ShareType::SingleFile,
+                total_size: 0,
+                skipped_files: Vec::new(),
+                custom_fields: BTreeMap::new(),
+                expires_at: None,
+                share_id: Some(share_id.to_string()),
+                content_root: None,
+            },
+            paths: vec![PathBuf::from("/tmp")],
+            bookmarks: Bookmarks::from([(PathBuf::from("/tmp"), vec![1, 2, 3])]),
+            sources: BTreeMap::from([("hash".to_string(), PathBuf::from("/tmp/file.txt"))]),
+            single_use: false,
+            locked: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_shares_and_reload() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("config").join(SAVED_SHARES_FILE_NAME);
+        let shares = SavedShares::load(path.clone()).await.unwrap();
+
+        shares.save(saved_share("share-1")).await;
+        shares.save(saved_share("share-2")).await;
+        shares.set_locked("share-1", false).await;
+        shares.remove("share-2").await;
+
+        let reloaded = SavedShares::load(path).await.unwrap();
+        let mut expected = saved_share("share-1");
+        expected.locked = false;
+        assert_eq!(reloaded.list().await, vec![expected]);
+    }
+}
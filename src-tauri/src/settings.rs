@@ -0,0 +1,193 @@
+//! User settings stored in the config directory
+//!
+//! Settings are kept as JSON next to the share templates. Missing fields fall
+//! back to their defaults, so settings files written by older versions keep
+//! working as new settings are added.
+
+use crate::bandwidth::{BandwidthLimits, BandwidthWindow};
+use crate::concurrency::ConcurrencyLimits;
+use crate::devices::{DeviceGroup, PairedDevice};
+use crate::organize::OrganizeRule;
+use crate::relays::RelayPreference;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Name of the file settings are stored in
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// User settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    /// Endpoint IDs of peers shown first in device lists
+    #[serde(default)]
+    pub pinned_peers: BTreeSet<String>,
+    /// IDs of shares shown first in the library and history
+    #[serde(default)]
+    pub pinned_shares: BTreeSet<String>,
+    /// Upload and download rate caps applied when the app starts
+    #[serde(default)]
+    pub bandwidth: BandwidthLimits,
+    /// Times of day with their own rate caps, overriding `bandwidth`
+    #[serde(default)]
+    pub bandwidth_schedule: Vec<BandwidthWindow>,
+    /// Files read or written and files downloaded at once
+    #[serde(default)]
+    pub concurrency: ConcurrencyLimits,
+    /// Folder shares are saved to in place of Downloads
+    #[serde(default)]
+    pub download_directory: Option<PathBuf>,
+    /// Download folders by endpoint ID, for shares received from that device
+    #[serde(default)]
+    pub device_folders: BTreeMap<String, PathBuf>,
+    /// Subfolders received shares are organized into, outermost first
+    #[serde(default)]
+    pub organize_rules: Vec<OrganizeRule>,
+    /// Template naming the folders shares of several files are saved in;
+    /// `Ginseng {date} {time}` if None
+    #[serde(default)]
+    pub folder_name_template: Option<String>,
+    /// Devices files can be sent to by name
+    #[serde(default)]
+    pub paired_devices: Vec<PairedDevice>,
+    /// Whether connections to paired devices are kept open so transfers
+    /// start right away
+    #[serde(default)]
+    pub warm_connections: bool,
+    /// Whether the router is asked to forward the endpoint's port over UPnP,
+    /// NAT-PMP or PCP, so peers can connect directly
+    #[serde(default)]
+    pub port_mapping: bool,
+    /// Whether downloaded files are removed from the store once saved,
+    /// instead of being kept in the library
+    #[serde(default)]
+    pub release_downloads: bool,
+    /// Groups of paired devices a share can be sent to at once
+    #[serde(default)]
+    pub device_groups: Vec<DeviceGroup>,
+    /// Token a companion browser extension authenticates with, created when
+    /// the companion endpoint is first started
+    #[serde(default)]
+    pub companion_token: Option<String>,
+    /// Largest total size shared without asking for confirmation, in bytes;
+    /// unlimited if None
+    #[serde(default)]
+    pub max_share_size: Option<u64>,
+    /// Relay servers the endpoint connects through, applied on the next start
+    #[serde(default)]
+    pub relays: RelayPreference,
+}
+
+/// Moves pinned items to the front, keeping the order within each group
+///
+/// # Arguments
+///
+/// * `items` - The items to reorder
+/// * `pinned` - IDs of the pinned items
+/// * `id` - Returns the ID of an item
+pub fn pinned_first<T>(items: &mut [T], pinned: &BTreeSet<String>, id: impl Fn(&T) -> &str) {
+    items.sort_by_key(|item| !pinned.contains(id(item)));
+}
+
+/// Settings saved in a JSON file
+#[derive(Debug, Clone)]
+pub struct SettingsStore {
+    path: PathBuf,
+}
+
+impl SettingsStore {
+    /// Creates a store backed by the given file
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Creates a store in the user's config directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config directory cannot be determined
+    pub fn open_default() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+        Ok(Self::new(
+            config_dir.join("ginseng").join(SETTINGS_FILE_NAME),
+        ))
+    }
+
+    /// Returns the saved settings, or the defaults if none were saved
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the settings file exists but cannot be read
+    pub async fn load(&self) -> Result<Settings> {
+        if !self.path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let content = fs::read_to_string(&self.path).await?;
+        serde_json::from_str(&content).map_err(|error| {
+            anyhow::anyhow!("Invalid settings file '{}': {}", self.path.display(), error)
+        })
+    }
+
+    /// Changes the saved settings and returns the result
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the settings cannot be read or written
+    pub async fn update(&self, updater: impl FnOnce(&mut Settings)) -> Result<Settings> {
+        let mut settings = self.load().await?;
+        updater(&mut settings);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let json = serde_json::to_string_pretty(&settings)?;
+        fs::write(&self.path, json).await.map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to write settings file '{}': {}",
+                self.path.display(),
+                error
+            )
+        })?;
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_update_and_load_settings() {
+        let temp_dir = tempdir().unwrap();
+        let store = SettingsStore::new(temp_dir.path().join("config").join(SETTINGS_FILE_NAME));
+        assert_eq!(store.load().await.unwrap(), Settings::default());
+
+        store
+            .update(|settings| {
+                settings.pinned_peers.insert("peer".to_string());
+            })
+            .await
+            .unwrap();
+
+        let settings = store.load().await.unwrap();
+        assert!(settings.pinned_peers.contains("peer"));
+        assert!(settings.pinned_shares.is_empty());
+    }
+
+    #[test]
+    fn test_pinned_first_keeps_order_within_groups() {
+        let mut items = vec!["a", "b", "c", "d"];
+        let pinned = BTreeSet::from(["c".to_string(), "b".to_string()]);
+
+        pinned_first(&mut items, &pinned, |item| item);
+
+        assert_eq!(items, vec!["b", "c", "a", "d"]);
+    }
+}
@@ -0,0 +1,461 @@
+//! Printable transfer slips for handing out shares in person
+//!
+//! A share card holds everything a recipient needs to receive a share on one
+//! page: short instructions, the ticket as a QR code and as text, a short code
+//! to refer to the share by, and when it expires. Cards are rendered as a PDF
+//! for printing or as a PNG image for sending on. Both formats are written
+//! directly, using the built-in fonts of PDF viewers and a small bitmap font
+//! for images, so no fonts need to be installed.
+
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use qrcode::{Color, QrCode};
+use std::path::Path;
+
+/// Steps printed on every card
+const INSTRUCTIONS: [&str; 3] = [
+    "1. Install Ginseng from github.com/alDuncanson/ginseng/releases.",
+    "2. Scan the QR code with your phone camera to get the ticket.",
+    "3. Paste the ticket into the Receive tab of Ginseng and download the files.",
+];
+
+/// Number of characters of the share ID shown as its short code
+const SHORT_CODE_LENGTH: usize = 8;
+
+/// Light modules kept around the QR code so scanners can find it
+const QR_QUIET_ZONE: usize = 4;
+
+/// Page size of PDF cards in points (A6 portrait)
+const PDF_PAGE: (f32, f32) = (298.0, 420.0);
+
+/// Margin around the content of PDF cards in points
+const PDF_MARGIN: f32 = 24.0;
+
+/// Width of PNG cards in pixels
+const PNG_WIDTH: usize = 600;
+
+/// Size of each pixel of the bitmap font on PNG cards
+const PNG_TEXT_SCALE: usize = 2;
+
+/// File format of a share card
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardFormat {
+    /// A one-page A6 document for printing
+    Pdf,
+    /// A grayscale image
+    Png,
+}
+
+impl CardFormat {
+    /// Picks the format from a file's extension, defaulting to PDF.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("png") => Self::Png,
+            _ => Self::Pdf,
+        }
+    }
+}
+
+/// The details of a share printed on a card
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareCard {
+    /// The ticket recipients download the share with
+    pub ticket: String,
+    /// Short code to refer to the share by (e.g., "1A2B-3C4D")
+    pub short_code: String,
+    /// Unix timestamp when the share stops being served, if it expires
+    pub expires_at: Option<u64>,
+}
+
+impl ShareCard {
+    /// Creates a card for a share.
+    ///
+    /// # Arguments
+    ///
+    /// * `share_id` - The ID of the share, which the short code is taken from
+    /// * `ticket` - The ticket handed out for the share
+    /// * `expires_at` - Unix timestamp when the share expires, if it does
+    pub fn new(share_id: &str, ticket: String, expires_at: Option<u64>) -> Self {
+        Self {
+            ticket,
+            short_code: short_code(share_id),
+            expires_at,
+        }
+    }
+
+    /// Renders the card in the given format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ticket is too long for a QR code or the image
+    /// cannot be encoded.
+    pub fn render(&self, format: CardFormat) -> Result<Vec<u8>> {
+        let qr = QrCode::new(self.ticket.as_bytes())
+            .map_err(|error| anyhow::anyhow!("Failed to create QR code: {}", error))?;
+        match format {
+            CardFormat::Pdf => Ok(self.render_pdf(&qr)),
+            CardFormat::Png => self.render_png(&qr),
+        }
+    }
+
+    /// Describes when the share expires.
+    fn expiry_line(&self) -> String {
+        match self
+            .expires_at
+            .and_then(|expires_at| Local.timestamp_opt(expires_at as i64, 0).single())
+        {
+            Some(expires_at) => format!("Expires {}", expires_at.format("%Y-%m-%d %H:%M")),
+            None => "Does not expire".to_string(),
+        }
+    }
+
+    fn render_pdf(&self, qr: &QrCode) -> Vec<u8> {
+        let (page_width, page_height) = PDF_PAGE;
+        let mut content = String::new();
+        let mut y = page_height - PDF_MARGIN - 16.0;
+
+        pdf_text(&mut content, "F2", 16.0, PDF_MARGIN, y, "Files for you");
+        y -= 22.0;
+        for instruction in INSTRUCTIONS {
+            for line in wrap(instruction, 58) {
+                pdf_text(&mut content, "F1", 8.0, PDF_MARGIN, y, &line);
+                y -= 11.0;
+            }
+        }
+
+        // The QR code, one filled square per dark module
+        let size = 160.0;
+        let modules = qr.width() + 2 * QR_QUIET_ZONE;
+        let module_size = size / modules as f32;
+        let left = (page_width - size) / 2.0;
+        let top = y - 4.0;
+        content.push_str("0 g\n");
+        for (index, color) in qr.to_colors().into_iter().enumerate() {
+            if color == Color::Dark {
+                let column = index % qr.width() + QR_QUIET_ZONE;
+                let row = index / qr.width() + QR_QUIET_ZONE;
+                content.push_str(&format!(
+                    "{:.2} {:.2} {:.2} {:.2} re\n",
+                    left + column as f32 * module_size,
+                    top - (row + 1) as f32 * module_size,
+                    module_size,
+                    module_size
+                ));
+            }
+        }
+        content.push_str("f\n");
+        y = top - size - 18.0;
+
+        let code_line = format!("Share code {}", self.short_code);
+        pdf_text(&mut content, "F2", 12.0, PDF_MARGIN, y, &code_line);
+        y -= 14.0;
+        pdf_text(&mut content, "F1", 9.0, PDF_MARGIN, y, &self.expiry_line());
+        y -= 14.0;
+        for line in wrap_chars(&self.ticket, 80) {
+            pdf_text(&mut content, "F3", 5.0, PDF_MARGIN, y, &line);
+            y -= 6.0;
+        }
+
+        pdf_document(page_width, page_height, &content)
+    }
+
+    fn render_png(&self, qr: &QrCode) -> Result<Vec<u8>> {
+        let margin = 24;
+        let line_height = (GLYPH_HEIGHT + 3) * PNG_TEXT_SCALE;
+        let columns = (PNG_WIDTH - 2 * margin) / ((GLYPH_WIDTH + 1) * PNG_TEXT_SCALE);
+
+        let mut header = vec!["FILES FOR YOU".to_string(), String::new()];
+        for instruction in INSTRUCTIONS {
+            header.extend(wrap(&instruction.to_uppercase(), columns));
+        }
+        let footer = [
+            format!("SHARE CODE {}", self.short_code),
+            self.expiry_line().to_uppercase(),
+        ];
+
+        let modules = qr.width() + 2 * QR_QUIET_ZONE;
+        let module_size = ((PNG_WIDTH - 2 * margin) * 3 / 4 / modules).max(1);
+        let qr_size = modules * module_size;
+        let height = 2 * margin + (header.len() + footer.len() + 2) * line_height + qr_size;
+
+        let mut canvas = Canvas::new(PNG_WIDTH, height);
+        let mut y = margin;
+        for line in &header {
+            canvas.text(margin, y, line);
+            y += line_height;
+        }
+
+        y += line_height;
+        let left = (PNG_WIDTH - qr_size) / 2;
+        for (index, color) in qr.to_colors().into_iter().enumerate() {
+            if color == Color::Dark {
+                let column = index % qr.width() + QR_QUIET_ZONE;
+                let row = index / qr.width() + QR_QUIET_ZONE;
+                canvas.fill(
+                    left + column * module_size,
+                    y + row * module_size,
+                    module_size,
+                    module_size,
+                );
+            }
+        }
+        y += qr_size + line_height;
+
+        for line in &footer {
+            canvas.text(margin, y, line);
+            y += line_height;
+        }
+        canvas.encode_png()
+    }
+}
+
+/// Returns the short code of a share, e.g. "1A2B-3C4D" for a share ID
+/// starting with "1a2b3c4d".
+pub fn short_code(share_id: &str) -> String {
+    let characters: Vec<char> = share_id
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(SHORT_CODE_LENGTH)
+        .map(|character| character.to_ascii_uppercase())
+        .collect();
+    characters
+        .chunks(SHORT_CODE_LENGTH / 2)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Splits text into lines of at most `width` characters at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Splits text without spaces, such as a ticket, into lines of `width` characters.
+fn wrap_chars(text: &str, width: usize) -> Vec<String> {
+    text.chars()
+        .collect::<Vec<_>>()
+        .chunks(width)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Appends a line of text to a PDF content stream.
+fn pdf_text(content: &mut String, font: &str, size: f32, x: f32, y: f32, text: &str) {
+    let escaped: String = text
+        .chars()
+        .map(|character| match character {
+            '\\' | '(' | ')' => format!("\\{}", character),
+            character if character.is_ascii() => character.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect();
+    content.push_str(&format!(
+        "BT /{} {} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+        font, size, x, y, escaped
+    ));
+}
+
+/// Builds a one-page PDF around a content stream, with Helvetica as `F1`,
+/// Helvetica-Bold as `F2` and Courier as `F3`.
+fn pdf_document(width: f32, height: f32, content: &str) -> Vec<u8> {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 5 0 R /F2 6 0 R /F3 7 0 R >> >> /Contents 4 0 R >>",
+            width, height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+    ];
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    pdf.into_bytes()
+}
+
+/// Width of a bitmap font glyph in pixels
+const GLYPH_WIDTH: usize = 5;
+
+/// Height of a bitmap font glyph in pixels
+const GLYPH_HEIGHT: usize = 7;
+
+/// A white grayscale image to draw on in black
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![u8::MAX; width * height],
+        }
+    }
+
+    /// Fills a rectangle, clipped to the canvas.
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for row in y..(y + height).min(self.height) {
+            let start = row * self.width;
+            for column in x..(x + width).min(self.width) {
+                self.pixels[start + column] = 0;
+            }
+        }
+    }
+
+    /// Draws a line of text in the bitmap font, which only has capital
+    /// letters, digits and a few punctuation marks.
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (index, character) in text.chars().enumerate() {
+            let left = x + index * (GLYPH_WIDTH + 1) * PNG_TEXT_SCALE;
+            for (row, bits) in glyph(character).into_iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill(
+                            left + column * PNG_TEXT_SCALE,
+                            y + row * PNG_TEXT_SCALE,
+                            PNG_TEXT_SCALE,
+                            PNG_TEXT_SCALE,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+}
+
+/// Returns the rows of a 5x7 glyph, most significant bit on the left.
+///
+/// Characters without a glyph are drawn as a question mark.
+#[rustfmt::skip]
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0; GLYPH_HEIGHT],
+        '-' => [0, 0, 0, 0b11111, 0, 0, 0],
+        ':' => [0, 0b01100, 0b01100, 0, 0b01100, 0b01100, 0],
+        '.' => [0, 0, 0, 0, 0, 0b01100, 0b01100],
+        ',' => [0, 0, 0, 0, 0b01100, 0b00100, 0b01000],
+        '/' => [0, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_share_card() {
+        let card = ShareCard::new(
+            "1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d",
+            "blobticket".repeat(20),
+            None,
+        );
+        assert_eq!(card.short_code, "1A2B-3C4D");
+        assert_eq!(card.expiry_line(), "Does not expire");
+
+        let pdf = card.render(CardFormat::Pdf).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let text = String::from_utf8(pdf).unwrap();
+        assert!(text.contains("(Share code 1A2B-3C4D) Tj"));
+
+        let png = card.render(CardFormat::Png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        assert_eq!(
+            CardFormat::from_path(Path::new("slip.PNG")),
+            CardFormat::Png
+        );
+        assert_eq!(CardFormat::from_path(Path::new("slip")), CardFormat::Pdf);
+    }
+}
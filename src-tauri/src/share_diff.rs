@@ -0,0 +1,142 @@
+//! Differences between two shares
+//!
+//! Compares the file lists of two shares, e.g. yesterday's and today's export
+//! of a folder, without downloading either. Files are matched by their path
+//! within the share; a file whose path is in both shares but whose content
+//! hash differs counts as changed. A file that was moved shows up as removed
+//! from its old path and added at its new one.
+
+use crate::core::{FileInfo, ShareMetadata};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A file whose content differs between two shares
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedFile {
+    /// Path of the file within both shares
+    pub relative_path: String,
+    /// The file in the older share
+    pub before: FileInfo,
+    /// The file in the newer share
+    pub after: FileInfo,
+}
+
+/// Files added, removed and changed from one share to another
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareDiff {
+    /// Files only in the newer share, by path
+    pub added: Vec<FileInfo>,
+    /// Files only in the older share, by path
+    pub removed: Vec<FileInfo>,
+    /// Files in both shares with different content, by path
+    pub changed: Vec<ChangedFile>,
+    /// Number of files identical in both shares
+    pub unchanged: usize,
+}
+
+impl ShareDiff {
+    /// Whether both shares hold the same files
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the file lists of two shares.
+///
+/// # Arguments
+///
+/// * `before` - Metadata of the older share
+/// * `after` - Metadata of the newer share
+///
+/// # Returns
+///
+/// The differences, each list sorted by path
+pub fn diff_shares(before: &ShareMetadata, after: &ShareMetadata) -> ShareDiff {
+    let before_files = files_by_path(&before.files);
+    let mut after_files = files_by_path(&after.files);
+    let mut diff = ShareDiff::default();
+
+    for (path, old) in before_files {
+        match after_files.remove(path) {
+            Some(new) if new.hash == old.hash => diff.unchanged += 1,
+            Some(new) => diff.changed.push(ChangedFile {
+                relative_path: path.to_string(),
+                before: old.clone(),
+                after: new.clone(),
+            }),
+            None => diff.removed.push(old.clone()),
+        }
+    }
+    diff.added = after_files.into_values().cloned().collect();
+
+    diff
+}
+
+/// Indexes files by their path within the share.
+fn files_by_path(files: &[FileInfo]) -> BTreeMap<&str, &FileInfo> {
+    files
+        .iter()
+        .map(|file| (file.relative_path.as_str(), file))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::core::ShareType;
+
+    fn file(path: &str, hash: &str) -> FileInfo {
+        FileInfo {
+            name: path.to_string(),
+            relative_path: path.to_string(),
+            size: 1,
+            hash: hash.to_string(),
+            compression: Compression::None,
+        }
+    }
+
+    fn metadata(files: Vec<FileInfo>) -> ShareMetadata {
+        ShareMetadata {
+            total_size: files.len() as u64,
+            files,
+            share_type: ShareType::MultipleFiles,
+            skipped_files: vec![],
+            custom_fields: BTreeMap::new(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_shares() {
+        let before = metadata(vec![
+            file("same.txt", "a"),
+            file("edited.txt", "b"),
+            file("deleted.txt", "c"),
+        ]);
+        let after = metadata(vec![
+            file("new.txt", "d"),
+            file("edited.txt", "e"),
+            file("same.txt", "a"),
+        ]);
+
+        let diff = diff_shares(&before, &after);
+        assert_eq!(diff.added, vec![file("new.txt", "d")]);
+        assert_eq!(diff.removed, vec![file("deleted.txt", "c")]);
+        assert_eq!(
+            diff.changed,
+            vec![ChangedFile {
+                relative_path: "edited.txt".to_string(),
+                before: file("edited.txt", "b"),
+                after: file("edited.txt", "e"),
+            }]
+        );
+        assert_eq!(diff.unchanged, 1);
+        assert!(!diff.is_empty());
+        assert!(diff_shares(&before, &before).is_empty());
+    }
+}
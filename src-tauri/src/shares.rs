@@ -0,0 +1,978 @@
+//! Registry of shares published by this node
+//!
+//! Every share gets an ID when its ticket is created. The registry keeps the
+//! hashes belonging to each share so that provider events (which only know
+//! about connections and blob hashes) can be attributed to the right share,
+//! recording who fetched it, how much was sent, and whether they finished.
+//! Receivers fetch the bundle and the files over separate connections (see
+//! [`crate::lanes`]), so a fetch is attributed to the peer's endpoint ID
+//! rather than to the connection it started on.
+//! Shares may expire, be limited to a single download, or be stopped by the
+//! user, after which their blobs are no longer served. Preview shares only
+//! serve their file list until the sender unlocks their files, e.g. once the
+//! receiver has confirmed or paid out of band.
+//!
+//! The registry doubles as the catalog of what this node is serving right now:
+//! [`ShareRegistry::list`] returns every open share with its ticket, shared
+//! paths and how much of it was served.
+
+use crate::core::ShareMetadata;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Notify, RwLock};
+use uuid::Uuid;
+
+/// Unique identifier for a share published by this node
+pub type ShareId = String;
+
+/// Identifier the blobs provider assigns to each incoming connection
+pub type ConnectionId = u64;
+
+/// A single fetch of a share by a peer
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Redemption {
+    /// Endpoint ID of the peer, if known
+    pub peer: Option<String>,
+    /// Unix timestamp when the peer fetched the share bundle
+    pub started_at: u64,
+    /// Bytes sent to the peer for the files of the share, as they went over
+    /// the wire: compressed, with their verification data, and including
+    /// files the peer stopped fetching
+    pub bytes_sent: u64,
+    /// Number of files sent to the peer
+    pub files_sent: usize,
+    /// Whether the peer fetched every file in the share
+    pub completed: bool,
+}
+
+/// Activity summary for a share
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareActivity {
+    /// The share this activity belongs to
+    pub share_id: ShareId,
+    /// Every fetch of the share, oldest first
+    pub redemptions: Vec<Redemption>,
+    /// Bytes sent for the files of the share across all fetches
+    pub total_bytes_sent: u64,
+    /// Number of fetches that received every file
+    pub completed_downloads: usize,
+    /// Whether the files of the share are held back until the sender unlocks them
+    pub locked: bool,
+    /// Root hash over the hashes of the shared files, if known
+    pub content_root: Option<String>,
+}
+
+/// A share this node is serving
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareSummary {
+    /// The ID returned when the share was created
+    pub share_id: ShareId,
+    /// The ticket handed out for the share
+    pub ticket: String,
+    /// Files and folders that were shared
+    pub paths: Vec<PathBuf>,
+    /// Unix timestamp when the share was published
+    pub created_at: u64,
+    /// Unix timestamp when the share expires, if it does
+    pub expires_at: Option<u64>,
+    /// Number of files in the share
+    pub file_count: usize,
+    /// Total size of the files in bytes
+    pub total_size: u64,
+    /// Bytes sent for the files of the share across all fetches
+    pub bytes_served: u64,
+    /// Number of fetches that received every file
+    pub completed_downloads: usize,
+    /// Whether the files of the share are held back until the sender unlocks them
+    pub locked: bool,
+}
+
+/// Who is fetching a share
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requester {
+    /// A peer with a known endpoint ID, across all its connections
+    Peer(String),
+    /// A single connection from a peer whose endpoint ID is unknown
+    Connection(ConnectionId),
+}
+
+/// Progress of one peer fetching a share
+#[derive(Debug, Clone)]
+struct RedemptionState {
+    requester: Requester,
+    peer: Option<String>,
+    started_at: u64,
+    files: HashSet<String>,
+    bytes_sent: u64,
+    /// Whether the peer has disconnected since
+    finished: bool,
+}
+
+/// Internal record of a published share
+#[derive(Debug, Clone)]
+struct ShareRecord {
+    ticket: String,
+    bundle_hash: String,
+    control_hashes: Vec<String>,
+    file_sizes: HashMap<String, u64>,
+    content_root: Option<String>,
+    paths: Vec<PathBuf>,
+    created_at: u64,
+    expires_at: Option<u64>,
+    single_use: bool,
+    locked: bool,
+    stopped: bool,
+    blobs_removed: bool,
+    redemptions: Vec<RedemptionState>,
+}
+
+impl ShareRecord {
+    /// Whether the share is no longer served, because it was stopped, it
+    /// expired, or its single download has completed
+    fn is_closed(&self, now: u64) -> bool {
+        self.stopped
+            || self.expires_at.is_some_and(|expires_at| now >= expires_at)
+            || self.is_used_up()
+    }
+
+    fn is_used_up(&self) -> bool {
+        self.single_use
+            && self
+                .redemptions
+                .iter()
+                .any(|state| state.files.len() >= self.file_sizes.len())
+    }
+
+    /// Returns the fetch a peer is still making, if any
+    fn open_redemption(&mut self, requester: &Requester) -> Option<&mut RedemptionState> {
+        self.redemptions
+            .iter_mut()
+            .rev()
+            .find(|state| !state.finished && state.requester == *requester)
+    }
+
+    fn contains_blob(&self, hash: &str) -> bool {
+        self.bundle_hash == hash
+            || self.control_hashes.iter().any(|control| control == hash)
+            || self.file_sizes.contains_key(hash)
+    }
+
+    fn redemption(&self, state: &RedemptionState) -> Redemption {
+        Redemption {
+            peer: state.peer.clone(),
+            started_at: state.started_at,
+            bytes_sent: state.bytes_sent,
+            files_sent: state.files.len(),
+            completed: state.files.len() >= self.file_sizes.len(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RegistryInner {
+    shares: HashMap<ShareId, ShareRecord>,
+    peers: HashMap<ConnectionId, Option<String>>,
+}
+
+impl RegistryInner {
+    /// Returns who is behind a connection
+    fn requester(&self, connection_id: ConnectionId) -> Requester {
+        match self.peers.get(&connection_id).cloned().flatten() {
+            Some(peer) => Requester::Peer(peer),
+            None => Requester::Connection(connection_id),
+        }
+    }
+}
+
+/// Thread-safe registry of published shares and their activity
+#[derive(Debug, Clone, Default)]
+pub struct ShareRegistry {
+    inner: Arc<RwLock<RegistryInner>>,
+    closed: Arc<Notify>,
+}
+
+impl ShareRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a newly published share and returns its ID
+    ///
+    /// The share keeps the ID the metadata was published with, which is the
+    /// one receivers see, or gets a new ID if the metadata has none.
+    ///
+    /// # Arguments
+    ///
+    /// * `ticket` - The ticket handed out for the share
+    /// * `bundle_hash` - Hash of the share bundle the ticket points at
+    /// * `control_hashes` - Hashes of the share's metadata and manifest pages
+    /// * `metadata` - Metadata listing the files in the share and its expiry
+    /// * `single_use` - Whether the share closes after its first complete download
+    pub async fn register(
+        &self,
+        ticket: String,
+        bundle_hash: String,
+        control_hashes: Vec<String>,
+        metadata: &ShareMetadata,
+        single_use: bool,
+    ) -> ShareId {
+        let share_id = metadata
+            .share_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let record = ShareRecord {
+            ticket,
+            bundle_hash,
+            control_hashes,
+            file_sizes: metadata
+                .files
+                .iter()
+                .map(|file| (file.hash.clone(), file.size))
+                .collect(),
+            content_root: metadata.content_root.clone(),
+            paths: Vec::new(),
+            created_at: current_unix_time(),
+            expires_at: metadata.expires_at,
+            single_use,
+            locked: false,
+            stopped: false,
+            blobs_removed: false,
+            redemptions: Vec::new(),
+        };
+
+        self.inner
+            .write()
+            .await
+            .shares
+            .insert(share_id.clone(), record);
+        share_id
+    }
+
+    /// Records which files and folders a registered share was created from
+    pub async fn set_paths(&self, share_id: &str, paths: Vec<PathBuf>) {
+        if let Some(record) = self.inner.write().await.shares.get_mut(share_id) {
+            record.paths = paths;
+        }
+    }
+
+    /// Returns every share still being served, oldest first
+    pub async fn list(&self) -> Vec<ShareSummary> {
+        let now = current_unix_time();
+        let inner = self.inner.read().await;
+        let mut shares: Vec<ShareSummary> = inner
+            .shares
+            .iter()
+            .filter(|(_, record)| !record.is_closed(now))
+            .map(|(share_id, record)| {
+                let redemptions: Vec<Redemption> = record
+                    .redemptions
+                    .iter()
+                    .map(|state| record.redemption(state))
+                    .collect();
+                ShareSummary {
+                    share_id: share_id.clone(),
+                    ticket: record.ticket.clone(),
+                    paths: record.paths.clone(),
+                    created_at: record.created_at,
+                    expires_at: record.expires_at,
+                    file_count: record.file_sizes.len(),
+                    total_size: record.file_sizes.values().sum(),
+                    bytes_served: redemptions.iter().map(|r| r.bytes_sent).sum(),
+                    completed_downloads: redemptions.iter().filter(|r| r.completed).count(),
+                    locked: record.locked,
+                }
+            })
+            .collect();
+        shares.sort_by(|a, b| (a.created_at, &a.share_id).cmp(&(b.created_at, &b.share_id)));
+        shares
+    }
+
+    /// Returns the ticket of a registered share
+    pub async fn ticket(&self, share_id: &str) -> Option<String> {
+        self.inner
+            .read()
+            .await
+            .shares
+            .get(share_id)
+            .map(|record| record.ticket.clone())
+    }
+
+    /// Returns the Unix timestamp when a registered share expires, if it does
+    pub async fn expires_at(&self, share_id: &str) -> Option<u64> {
+        self.inner
+            .read()
+            .await
+            .shares
+            .get(share_id)
+            .and_then(|record| record.expires_at)
+    }
+
+    /// Returns who has fetched a share and how far they got
+    pub async fn activity(&self, share_id: &str) -> Option<ShareActivity> {
+        let inner = self.inner.read().await;
+        let record = inner.shares.get(share_id)?;
+
+        let redemptions: Vec<Redemption> = record
+            .redemptions
+            .iter()
+            .map(|state| record.redemption(state))
+            .collect();
+
+        Some(ShareActivity {
+            share_id: share_id.to_string(),
+            total_bytes_sent: redemptions.iter().map(|r| r.bytes_sent).sum(),
+            completed_downloads: redemptions.iter().filter(|r| r.completed).count(),
+            locked: record.locked,
+            content_root: record.content_root.clone(),
+            redemptions,
+        })
+    }
+
+    /// Checks whether a share is still served
+    ///
+    /// Returns false if the share is unknown.
+    pub async fn is_open(&self, share_id: &str) -> bool {
+        let now = current_unix_time();
+        self.inner
+            .read()
+            .await
+            .shares
+            .get(share_id)
+            .is_some_and(|record| !record.is_closed(now))
+    }
+
+    /// Checks whether a blob may no longer be served because its shares closed
+    ///
+    /// Blobs that are also part of a share that is still open are still
+    /// served, as are blobs that belong to no share at all.
+    pub async fn is_closed_blob(&self, hash: &str) -> bool {
+        let now = current_unix_time();
+        let inner = self.inner.read().await;
+        let mut shares = inner
+            .shares
+            .values()
+            .filter(|record| record.contains_blob(hash))
+            .peekable();
+
+        shares.peek().is_some() && shares.all(|record| record.is_closed(now))
+    }
+
+    /// Checks whether a file may not be served yet because its shares are locked
+    ///
+    /// Only file content is held back; the bundle and manifest pages of locked
+    /// shares are served, so receivers can preview the file list. Files that
+    /// are also part of an open share that is not locked are served.
+    pub async fn is_locked_blob(&self, hash: &str) -> bool {
+        let now = current_unix_time();
+        let inner = self.inner.read().await;
+        let mut shares = inner
+            .shares
+            .values()
+            .filter(|record| !record.is_closed(now) && record.file_sizes.contains_key(hash))
+            .peekable();
+
+        shares.peek().is_some() && shares.all(|record| record.locked)
+    }
+
+    /// Holds back the files of a share until it is unlocked
+    ///
+    /// Returns false if the share is unknown.
+    pub async fn lock(&self, share_id: &str) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(record) = inner.shares.get_mut(share_id) else {
+            return false;
+        };
+        record.locked = true;
+        true
+    }
+
+    /// Starts serving the files of a locked share
+    ///
+    /// Returns false if the share is unknown or not locked.
+    pub async fn unlock(&self, share_id: &str) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(record) = inner.shares.get_mut(share_id) else {
+            return false;
+        };
+        if !record.locked {
+            return false;
+        }
+        record.locked = false;
+        true
+    }
+
+    /// Checks whether a blob is part of a share that is still open
+    ///
+    /// The blobs of open shares are kept in the store, so a new share can use
+    /// them too.
+    pub async fn is_open_blob(&self, hash: &str) -> bool {
+        let now = current_unix_time();
+        self.inner
+            .read()
+            .await
+            .shares
+            .values()
+            .any(|record| !record.is_closed(now) && record.contains_blob(hash))
+    }
+
+    /// Marks the blobs of a closed share as removed
+    ///
+    /// The share stays registered, so its blobs are still refused and its
+    /// activity can still be looked up. Returns the blobs no open share uses,
+    /// with their sizes, so they can be dropped from the store. Returns None
+    /// if the share is unknown, still open, or its blobs were already removed.
+    pub async fn take_closed_blobs(&self, share_id: &str) -> Option<HashMap<String, u64>> {
+        let now = current_unix_time();
+        let mut inner = self.inner.write().await;
+        let record = inner.shares.get_mut(share_id)?;
+        if !record.is_closed(now) || record.blobs_removed {
+            return None;
+        }
+        record.blobs_removed = true;
+
+        let mut blobs = record.file_sizes.clone();
+        blobs.insert(record.bundle_hash.clone(), 0);
+        blobs.extend(record.control_hashes.iter().map(|hash| (hash.clone(), 0)));
+        blobs.retain(|hash, _| {
+            !inner
+                .shares
+                .values()
+                .any(|other| !other.is_closed(now) && other.contains_blob(hash))
+        });
+        Some(blobs)
+    }
+
+    /// Stops serving a share before it would otherwise close
+    ///
+    /// Returns false if the share is unknown or was already stopped.
+    pub async fn stop(&self, share_id: &str) -> bool {
+        let mut inner = self.inner.write().await;
+        let Some(record) = inner.shares.get_mut(share_id) else {
+            return false;
+        };
+        if record.stopped {
+            return false;
+        }
+        record.stopped = true;
+        drop(inner);
+
+        self.closed.notify_waiters();
+        true
+    }
+
+    /// Waits until a share has been stopped or, if it is single-use,
+    /// downloaded completely
+    ///
+    /// Returns right away if the share is unknown. Expiry is not awaited, since
+    /// it needs no event to happen.
+    pub async fn closed(&self, share_id: &str) {
+        loop {
+            let notified = self.closed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match self.inner.read().await.shares.get(share_id) {
+                Some(record) if !record.stopped && !record.is_used_up() => {}
+                _ => return,
+            }
+            notified.await;
+        }
+    }
+
+    /// Records a peer connecting to the provider
+    pub async fn connection_opened(&self, connection_id: ConnectionId, peer: Option<String>) {
+        self.inner.write().await.peers.insert(connection_id, peer);
+    }
+
+    /// Forgets a closed provider connection
+    ///
+    /// Returns the redemptions of the peer that finished now that its last
+    /// connection is closed.
+    pub async fn connection_closed(&self, connection_id: ConnectionId) -> Vec<Redemption> {
+        let mut inner = self.inner.write().await;
+        let requester = inner.requester(connection_id);
+        let peer = inner.peers.remove(&connection_id).flatten();
+        if peer.is_some() && inner.peers.values().any(|other| *other == peer) {
+            return Vec::new();
+        }
+
+        let mut finished = Vec::new();
+        for record in inner.shares.values_mut() {
+            let mut closed = Vec::new();
+            for state in record.redemptions.iter_mut() {
+                if !state.finished && state.requester == requester {
+                    state.finished = true;
+                    closed.push(state.clone());
+                }
+            }
+            finished.extend(closed.iter().map(|state| record.redemption(state)));
+        }
+        finished
+    }
+
+    /// Records a peer requesting a blob
+    ///
+    /// Requests for a share's bundle start a new redemption of that share,
+    /// unless the peer is still fetching it. A peer that requests files after
+    /// it disconnected continues its last redemption that did not receive
+    /// every file.
+    pub async fn request_received(&self, connection_id: ConnectionId, hash: &str) {
+        let mut inner = self.inner.write().await;
+        let requester = inner.requester(connection_id);
+        let peer = inner.peers.get(&connection_id).cloned().flatten();
+
+        for (share_id, record) in inner.shares.iter_mut() {
+            if !record.contains_blob(hash) {
+                continue;
+            }
+            tracing::debug!(%share_id, connection_id, peer = peer.as_deref(), hash, "blob requested");
+            if record.open_redemption(&requester).is_some() {
+                continue;
+            }
+
+            if record.bundle_hash == hash {
+                tracing::info!(%share_id, connection_id, peer = peer.as_deref(), "share fetch started");
+                record.redemptions.push(RedemptionState {
+                    requester: requester.clone(),
+                    peer: peer.clone(),
+                    started_at: current_unix_time(),
+                    files: HashSet::new(),
+                    bytes_sent: 0,
+                    finished: false,
+                });
+            } else if record.file_sizes.contains_key(hash) {
+                let file_count = record.file_sizes.len();
+                let unfinished =
+                    record.redemptions.iter_mut().rev().find(|state| {
+                        state.requester == requester && state.files.len() < file_count
+                    });
+                if let Some(state) = unfinished {
+                    state.finished = false;
+                }
+            }
+        }
+    }
+
+    /// Records a blob having been sent completely to a peer
+    ///
+    /// `bytes_sent` is what the provider sent for the request.
+    pub async fn request_completed(
+        &self,
+        connection_id: ConnectionId,
+        hash: &str,
+        bytes_sent: u64,
+    ) {
+        self.record_sent(connection_id, hash, bytes_sent, true)
+            .await;
+    }
+
+    /// Records a peer having stopped fetching a blob part of the way
+    ///
+    /// `bytes_sent` is what the provider sent for the request until then.
+    pub async fn request_aborted(&self, connection_id: ConnectionId, hash: &str, bytes_sent: u64) {
+        self.record_sent(connection_id, hash, bytes_sent, false)
+            .await;
+    }
+
+    /// Adds the bytes sent for a file to the peer's redemptions, counting the
+    /// file as received if it was sent completely.
+    async fn record_sent(
+        &self,
+        connection_id: ConnectionId,
+        hash: &str,
+        bytes_sent: u64,
+        completed: bool,
+    ) {
+        let mut inner = self.inner.write().await;
+        let requester = inner.requester(connection_id);
+        let mut used_up = false;
+
+        for (share_id, record) in inner.shares.iter_mut() {
+            if !record.file_sizes.contains_key(hash) {
+                continue;
+            }
+
+            if let Some(state) = record.open_redemption(&requester) {
+                state.bytes_sent += bytes_sent;
+                if completed {
+                    state.files.insert(hash.to_string());
+                }
+                tracing::debug!(%share_id, connection_id, hash, bytes_sent, completed, "blob sent");
+            }
+            used_up |= record.is_used_up();
+        }
+
+        if used_up {
+            self.closed.notify_waiters();
+        }
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::core::{FileInfo, ShareType};
+
+    fn metadata() -> ShareMetadata {
+        let file = |name: &str, hash: &str, size| FileInfo {
+            name: name.to_string(),
+            relative_path: name.to_string(),
+            size,
+            hash: hash.to_string(),
+            compression: Compression::None,
+        };
+
+        ShareMetadata {
+            files: vec![file("a.txt", "hash-a", 10), file("b.txt", "hash-b", 20)],
+            share_type: ShareType::MultipleFiles,
+            total_size: 30,
+            skipped_files: vec![],
+            custom_fields: Default::default(),
+            expires_at: None,
+            share_id: None,
+            content_root: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_lookup_ticket() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                false,
+            )
+            .await;
+
+        assert_eq!(registry.ticket(&share_id).await.as_deref(), Some("ticket"));
+        assert!(registry.ticket("unknown").await.is_none());
+
+        let assigned = registry
+            .register(
+                "assigned".to_string(),
+                "bundle-2".to_string(),
+                vec![],
+                &ShareMetadata {
+                    share_id: Some("share-1".to_string()),
+                    ..metadata()
+                },
+                false,
+            )
+            .await;
+        assert_eq!(assigned, "share-1");
+        assert_eq!(
+            registry.ticket("share-1").await.as_deref(),
+            Some("assigned")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_activity_tracks_redemptions() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                false,
+            )
+            .await;
+
+        registry
+            .connection_opened(1, Some("alice".to_string()))
+            .await;
+        registry.request_received(1, "bundle").await;
+        // Compressed files take fewer bytes on the wire than their size
+        registry.request_completed(1, "hash-a", 6).await;
+        registry.request_completed(1, "hash-b", 20).await;
+
+        registry.connection_opened(2, Some("bob".to_string())).await;
+        registry.request_received(2, "bundle").await;
+        registry.request_completed(2, "hash-a", 10).await;
+        registry.request_completed(2, "hash-a", 10).await;
+        registry.request_aborted(2, "hash-b", 5).await;
+
+        let activity = registry.activity(&share_id).await.unwrap();
+        assert_eq!(activity.redemptions.len(), 2);
+        assert_eq!(activity.completed_downloads, 1);
+        assert_eq!(activity.total_bytes_sent, 51);
+
+        let bob = &activity.redemptions[1];
+        assert_eq!(bob.peer.as_deref(), Some("bob"));
+        assert_eq!(bob.bytes_sent, 25);
+        assert_eq!(bob.files_sent, 1);
+        assert!(!bob.completed);
+
+        let finished = registry.connection_closed(2).await;
+        assert_eq!(finished, vec![bob.clone()]);
+        assert!(registry.connection_closed(3).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_open_shares() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                false,
+            )
+            .await;
+        registry
+            .set_paths(&share_id, vec![PathBuf::from("/home/user/docs")])
+            .await;
+        registry.connection_opened(1, None).await;
+        registry.request_received(1, "bundle").await;
+        registry.request_completed(1, "hash-b", 20).await;
+
+        let shares = registry.list().await;
+        assert_eq!(shares.len(), 1);
+        assert_eq!(shares[0].share_id, share_id);
+        assert_eq!(shares[0].ticket, "ticket");
+        assert_eq!(shares[0].paths, vec![PathBuf::from("/home/user/docs")]);
+        assert_eq!(shares[0].file_count, 2);
+        assert_eq!(shares[0].total_size, 30);
+        assert_eq!(shares[0].bytes_served, 20);
+        assert_eq!(shares[0].completed_downloads, 0);
+
+        registry.stop(&share_id).await;
+        assert!(registry.list().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_over_several_connections_counts_once() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                true,
+            )
+            .await;
+
+        // The bundle and the files come in on connections of different lanes
+        let alice = Some("alice".to_string());
+        registry.connection_opened(1, alice.clone()).await;
+        registry.connection_opened(2, alice.clone()).await;
+        registry.request_received(1, "bundle").await;
+        assert!(registry.connection_closed(1).await.is_empty());
+        registry.request_received(2, "hash-a").await;
+        registry.request_completed(2, "hash-a", 10).await;
+        registry.request_received(2, "hash-b").await;
+        registry.request_completed(2, "hash-b", 20).await;
+
+        let activity = registry.activity(&share_id).await.unwrap();
+        assert_eq!(activity.redemptions.len(), 1);
+        assert_eq!(activity.completed_downloads, 1);
+        assert!(!registry.is_open(&share_id).await);
+
+        let finished = registry.connection_closed(2).await;
+        assert_eq!(finished, activity.redemptions);
+        assert!(registry.connection_closed(2).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_files_after_reconnecting_continue_the_fetch() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                true,
+            )
+            .await;
+
+        let bob = Some("bob".to_string());
+        registry.connection_opened(1, bob.clone()).await;
+        registry.request_received(1, "bundle").await;
+        registry.request_completed(1, "hash-a", 10).await;
+        assert_eq!(registry.connection_closed(1).await.len(), 1);
+
+        registry.connection_opened(2, bob).await;
+        registry.request_received(2, "hash-b").await;
+        registry.request_completed(2, "hash-b", 20).await;
+
+        let activity = registry.activity(&share_id).await.unwrap();
+        assert_eq!(activity.redemptions.len(), 1);
+        assert!(activity.redemptions[0].completed);
+        assert!(!registry.is_open(&share_id).await);
+    }
+
+    #[tokio::test]
+    async fn test_expired_shares_stop_serving_their_blobs() {
+        let registry = ShareRegistry::new();
+        let expired = registry
+            .register(
+                "expired".to_string(),
+                "bundle-1".to_string(),
+                vec!["page-1".to_string()],
+                &ShareMetadata {
+                    expires_at: Some(0),
+                    ..metadata()
+                },
+                false,
+            )
+            .await;
+        let live = ShareMetadata {
+            files: metadata().files[..1].to_vec(),
+            ..metadata()
+        };
+        let live = registry
+            .register(
+                "live".to_string(),
+                "bundle-2".to_string(),
+                vec![],
+                &live,
+                false,
+            )
+            .await;
+
+        assert!(registry.is_closed_blob("bundle-1").await);
+        assert!(registry.is_closed_blob("hash-b").await);
+        assert!(!registry.is_closed_blob("hash-a").await);
+        assert!(!registry.is_closed_blob("unknown").await);
+        assert!(registry.is_open_blob("hash-a").await);
+        assert!(registry.is_open(&live).await);
+        assert!(!registry.is_open(&expired).await);
+        assert!(!registry.is_open("unknown").await);
+        assert!(!registry.is_open_blob("hash-b").await);
+        assert!(!registry.is_open_blob("unknown").await);
+
+        assert!(registry.take_closed_blobs(&live).await.is_none());
+        let removed = registry.take_closed_blobs(&expired).await.unwrap();
+        assert_eq!(
+            removed,
+            HashMap::from([
+                ("bundle-1".to_string(), 0),
+                ("page-1".to_string(), 0),
+                ("hash-b".to_string(), 20),
+            ])
+        );
+        assert!(registry.take_closed_blobs(&expired).await.is_none());
+        assert!(registry.is_closed_blob("bundle-1").await);
+    }
+
+    #[tokio::test]
+    async fn test_single_use_share_closes_after_first_download() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                true,
+            )
+            .await;
+
+        registry.connection_opened(1, None).await;
+        registry.request_received(1, "bundle").await;
+        registry.request_completed(1, "hash-a", 10).await;
+        assert!(!registry.is_closed_blob("bundle").await);
+
+        let closed = registry.closed(&share_id);
+        tokio::pin!(closed);
+        assert!(futures::poll!(closed.as_mut()).is_pending());
+
+        registry.request_completed(1, "hash-b", 20).await;
+        closed.await;
+        assert!(registry.is_closed_blob("bundle").await);
+        assert!(registry.take_closed_blobs(&share_id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stopped_share_closes_right_away() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec![],
+                &metadata(),
+                false,
+            )
+            .await;
+
+        let closed = registry.closed(&share_id);
+        tokio::pin!(closed);
+        assert!(futures::poll!(closed.as_mut()).is_pending());
+        assert!(registry.take_closed_blobs(&share_id).await.is_none());
+
+        assert!(registry.stop(&share_id).await);
+        closed.await;
+        assert!(!registry.stop(&share_id).await);
+        assert!(!registry.stop("unknown").await);
+
+        assert!(registry.is_closed_blob("hash-a").await);
+        let removed = registry.take_closed_blobs(&share_id).await.unwrap();
+        assert_eq!(removed.len(), 3);
+        assert_eq!(registry.ticket(&share_id).await.as_deref(), Some("ticket"));
+    }
+
+    #[tokio::test]
+    async fn test_locked_share_serves_only_its_file_list() {
+        let registry = ShareRegistry::new();
+        let share_id = registry
+            .register(
+                "ticket".to_string(),
+                "bundle".to_string(),
+                vec!["page".to_string()],
+                &metadata(),
+                false,
+            )
+            .await;
+        assert!(!registry.unlock(&share_id).await);
+        assert!(registry.lock(&share_id).await);
+        assert!(!registry.lock("unknown").await);
+
+        assert!(registry.is_locked_blob("hash-a").await);
+        assert!(!registry.is_locked_blob("bundle").await);
+        assert!(!registry.is_locked_blob("page").await);
+        assert!(registry.activity(&share_id).await.unwrap().locked);
+
+        // A file also in an unlocked share is served through that share
+        registry
+            .register(
+                "other".to_string(),
+                "bundle-2".to_string(),
+                vec![],
+                &ShareMetadata {
+                    files: metadata().files[1..].to_vec(),
+                    ..metadata()
+                },
+                false,
+            )
+            .await;
+        assert!(!registry.is_locked_blob("hash-b").await);
+
+        assert!(registry.unlock(&share_id).await);
+        assert!(!registry.is_locked_blob("hash-a").await);
+        assert!(!registry.activity(&share_id).await.unwrap().locked);
+    }
+}
@@ -0,0 +1,111 @@
+//! Watching the source files of shares
+//!
+//! Files are read into the blob store when a share is created, so receivers
+//! always get the content as it was read. If a source file changes afterwards,
+//! or while it is being read, receivers silently get an outdated version. The
+//! shared paths are therefore watched for as long as the share is served, and
+//! every changed file is reported once so the sender can share it again.
+
+use crate::shares::ShareId;
+use anyhow::Result;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Watches the files of one share until dropped
+pub struct SourceWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl SourceWatcher {
+    /// Starts watching the shared files below the given roots
+    ///
+    /// Events for files that are not part of the share are ignored, and each
+    /// changed file is reported only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - The files and directories that were shared
+    /// * `files` - Every shared file
+    /// * `on_change` - Called with the path of each changed file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file system watcher cannot be created or a root
+    /// cannot be watched.
+    pub fn start<F>(roots: &[PathBuf], files: HashSet<PathBuf>, mut on_change: F) -> Result<Self>
+    where
+        F: FnMut(PathBuf) + Send + 'static,
+    {
+        let mut unchanged = files;
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                let Ok(event) = result else {
+                    return;
+                };
+                if !is_content_change(&event.kind) {
+                    return;
+                }
+                for path in event.paths {
+                    if unchanged.remove(&path) {
+                        on_change(path);
+                    }
+                }
+            })?;
+
+        for root in roots {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Whether an event means the content of a file may have changed
+fn is_content_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Modify(_) | EventKind::Remove(_) | EventKind::Create(_)
+    )
+}
+
+/// Source watchers of the shares being served
+#[derive(Clone, Default)]
+pub struct SourceWatchers {
+    inner: Arc<Mutex<HashMap<ShareId, SourceWatcher>>>,
+}
+
+impl SourceWatchers {
+    /// Creates an empty set of watchers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps a share's watcher running for as long as the share is served
+    pub fn insert(&self, share_id: ShareId, watcher: SourceWatcher) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.insert(share_id, watcher);
+        }
+    }
+
+    /// Stops watching the files of a share that is no longer served
+    pub fn remove(&self, share_id: &str) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.remove(share_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    #[test]
+    fn test_is_content_change() {
+        assert!(is_content_change(&EventKind::Modify(ModifyKind::Any)));
+        assert!(is_content_change(&EventKind::Create(CreateKind::File)));
+        assert!(!is_content_change(&EventKind::Access(AccessKind::Any)));
+    }
+}
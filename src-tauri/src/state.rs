@@ -1,10 +1,65 @@
-use crate::core::{GinsengCore, ShareMetadata};
-use tokio::sync::OnceCell;
+use crate::collisions::FileCollision;
+use crate::core::{DownloadedShare, GinsengCore, ShareMetadata, SkippedFile};
+use crate::errors::GinsengError;
+use crate::relays::RelayPreference;
+use crate::settings::SettingsStore;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{Mutex, Notify, OnceCell};
+
+/// Name of the app event carrying core initialization status updates
+pub const CORE_STATUS_EVENT: &str = "core-status";
+
+/// Name of the app event announcing files picked from the file manager context menu
+pub const CONTEXT_MENU_EVENT: &str = "context-menu-share";
+
+/// How long to wait for further files picked from the context menu, since
+/// some file managers launch the app once per selected file
+const CONTEXT_MENU_BATCH_DELAY: Duration = Duration::from_millis(300);
+
+/// Delay before the first retry of a failed core initialization
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Maximum delay between retries of a failed core initialization
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Initialization status of the Ginseng core
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "status",
+    content = "data"
+)]
+pub enum CoreStatus {
+    /// The core is being created in the background
+    #[default]
+    Initializing,
+    /// The core is ready and commands can use it
+    Ready,
+    /// Creating the core failed and will be retried
+    Failed { error: String, retry_in_secs: u64 },
+}
 
 /// Application state that holds the Ginseng core instance
 #[derive(Default)]
 pub struct AppState {
     pub(crate) core: OnceCell<GinsengCore>,
+    /// Initialization status of the core
+    pub(crate) core_status: std::sync::Mutex<CoreStatus>,
+    /// Wakes the initialization task to retry before its backoff delay ends
+    pub(crate) retry_requested: Notify,
+    /// Background task watching a folder for ticket files, if running
+    pub(crate) ticket_watcher: TaskSlot,
+    /// Background task forwarding drop box events to the frontend, if running
+    pub(crate) drop_box_events: TaskSlot,
+    /// Localhost endpoint serving a companion browser extension, if running
+    pub(crate) companion: TaskSlot,
+    /// Files picked from the file manager context menu, not yet taken by the frontend
+    pub(crate) context_menu_paths: std::sync::Mutex<Vec<PathBuf>>,
 }
 
 /// Result structure for download operations
@@ -12,6 +67,69 @@ pub struct AppState {
 pub struct DownloadResult {
     pub metadata: ShareMetadata,
     pub download_path: String,
+    /// Files that already existed where they were to be saved, and how each was handled
+    pub collisions: Vec<FileCollision>,
+    /// Files that could not be downloaded, with the reason
+    pub failed_files: Vec<SkippedFile>,
+    /// Root hash over the hashes of the files received, if every file of the
+    /// share was received
+    pub content_root: Option<String>,
+}
+
+impl From<DownloadedShare> for DownloadResult {
+    fn from(download: DownloadedShare) -> Self {
+        Self {
+            metadata: download.metadata,
+            download_path: download.location.to_string_lossy().to_string(),
+            collisions: download.collisions,
+            failed_files: download.failed,
+            content_root: download.content_root,
+        }
+    }
+}
+
+/// Slot holding a single background task that can be replaced or stopped
+#[derive(Default)]
+pub struct TaskSlot(Mutex<Option<JoinHandle<()>>>);
+
+impl TaskSlot {
+    /// Store a new task, stopping any previously running one
+    ///
+    /// # Arguments
+    /// * `handle` - Handle of the spawned task
+    pub async fn replace(&self, handle: JoinHandle<()>) {
+        if let Some(previous) = self.0.lock().await.replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the running task
+    ///
+    /// # Returns
+    /// true if a task was running and has been stopped
+    pub async fn stop(&self) -> bool {
+        match self.0.lock().await.take() {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop the running task and wait until it has finished, so resources it
+    /// holds (e.g. a bound port) are released
+    ///
+    /// # Returns
+    /// true if a task was running and has been stopped
+    pub async fn stop_and_wait(&self) -> bool {
+        let Some(handle) = self.0.lock().await.take() else {
+            return false;
+        };
+        handle.abort();
+        let _ = handle.await;
+        true
+    }
 }
 
 impl AppState {
@@ -21,14 +139,130 @@ impl AppState {
     /// A reference to the GinsengCore instance
     ///
     /// # Errors
-    /// Returns an error if the core has not been initialized yet
-    pub fn get_core(&self) -> Result<&GinsengCore, String> {
-        self.core
-            .get()
-            .ok_or_else(|| "Ginseng core not initialized yet".to_string())
+    /// Returns an error if the core is still initializing or failed to initialize
+    pub fn get_core(&self) -> Result<&GinsengCore, GinsengError> {
+        if let Some(core) = self.core.get() {
+            return Ok(core);
+        }
+
+        match self.core_status() {
+            CoreStatus::Failed { error, .. } => Err(GinsengError::CoreUnavailable(format!(
+                "Ginseng core failed to initialize: {}",
+                error
+            ))),
+            _ => Err(GinsengError::CoreUnavailable(
+                "Ginseng core is still initializing".to_string(),
+            )),
+        }
+    }
+
+    /// Get the current initialization status of the core
+    pub fn core_status(&self) -> CoreStatus {
+        self.core_status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    /// Take the files picked from the context menu since the last call
+    pub fn take_context_menu_paths(&self) -> Vec<PathBuf> {
+        self.context_menu_paths
+            .lock()
+            .map(|mut paths| std::mem::take(&mut *paths))
+            .unwrap_or_default()
+    }
+
+    /// Update the initialization status of the core
+    fn set_core_status(&self, status: CoreStatus) {
+        if let Ok(mut current) = self.core_status.lock() {
+            *current = status;
+        }
+    }
+}
+
+/// Initialize the Ginseng core in the background
+///
+/// Window startup is not delayed by the network setup. Status changes are
+/// reported through `core-status` app events, and commands return a clear
+/// error while the core is still initializing. If initialization fails (e.g.,
+/// no network), it is retried periodically with exponential backoff, or
+/// immediately when `retry_initialization` is called.
+///
+/// # Arguments
+/// * `app` - The Tauri application handle
+pub fn spawn_setup(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+
+        loop {
+            update_core_status(&app, &state, CoreStatus::Initializing);
+
+            let error = match setup_ginseng(state.clone()).await {
+                Ok(()) => {
+                    update_core_status(&app, &state, CoreStatus::Ready);
+                    // Stored again in the background, since reading the files
+                    // of large shares takes a while
+                    if let Some(core) = state.core.get() {
+                        let restored = core.restore_saved_shares().await;
+                        tracing::info!(restored, "saved shares restored");
+                    }
+                    return;
+                }
+                Err(error) => error,
+            };
+
+            update_core_status(
+                &app,
+                &state,
+                CoreStatus::Failed {
+                    error: error.to_string(),
+                    retry_in_secs: retry_delay.as_secs(),
+                },
+            );
+
+            tokio::select! {
+                _ = tokio::time::sleep(retry_delay) => {}
+                _ = state.retry_requested.notified() => {}
+            }
+            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+        }
+    });
+}
+
+/// Hand files picked from the file manager context menu to the frontend
+///
+/// The files are kept until the frontend takes them, so none are lost while
+/// the window is still loading. Files picked in quick succession are announced
+/// with a single `context-menu-share` app event.
+///
+/// # Arguments
+/// * `app` - Handle of the running app
+/// * `paths` - The picked files and folders
+pub fn queue_context_menu_share(app: &AppHandle, paths: Vec<PathBuf>) {
+    let state = app.state::<AppState>();
+    let Ok(mut pending) = state.context_menu_paths.lock() else {
+        return;
+    };
+    let first = pending.is_empty();
+    pending.extend(paths);
+    drop(pending);
+
+    if first {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(CONTEXT_MENU_BATCH_DELAY).await;
+            let _ = app.emit(CONTEXT_MENU_EVENT, ());
+        });
     }
 }
 
+/// Store a new core status and notify the frontend about it
+fn update_core_status(app: &AppHandle, state: &AppState, status: CoreStatus) {
+    state.set_core_status(status.clone());
+    let _ = app.emit(CORE_STATUS_EVENT, status);
+}
+
 /// Initialize the Ginseng core and store it in the application state
 ///
 /// # Arguments
@@ -40,7 +274,38 @@ impl AppState {
 /// # Errors
 /// Returns an error if core creation fails or if already initialized
 pub async fn setup_ginseng(state: tauri::State<'_, AppState>) -> Result<(), anyhow::Error> {
-    let core = GinsengCore::new().await?;
+    // Unreadable settings fall back to the defaults rather than failing startup
+    let settings = match SettingsStore::open_default() {
+        Ok(store) => store.load().await.ok(),
+        Err(_) => None,
+    };
+
+    // The app keeps its identity, so tickets it handed out survive a restart
+    let core = match &settings {
+        Some(settings) => match GinsengCore::persistent(&settings.relays).await {
+            Ok(core) => core,
+            Err(error) => {
+                tracing::warn!(%error, "preferred relays unusable, using the default relays");
+                GinsengCore::persistent(&RelayPreference::default()).await?
+            }
+        },
+        None => GinsengCore::persistent(&RelayPreference::default()).await?,
+    };
+
+    if let Some(settings) = settings {
+        core.set_bandwidth_limits(settings.bandwidth);
+        core.set_bandwidth_schedule(settings.bandwidth_schedule);
+        core.set_concurrency_limits(settings.concurrency);
+        core.set_download_directory(settings.download_directory);
+        core.set_device_folders(settings.device_folders);
+        core.set_organize_rules(settings.organize_rules);
+        core.set_folder_name_template(settings.folder_name_template);
+        core.warmer.set_devices(&settings.paired_devices);
+        core.warmer.set_enabled(settings.warm_connections);
+        core.set_port_mapping(settings.port_mapping);
+        core.set_release_downloads(settings.release_downloads);
+    }
+    core.remove_stale_partials();
 
     state
         .core
@@ -0,0 +1,240 @@
+//! Share templates for repeated sharing workflows
+//!
+//! A template names a set of paths together with the options used to share
+//! them: an include selection, custom fields, a ticket file to write, and drop
+//! boxes to deliver the share to. Running a template performs the whole
+//! workflow in one step, e.g. "send the daily export folder to the office NAS".
+//! Templates are stored as JSON in the user's config directory.
+
+use crate::core::ShareResult;
+use crate::dropbox::DropResponse;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Name of the file templates are stored in
+const TEMPLATES_FILE_NAME: &str = "templates.json";
+
+/// A saved set of paths and options that can be shared in one step
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareTemplate {
+    /// Unique name of the template (e.g., "Daily export")
+    pub name: String,
+    /// Files and directories to share
+    pub paths: Vec<PathBuf>,
+    /// Optional selection of directory-relative file paths to include
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// Custom fields attached to the share
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom_fields: BTreeMap<String, String>,
+    /// Ticket file to write the share ticket to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket_file: Option<PathBuf>,
+    /// Lifetime of the written ticket file in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in_secs: Option<u64>,
+    /// Drop tickets of the drop boxes the share is delivered to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<String>,
+}
+
+impl ShareTemplate {
+    /// Checks that the template can be run
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is empty or no paths are given
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            anyhow::bail!("Template name cannot be empty");
+        }
+        if self.paths.is_empty() {
+            anyhow::bail!("Template '{}' has no paths to share", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Delivery of a template share to one recipient drop box
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    /// The recipient's drop ticket
+    pub recipient: String,
+    /// What the drop box responded with
+    pub response: DropResponse,
+}
+
+/// Outcome of running a template
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateRun {
+    /// The share created by the template
+    pub share: ShareResult,
+    /// Ticket file the ticket was written to, if any
+    pub ticket_file: Option<PathBuf>,
+    /// Deliveries to the template's recipients
+    pub deliveries: Vec<Delivery>,
+}
+
+/// Templates saved in a JSON file
+#[derive(Debug, Clone)]
+pub struct TemplateStore {
+    path: PathBuf,
+}
+
+impl TemplateStore {
+    /// Creates a store backed by the given file
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Creates a store in the user's config directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config directory cannot be determined
+    pub fn open_default() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine config directory"))?;
+        Ok(Self::new(
+            config_dir.join("ginseng").join(TEMPLATES_FILE_NAME),
+        ))
+    }
+
+    /// Returns every saved template, sorted by name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the templates file exists but cannot be read
+    pub async fn list(&self) -> Result<Vec<ShareTemplate>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&self.path).await?;
+        let mut templates: Vec<ShareTemplate> =
+            serde_json::from_str(&content).map_err(|error| {
+                anyhow::anyhow!(
+                    "Invalid templates file '{}': {}",
+                    self.path.display(),
+                    error
+                )
+            })?;
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// Returns a saved template by name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template does not exist
+    pub async fn get(&self, name: &str) -> Result<ShareTemplate> {
+        self.list()
+            .await?
+            .into_iter()
+            .find(|template| template.name == name)
+            .ok_or_else(|| anyhow::anyhow!("No template named '{}'", name))
+    }
+
+    /// Saves a template, replacing any template with the same name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid or cannot be written
+    pub async fn save(&self, template: ShareTemplate) -> Result<()> {
+        template.validate()?;
+
+        let mut templates = self.list().await?;
+        templates.retain(|existing| existing.name != template.name);
+        templates.push(template);
+        self.write(&templates).await
+    }
+
+    /// Deletes a template by name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template does not exist or cannot be removed
+    pub async fn remove(&self, name: &str) -> Result<()> {
+        let mut templates = self.list().await?;
+        let count = templates.len();
+        templates.retain(|template| template.name != name);
+        if templates.len() == count {
+            anyhow::bail!("No template named '{}'", name);
+        }
+        self.write(&templates).await
+    }
+
+    /// Writes all templates to the templates file.
+    async fn write(&self, templates: &[ShareTemplate]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let json = serde_json::to_string_pretty(templates)?;
+        fs::write(&self.path, json).await.map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to write templates file '{}': {}",
+                self.path.display(),
+                error
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn template(name: &str) -> ShareTemplate {
+        ShareTemplate {
+            name: name.to_string(),
+            paths: vec![PathBuf::from("/exports/daily")],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_list_and_remove_templates() {
+        let temp_dir = tempdir().unwrap();
+        let store = TemplateStore::new(temp_dir.path().join("config").join(TEMPLATES_FILE_NAME));
+        assert!(store.list().await.unwrap().is_empty());
+
+        store.save(template("daily")).await.unwrap();
+        store.save(template("archive")).await.unwrap();
+
+        let mut updated = template("daily");
+        updated.recipients.push("dropticket".to_string());
+        store.save(updated.clone()).await.unwrap();
+
+        let templates = store.list().await.unwrap();
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].name, "archive");
+        assert_eq!(store.get("daily").await.unwrap(), updated);
+
+        store.remove("archive").await.unwrap();
+        assert!(store.get("archive").await.is_err());
+        assert!(store.remove("archive").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_templates_are_rejected() {
+        let temp_dir = tempdir().unwrap();
+        let store = TemplateStore::new(temp_dir.path().join(TEMPLATES_FILE_NAME));
+
+        assert!(store.save(template(" ")).await.is_err());
+        assert!(store
+            .save(ShareTemplate {
+                name: "empty".to_string(),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+    }
+}
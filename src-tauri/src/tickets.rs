@@ -0,0 +1,294 @@
+//! Saving and loading share tickets as files
+//!
+//! A ticket file is a small JSON document holding a share ticket together with
+//! an optional note and expiry. Plain text files that only contain a ticket are
+//! accepted as well, so tickets written by other tools can be loaded too.
+
+use crate::dns::{looks_like_domain, resolve_ticket};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// File extension used for ticket files (e.g., "photos.ginseng-ticket")
+pub const TICKET_FILE_EXTENSION: &str = "ginseng-ticket";
+
+/// Current version of the ticket file format
+const TICKET_FILE_VERSION: u32 = 1;
+
+/// Prefix every share ticket starts with
+const TICKET_PREFIX: &str = "blob";
+
+/// A share ticket stored in a file along with user-facing details.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketFile {
+    /// Version of the ticket file format
+    pub version: u32,
+    /// The share ticket string
+    pub ticket: String,
+    /// Optional note from the sender (e.g., "Holiday photos")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Optional Unix timestamp after which the ticket should no longer be used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl TicketFile {
+    /// Creates a ticket file for the given ticket with no note or expiry
+    pub fn new(ticket: String) -> Self {
+        Self {
+            version: TICKET_FILE_VERSION,
+            ticket,
+            note: None,
+            expires_at: None,
+        }
+    }
+
+    /// Checks if the ticket file has passed its expiry time
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| current_unix_time() >= expires_at)
+    }
+}
+
+/// Writes a ticket file to disk as pretty-printed JSON.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be serialized or written.
+pub async fn save_ticket_file(path: &Path, ticket_file: &TicketFile) -> Result<()> {
+    let json = serde_json::to_string_pretty(ticket_file)?;
+    fs::write(path, json).await.map_err(|error| {
+        anyhow::anyhow!(
+            "Failed to write ticket file '{}': {}",
+            path.display(),
+            error
+        )
+    })
+}
+
+/// Reads a ticket file from disk.
+///
+/// Accepts both the JSON ticket file format and plain text files containing
+/// only a ticket string.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or contains no ticket.
+pub async fn load_ticket_file(path: &Path) -> Result<TicketFile> {
+    let content = fs::read_to_string(path).await.map_err(|error| {
+        anyhow::anyhow!("Failed to read ticket file '{}': {}", path.display(), error)
+    })?;
+
+    parse_ticket_file(&content)
+        .ok_or_else(|| anyhow::anyhow!("No ticket found in '{}'", path.display()))
+}
+
+/// Resolves user input that is a ticket string, a path to a ticket file, or a
+/// domain publishing a ticket in DNS.
+///
+/// # Errors
+///
+/// Returns an error if the input points to a ticket file that cannot be read
+/// or that has expired, or to a domain without a published ticket.
+pub async fn resolve_ticket_input(input: &str) -> Result<String> {
+    let path = Path::new(input);
+    if !path.is_file() {
+        if looks_like_domain(input) {
+            return resolve_ticket(input).await;
+        }
+        return Ok(input.trim().to_string());
+    }
+
+    let ticket_file = load_ticket_file(path).await?;
+    if ticket_file.is_expired() {
+        anyhow::bail!("Ticket file '{}' has expired", path.display());
+    }
+
+    Ok(ticket_file.ticket)
+}
+
+/// Splits input holding several tickets, e.g. pasted one after another.
+///
+/// Tickets may be separated by whitespace, commas or semicolons, or follow
+/// each other directly. Where tickets follow each other directly, the input is
+/// split before each occurrence of the ticket prefix such that every part is
+/// a valid ticket.
+///
+/// # Arguments
+///
+/// * `input` - The pasted text
+/// * `is_ticket` - Checks whether a string is a valid ticket
+///
+/// # Returns
+///
+/// Every ticket in the input, in order, or None if any part of the input is
+/// not a ticket
+pub fn split_tickets(input: &str, is_ticket: impl Fn(&str) -> bool) -> Option<Vec<String>> {
+    let mut tickets = Vec::new();
+    for token in input
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|token| !token.is_empty())
+    {
+        tickets.extend(split_concatenated(token, &is_ticket)?);
+    }
+
+    if tickets.is_empty() {
+        None
+    } else {
+        Some(tickets)
+    }
+}
+
+/// Splits a string of tickets that directly follow each other.
+fn split_concatenated(token: &str, is_ticket: &impl Fn(&str) -> bool) -> Option<Vec<String>> {
+    let mut boundaries: Vec<usize> = token
+        .match_indices(TICKET_PREFIX)
+        .map(|(index, _)| index)
+        .filter(|index| *index > 0)
+        .collect();
+    boundaries.insert(0, 0);
+    boundaries.push(token.len());
+
+    // For every boundary, the boundary the ticket ending there starts at
+    let mut starts: Vec<Option<usize>> = vec![None; boundaries.len()];
+    for end in 1..boundaries.len() {
+        starts[end] = (0..end).find(|&start| {
+            (start == 0 || starts[start].is_some())
+                && is_ticket(&token[boundaries[start]..boundaries[end]])
+        });
+    }
+
+    let mut tickets = Vec::new();
+    let mut end = boundaries.len() - 1;
+    while end > 0 {
+        let start = starts[end]?;
+        tickets.push(token[boundaries[start]..boundaries[end]].to_string());
+        end = start;
+    }
+    tickets.reverse();
+    Some(tickets)
+}
+
+/// Parses ticket file content, falling back to treating it as a raw ticket.
+fn parse_ticket_file(content: &str) -> Option<TicketFile> {
+    if let Ok(ticket_file) = serde_json::from_str::<TicketFile>(content) {
+        return Some(ticket_file);
+    }
+
+    let ticket = content.trim();
+    if ticket.is_empty() {
+        None
+    } else {
+        Some(TicketFile::new(ticket.to_string()))
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn current_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_ticket_file_json() {
+        let content = r#"{"version":1,"ticket":"blobabc","note":"Photos"}"#;
+        let ticket_file = parse_ticket_file(content).unwrap();
+        assert_eq!(ticket_file.ticket, "blobabc");
+        assert_eq!(ticket_file.note.as_deref(), Some("Photos"));
+        assert_eq!(ticket_file.expires_at, None);
+    }
+
+    #[test]
+    fn test_parse_ticket_file_plain_text() {
+        let ticket_file = parse_ticket_file("  blobabc\n").unwrap();
+        assert_eq!(ticket_file, TicketFile::new("blobabc".to_string()));
+    }
+
+    #[test]
+    fn test_parse_ticket_file_empty() {
+        assert!(parse_ticket_file("   \n").is_none());
+    }
+
+    #[test]
+    fn test_split_tickets() {
+        // Stand-in tickets: the prefix followed by three letters
+        let is_ticket = |ticket: &str| {
+            ticket.len() == 7 && ticket.starts_with("blob") && !ticket[4..].contains("blob")
+        };
+
+        assert_eq!(
+            split_tickets("blobabc", is_ticket),
+            Some(vec!["blobabc".to_string()])
+        );
+        assert_eq!(
+            split_tickets(" blobabc,\nblobdef; blobghi ", is_ticket),
+            Some(vec![
+                "blobabc".to_string(),
+                "blobdef".to_string(),
+                "blobghi".to_string()
+            ])
+        );
+        assert_eq!(
+            split_tickets("blobabcblobdef blobghi", is_ticket),
+            Some(vec![
+                "blobabc".to_string(),
+                "blobdef".to_string(),
+                "blobghi".to_string()
+            ])
+        );
+        assert_eq!(split_tickets("blobabc notaticket", is_ticket), None);
+        assert_eq!(split_tickets("blobabcblob", is_ticket), None);
+        assert_eq!(split_tickets("  ", is_ticket), None);
+    }
+
+    #[test]
+    fn test_ticket_file_expiry() {
+        let mut ticket_file = TicketFile::new("blobabc".to_string());
+        assert!(!ticket_file.is_expired());
+
+        ticket_file.expires_at = Some(0);
+        assert!(ticket_file.is_expired());
+
+        ticket_file.expires_at = Some(current_unix_time() + 3600);
+        assert!(!ticket_file.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_ticket_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("share.ginseng-ticket");
+
+        let mut ticket_file = TicketFile::new("blobabc".to_string());
+        ticket_file.note = Some("Quarterly report".to_string());
+        save_ticket_file(&path, &ticket_file).await.unwrap();
+
+        let loaded = load_ticket_file(&path).await.unwrap();
+        assert_eq!(loaded, ticket_file);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_ticket_input() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("expired.ginseng-ticket");
+
+        assert_eq!(resolve_ticket_input("blobabc").await.unwrap(), "blobabc");
+
+        let mut ticket_file = TicketFile::new("blobabc".to_string());
+        ticket_file.expires_at = Some(0);
+        save_ticket_file(&path, &ticket_file).await.unwrap();
+
+        let result = resolve_ticket_input(path.to_str().unwrap()).await;
+        assert!(result.is_err());
+    }
+}
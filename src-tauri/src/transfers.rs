@@ -0,0 +1,344 @@
+//! Bookkeeping for running and finished transfers
+//!
+//! Running transfers are registered with a cancellation token so they can be
+//! cancelled by ID, and with the blobs they still need, so a transfer that
+//! finishes first does not remove them from the store. When some files of a share or download fail, the transfer
+//! is recorded together with its progress tracker, so the failed files can be
+//! retried in place or in a follow-up transfer without the user having to pick
+//! them again.
+
+use crate::collisions::CollisionPolicy;
+use crate::compression::Compression;
+use crate::core::ShareMetadata;
+use crate::paths::calculate_relative_path;
+use crate::progress::{ProgressTracker, TransferId};
+use crate::sinks::OutputSink;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+/// The failed part of a finished transfer
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailedTransfer {
+    /// Files that could not be read while sharing
+    Upload {
+        /// Each failed file with the root path it was shared from
+        failed: Vec<(PathBuf, PathBuf)>,
+        /// Custom fields of the original share
+        custom_fields: BTreeMap<String, String>,
+        /// How the original share compressed its files
+        compression: Compression,
+    },
+    /// Files that could not be downloaded or exported
+    Download {
+        /// The ticket the files were downloaded from
+        ticket: String,
+        /// Relative paths of the failed files within the share
+        failed: Vec<String>,
+        /// Metadata of the share, for retrying the failed files without
+        /// fetching the share again
+        metadata: ShareMetadata,
+        /// Whether every file of the share was part of the transfer
+        whole_share: bool,
+        /// Directory the rest of the share was saved to (a key prefix for remote sinks)
+        target_directory: PathBuf,
+        /// Where the files were written to
+        sink: OutputSink,
+        /// How files that already existed were handled, applied again on retry
+        collisions: CollisionPolicy,
+    },
+}
+
+impl FailedTransfer {
+    /// Builds the paths and include selection that re-share only the failed files
+    ///
+    /// Returns `None` for downloads.
+    pub fn upload_selection(&self) -> Option<(Vec<PathBuf>, Vec<String>)> {
+        let FailedTransfer::Upload { failed, .. } = self else {
+            return None;
+        };
+
+        let mut roots = Vec::new();
+        let mut seen = HashSet::new();
+        let mut include = Vec::new();
+
+        for (file_path, base_path) in failed {
+            if seen.insert(base_path.clone()) {
+                roots.push(base_path.clone());
+            }
+            // Files shared directly are always included, only files found
+            // inside shared directories need to be selected
+            if file_path == base_path {
+                continue;
+            }
+            if let Ok(relative_path) = calculate_relative_path(file_path, base_path) {
+                include.push(relative_path);
+            }
+        }
+
+        Some((roots, include))
+    }
+}
+
+/// A logged transfer with the tracker its progress was reported through
+#[derive(Clone)]
+struct LoggedTransfer {
+    failed: FailedTransfer,
+    tracker: ProgressTracker,
+}
+
+/// Thread-safe log of transfers with failed files
+#[derive(Clone, Default)]
+pub struct TransferLog {
+    inner: Arc<RwLock<HashMap<TransferId, LoggedTransfer>>>,
+}
+
+impl TransferLog {
+    /// Creates an empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the failed files of a finished transfer, replacing any earlier
+    /// record of the transfer
+    pub async fn record(&self, tracker: &ProgressTracker, failed: FailedTransfer) {
+        let transfer_id = tracker.get_snapshot().await.transfer_id;
+        let logged = LoggedTransfer {
+            failed,
+            tracker: tracker.clone(),
+        };
+        self.inner.write().await.insert(transfer_id, logged);
+    }
+
+    /// Returns the failed files of a transfer, if it had any
+    pub async fn get(&self, transfer_id: &str) -> Option<FailedTransfer> {
+        let inner = self.inner.read().await;
+        inner.get(transfer_id).map(|logged| logged.failed.clone())
+    }
+
+    /// Returns the failed files of a transfer together with its progress tracker
+    pub async fn get_with_tracker(
+        &self,
+        transfer_id: &str,
+    ) -> Option<(FailedTransfer, ProgressTracker)> {
+        let inner = self.inner.read().await;
+        inner
+            .get(transfer_id)
+            .map(|logged| (logged.failed.clone(), logged.tracker.clone()))
+    }
+
+    /// Forgets a transfer, e.g. after all of its failed files were retried
+    pub async fn remove(&self, transfer_id: &str) {
+        self.inner.write().await.remove(transfer_id);
+    }
+}
+
+/// A transfer currently running
+#[derive(Debug)]
+struct Running {
+    token: CancellationToken,
+    /// Hashes of the blobs the transfer fetches or reads
+    blobs: HashSet<String>,
+}
+
+/// Cancellation tokens and blobs of the transfers currently running
+#[derive(Debug, Clone, Default)]
+pub struct ActiveTransfers {
+    inner: Arc<Mutex<HashMap<TransferId, Running>>>,
+}
+
+impl ActiveTransfers {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a running transfer
+    ///
+    /// The transfer stays registered until the returned handle is dropped.
+    pub fn start(&self, transfer_id: TransferId) -> ActiveTransfer {
+        let token = CancellationToken::new();
+        if let Ok(mut inner) = self.inner.lock() {
+            let running = Running {
+                token: token.clone(),
+                blobs: HashSet::new(),
+            };
+            inner.insert(transfer_id.clone(), running);
+        }
+        ActiveTransfer {
+            transfers: self.clone(),
+            transfer_id,
+            token,
+        }
+    }
+
+    /// Returns the cancellation token of a running transfer
+    ///
+    /// Lets work that cannot be dropped mid-way, e.g. on a blocking thread,
+    /// check whether it should stop.
+    pub fn token(&self, transfer_id: &str) -> Option<CancellationToken> {
+        let inner = self.inner.lock().ok()?;
+        inner.get(transfer_id).map(|running| running.token.clone())
+    }
+
+    /// Marks blobs as needed by a running transfer until it ends
+    ///
+    /// Does nothing if no transfer with the ID is running.
+    pub fn claim_blobs(&self, transfer_id: &str, hashes: impl IntoIterator<Item = String>) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if let Some(running) = inner.get_mut(transfer_id) {
+                running.blobs.extend(hashes);
+            }
+        }
+    }
+
+    /// Checks whether a running transfer other than `transfer_id` needs a blob
+    pub fn is_claimed_by_other(&self, transfer_id: &str, hash: &str) -> bool {
+        let Ok(inner) = self.inner.lock() else {
+            // Keeps the blob if in doubt
+            return true;
+        };
+        inner
+            .iter()
+            .any(|(id, running)| id != transfer_id && running.blobs.contains(hash))
+    }
+
+    /// Cancels a running transfer
+    ///
+    /// Returns false if no transfer with the ID is running.
+    pub fn cancel(&self, transfer_id: &str) -> bool {
+        let Ok(inner) = self.inner.lock() else {
+            return false;
+        };
+        match inner.get(transfer_id) {
+            Some(running) => {
+                running.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Registration of a running transfer, removed when dropped
+pub struct ActiveTransfer {
+    transfers: ActiveTransfers,
+    transfer_id: TransferId,
+    token: CancellationToken,
+}
+
+impl ActiveTransfer {
+    /// Returns the token cancelled when the transfer should stop
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for ActiveTransfer {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.transfers.inner.lock() {
+            inner.remove(&self.transfer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ShareType;
+    use crate::progress::TransferType;
+
+    #[test]
+    fn test_upload_selection_groups_failed_files_by_root() {
+        let root = PathBuf::from("/share/photos");
+        let single = PathBuf::from("/share/notes.txt");
+        let failed = FailedTransfer::Upload {
+            failed: vec![
+                (root.join("a.jpg"), root.clone()),
+                (root.join("2024").join("b.jpg"), root.clone()),
+                (single.clone(), single.clone()),
+            ],
+            custom_fields: BTreeMap::new(),
+            compression: Compression::None,
+        };
+
+        let (roots, include) = failed.upload_selection().unwrap();
+
+        assert_eq!(roots, vec![root, single]);
+        assert_eq!(include.len(), 2);
+        assert_eq!(include[0], "a.jpg");
+    }
+
+    #[tokio::test]
+    async fn test_transfer_log_record_and_get() {
+        let log = TransferLog::new();
+        let failed = FailedTransfer::Download {
+            ticket: "ticket".to_string(),
+            failed: vec!["a.txt".to_string()],
+            metadata: ShareMetadata {
+                files: Vec::new(),
+                share_type: ShareType::MultipleFiles,
+                total_size: 0,
+                skipped_files: Vec::new(),
+                custom_fields: BTreeMap::new(),
+                expires_at: None,
+                share_id: None,
+                content_root: None,
+            },
+            whole_share: true,
+            target_directory: PathBuf::from("/downloads"),
+            sink: OutputSink::Local,
+            collisions: CollisionPolicy::default(),
+        };
+        let tracker = ProgressTracker::new("transfer".to_string(), TransferType::Download);
+
+        log.record(&tracker, failed.clone()).await;
+
+        assert_eq!(log.get("transfer").await, Some(failed.clone()));
+        assert_eq!(failed.upload_selection(), None);
+        assert!(log.get("unknown").await.is_none());
+
+        let (_, logged_tracker) = log.get_with_tracker("transfer").await.unwrap();
+        assert_eq!(logged_tracker.get_snapshot().await.transfer_id, "transfer");
+
+        log.remove("transfer").await;
+        assert!(log.get("transfer").await.is_none());
+    }
+
+    #[test]
+    fn test_active_transfers_cancel_running_transfers_only() {
+        let transfers = ActiveTransfers::new();
+        let active = transfers.start("transfer".to_string());
+
+        assert!(!transfers.cancel("unknown"));
+        assert!(transfers.cancel("transfer"));
+        assert!(active.token().is_cancelled());
+        assert!(transfers.token("transfer").unwrap().is_cancelled());
+
+        drop(active);
+        assert!(!transfers.cancel("transfer"));
+        assert!(transfers.token("transfer").is_none());
+    }
+
+    #[test]
+    fn test_active_transfers_keep_blobs_of_other_transfers() {
+        let transfers = ActiveTransfers::new();
+        let first = transfers.start("first".to_string());
+        let second = transfers.start("second".to_string());
+        transfers.claim_blobs("first", ["shared".to_string(), "own".to_string()]);
+        transfers.claim_blobs("second", ["shared".to_string()]);
+        transfers.claim_blobs("unknown", ["other".to_string()]);
+
+        assert!(transfers.is_claimed_by_other("first", "shared"));
+        assert!(!transfers.is_claimed_by_other("first", "own"));
+        assert!(transfers.is_claimed_by_other("second", "own"));
+        assert!(!transfers.is_claimed_by_other("second", "other"));
+
+        drop(second);
+        assert!(!transfers.is_claimed_by_other("first", "shared"));
+        drop(first);
+        assert!(!transfers.is_claimed_by_other("second", "own"));
+    }
+}
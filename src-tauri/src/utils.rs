@@ -4,12 +4,17 @@ use anyhow::Result;
 use std::path::{Path, PathBuf};
 
 /// Validate and canonicalize paths for ProgressEvent channel
-pub fn validate_and_canonicalize_paths(paths: Vec<String>) -> Result<Vec<PathBuf>, String> {
+///
+/// The I/O error is kept in the error chain, so a missing path can be told
+/// apart from other failures.
+pub fn validate_and_canonicalize_paths(paths: Vec<String>) -> Result<Vec<PathBuf>> {
     paths
         .iter()
         .map(|path| {
-            std::fs::canonicalize(path)
-                .map_err(|error| format!("Invalid file path '{}': {}", path, error))
+            std::fs::canonicalize(path).map_err(|error| {
+                let message = format!("Invalid file path '{}': {}", path, error);
+                anyhow::Error::new(error).context(message)
+            })
         })
         .collect()
 }
@@ -44,31 +49,6 @@ pub fn extract_directory_name(dir_path: &Path) -> String {
         .to_string()
 }
 
-/// Calculates the relative path from a base path to a file path.
-///
-/// If the file path equals the base path, returns just the file name.
-/// Otherwise, strips the base path prefix to get the relative path.
-///
-/// # Arguments
-/// * `file_path` - The target file path
-/// * `base_path` - The base path to calculate relative to
-///
-/// # Returns
-/// The relative path as a string
-///
-/// # Errors
-/// Returns an error if the file path is not within the base path
-pub fn calculate_relative_path(file_path: &Path, base_path: &Path) -> Result<String> {
-    if file_path == base_path {
-        Ok(extract_file_name(file_path))
-    } else {
-        file_path
-            .strip_prefix(base_path)
-            .map(|path| path.to_str().unwrap_or("unknown").to_string())
-            .map_err(|error| anyhow::anyhow!("Failed to calculate relative path: {}", error))
-    }
-}
-
 /// Calculates the total size of a collection of files.
 ///
 /// # Arguments
@@ -123,6 +103,34 @@ pub fn get_downloads_directory() -> Result<PathBuf> {
         .ok_or_else(|| anyhow::anyhow!("Could not determine downloads directory"))
 }
 
+/// Windows error code for a file opened by another process without sharing
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// Windows error code for a file region locked by another process
+const ERROR_LOCK_VIOLATION: i32 = 33;
+
+/// Checks whether an I/O error means the file is locked by another application.
+///
+/// Only Windows enforces mandatory file locks, so this is always false elsewhere.
+///
+/// # Arguments
+/// * `error` - The error returned when opening or reading the file
+pub fn is_file_in_use_error(error: &std::io::Error) -> bool {
+    cfg!(windows)
+        && matches!(
+            error.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+}
+
+/// Checks whether a file is currently locked by another application.
+///
+/// # Arguments
+/// * `path` - The file to check
+pub fn is_file_in_use(path: &Path) -> bool {
+    std::fs::File::open(path).is_err_and(|error| is_file_in_use_error(&error))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,7 +158,10 @@ mod tests {
         let result = validate_and_canonicalize_paths(paths);
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid file path"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid file path"));
     }
 
     #[test]
@@ -179,22 +190,6 @@ mod tests {
         assert_eq!(extract_directory_name(Path::new("/path/to/")), "to");
     }
 
-    #[test]
-    fn test_calculate_relative_path_same_file() {
-        let path = Path::new("/home/user/file.txt");
-        assert_eq!(calculate_relative_path(path, path).unwrap(), "file.txt");
-    }
-
-    #[test]
-    fn test_calculate_relative_path_nested() {
-        let base = Path::new("/home/user");
-        let file = Path::new("/home/user/docs/file.txt");
-        assert_eq!(
-            calculate_relative_path(file, base).unwrap(),
-            "docs/file.txt"
-        );
-    }
-
     #[test]
     fn test_calculate_total_size() {
         let sizes = vec![100u64, 200u64, 300u64];
@@ -222,4 +217,22 @@ mod tests {
         let result = get_downloads_directory();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_is_file_in_use_error() {
+        let sharing_violation = std::io::Error::from_raw_os_error(ERROR_SHARING_VIOLATION);
+        assert_eq!(is_file_in_use_error(&sharing_violation), cfg!(windows));
+
+        let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(!is_file_in_use_error(&not_found));
+    }
+
+    #[test]
+    fn test_is_file_in_use_unlocked_file() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("unlocked.txt");
+        File::create(&file_path).unwrap();
+
+        assert!(!is_file_in_use(&file_path));
+    }
 }
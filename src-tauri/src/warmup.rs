@@ -0,0 +1,185 @@
+//! Warm connections to paired devices
+//!
+//! Reaching a device for the first time costs a relay round trip and hole
+//! punching before any data moves. When enabled, this node keeps an idle
+//! connection open to each paired device and pings it every
+//! [`PING_INTERVAL`], so the direct path to the device stays known and a push
+//! or download starts right away. The connection carries no data; transfers
+//! open connections of their own, which reuse the path found by the warm one.
+//!
+//! Devices that cannot be reached are tried again every
+//! [`RECONNECT_INTERVAL`].
+
+use crate::devices::PairedDevice;
+use crate::dropbox::parse_drop_ticket;
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::{Endpoint, EndpointAddr, EndpointId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// ALPN of the connections kept warm
+pub const WARMUP_ALPN: &[u8] = b"ginseng/warmup/0";
+
+/// How often warm connections are pinged to keep them open
+pub const PING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait before trying to reach a device again
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long connecting to a device may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which devices are kept warm
+#[derive(Debug, Default)]
+struct WarmerConfig {
+    /// Whether connections are kept warm at all
+    enabled: bool,
+    /// Addresses of the paired devices
+    devices: Vec<EndpointAddr>,
+}
+
+/// A connection kept warm, or the last failed attempt to open one
+enum WarmConnection {
+    Open(Connection),
+    Failed(Instant),
+}
+
+/// Keeps connections to paired devices open while enabled
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionWarmer {
+    config: Arc<Mutex<WarmerConfig>>,
+}
+
+impl ConnectionWarmer {
+    /// Creates a warmer that keeps nothing warm until enabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turns keeping connections warm on or off
+    ///
+    /// Warm connections are closed within [`PING_INTERVAL`] of turning it off.
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut config) = self.config.lock() {
+            config.enabled = enabled;
+        }
+    }
+
+    /// Replaces the devices kept warm
+    ///
+    /// Devices whose drop ticket cannot be parsed are left out.
+    pub fn set_devices(&self, devices: &[PairedDevice]) {
+        let addresses = devices
+            .iter()
+            .filter_map(|device| parse_drop_ticket(&device.drop_ticket).ok())
+            .map(|drop_ticket| drop_ticket.addr().clone())
+            .collect();
+        if let Ok(mut config) = self.config.lock() {
+            config.devices = addresses;
+        }
+    }
+
+    /// Returns the devices to keep warm right now
+    fn targets(&self) -> Vec<EndpointAddr> {
+        self.config
+            .lock()
+            .map(|config| {
+                if config.enabled {
+                    config.devices.clone()
+                } else {
+                    Vec::new()
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Spawns a task keeping connections warm until the endpoint is closed or
+    /// the warmer is dropped.
+    pub fn spawn(&self, endpoint: Endpoint) {
+        let config = Arc::downgrade(&self.config);
+        tokio::spawn(async move {
+            let mut connections: HashMap<EndpointId, WarmConnection> = HashMap::new();
+            loop {
+                let Some(config) = config.upgrade() else {
+                    return;
+                };
+                if endpoint.is_closed() {
+                    return;
+                }
+                let targets = ConnectionWarmer { config }.targets();
+
+                connections.retain(|id, connection| {
+                    let wanted = targets.iter().any(|target| target.id == *id);
+                    if !wanted {
+                        if let WarmConnection::Open(connection) = connection {
+                            connection.close(0u32.into(), b"no longer warm");
+                        }
+                    }
+                    wanted
+                });
+
+                for target in targets {
+                    let ready = match connections.get(&target.id) {
+                        Some(WarmConnection::Open(connection)) => {
+                            connection.close_reason().is_none() && ping(connection).await
+                        }
+                        Some(WarmConnection::Failed(at)) => at.elapsed() < RECONNECT_INTERVAL,
+                        None => false,
+                    };
+                    if ready {
+                        continue;
+                    }
+
+                    let id = target.id;
+                    let connection = tokio::time::timeout(
+                        CONNECT_TIMEOUT,
+                        endpoint.connect(target, WARMUP_ALPN),
+                    )
+                    .await;
+                    let connection = match connection {
+                        Ok(Ok(connection)) => {
+                            tracing::debug!(peer = %id, "connection warmed up");
+                            WarmConnection::Open(connection)
+                        }
+                        Ok(Err(error)) => {
+                            tracing::debug!(peer = %id, %error, "device not reachable");
+                            WarmConnection::Failed(Instant::now())
+                        }
+                        Err(_) => WarmConnection::Failed(Instant::now()),
+                    };
+                    connections.insert(id, connection);
+                }
+
+                tokio::time::sleep(PING_INTERVAL).await;
+            }
+        });
+    }
+}
+
+/// Sends an empty stream over a warm connection.
+///
+/// # Returns
+///
+/// Whether the connection is still usable
+async fn ping(connection: &Connection) -> bool {
+    match connection.open_uni().await {
+        Ok(mut send) => send.finish().is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Protocol handler accepting warm connections from paired devices
+#[derive(Debug, Clone, Default)]
+pub struct Warmup;
+
+impl ProtocolHandler for Warmup {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        // Pings carry no data; the connection is kept until the device closes it
+        while let Ok(mut recv) = connection.accept_uni().await {
+            recv.read_to_end(0).await.ok();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Ticket folder watcher for auto-receive workflows
+//!
+//! Watches a directory for `.ginseng-ticket` files dropped by other tools and
+//! automatically downloads the share each ticket points to. Processed ticket
+//! files are renamed with a `.done` or `.failed` suffix so they are never
+//! received twice.
+
+use crate::core::GinsengCore;
+use crate::tickets::{load_ticket_file, TICKET_FILE_EXTENSION};
+use anyhow::Result;
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+use tokio::sync::mpsc;
+
+/// Delay before reading a newly detected ticket file, giving the writer time to finish
+const SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// Events emitted while watching a ticket folder
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    tag = "event",
+    content = "data"
+)]
+pub enum WatchEvent {
+    /// A ticket file was found and its download is starting
+    TicketDetected { path: String },
+    /// The share referenced by a ticket file was downloaded
+    DownloadCompleted { path: String, download_path: String },
+    /// The share referenced by a ticket file could not be downloaded
+    DownloadFailed { path: String, error: String },
+    /// The watcher itself stopped because of an error
+    WatcherFailed { error: String },
+}
+
+/// Watches a directory and downloads every ticket file that appears in it.
+///
+/// Ticket files already present when the watcher starts are processed first.
+/// The directory is created if it does not exist. This function only returns
+/// if the underlying file system watcher fails.
+///
+/// # Arguments
+///
+/// * `core` - The Ginseng core used to perform downloads
+/// * `directory` - The folder to watch for ticket files
+/// * `on_event` - Callback invoked for every watcher event
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or watched.
+pub async fn watch_ticket_folder<F>(
+    core: &GinsengCore,
+    directory: &Path,
+    mut on_event: F,
+) -> Result<()>
+where
+    F: FnMut(WatchEvent),
+{
+    fs::create_dir_all(directory).await?;
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        if let Ok(event) = result {
+            for path in event.paths {
+                let _ = sender.send(path);
+            }
+        }
+    })?;
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+    for path in list_ticket_files(directory).await? {
+        process_ticket_file(core, &path, &mut on_event).await;
+    }
+
+    while let Some(path) = receiver.recv().await {
+        if !is_ticket_file(&path) {
+            continue;
+        }
+
+        tokio::time::sleep(SETTLE_DELAY).await;
+
+        if fs::try_exists(&path).await.unwrap_or(false) {
+            process_ticket_file(core, &path, &mut on_event).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the ticket files currently present in a directory.
+async fn list_ticket_files(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(directory).await?;
+    let mut ticket_files = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if is_ticket_file(&path) {
+            ticket_files.push(path);
+        }
+    }
+
+    ticket_files.sort();
+    Ok(ticket_files)
+}
+
+/// Reads a ticket file, downloads its share, and marks the file as processed.
+///
+/// Both plain ticket text and the JSON ticket file format are accepted, and
+/// expired ticket files are marked as failed. Empty files are left untouched,
+/// since they are usually still being written and will trigger another event
+/// once their content lands.
+async fn process_ticket_file<F>(core: &GinsengCore, path: &Path, on_event: &mut F)
+where
+    F: FnMut(WatchEvent),
+{
+    let display_path = path.to_string_lossy().to_string();
+
+    let Ok(ticket_file) = load_ticket_file(path).await else {
+        return;
+    };
+
+    on_event(WatchEvent::TicketDetected {
+        path: display_path.clone(),
+    });
+
+    let result = if ticket_file.is_expired() {
+        Err(anyhow::anyhow!("Ticket file has expired"))
+    } else {
+        core.download_files_cli(ticket_file.ticket).await
+    };
+
+    match result {
+        Ok((_, download_path)) => {
+            let _ = fs::rename(path, processed_path(path, "done")).await;
+            on_event(WatchEvent::DownloadCompleted {
+                path: display_path,
+                download_path: download_path.to_string_lossy().to_string(),
+            });
+        }
+        Err(error) => {
+            let _ = fs::rename(path, processed_path(path, "failed")).await;
+            on_event(WatchEvent::DownloadFailed {
+                path: display_path,
+                error: error.to_string(),
+            });
+        }
+    }
+}
+
+/// Checks whether a path has the ticket file extension.
+fn is_ticket_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension == TICKET_FILE_EXTENSION)
+}
+
+/// Builds the path a ticket file is renamed to once it has been processed.
+fn processed_path(path: &Path, suffix: &str) -> PathBuf {
+    path.with_extension(format!("{}.{}", TICKET_FILE_EXTENSION, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ticket_file() {
+        assert!(is_ticket_file(Path::new("/drop/photos.ginseng-ticket")));
+        assert!(!is_ticket_file(Path::new("/drop/photos.txt")));
+        assert!(!is_ticket_file(Path::new(
+            "/drop/photos.ginseng-ticket.done"
+        )));
+    }
+
+    #[test]
+    fn test_processed_path() {
+        let path = Path::new("/drop/photos.ginseng-ticket");
+        assert_eq!(
+            processed_path(path, "done"),
+            PathBuf::from("/drop/photos.ginseng-ticket.done")
+        );
+        assert_eq!(
+            processed_path(path, "failed"),
+            PathBuf::from("/drop/photos.ginseng-ticket.failed")
+        );
+    }
+}
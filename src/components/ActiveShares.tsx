@@ -0,0 +1,123 @@
+import { invoke } from "@tauri-apps/api/core";
+import { Copy, RefreshCw, Square } from "lucide-react";
+import { useCallback, useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Button } from "@/components/ui/button";
+import { describeError } from "@/types/errors";
+import { formatBytes } from "@/types/progress";
+
+interface ShareSummary {
+	shareId: string;
+	ticket: string;
+	paths: string[];
+	createdAt: number;
+	expiresAt?: number;
+	fileCount: number;
+	totalSize: number;
+	bytesServed: number;
+	completedDownloads: number;
+	locked: boolean;
+}
+
+const getShareName = (share: ShareSummary) => {
+	if (share.paths.length === 1) {
+		return share.paths[0].split(/[\\/]/).pop() || share.paths[0];
+	}
+	if (share.paths.length > 1) {
+		return `${share.paths.length} items`;
+	}
+	return `${share.fileCount} files`;
+};
+
+export function ActiveShares() {
+	const [shares, setShares] = useState<ShareSummary[]>([]);
+
+	const refresh = useCallback(async () => {
+		try {
+			setShares(await invoke<ShareSummary[]>("list_shares"));
+		} catch (error) {
+			toast.error(`Failed to load shares: ${describeError(error)}`);
+		}
+	}, []);
+
+	useEffect(() => {
+		refresh();
+	}, [refresh]);
+
+	const copyTicket = async (share: ShareSummary) => {
+		try {
+			await navigator.clipboard.writeText(share.ticket);
+			toast.success("Ticket copied to clipboard");
+		} catch (error) {
+			toast.error(`Failed to copy: ${describeError(error)}`);
+		}
+	};
+
+	const stopShare = async (share: ShareSummary) => {
+		try {
+			await invoke("stop_share", { shareId: share.shareId });
+			await refresh();
+		} catch (error) {
+			toast.error(`Failed to stop sharing: ${describeError(error)}`);
+		}
+	};
+
+	return (
+		<div className="space-y-4">
+			<div className="flex items-center justify-between">
+				<div className="text-xs uppercase tracking-wider text-muted-foreground">
+					Sharing · {shares.length} {shares.length === 1 ? "share" : "shares"}
+				</div>
+				<Button variant="ghost" size="sm" onClick={refresh} className="h-7 w-7 p-0">
+					<RefreshCw className="h-3.5 w-3.5" />
+				</Button>
+			</div>
+
+			{shares.length > 0 && (
+				<div className="space-y-0 border-t border-foreground/10">
+					{shares.map((share) => (
+						<div
+							key={share.shareId}
+							className="flex items-center justify-between py-2.5 border-b border-foreground/10 last:border-0"
+						>
+							<div className="flex-1 min-w-0">
+								<div className="text-sm truncate" title={share.paths.join("\n")}>
+									{getShareName(share)}
+									{share.locked && <span className="text-muted-foreground"> · locked</span>}
+								</div>
+								<div className="text-xs text-muted-foreground">
+									{share.fileCount} files · {formatBytes(share.totalSize)} · since{" "}
+									{new Date(share.createdAt * 1000).toLocaleString()}
+								</div>
+								<div className="text-xs text-muted-foreground">
+									{formatBytes(share.bytesServed)} served · {share.completedDownloads}{" "}
+									{share.completedDownloads === 1 ? "download" : "downloads"}
+									{share.expiresAt &&
+										` · expires ${new Date(share.expiresAt * 1000).toLocaleString()}`}
+								</div>
+							</div>
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={() => copyTicket(share)}
+								title="Copy ticket"
+								className="h-7 w-7 p-0 flex-shrink-0 hover:bg-transparent"
+							>
+								<Copy className="h-3.5 w-3.5" />
+							</Button>
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={() => stopShare(share)}
+								title="Stop sharing"
+								className="h-7 w-7 p-0 flex-shrink-0 hover:bg-transparent"
+							>
+								<Square className="h-3.5 w-3.5" />
+							</Button>
+						</div>
+					))}
+				</div>
+			)}
+		</div>
+	);
+}
@@ -1,14 +1,19 @@
 import { Channel, invoke } from "@tauri-apps/api/core";
-import { open } from "@tauri-apps/plugin-dialog";
+import { listen } from "@tauri-apps/api/event";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { Copy, File, Files, Folder, X } from "lucide-react";
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { toast } from "sonner";
+import { ActiveShares } from "@/components/ActiveShares";
+import { Library } from "@/components/Library";
 import { ParallelProgress } from "@/components/ParallelProgress";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "@/components/ui/tabs";
+import { describeError, errorCode } from "@/types/errors";
 import type { ProgressEvent, TransferProgress } from "@/types/progress";
+import { formatBytes } from "@/types/progress";
 
 interface FileInfo {
 	name: string;
@@ -16,28 +21,100 @@ interface FileInfo {
 	size: number;
 }
 
+interface SkippedFile {
+	relative_path: string;
+	error: string;
+}
+
 interface ShareMetadata {
 	files: FileInfo[];
-	share_type: "SingleFile" | "MultipleFiles" | { Directory: { name: string } };
+	share_type:
+		| "SingleFile"
+		| "MultipleFiles"
+		| { Directory: { name: string } }
+		| { Archive: { name: string } };
 	total_size: number;
+	skipped_files: SkippedFile[];
+	custom_fields: Record<string, string>;
+	expires_at?: number;
+	content_root?: string;
 }
 
+type CollisionPolicy = "rename" | "skip" | "overwrite" | "ask";
+
+type FileCollision = {
+	relativePath: string;
+	hash: string;
+	existingPath: string;
+} & (
+	| { resolution: "renamed"; path: string }
+	| { resolution: "skipped" | "overwritten" | "asked" }
+);
+
 interface DownloadResult {
 	metadata: ShareMetadata;
 	download_path: string;
+	collisions: FileCollision[];
+	failed_files: SkippedFile[];
+	content_root: string | null;
+}
+
+interface ShareResult {
+	share_id: string;
+	ticket: string;
+	skipped_files: SkippedFile[];
+	content_root: string;
 }
 
+type ShareOutcome =
+	| ({ status: "shared" } & ShareResult)
+	| { status: "tooLarge"; totalSize: number; maxSize: number };
+
 export function FileTransfer() {
 	const [selectedPaths, setSelectedPaths] = useState<string[]>([]);
 	const [ticket, setTicket] = useState<string>("");
+	const [shareId, setShareId] = useState<string | null>(null);
+	const [contentRoot, setContentRoot] = useState<string | null>(null);
 	const [sendLoading, setSendLoading] = useState(false);
+	const [customFields, setCustomFields] = useState<string>("");
+	const [expiresInHours, setExpiresInHours] = useState<string>("");
+	const [singleUse, setSingleUse] = useState(false);
+	const [compress, setCompress] = useState(false);
+	const [archive, setArchive] = useState(false);
+	const [delta, setDelta] = useState(false);
+	const [excludePatterns, setExcludePatterns] = useState<string>("");
+	const [ignoreFiles, setIgnoreFiles] = useState(false);
+	const [preview, setPreview] = useState(false);
+	const [shareLocked, setShareLocked] = useState(false);
 
 	const [receiveTicket, setReceiveTicket] = useState<string>("");
 	const [receiveLoading, setReceiveLoading] = useState(false);
+	const [collisionPolicy, setCollisionPolicy] = useState<CollisionPolicy>("rename");
+	const [saveTo, setSaveTo] = useState<string | null>(null);
 	const [lastDownload, setLastDownload] = useState<DownloadResult | null>(null);
 
 	const [uploadProgress, setUploadProgress] = useState<TransferProgress | null>(null);
 	const [downloadProgress, setDownloadProgress] = useState<TransferProgress | null>(null);
+	const [downloadStageMessage, setDownloadStageMessage] = useState<string | null>(null);
+	const [uploadTransferId, setUploadTransferId] = useState<string | null>(null);
+	const [downloadTransferId, setDownloadTransferId] = useState<string | null>(null);
+
+	useEffect(() => {
+		// Files picked from the file manager's "Share with Ginseng" entry
+		const takeContextMenuPaths = async () => {
+			const paths = await invoke<string[]>("take_context_menu_paths");
+			if (paths.length > 0) {
+				setSelectedPaths(paths);
+				setTicket("");
+				toast.info(`${paths.length} item(s) from the file manager are ready to share`);
+			}
+		};
+		takeContextMenuPaths();
+		const unlisten = listen("context-menu-share", takeContextMenuPaths);
+		return () => {
+			unlisten.then((stop) => stop());
+		};
+	}, []);
 
 	const selectFiles = async () => {
 		try {
@@ -70,6 +147,20 @@ export function FileTransfer() {
 		}
 	};
 
+	const chooseSaveTo = async () => {
+		try {
+			const folder = await open({
+				multiple: false,
+				directory: true,
+			});
+			if (folder) {
+				setSaveTo(folder as string);
+			}
+		} catch {
+			toast.error("Failed to select folder");
+		}
+	};
+
 	const removeFile = (pathToRemove: string) => {
 		const newPaths = selectedPaths.filter((path) => path !== pathToRemove);
 		setSelectedPaths(newPaths);
@@ -78,7 +169,18 @@ export function FileTransfer() {
 		}
 	};
 
-	const sendFiles = async () => {
+	const cancelTransfer = async (transferId: string | null) => {
+		if (!transferId) return;
+		try {
+			await invoke<boolean>("cancel_transfer", { transferId });
+		} catch (error) {
+			toast.error(`Failed to cancel: ${describeError(error)}`);
+		}
+	};
+
+	const isCancelled = (error: unknown) => errorCode(error) === "cancelled";
+
+	const sendFiles = async (force = false) => {
 		if (selectedPaths.length === 0) {
 			toast.error("Please select files or a folder first");
 			return;
@@ -100,27 +202,83 @@ export function FileTransfer() {
 						toast.success("Share ticket generated!");
 					}
 					break;
+				case "transferCancelled":
+					setUploadProgress(event.data.transfer);
+					toast.info("Share cancelled");
+					break;
 				case "transferFailed":
 					setUploadProgress(event.data.transfer);
 					toast.error(`Failed: ${event.data.error}`);
 					break;
+				case "stageChanged":
+					if (event.data.stage === "queued" && event.data.message) {
+						toast.info(event.data.message);
+					}
+					break;
+				case "sourceChanged":
+					toast.warning(
+						`${event.data.path} changed after it was shared. Share it again to send the new version.`,
+					);
+					break;
 			}
 		};
 
+		const transferId = crypto.randomUUID();
 		setSendLoading(true);
 		setUploadProgress(null);
+		setUploadTransferId(transferId);
 
 		try {
-			generatedTicket = await invoke<string>("share_files_parallel", {
+			const result = await invoke<ShareOutcome>("share_files_parallel", {
 				channel,
 				paths: selectedPaths,
+				transferId,
+				customFields: parseCustomFields(customFields),
+				expiresInSecs: expiresInHours ? Math.round(Number(expiresInHours) * 3600) : null,
+				singleUse,
+				compression: compress ? "zstd" : null,
+				archive,
+				delta,
+				exclude: {
+					patterns: excludePatterns
+						.split(/[\n,]/)
+						.map((pattern) => pattern.trim())
+						.filter(Boolean),
+					ignoreFiles,
+				},
+				force,
+				preview,
 			});
+			if (result.status === "tooLarge") {
+				setUploadProgress(null);
+				toast.warning(
+					`These files total ${formatBytes(result.totalSize)}, more than the ${formatBytes(result.maxSize)} limit`,
+					{
+						action: {
+							label: "Share anyway",
+							onClick: () => sendFiles(true),
+						},
+					},
+				);
+				return;
+			}
+			generatedTicket = result.ticket;
 			setTicket(generatedTicket);
+			setShareId(result.share_id);
+			setContentRoot(result.content_root);
+			setShareLocked(preview);
+			if (result.skipped_files.length > 0) {
+				const skipped = result.skipped_files.map((file) => file.relative_path).join(", ");
+				toast.warning(`Skipped ${result.skipped_files.length} unreadable files: ${skipped}`);
+			}
 		} catch (error) {
-			toast.error(`Failed to share files: ${error}`);
-			setUploadProgress(null);
+			if (!isCancelled(error)) {
+				toast.error(`Failed to share files: ${describeError(error)}`);
+				setUploadProgress(null);
+			}
 		} finally {
 			setSendLoading(false);
+			setUploadTransferId(null);
 		}
 	};
 
@@ -133,12 +291,130 @@ export function FileTransfer() {
 		}
 	};
 
+	const stopSharing = async () => {
+		if (!shareId) return;
+		try {
+			await invoke("stop_share", { shareId });
+			setTicket("");
+			setShareId(null);
+			toast.success("Stopped sharing");
+		} catch (error) {
+			toast.error(`Failed to stop sharing: ${describeError(error)}`);
+		}
+	};
+
+	const unlockShare = async () => {
+		if (!shareId) return;
+		try {
+			await invoke("unlock_share", { shareId });
+			setShareLocked(false);
+			toast.success("Files unlocked");
+		} catch (error) {
+			toast.error(`Failed to unlock files: ${describeError(error)}`);
+		}
+	};
+
+	const exportShareCard = async () => {
+		if (!shareId) return;
+		try {
+			const destination = await save({
+				defaultPath: "ginseng-transfer-slip.pdf",
+				filters: [
+					{ name: "PDF", extensions: ["pdf"] },
+					{ name: "PNG image", extensions: ["png"] },
+				],
+			});
+			if (!destination) return;
+			await invoke("export_share_card", { shareId, destination });
+			toast.success(`Saved transfer slip to ${destination}`);
+		} catch (error) {
+			toast.error(`Failed to save transfer slip: ${describeError(error)}`);
+		}
+	};
+
 	const receiveFiles = async () => {
 		if (!receiveTicket) {
 			toast.error("Please enter a ticket");
 			return;
 		}
 
+		const tickets = await invoke<string[] | null>("split_pasted_tickets", {
+			input: receiveTicket,
+		});
+		if (tickets) {
+			toast.info(`Found ${tickets.length} tickets`, {
+				action: {
+					label: "Receive all",
+					onClick: () => receiveAll(tickets),
+				},
+			});
+			return;
+		}
+
+		if (await receiveShare(receiveTicket)) {
+			setReceiveTicket("");
+		}
+	};
+
+	const receiveAll = async (tickets: string[]) => {
+		let received = 0;
+		for (const ticket of tickets) {
+			if (await receiveShare(ticket)) {
+				received++;
+			}
+		}
+		if (received === tickets.length) {
+			setReceiveTicket("");
+		}
+		toast.info(`Received ${received} of ${tickets.length} shares`);
+	};
+
+	const reportCollisions = (shareTicket: string, collisions: FileCollision[]) => {
+		const count = (resolution: FileCollision["resolution"]) =>
+			collisions.filter((collision) => collision.resolution === resolution).length;
+		const renamed = count("renamed");
+		const skipped = count("skipped");
+		if (renamed > 0 || skipped > 0) {
+			toast.info(`${renamed} file(s) saved under a new name, ${skipped} existing file(s) kept`);
+		}
+
+		const asked = collisions.filter((collision) => collision.resolution === "asked");
+		if (asked.length > 0) {
+			const resolve = (policy: CollisionPolicy) =>
+				resolveCollisions(
+					shareTicket,
+					asked.map((collision) => collision.hash),
+					policy,
+				);
+			toast.warning(`${asked.length} file(s) already exist and were not saved`, {
+				duration: Number.POSITIVE_INFINITY,
+				action: { label: "Overwrite", onClick: () => resolve("overwrite") },
+				cancel: { label: "Keep both", onClick: () => resolve("rename") },
+			});
+		}
+	};
+
+	const resolveCollisions = async (
+		shareTicket: string,
+		fileHashes: string[],
+		policy: CollisionPolicy,
+	) => {
+		try {
+			const result = await invoke<DownloadResult>("download_selected_files", {
+				channel: new Channel<ProgressEvent>(),
+				ticket: shareTicket,
+				fileHashes,
+				collisions: policy,
+				targetDir: saveTo,
+			});
+			const saved = fileHashes.length - result.failed_files.length;
+			toast.success(`Saved ${saved} of ${fileHashes.length} file(s)`);
+		} catch (error) {
+			toast.error(`Failed to save files: ${describeError(error)}`);
+		}
+	};
+
+	const receiveShare = async (shareTicket: string): Promise<boolean> => {
 		const channel = new Channel<ProgressEvent>();
 
 		channel.onmessage = (event: ProgressEvent) => {
@@ -147,9 +423,23 @@ export function FileTransfer() {
 				case "transferProgress":
 					setDownloadProgress(event.data.transfer);
 					break;
+				case "stageChanged":
+					setDownloadStageMessage(event.data.message ?? null);
+					break;
+				case "stageProgress":
+					setDownloadStageMessage(
+						`${event.data.message} · ${formatBytes(event.data.processedBytes)}`,
+					);
+					break;
 				case "transferCompleted":
 					setDownloadProgress(event.data.transfer);
-					toast.success("Files downloaded successfully!");
+					if (event.data.transfer.failedFiles === 0) {
+						toast.success("Files downloaded successfully!");
+					}
+					break;
+				case "transferCancelled":
+					setDownloadProgress(event.data.transfer);
+					toast.info("Download cancelled");
 					break;
 				case "transferFailed":
 					setDownloadProgress(event.data.transfer);
@@ -158,24 +448,74 @@ export function FileTransfer() {
 			}
 		};
 
+		const transferId = crypto.randomUUID();
 		setReceiveLoading(true);
 		setDownloadProgress(null);
+		setDownloadStageMessage(null);
+		setDownloadTransferId(transferId);
 
 		try {
 			const result = await invoke<DownloadResult>("download_files_parallel", {
 				channel,
-				ticket: receiveTicket,
+				ticket: shareTicket,
+				transferId,
+				collisions: collisionPolicy,
+				targetDir: saveTo,
 			});
 			setLastDownload(result);
-			setReceiveTicket("");
+			reportCollisions(shareTicket, result.collisions);
+			if (result.failed_files.length > 0) {
+				const failed = result.failed_files.map((file) => file.relative_path).join(", ");
+				toast.error(`${result.failed_files.length} file(s) could not be downloaded: ${failed}`);
+			}
+			return true;
 		} catch (error) {
-			toast.error(`Failed to download files: ${error}`);
+			if (isCancelled(error)) {
+				return false;
+			}
+			if (errorCode(error) === "notAGinsengShare") {
+				toast.error(describeError(error), {
+					action: {
+						label: "Download raw",
+						onClick: () => receiveRawBlob(shareTicket),
+					},
+				});
+			} else {
+				toast.error(`Failed to download files: ${describeError(error)}`);
+			}
 			setDownloadProgress(null);
+			return false;
+		} finally {
+			setReceiveLoading(false);
+			setDownloadTransferId(null);
+		}
+	};
+
+	const receiveRawBlob = async (rawTicket: string) => {
+		setReceiveLoading(true);
+
+		try {
+			const path = await invoke<string>("download_raw_blob", { ticket: rawTicket });
+			setReceiveTicket("");
+			toast.success(`Saved blob to ${path}`);
+		} catch (error) {
+			toast.error(`Failed to download blob: ${describeError(error)}`);
 		} finally {
 			setReceiveLoading(false);
 		}
 	};
 
+	const parseCustomFields = (input: string): Record<string, string> => {
+		const fields: Record<string, string> = {};
+		for (const pair of input.split(",")) {
+			const separator = pair.indexOf("=");
+			if (separator > 0) {
+				fields[pair.slice(0, separator).trim()] = pair.slice(separator + 1).trim();
+			}
+		}
+		return fields;
+	};
+
 	const getFileName = (path: string) => {
 		return path.split("/").pop() || path.split("\\").pop() || path;
 	};
@@ -195,6 +535,9 @@ export function FileTransfer() {
 		if (typeof shareType === "object" && "Directory" in shareType) {
 			return `Directory: ${shareType.Directory.name}`;
 		}
+		if (typeof shareType === "object" && "Archive" in shareType) {
+			return `Directory (archive): ${shareType.Archive.name}`;
+		}
 		return "Unknown";
 	};
 
@@ -212,6 +555,7 @@ export function FileTransfer() {
 					<TabsList className="mb-12">
 						<TabsTrigger value="send">send</TabsTrigger>
 						<TabsTrigger value="receive">receive</TabsTrigger>
+						<TabsTrigger value="library">library</TabsTrigger>
 					</TabsList>
 
 					<TabsContent value="send" className="space-y-8">
@@ -265,15 +609,105 @@ export function FileTransfer() {
 							</div>
 						)}
 
+						<div className="space-y-3">
+							<Label className="text-xs uppercase tracking-wider text-muted-foreground">
+								Fields
+							</Label>
+							<Input
+								placeholder="optional, e.g. project=apollo, order=1234"
+								value={customFields}
+								onChange={(e) => setCustomFields(e.target.value)}
+								className="text-xs border"
+							/>
+						</div>
+
+						<div className="space-y-3">
+							<Label className="text-xs uppercase tracking-wider text-muted-foreground">
+								Expires after (hours)
+							</Label>
+							<Input
+								type="number"
+								min="0"
+								step="any"
+								placeholder="optional, e.g. 2"
+								value={expiresInHours}
+								onChange={(e) => setExpiresInHours(e.target.value)}
+								className="text-xs border"
+							/>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={singleUse}
+									onChange={(e) => setSingleUse(e.target.checked)}
+								/>
+								one-time ticket, refused after the first complete download
+							</label>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={compress}
+									onChange={(e) => setCompress(e.target.checked)}
+								/>
+								compress files, faster for text over slow connections
+							</label>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={archive}
+									onChange={(e) => setArchive(e.target.checked)}
+								/>
+								send a single folder as one archive, faster for many small files
+							</label>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={delta}
+									onChange={(e) => setDelta(e.target.checked)}
+								/>
+								only re-read files changed since they were last shared
+							</label>
+							<Label className="text-xs uppercase tracking-wider text-muted-foreground">
+								Exclude from folders
+							</Label>
+							<Input
+								placeholder="optional, e.g. node_modules, *.log"
+								value={excludePatterns}
+								onChange={(e) => setExcludePatterns(e.target.value)}
+								className="text-xs border"
+							/>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={ignoreFiles}
+									onChange={(e) => setIgnoreFiles(e.target.checked)}
+								/>
+								also leave out files listed in .gitignore and .ginsengignore
+							</label>
+							<label className="flex items-center gap-2 text-xs text-muted-foreground">
+								<input
+									type="checkbox"
+									checked={preview}
+									onChange={(e) => setPreview(e.target.checked)}
+								/>
+								preview only: share the file list, and unlock the files when you approve
+							</label>
+						</div>
+
 						<Button
-							onClick={sendFiles}
+							onClick={() => sendFiles()}
 							disabled={selectedPaths.length === 0 || sendLoading}
 							className="w-full h-11 font-normal"
 						>
 							{sendLoading ? "generating ticket..." : "generate share ticket"}
 						</Button>
 
-						{uploadProgress && <ParallelProgress transfer={uploadProgress} compact={false} />}
+						{uploadProgress && (
+							<ParallelProgress
+								transfer={uploadProgress}
+								onCancel={uploadTransferId ? () => cancelTransfer(uploadTransferId) : undefined}
+								compact={false}
+							/>
+						)}
 
 						{ticket && (
 							<div className="border border-foreground/20 p-6">
@@ -287,9 +721,42 @@ export function FileTransfer() {
 											<Copy className="h-4 w-4" />
 										</Button>
 									</div>
+									{contentRoot && (
+										<div className="flex justify-between text-xs text-muted-foreground">
+											<span>Checksum</span>
+											<span className="truncate ml-4 font-mono" title={contentRoot}>
+												{contentRoot}
+											</span>
+										</div>
+									)}
+									{shareId && shareLocked && (
+										<Button onClick={unlockShare} className="w-full h-9 font-normal">
+											unlock files
+										</Button>
+									)}
+									{shareId && (
+										<div className="flex gap-2">
+											<Button
+												variant="outline"
+												onClick={exportShareCard}
+												className="flex-1 h-9 font-normal border"
+											>
+												print transfer slip
+											</Button>
+											<Button
+												variant="outline"
+												onClick={stopSharing}
+												className="flex-1 h-9 font-normal border"
+											>
+												stop sharing
+											</Button>
+										</div>
+									)}
 								</div>
 							</div>
 						)}
+						{/* Remounted whenever a share starts or stops, so the list reloads */}
+						<ActiveShares key={shareId ?? "none"} />
 					</TabsContent>
 
 					<TabsContent value="receive" className="space-y-8">
@@ -303,6 +770,47 @@ export function FileTransfer() {
 								onChange={(e) => setReceiveTicket(e.target.value)}
 								className="text-xs border"
 							/>
+							<Label className="text-xs uppercase tracking-wider text-muted-foreground">
+								If a file already exists
+							</Label>
+							<select
+								value={collisionPolicy}
+								onChange={(e) => setCollisionPolicy(e.target.value as CollisionPolicy)}
+								className="h-9 w-full rounded-md border bg-transparent px-3 text-xs"
+							>
+								<option value="rename">keep both, renaming the new file</option>
+								<option value="skip">keep the existing file</option>
+								<option value="overwrite">replace the existing file</option>
+								<option value="ask">ask me</option>
+							</select>
+							<Label className="text-xs uppercase tracking-wider text-muted-foreground">
+								Save to
+							</Label>
+							<div className="flex items-center justify-between gap-4 text-xs">
+								<span className="truncate text-muted-foreground">
+									{saveTo ?? "download folder"}
+								</span>
+								<div className="flex shrink-0 gap-4">
+									<Button
+										variant="ghost"
+										size="sm"
+										onClick={chooseSaveTo}
+										className="h-auto p-0 text-xs uppercase tracking-wider text-muted-foreground hover:bg-transparent"
+									>
+										choose
+									</Button>
+									{saveTo && (
+										<Button
+											variant="ghost"
+											size="sm"
+											onClick={() => setSaveTo(null)}
+											className="h-auto p-0 text-xs uppercase tracking-wider text-muted-foreground hover:bg-transparent"
+										>
+											reset
+										</Button>
+									)}
+								</div>
+							</div>
 						</div>
 
 						<Button
@@ -313,7 +821,16 @@ export function FileTransfer() {
 							{receiveLoading ? "downloading..." : "download files"}
 						</Button>
 
-						{downloadProgress && <ParallelProgress transfer={downloadProgress} compact={false} />}
+						{downloadProgress && (
+							<ParallelProgress
+								transfer={downloadProgress}
+								stageMessage={downloadStageMessage}
+								onCancel={
+									downloadTransferId ? () => cancelTransfer(downloadTransferId) : undefined
+								}
+								compact={false}
+							/>
+						)}
 
 						{lastDownload && (
 							<div className="border border-foreground/20 p-6">
@@ -337,6 +854,28 @@ export function FileTransfer() {
 											<span>{formatFileSize(lastDownload.metadata.total_size)}</span>
 										</div>
 
+										{Object.entries(lastDownload.metadata.custom_fields ?? {}).map(([key, value]) => (
+											<div
+												key={key}
+												className="flex justify-between text-sm py-1.5 border-b border-foreground/10"
+											>
+												<span className="text-muted-foreground">{key}</span>
+												<span className="truncate ml-4 text-right">{value}</span>
+											</div>
+										))}
+
+										{lastDownload.content_root && (
+											<div className="flex justify-between text-sm py-1.5 border-b border-foreground/10">
+												<span className="text-muted-foreground">Checksum</span>
+												<span
+													className="truncate ml-4 text-right font-mono"
+													title={lastDownload.content_root}
+												>
+													{lastDownload.content_root}
+												</span>
+											</div>
+										)}
+
 										<div className="flex justify-between text-sm py-1.5">
 											<span className="text-muted-foreground">Location</span>
 											<span className="truncate ml-4 text-right" title={lastDownload.download_path}>
@@ -367,10 +906,37 @@ export function FileTransfer() {
 											</div>
 										</div>
 									)}
+
+									{lastDownload.failed_files.length > 0 && (
+										<div className="pt-2 space-y-3">
+											<div className="text-xs uppercase tracking-wider text-destructive">
+												Not downloaded
+											</div>
+											<div className="max-h-40 overflow-y-auto space-y-0 border-t border-foreground/10">
+												{lastDownload.failed_files.map((file) => (
+													<div
+														key={file.relative_path}
+														className="flex items-center justify-between text-xs py-2 border-b border-foreground/10 last:border-0"
+													>
+														<span className="truncate" title={file.relative_path}>
+															{file.relative_path}
+														</span>
+														<span className="text-destructive truncate ml-4" title={file.error}>
+															{file.error}
+														</span>
+													</div>
+												))}
+											</div>
+										</div>
+									)}
 								</div>
 							</div>
 						)}
 					</TabsContent>
+
+					<TabsContent value="library" className="space-y-8">
+						<Library />
+					</TabsContent>
 				</Tabs>
 			</div>
 		</div>
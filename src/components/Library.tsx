@@ -0,0 +1,186 @@
+import { invoke } from "@tauri-apps/api/core";
+import { open } from "@tauri-apps/plugin-dialog";
+import { FolderOutput, Pin, PinOff, RefreshCw, Trash2 } from "lucide-react";
+import { useCallback, useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Button } from "@/components/ui/button";
+import { describeError } from "@/types/errors";
+import { formatBytes } from "@/types/progress";
+
+interface LibraryEntry {
+	share_id: string;
+	ticket: string;
+	metadata: {
+		files: { relative_path: string; size: number }[];
+		share_type:
+			| "SingleFile"
+			| "MultipleFiles"
+			| { Directory: { name: string } }
+			| { Archive: { name: string } };
+		total_size: number;
+		custom_fields?: Record<string, string>;
+		content_root?: string;
+	};
+	received_at: number;
+}
+
+interface Settings {
+	pinnedPeers: string[];
+	pinnedShares: string[];
+}
+
+const getEntryName = (entry: LibraryEntry) => {
+	const shareType = entry.metadata.share_type;
+	if (typeof shareType === "object" && "Directory" in shareType) {
+		return shareType.Directory.name;
+	}
+	if (typeof shareType === "object" && "Archive" in shareType) {
+		return shareType.Archive.name;
+	}
+	if (shareType === "SingleFile" && entry.metadata.files.length > 0) {
+		return entry.metadata.files[0].relative_path;
+	}
+	return `${entry.metadata.files.length} files`;
+};
+
+export function Library() {
+	const [entries, setEntries] = useState<LibraryEntry[]>([]);
+	const [busyShare, setBusyShare] = useState<string | null>(null);
+	const [pinnedShares, setPinnedShares] = useState<string[]>([]);
+
+	const refresh = useCallback(async () => {
+		try {
+			const settings = await invoke<Settings>("get_settings");
+			setPinnedShares(settings.pinnedShares);
+			setEntries(await invoke<LibraryEntry[]>("library_shares"));
+		} catch (error) {
+			toast.error(`Failed to load library: ${describeError(error)}`);
+		}
+	}, []);
+
+	useEffect(() => {
+		refresh();
+	}, [refresh]);
+
+	const exportEntry = async (entry: LibraryEntry) => {
+		const destination = await open({ multiple: false, directory: true });
+		if (!destination) return;
+
+		setBusyShare(entry.share_id);
+		try {
+			const path = await invoke<string>("export_from_library", {
+				shareId: entry.share_id,
+				destination,
+			});
+			toast.success(`Exported to ${path}`);
+		} catch (error) {
+			toast.error(`Failed to export: ${describeError(error)}`);
+		} finally {
+			setBusyShare(null);
+		}
+	};
+
+	const togglePinned = async (entry: LibraryEntry) => {
+		try {
+			await invoke<Settings>("set_share_pinned", {
+				shareId: entry.share_id,
+				pinned: !pinnedShares.includes(entry.share_id),
+			});
+			await refresh();
+		} catch (error) {
+			toast.error(`Failed to update pin: ${describeError(error)}`);
+		}
+	};
+
+	const removeEntry = async (entry: LibraryEntry) => {
+		try {
+			await invoke("remove_from_library", { shareId: entry.share_id });
+			await refresh();
+		} catch (error) {
+			toast.error(`Failed to remove: ${describeError(error)}`);
+		}
+	};
+
+	return (
+		<div className="space-y-4">
+			<div className="flex items-center justify-between">
+				<div className="text-xs uppercase tracking-wider text-muted-foreground">
+					Received · {entries.length} {entries.length === 1 ? "share" : "shares"}
+				</div>
+				<Button variant="ghost" size="sm" onClick={refresh} className="h-7 w-7 p-0">
+					<RefreshCw className="h-3.5 w-3.5" />
+				</Button>
+			</div>
+
+			{entries.length === 0 ? (
+				<div className="text-sm text-muted-foreground">
+					Shares you receive stay here and can be exported to another folder later.
+				</div>
+			) : (
+				<div className="space-y-0 border-t border-foreground/10">
+					{entries.map((entry) => (
+						<div
+							key={entry.share_id}
+							className="flex items-center justify-between py-2.5 border-b border-foreground/10 last:border-0"
+						>
+							<div className="flex-1 min-w-0">
+								<div className="text-sm truncate" title={getEntryName(entry)}>
+									{getEntryName(entry)}
+								</div>
+								<div className="text-xs text-muted-foreground">
+									{entry.metadata.files.length} files · {formatBytes(entry.metadata.total_size)} ·{" "}
+									{new Date(entry.received_at * 1000).toLocaleString()}
+								</div>
+								{entry.metadata.content_root && (
+									<div
+										className="text-xs text-muted-foreground truncate font-mono"
+										title={entry.metadata.content_root}
+									>
+										{entry.metadata.content_root}
+									</div>
+								)}
+								{Object.entries(entry.metadata.custom_fields ?? {}).length > 0 && (
+									<div className="text-xs text-muted-foreground truncate">
+										{Object.entries(entry.metadata.custom_fields ?? {})
+											.map(([key, value]) => `${key}: ${value}`)
+											.join(" · ")}
+									</div>
+								)}
+							</div>
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={() => togglePinned(entry)}
+								title={pinnedShares.includes(entry.share_id) ? "Unpin" : "Pin"}
+								className="h-7 w-7 p-0 flex-shrink-0 hover:bg-transparent"
+							>
+								{pinnedShares.includes(entry.share_id) ? (
+									<PinOff className="h-3.5 w-3.5" />
+								) : (
+									<Pin className="h-3.5 w-3.5" />
+								)}
+							</Button>
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={() => exportEntry(entry)}
+								disabled={busyShare === entry.share_id}
+								className="h-7 w-7 p-0 flex-shrink-0 hover:bg-transparent"
+							>
+								<FolderOutput className="h-3.5 w-3.5" />
+							</Button>
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={() => removeEntry(entry)}
+								className="h-7 w-7 p-0 flex-shrink-0 hover:bg-transparent"
+							>
+								<Trash2 className="h-3.5 w-3.5" />
+							</Button>
+						</div>
+					))}
+				</div>
+			)}
+		</div>
+	);
+}
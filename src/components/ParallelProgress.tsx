@@ -1,15 +1,39 @@
 import { AlertCircle, Check, Clock, Download, Upload } from "lucide-react";
+import { Button } from "@/components/ui/button";
 import { Progress } from "@/components/ui/progress";
 import type { FileProgress, TransferProgress } from "@/types/progress";
-import { calculateProgress, formatBytes, formatDuration } from "@/types/progress";
+import {
+	calculateProgress,
+	formatBytes,
+	formatDuration,
+	formatPreparation,
+	formatTransferred,
+} from "@/types/progress";
 
 interface ParallelProgressProps {
 	transfer: TransferProgress;
+	stageMessage?: string | null;
+	onCancel?: () => void;
 	compact?: boolean;
 }
 
-export function ParallelProgress({ transfer, compact = false }: ParallelProgressProps) {
+export function ParallelProgress({
+	transfer,
+	stageMessage,
+	onCancel,
+	compact = false,
+}: ParallelProgressProps) {
 	const overallProgress = calculateProgress(transfer.transferredBytes, transfer.totalBytes);
+	const overallLabel = transfer.indeterminate ? "size unknown" : `${overallProgress}%`;
+	const transferred = formatTransferred(
+		transfer.transferredBytes,
+		transfer.totalBytes,
+		transfer.indeterminate,
+	);
+	const isFinished = ["completed", "failed", "cancelled"].includes(transfer.stage);
+	const isPreparing =
+		transfer.transferType === "upload" &&
+		(transfer.stage === "initializing" || transfer.stage === "transferring");
 
 	const getStageDisplay = () => {
 		switch (transfer.stage) {
@@ -17,6 +41,10 @@ export function ParallelProgress({ transfer, compact = false }: ParallelProgress
 				return "COMPLETED";
 			case "failed":
 				return "FAILED";
+			case "cancelled":
+				return "CANCELLED";
+			case "initializing":
+				return transfer.transferType === "upload" ? "PREPARING" : "QUEUED";
 			case "transferring":
 				return "RUNNING";
 			default:
@@ -41,14 +69,18 @@ export function ParallelProgress({ transfer, compact = false }: ParallelProgress
 							</span>
 							<span className="text-xs uppercase tracking-wider">{getStageDisplay()}</span>
 						</div>
+						{isPreparing && (
+							<div className="text-xs text-muted-foreground">{formatPreparation(transfer)}</div>
+						)}
 						{transfer.stage === "transferring" && (
 							<>
-								<Progress value={overallProgress} />
+								<IndeterminateProgress
+									value={overallProgress}
+									indeterminate={transfer.indeterminate}
+								/>
 								<div className="flex justify-between text-xs text-muted-foreground">
-									<span>
-										{formatBytes(transfer.transferredBytes)} / {formatBytes(transfer.totalBytes)}
-									</span>
-									<span>{overallProgress}%</span>
+									<span>{transferred}</span>
+									<span>{overallLabel}</span>
 								</div>
 							</>
 						)}
@@ -65,19 +97,41 @@ export function ParallelProgress({ transfer, compact = false }: ParallelProgress
 					<div className="text-xs uppercase tracking-wider text-muted-foreground">
 						{transfer.transferType === "upload" ? "Upload" : "Download"} Progress
 					</div>
-					<div className="text-xs uppercase tracking-wider">{getStageDisplay()}</div>
+					<div className="flex items-center gap-4">
+						{onCancel && !isFinished && (
+							<Button
+								variant="ghost"
+								size="sm"
+								onClick={onCancel}
+								className="h-auto p-0 text-xs uppercase tracking-wider text-muted-foreground hover:bg-transparent"
+							>
+								cancel
+							</Button>
+						)}
+						<div className="text-xs uppercase tracking-wider">{getStageDisplay()}</div>
+					</div>
 				</div>
 
+				{stageMessage && (transfer.stage === "connecting" || transfer.stage === "initializing") && (
+					<div className="text-sm text-muted-foreground">{stageMessage}</div>
+				)}
+
+				{isPreparing && (
+					<div className="text-sm text-muted-foreground">{formatPreparation(transfer)}</div>
+				)}
+
 				<div className="space-y-3">
 					<div className="flex justify-between text-sm py-1">
 						<span className="text-muted-foreground">Overall</span>
-						<span>{overallProgress}%</span>
+						<span>{overallLabel}</span>
 					</div>
-					<Progress value={overallProgress} className="h-0.5" />
+					<IndeterminateProgress
+						value={overallProgress}
+						indeterminate={transfer.indeterminate}
+						className="h-0.5"
+					/>
 					<div className="flex justify-between text-xs text-muted-foreground pt-1">
-						<span>
-							{formatBytes(transfer.transferredBytes)} / {formatBytes(transfer.totalBytes)}
-						</span>
+						<span>{transferred}</span>
 						<span>
 							{transfer.completedFiles} / {transfer.totalFiles} files
 						</span>
@@ -130,8 +184,26 @@ export function ParallelProgress({ transfer, compact = false }: ParallelProgress
 	);
 }
 
+/** A progress bar that pulses while the total is unknown */
+function IndeterminateProgress({
+	value,
+	indeterminate,
+	className,
+}: {
+	value: number;
+	indeterminate: boolean;
+	className?: string;
+}) {
+	return indeterminate ? (
+		<Progress value={100} className={`${className ?? ""} animate-pulse opacity-50`} />
+	) : (
+		<Progress value={value} className={className} />
+	);
+}
+
 function FileProgressItem({ file }: { file: FileProgress }) {
 	const progress = calculateProgress(file.transferredBytes, file.totalBytes);
+	const writeProgress = calculateProgress(file.writtenBytes, file.totalBytes);
 
 	const getIcon = () => {
 		switch (file.status) {
@@ -140,6 +212,7 @@ function FileProgressItem({ file }: { file: FileProgress }) {
 			case "failed":
 				return <AlertCircle className="h-3 w-3 text-destructive" />;
 			case "transferring":
+			case "writing":
 				return <Clock className="h-3 w-3 animate-pulse text-muted-foreground" />;
 			default:
 				return <Clock className="h-3 w-3 text-muted-foreground/50" />;
@@ -153,7 +226,9 @@ function FileProgressItem({ file }: { file: FileProgress }) {
 			case "failed":
 				return "FAILED";
 			case "transferring":
-				return `${progress}%`;
+				return file.indeterminate ? formatBytes(file.transferredBytes) : `${progress}%`;
+			case "writing":
+				return `WRITING ${writeProgress}%`;
 			default:
 				return "0%";
 		}
@@ -170,16 +245,32 @@ function FileProgressItem({ file }: { file: FileProgress }) {
 				</div>
 				<div className="flex items-center gap-4">
 					<span className="text-xs font-normal">{getStatusDisplay()}</span>
-					<span className="text-xs text-muted-foreground">{formatBytes(file.totalBytes)}</span>
+					<span className="text-xs text-muted-foreground">
+						{file.indeterminate ? "size unknown" : formatBytes(file.totalBytes)}
+					</span>
 				</div>
 			</div>
 
 			{file.status === "transferring" && (
 				<div className="space-y-2">
-					<Progress value={progress} className="h-px" />
+					<IndeterminateProgress
+						value={progress}
+						indeterminate={file.indeterminate}
+						className="h-px"
+					/>
+					<div className="flex justify-between text-xs text-muted-foreground pt-0.5">
+						<span>{formatTransferred(file.transferredBytes, file.totalBytes, file.indeterminate)}</span>
+						{file.transferRate && <span>{formatBytes(file.transferRate)}/s</span>}
+					</div>
+				</div>
+			)}
+
+			{file.status === "writing" && (
+				<div className="space-y-2">
+					<Progress value={writeProgress} className="h-px" />
 					<div className="flex justify-between text-xs text-muted-foreground pt-0.5">
-						<span>{formatBytes(file.transferredBytes)}</span>
-						<span>{formatBytes(file.totalBytes)}</span>
+						<span>{formatBytes(file.writtenBytes)} written</span>
+						{file.writeRate && <span>{formatBytes(file.writeRate)}/s to disk</span>}
 					</div>
 				</div>
 			)}
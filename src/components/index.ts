@@ -1 +1,3 @@
+export { ActiveShares } from "./ActiveShares";
 export { FileTransfer } from "./FileTransfer";
+export { Library } from "./Library";
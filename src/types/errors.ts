@@ -0,0 +1,46 @@
+/** Kinds of errors commands fail with */
+export type ErrorCode =
+	| "invalidTicket"
+	| "peerUnreachable"
+	| "shareExpired"
+	| "notAGinsengShare"
+	| "shareTooLarge"
+	| "cancelled"
+	| "diskFull"
+	| "pathNotFound"
+	| "permissionDenied"
+	| "coreUnavailable"
+	| "other";
+
+/** An error a command failed with */
+export interface GinsengError {
+	code: ErrorCode;
+	message: string;
+}
+
+/** What the user can do about each kind of error */
+const HINTS: Partial<Record<ErrorCode, string>> = {
+	invalidTicket: "Check that the whole ticket was copied.",
+	peerUnreachable: "Make sure the sender's app is open and online, then try again.",
+	shareExpired: "Ask the sender for a new ticket.",
+	diskFull: "Free up some disk space and try again.",
+	pathNotFound: "The file or folder may have been moved or deleted.",
+	permissionDenied: "Check that Ginseng is allowed to access the file or folder.",
+	coreUnavailable: "Wait a moment or restart Ginseng.",
+};
+
+export const isGinsengError = (error: unknown): error is GinsengError =>
+	typeof error === "object" && error !== null && "code" in error && "message" in error;
+
+/** Returns the kind of an error thrown by a command */
+export const errorCode = (error: unknown): ErrorCode =>
+	isGinsengError(error) ? error.code : "other";
+
+/** Returns the message of an error followed by what the user can do about it */
+export const describeError = (error: unknown): string => {
+	if (!isGinsengError(error)) {
+		return String(error);
+	}
+	const hint = HINTS[error.code];
+	return hint ? `${error.message.replace(/\.$/, "")}. ${hint}` : error.message;
+};
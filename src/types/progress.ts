@@ -4,6 +4,7 @@ export type FileId = string;
 export type TransferType = "upload" | "download";
 
 export type TransferStage =
+	| "queued"
 	| "initializing"
 	| "connecting"
 	| "transferring"
@@ -12,19 +13,37 @@ export type TransferStage =
 	| "failed"
 	| "cancelled";
 
-export type FileStatus = "pending" | "transferring" | "completed" | "failed" | "skipped";
+export type FileStatus =
+	| "pending"
+	| "transferring"
+	| "writing"
+	| "completed"
+	| "failed"
+	| "skipped";
 
 export interface FileProgress {
 	fileId: FileId;
 	name: string;
 	relativePath: string;
 	totalBytes: number;
+	indeterminate: boolean;
 	transferredBytes: number;
 	status: FileStatus;
 	transferRate?: number;
+	writtenBytes: number;
+	writeRate?: number;
 	error?: string;
 }
 
+/** Protocol version and optional features negotiated with a peer */
+export interface Capabilities {
+	version: number;
+	blobsAlpn: string;
+	compression: boolean;
+	encryption: boolean;
+	resume: boolean;
+}
+
 export interface TransferProgress {
 	transferId: TransferId;
 	transferType: TransferType;
@@ -33,12 +52,33 @@ export interface TransferProgress {
 	completedFiles: number;
 	failedFiles: number;
 	totalBytes: number;
+	indeterminate: boolean;
 	transferredBytes: number;
+	scannedFiles: number;
+	hashedBytes: number;
 	transferRate?: number;
 	startTime: number;
 	etaSeconds?: number;
 	files: FileProgress[];
 	error?: string;
+	shareId?: string;
+	capabilities?: Capabilities;
+}
+
+export type QueueState = "queued" | "running";
+
+export interface QueueEntry {
+	transferId: TransferId;
+	transferType: TransferType;
+	state: QueueState;
+	position?: number;
+	enqueuedAt: number;
+}
+
+export interface CleanupReport {
+	removedFiles: number;
+	droppedTags: number;
+	reclaimedBytes: number;
 }
 
 export type ProgressEvent =
@@ -52,10 +92,25 @@ export type ProgressEvent =
 			event: "stageChanged";
 			data: { transferId: TransferId; stage: TransferStage; message?: string };
 	  }
+	| {
+			event: "stageProgress";
+			data: {
+				transferId: TransferId;
+				stage: TransferStage;
+				message: string;
+				processedBytes: number;
+			};
+	  }
 	| { event: "transferCompleted"; data: { transfer: TransferProgress } }
+	| { event: "transferCancelled"; data: { transfer: TransferProgress } }
 	| {
 			event: "transferFailed";
 			data: { transfer: TransferProgress; error: string };
+	  }
+	| { event: "sourceChanged"; data: { transferId: TransferId; path: string } }
+	| {
+			event: "cleanupCompleted";
+			data: { transferId: TransferId; report: CleanupReport };
 	  };
 
 export const formatBytes = (bytes: number): string => {
@@ -83,6 +138,23 @@ export const formatDuration = (seconds: number): string => {
 	return `${secs}s`;
 };
 
+/** Bytes transferred out of the total, or just so far while the total is unknown */
+export const formatTransferred = (
+	transferred: number,
+	total: number,
+	indeterminate: boolean,
+): string =>
+	indeterminate
+		? `${formatBytes(transferred)} so far`
+		: `${formatBytes(transferred)} / ${formatBytes(total)}`;
+
+/** Files scanned and bytes hashed so far while a share is prepared */
+export const formatPreparation = (transfer: TransferProgress): string => {
+	const scanned = `${transfer.scannedFiles} file(s) scanned`;
+	if (transfer.hashedBytes === 0) return scanned;
+	return `${scanned} · ${formatBytes(transfer.hashedBytes)} / ${formatBytes(transfer.totalBytes)} hashed`;
+};
+
 export const calculateProgress = (transferred: number, total: number): number => {
 	if (total === 0) return 0;
 	return Math.round((transferred / total) * 100);